@@ -0,0 +1,112 @@
+//! Export of signature collections as Arrow record batches and Parquet files, so they can be
+//! joined with other data in tools like DuckDB, Polars, or Spark.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, FixedSizeBinaryArray, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+
+use crate::{Signature, SignatureError, SignatureParams};
+
+/// The name of the column of ids in [signature_record_batch]es.
+pub const ID_COLUMN: &str = "id";
+
+/// The name of the column of signatures in [signature_record_batch]es.
+pub const SIGNATURE_COLUMN: &str = "signature";
+
+/// The number of rows [write_parquet] puts in each record batch.
+const ROWS_PER_BATCH: usize = 65536;
+
+/// The schema of [signature_record_batch]es of signatures computed with `params`: a string
+/// [ID_COLUMN] and a [SIGNATURE_COLUMN] of fixed size binary values holding one byte per element,
+/// which reads as a signed 8-bit integer. The params are recorded in the schema's metadata, under
+/// keys like `image_match.grid_size`.
+pub fn signature_schema(params: &SignatureParams) -> Schema {
+    let length = params.signature_length() as i32;
+    let metadata = HashMap::from([
+        ("crop", params.crop().to_string()),
+        ("grid_size", params.grid_size().to_string()),
+        ("identical_tolerance", params.identical_tolerance().to_string()),
+        ("grid_placement", format!("{:?}", params.grid_placement())),
+        ("neighbor_padding", format!("{:?}", params.neighbor_padding())),
+        ("compat", format!("{:?}", params.compat())),
+        ("grayscale_mode", format!("{:?}", params.grayscale_mode())),
+        ("alpha_mode", format!("{:?}", params.alpha_mode())),
+        ("arithmetic", format!("{:?}", params.arithmetic())),
+    ]);
+    Schema::new(vec![
+        Field::new(ID_COLUMN, DataType::Utf8, false),
+        Field::new(SIGNATURE_COLUMN, DataType::FixedSizeBinary(length), false),
+    ])
+    .with_metadata(
+        metadata.into_iter().map(|(key, value)| (format!("image_match.{}", key), value)).collect(),
+    )
+}
+
+/// The signatures as a column of fixed size binary values, one byte per element, for adding to
+/// record batches with other columns.
+///
+/// Returns [SignatureError::IncompatibleSignatures] if any signature wasn't computed with
+/// `params`.
+pub fn signature_array<'a, I>(
+    params: &SignatureParams,
+    signatures: I,
+) -> Result<ArrayRef, SignatureError>
+where
+    I: IntoIterator<Item = &'a Signature>,
+{
+    let mut values = Vec::new();
+    for signature in signatures {
+        params.check_compatible(signature.params())?;
+        values.extend(signature.iter().map(|element| *element as u8));
+    }
+    let length = params.signature_length() as i32;
+    Ok(Arc::new(FixedSizeBinaryArray::new(length, values.into(), None)))
+}
+
+/// A record batch of ids and signatures computed with `params`, with the [signature_schema].
+///
+/// Returns [SignatureError::IncompatibleSignatures] if any signature wasn't computed with
+/// `params`.
+pub fn signature_record_batch<'a, I>(
+    params: &SignatureParams,
+    records: I,
+) -> Result<RecordBatch, SignatureError>
+where
+    I: IntoIterator<Item = (&'a str, &'a Signature)>,
+{
+    let (ids, signatures): (Vec<&str>, Vec<&Signature>) = records.into_iter().unzip();
+    let ids: ArrayRef = Arc::new(StringArray::from(ids));
+    let signatures = signature_array(params, signatures)?;
+    let batch = RecordBatch::try_new(Arc::new(signature_schema(params)), vec![ids, signatures]);
+    Ok(batch.expect("columns match the schema"))
+}
+
+/// Writes ids and signatures computed with `params` as a Parquet file with the
+/// [signature_schema], without compression.
+///
+/// Returns an error if writing fails, or an external error holding
+/// [SignatureError::IncompatibleSignatures] if any signature wasn't computed with `params`.
+pub fn write_parquet<'a, W, I>(
+    writer: W,
+    params: &SignatureParams,
+    records: I,
+) -> Result<(), ParquetError>
+where
+    W: Write + Send,
+    I: IntoIterator<Item = (&'a str, &'a Signature)>,
+{
+    let mut writer = ArrowWriter::try_new(writer, Arc::new(signature_schema(params)), None)?;
+    let mut records = records.into_iter().peekable();
+    while records.peek().is_some() {
+        let batch = signature_record_batch(params, records.by_ref().take(ROWS_PER_BATCH))
+            .map_err(|e| ParquetError::External(Box::new(e)))?;
+        writer.write(&batch)?;
+    }
+    writer.close()?;
+    Ok(())
+}
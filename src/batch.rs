@@ -0,0 +1,79 @@
+#[cfg(not(feature = "rayon"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{SignatureConfig, SignatureError};
+
+/// Computes the signatures of many images at once, spread over a pool of threads, with default
+/// settings. Each image is a buffer of conceptually grouped RGBA bytes and its width. Results are
+/// in the order of `images`, and an image whose signature can't be computed doesn't stop the
+/// rest, see [SignatureConfig::compute_batch] for details.
+pub fn compute_batch<B: AsRef<[u8]> + Sync>(
+    images: &[(B, usize)],
+) -> Vec<Result<Vec<i8>, SignatureError>> {
+    SignatureConfig::default().compute_batch(images)
+}
+
+impl SignatureConfig {
+    /// Computes the signatures of many images at once, spread over a pool of threads. Each image
+    /// is a buffer in the configured [crate::PixelFormat] and its width. Results are in the order
+    /// of `images`, each as [SignatureConfig::try_compute] would return it.
+    ///
+    /// Each thread computes one signature at a time with its own [crate::SignatureComputer], so
+    /// the memory used while computing is bounded by the number of threads and the largest image,
+    /// however many images there are. Threads come from rayon's pool with the `rayon` feature, or
+    /// one per available core otherwise.
+    pub fn compute_batch<B: AsRef<[u8]> + Sync>(
+        &self,
+        images: &[(B, usize)],
+    ) -> Vec<Result<Vec<i8>, SignatureError>> {
+        #[cfg(feature = "rayon")]
+        return images.par_iter()
+            .map_init(|| self.computer(), |computer, (buffer, width)| {
+                computer.try_compute(buffer.as_ref(), *width)
+            })
+            .collect();
+
+        #[cfg(not(feature = "rayon"))]
+        return self.compute_batch_threads(images);
+    }
+
+    /// Workers take the next image off a shared counter until none are left, so a slow image
+    /// only holds up the thread computing it.
+    #[cfg(not(feature = "rayon"))]
+    fn compute_batch_threads<B: AsRef<[u8]> + Sync>(
+        &self,
+        images: &[(B, usize)],
+    ) -> Vec<Result<Vec<i8>, SignatureError>> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let next = AtomicUsize::new(0);
+        let work = || {
+            let mut computer = self.computer();
+            let mut signatures = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some((buffer, width)) = images.get(index) else {
+                    return signatures;
+                };
+                signatures.push((index, computer.try_compute(buffer.as_ref(), *width)));
+            }
+        };
+
+        let mut results: Vec<_> = images.iter().map(|_| None).collect();
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.min(images.len()))
+                .map(|_| scope.spawn(work))
+                .collect();
+            for worker in workers {
+                let signatures = worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e));
+                for (index, signature) in signatures {
+                    results[index] = Some(signature);
+                }
+            }
+        });
+
+        results.into_iter().map(|result| result.expect("every image is computed")).collect()
+    }
+}
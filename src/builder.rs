@@ -0,0 +1,76 @@
+use crate::{
+    check_buffer_length, compute_from_gray, grayscale_rows_into, GrayImage, SignatureConfig,
+    SignatureError,
+};
+
+/// Computes a signature from rows of pixels pushed as they're decoded, so that a huge image never
+/// has to be held in memory in its decoded form. Rows are converted to gray as soon as they arrive,
+/// leaving a quarter of the memory of the RGBA pixels.
+///
+/// Where the grid lands depends on the crop, which depends on every row of the image, so the gray
+/// levels of all rows are kept until [SignatureBuilder::finish].
+///
+/// ```
+/// use image_match::{SignatureBuilder, SignatureConfig};
+///
+/// # let rgba: Vec<u8> = (0..4 * 120 * 80).map(|i| ((i % 480) * (i / 480) % 251) as u8).collect();
+/// let mut builder = SignatureBuilder::new(120);
+/// for rows in rgba.chunks(4 * 120 * 16) {
+///     builder.push_rows(rows).unwrap();
+/// }
+///
+/// assert_eq!(builder.finish().unwrap(), SignatureConfig::default().compute(&rgba, 120));
+/// ```
+#[derive(Clone, Debug)]
+pub struct SignatureBuilder {
+    config: SignatureConfig,
+    width: usize,
+    gray: Vec<u8>,
+}
+
+impl SignatureBuilder {
+    /// Starts a signature for an image of the provided width, with default settings.
+    pub fn new(width: usize) -> Self {
+        SignatureConfig::default().builder(width)
+    }
+
+    /// Converts one or more whole rows of pixels, laid out in the configured pixel format, and
+    /// adds them to the bottom of the image. Returns an error if `rows` doesn't hold a whole number
+    /// of rows.
+    pub fn push_rows(&mut self, rows: &[u8]) -> Result<(), SignatureError> {
+        let channels = self.config.pixel_format.channels();
+        check_buffer_length(rows.len(), self.width, channels)?;
+
+        let first_y = self.rows();
+        let start = self.gray.len();
+        self.gray.resize(start + rows.len() / channels, 0);
+        grayscale_rows_into(
+            rows.chunks_exact(channels * self.width),
+            first_y,
+            self.config.pixel_format,
+            &self.config,
+            &mut self.gray[start..],
+        );
+
+        Ok(())
+    }
+
+    /// The number of rows pushed so far.
+    pub fn rows(&self) -> usize {
+        self.gray.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    /// Produces the signature of the rows pushed so far. Returns an error if the image is too
+    /// small or too featureless to place the signature grid on.
+    pub fn finish(self) -> Result<Vec<i8>, SignatureError> {
+        let height = self.rows();
+        compute_from_gray(&GrayImage::from_raw(self.gray, self.width, height), &self.config)
+    }
+}
+
+impl SignatureConfig {
+    /// Starts a [SignatureBuilder] for an image of the provided width, computed with this config.
+    pub fn builder(&self, width: usize) -> SignatureBuilder {
+        SignatureBuilder { config: *self, width, gray: Vec::new() }
+    }
+}
@@ -0,0 +1,193 @@
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::index::{emptied, HnswIndex, Index};
+use crate::SignatureError;
+
+/// Groups `signatures` whose cosine similarity is at least `threshold` into clusters of
+/// duplicates, as the positions of their members in `signatures`, in order. A signature joins a
+/// cluster by being similar enough to any one of its members, so a chain of slight edits ends up
+/// in one cluster even when its ends are no longer similar. Signatures without a duplicate are
+/// left out, and clusters are in order of their first member.
+///
+/// Each signature's duplicates are found through an [HnswIndex], so in roughly logarithmic time,
+/// and in parallel with the `rayon` feature. The search is approximate, but a pair it misses from
+/// one side is usually found from the other, or through another member of the cluster; see
+/// [cluster_duplicates_with] to search through another index.
+///
+/// Panics if the signatures aren't all the same length, see [try_cluster_duplicates] for a
+/// non-panicking version.
+pub fn cluster_duplicates<S: AsRef<[i8]>>(signatures: &[S], threshold: f64) -> Vec<Vec<usize>> {
+    try_cluster_duplicates(signatures, threshold).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// A non-panicking version of [cluster_duplicates(signatures, threshold)] which returns an error
+/// rather than crashing when the signatures aren't all the same length.
+pub fn try_cluster_duplicates<S: AsRef<[i8]>>(
+    signatures: &[S],
+    threshold: f64,
+) -> Result<Vec<Vec<usize>>, SignatureError> {
+    try_cluster_duplicates_with(HnswIndex::new(), signatures, threshold)
+}
+
+/// Groups `signatures` into clusters of duplicates like
+/// [cluster_duplicates(signatures, threshold)], finding each signature's duplicates through
+/// `index`, after dropping any signatures already in it, e.g. a
+/// [LinearIndex](crate::index::LinearIndex) for exact clusters of up to a few thousand signatures.
+///
+/// Panics if the signatures aren't all the same length, see [try_cluster_duplicates_with] for a
+/// non-panicking version.
+pub fn cluster_duplicates_with<I, S>(
+    index: I,
+    signatures: &[S],
+    threshold: f64,
+) -> Vec<Vec<usize>>
+where
+    I: Index + Sync,
+    S: AsRef<[i8]>,
+{
+    try_cluster_duplicates_with(index, signatures, threshold).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// A non-panicking version of [cluster_duplicates_with(index, signatures, threshold)] which
+/// returns an error rather than crashing when the signatures aren't all the same length.
+pub fn try_cluster_duplicates_with<I, S>(
+    index: I,
+    signatures: &[S],
+    threshold: f64,
+) -> Result<Vec<Vec<usize>>, SignatureError>
+where
+    I: Index + Sync,
+    S: AsRef<[i8]>,
+{
+    let mut index = emptied(index);
+    for signature in signatures {
+        index.try_insert(signature.as_ref())?;
+    }
+    let duplicates = |key: usize| -> Vec<usize> {
+        let signature = index.get(key).expect("every signature was inserted");
+        index.find_all_within(signature, threshold)
+            .map(|(duplicate, _)| duplicate)
+            .filter(|duplicate| *duplicate != key)
+            .collect()
+    };
+
+    #[cfg(feature = "rayon")]
+    let duplicates: Vec<Vec<usize>> = (0..index.len()).into_par_iter().map(duplicates).collect();
+    #[cfg(not(feature = "rayon"))]
+    let duplicates: Vec<Vec<usize>> = (0..index.len()).map(duplicates).collect();
+
+    // Approximate indexes may find a pair from one side only, which links it all the same.
+    let mut sets = DisjointSets::new(signatures.len());
+    for (key, duplicates) in duplicates.into_iter().enumerate() {
+        for duplicate in duplicates {
+            sets.union(key, duplicate);
+        }
+    }
+
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    let mut cluster_of_root = vec![None; signatures.len()];
+    for key in 0..signatures.len() {
+        let root = sets.find(key);
+        if sets.sizes[root] < 2 {
+            continue;
+        }
+        let cluster = *cluster_of_root[root].get_or_insert_with(|| {
+            clusters.push(Vec::new());
+            clusters.len() - 1
+        });
+        clusters[cluster].push(key);
+    }
+    Ok(clusters)
+}
+
+/// The element-wise median of `signatures`, a representative of a cluster of them, e.g. one found
+/// by [cluster_duplicates], for matching in two stages: a query against each cluster's centroid
+/// first, then against the members of the clusters whose centroid it's similar to.
+///
+/// Each element is the median of the signatures' elements at its position, so already one of -2
+/// to 2. With an even number of signatures it's the mean of the two middle elements rounded
+/// toward zero, so that negating every signature negates the centroid. The centroid of no
+/// signatures is empty.
+///
+/// Panics if the signatures aren't all the same length, or have elements outside of -2 to 2, see
+/// [try_centroid] for a non-panicking version.
+pub fn centroid<S: AsRef<[i8]>>(signatures: &[S]) -> Vec<i8> {
+    try_centroid(signatures).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// A non-panicking version of [centroid(signatures)] which returns an error rather than crashing
+/// when the signatures aren't all the same length, or have elements outside of -2 to 2.
+pub fn try_centroid<S: AsRef<[i8]>>(signatures: &[S]) -> Result<Vec<i8>, SignatureError> {
+    let Some(first) = signatures.first() else {
+        return Ok(Vec::new());
+    };
+    let length = first.as_ref().len();
+    // How many signatures have each of -2 to 2 at each position.
+    let mut counts = vec![[0usize; 5]; length];
+    for signature in signatures {
+        let signature = signature.as_ref();
+        if signature.len() != length {
+            return Err(SignatureError::MismatchedLengths { left: length, right: signature.len() });
+        }
+        for (index, (&value, counts)) in signature.iter().zip(&mut counts).enumerate() {
+            if !(-2..=2).contains(&value) {
+                return Err(SignatureError::ElementOutOfRange { index, value });
+            }
+            counts[(value + 2) as usize] += 1;
+        }
+    }
+
+    // The elements at the middle positions, counting from 0, of each position's sorted elements.
+    let (lower, upper) = ((signatures.len() - 1) / 2, signatures.len() / 2);
+    let nth = |counts: &[usize; 5], n: usize| -> i8 {
+        let mut seen = 0;
+        let value = counts.iter()
+            .position(|count| {
+                seen += count;
+                seen > n
+            })
+            .expect("every signature has an element at each position");
+        value as i8 - 2
+    };
+    Ok(counts.iter()
+        .map(|counts| {
+            let (lower, upper) = (nth(counts, lower), nth(counts, upper));
+            // Integer division truncates toward zero.
+            (lower + upper) / 2
+        })
+        .collect())
+}
+
+/// A union-find forest over `0..n`, with union by size and path halving.
+struct DisjointSets {
+    parents: Vec<usize>,
+    /// The number of elements in the set of each root.
+    sizes: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(n: usize) -> Self {
+        DisjointSets { parents: (0..n).collect(), sizes: vec![1; n] }
+    }
+
+    /// The root of the set holding `element`.
+    fn find(&mut self, mut element: usize) -> usize {
+        while self.parents[element] != element {
+            self.parents[element] = self.parents[self.parents[element]];
+            element = self.parents[element];
+        }
+        element
+    }
+
+    /// Merges the sets holding `a` and `b`.
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        let (larger, smaller) = if self.sizes[a] >= self.sizes[b] { (a, b) } else { (b, a) };
+        self.parents[smaller] = larger;
+        self.sizes[larger] += self.sizes[smaller];
+    }
+}
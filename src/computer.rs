@@ -0,0 +1,120 @@
+use crate::{
+    check_buffer_length, compute_from_gray_with, goldberg, grayscale_rows_into, Compat, GrayImage,
+    SignatureConfig, SignatureError,
+};
+
+/// Computes signatures one after another while reusing the memory of the previous computation,
+/// so that a long run of images of similar sizes doesn't allocate for the gray image, the crop
+/// sums, or the signature grid on every call.
+///
+/// ```
+/// use image_match::{SignatureComputer, SignatureConfig};
+///
+/// # let images: Vec<Vec<u8>> = (1..4)
+/// #     .map(|n| (0..4 * 120 * 80).map(|i| ((i % 480) * (i / 480) * n % 251) as u8).collect())
+/// #     .collect();
+/// let mut computer = SignatureComputer::new();
+/// for rgba in &images {
+///     assert_eq!(computer.compute(rgba, 120), SignatureConfig::default().compute(rgba, 120));
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SignatureComputer {
+    config: SignatureConfig,
+    gray: Vec<u8>,
+    scratch: Scratch,
+}
+
+/// The intermediate results of a signature, kept between computations for their allocations.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Scratch {
+    pub(crate) row_diff_sums: Vec<u64>,
+    pub(crate) col_diff_sums: Vec<u64>,
+    pub(crate) points: Vec<(usize, usize)>,
+    pub(crate) averages: Vec<u8>,
+    pub(crate) raw_diffs: Vec<i16>,
+    pub(crate) sorted_diffs: Vec<i16>,
+}
+
+impl SignatureComputer {
+    /// Creates a computer with default settings.
+    pub fn new() -> Self {
+        SignatureConfig::default().computer()
+    }
+
+    /// Produces a signature for a provided image that's encoded as an array of conceptually
+    /// grouped bytes in the configured [crate::PixelFormat], RGBA by default, with the provided
+    /// width.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureComputer::try_compute] for
+    /// details.
+    pub fn compute(&mut self, buffer: &[u8], width: usize) -> Vec<i8> {
+        self.try_compute(buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureComputer::compute]. Returns an error if the buffer
+    /// doesn't hold whole rows of pixels, or if the image is too small or too featureless to
+    /// place the signature grid on.
+    pub fn try_compute(&mut self, buffer: &[u8], width: usize) -> Result<Vec<i8>, SignatureError> {
+        self.config.validate()?;
+        let mut signature = vec![0; self.config.signature_length()];
+        self.try_compute_into(buffer, width, &mut signature)?;
+        Ok(signature)
+    }
+
+    /// Like [SignatureComputer::compute], but writes the signature to `out` rather than allocating
+    /// a vector for it. Once the computer has seen an image at least as large, this doesn't
+    /// allocate at all.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureComputer::try_compute_into] for
+    /// details.
+    pub fn compute_into(&mut self, buffer: &[u8], width: usize, out: &mut [i8]) {
+        self.try_compute_into(buffer, width, out).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureComputer::compute_into]. In addition to the errors of
+    /// [SignatureComputer::try_compute], returns [SignatureError::MismatchedLengths] if `out` isn't
+    /// the config's signature length. The contents of `out` are unspecified after an error.
+    pub fn try_compute_into(
+        &mut self,
+        buffer: &[u8],
+        width: usize,
+        out: &mut [i8],
+    ) -> Result<(), SignatureError> {
+        if self.config.compat == Compat::Goldberg {
+            return goldberg::compute_into(buffer, width, &self.config, out);
+        }
+
+        let channels = self.config.pixel_format.channels();
+        check_buffer_length(buffer.len(), width, channels)?;
+
+        let mut gray = std::mem::take(&mut self.gray);
+        gray.resize(buffer.len() / channels, 0);
+        grayscale_rows_into(
+            buffer.chunks_exact(channels * width),
+            0,
+            self.config.pixel_format,
+            &self.config,
+            &mut gray,
+        );
+
+        let height = gray.len() / width;
+        let gray = GrayImage::from_raw(gray, width, height);
+        let result = compute_from_gray_with(&gray, &self.config, &mut self.scratch, out);
+        self.gray = gray.into_data();
+        result
+    }
+}
+
+impl Default for SignatureComputer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SignatureConfig {
+    /// Creates a [SignatureComputer] that computes signatures with this config.
+    pub fn computer(&self) -> SignatureComputer {
+        SignatureComputer { config: *self, gray: Vec::new(), scratch: Scratch::default() }
+    }
+}
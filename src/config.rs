@@ -0,0 +1,832 @@
+use std::cmp::{max, min};
+
+use crate::computer::Scratch;
+use crate::goldberg;
+use crate::{
+    compute_from_gray, compute_from_gray_with, gray_buffer_rows, grayscale_buffer,
+    grayscale_strided_buffer, signature_length, tonemap_buffer, SignatureError,
+};
+
+pub(crate) const DEFAULT_CROP: f32 = 0.05;
+pub(crate) const DEFAULT_GRID_SIZE: usize = 10;
+
+/// The paper's tolerance of 2 gray levels, which [SignatureConfig::identical_tolerance] defaults
+/// to. Suits photos and other images with a little noise.
+pub const DEFAULT_IDENTICAL_TOLERANCE: u8 = 2;
+
+/// A tolerance for clean synthetic images like screenshots, renders, and diagrams, whose flat
+/// areas are exactly flat, so that subtle gradients register. Still allows for the rounding of
+/// a lossy re-encode.
+pub const CLEAN_RENDER_TOLERANCE: u8 = 1;
+
+/// A tolerance for noisy sources like scans, photocopies, and low-light photos, whose paper
+/// texture, grain, and uneven lighting would otherwise register as detail in flat areas.
+pub const NOISY_SCAN_TOLERANCE: u8 = 6;
+
+/// The smallest grid with points that have neighbors to compare against.
+const MIN_GRID_SIZE: usize = 3;
+
+/// The number of pixels per grid cell that [SmallImagePolicy::Upscale] enlarges the shorter side
+/// of small images to, at least.
+const UPSCALED_CELL_SIDE: usize = 4;
+
+/// Tuning parameters for signature computation. A default config reproduces the parameters from
+/// the paper and the un-tuned signature functions; each builder method overrides a single knob.
+///
+/// ```
+/// use image_match::SignatureConfig;
+///
+/// let config = SignatureConfig::new()
+///     .crop(0.1)
+///     .grid_size(12);
+/// # let rgba = vec![0_u8; 4 * 64 * 64];
+/// let signature = config.compute(&rgba, 64);
+/// ```
+///
+/// Signatures are only comparable to other signatures produced with an identical config. Options
+/// added over time default to the behavior from before they existed, so signatures computed with
+/// a default config stay comparable across releases unless a release notes otherwise.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
+pub struct SignatureConfig {
+    pub(crate) crop: f32,
+    pub(crate) grid_size: usize,
+    pub(crate) identical_tolerance: u8,
+    pub(crate) pixel_format: PixelFormat,
+    pub(crate) grayscale_mode: GrayscaleMode,
+    pub(crate) alpha_mode: AlphaMode,
+    pub(crate) tonemap: Tonemap,
+    pub(crate) arithmetic: Arithmetic,
+    pub(crate) grid_placement: GridPlacement,
+    pub(crate) neighbor_padding: NeighborPadding,
+    pub(crate) compat: Compat,
+    pub(crate) small_image_policy: SmallImagePolicy,
+    pub(crate) reject_flat_images: bool,
+    pub(crate) exif_orientation: bool,
+    pub(crate) icc_profiles: bool,
+    pub(crate) max_megapixels: Option<f32>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) average_square_width_fn: fn(width: usize, height: usize) -> usize,
+}
+
+impl SignatureConfig {
+    /// Creates a config with the default parameters from the paper.
+    pub fn new() -> Self {
+        SignatureConfig {
+            crop: DEFAULT_CROP,
+            grid_size: DEFAULT_GRID_SIZE,
+            identical_tolerance: DEFAULT_IDENTICAL_TOLERANCE,
+            pixel_format: PixelFormat::Rgba,
+            grayscale_mode: GrayscaleMode::Average,
+            alpha_mode: AlphaMode::Multiply,
+            tonemap: Tonemap::Clamp,
+            arithmetic: Arithmetic::Float,
+            grid_placement: GridPlacement::ImageOrigin,
+            neighbor_padding: NeighborPadding::Omit,
+            compat: Compat::Native,
+            small_image_policy: SmallImagePolicy::Error,
+            reject_flat_images: false,
+            exif_orientation: true,
+            icc_profiles: true,
+            max_megapixels: None,
+            average_square_width_fn: default_average_square_width,
+        }
+    }
+
+    /// A value in [0, 0.5) indicating what percentage of the image to crop on all sides before grid
+    /// placement. Note that this percentage is based not on the raw width but a calculation of
+    /// color density. Defaults to `0.05`.
+    pub fn crop(mut self, crop: f32) -> Self {
+        self.crop = crop;
+        self
+    }
+
+    /// How many points to place on the image for measurement in the resulting signature. Changing
+    /// `grid_size` will alter the length of the signature to
+    /// `8 * (grid_size - 1)^2 - 12 * (grid_size - 3) - 20`, unless neighbors are padded, see
+    /// [SignatureConfig::neighbor_padding]. Must be at least 3, and images must be larger than
+    /// `grid_size` pixels along both sides, see [SmallImagePolicy]. Defaults to `10`.
+    pub fn grid_size(mut self, grid_size: usize) -> Self {
+        self.grid_size = grid_size;
+        self
+    }
+
+    /// The largest difference in gray level, on a scale of 0 to 255, between two neighboring grid
+    /// points that's still considered "same". Defaults to [DEFAULT_IDENTICAL_TOLERANCE]; see
+    /// [CLEAN_RENDER_TOLERANCE] and [NOISY_SCAN_TOLERANCE] for starting points for other kinds
+    /// of images.
+    pub fn identical_tolerance(mut self, identical_tolerance: u8) -> Self {
+        self.identical_tolerance = identical_tolerance;
+        self
+    }
+
+    /// The channel order of color buffers passed to [SignatureConfig::compute] and its strided,
+    /// 16-bit, and `f32` variants. Defaults to [PixelFormat::Rgba].
+    pub fn pixel_format(mut self, pixel_format: PixelFormat) -> Self {
+        self.pixel_format = pixel_format;
+        self
+    }
+
+    /// How color pixels are converted to gray before the rest of the pipeline. Defaults to
+    /// [GrayscaleMode::Average]. Has no effect on gray inputs.
+    pub fn grayscale_mode(mut self, grayscale_mode: GrayscaleMode) -> Self {
+        self.grayscale_mode = grayscale_mode;
+        self
+    }
+
+    /// How transparency is accounted for when converting to gray. Defaults to
+    /// [AlphaMode::Multiply]. Has no effect on inputs without an alpha channel.
+    pub fn alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// How linear high dynamic range values are brought into displayable range before the
+    /// grayscale conversion. Defaults to [Tonemap::Clamp]. Only affects `f32` inputs.
+    pub fn tonemap(mut self, tonemap: Tonemap) -> Self {
+        self.tonemap = tonemap;
+        self
+    }
+
+    /// Whether the grayscale conversion and crop use floating point or integer arithmetic. Defaults
+    /// to [Arithmetic::Float].
+    pub fn arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    /// Where the grid is placed relative to the crop. Defaults to [GridPlacement::ImageOrigin].
+    pub fn grid_placement(mut self, grid_placement: GridPlacement) -> Self {
+        self.grid_placement = grid_placement;
+        self
+    }
+
+    /// How the comparisons with neighbors that grid points on the edge of the grid lack are
+    /// represented. Defaults to [NeighborPadding::Omit].
+    pub fn neighbor_padding(mut self, neighbor_padding: NeighborPadding) -> Self {
+        self.neighbor_padding = neighbor_padding;
+        self
+    }
+
+    /// Which implementation's signatures to reproduce. Defaults to [Compat::Native].
+    pub fn compat(mut self, compat: Compat) -> Self {
+        self.compat = compat;
+        self
+    }
+
+    /// What happens to images too small to place the grid on, like thumbnails and icons. Defaults
+    /// to [SmallImagePolicy::Error].
+    pub fn small_image_policy(mut self, small_image_policy: SmallImagePolicy) -> Self {
+        self.small_image_policy = small_image_policy;
+        self
+    }
+
+    /// Whether images without any detail at the scale of the grid, like solid colors, are rejected
+    /// with [SignatureError::FlatImage] rather than given an all-zero signature. All-zero
+    /// signatures have no direction to compare, so
+    /// [cosine_similarity(a, b)](crate::cosine_similarity) can only report them as entirely
+    /// dissimilar to everything but each other. Defaults to `false`.
+    pub fn reject_flat_images(mut self, reject_flat_images: bool) -> Self {
+        self.reject_flat_images = reject_flat_images;
+        self
+    }
+
+    /// Whether encoded images are turned upright by their EXIF Orientation tag before they're
+    /// signed, so that a phone photo rotated only by its metadata signs like a copy whose pixels
+    /// were rotated. Only affects images decoded from files, bytes, or streams with the `img`
+    /// feature, and never those signed with [Compat::Goldberg], since the Python library signs
+    /// the stored pixels. Defaults to `true`.
+    pub fn exif_orientation(mut self, exif_orientation: bool) -> Self {
+        self.exif_orientation = exif_orientation;
+        self
+    }
+
+    /// Whether the colors of encoded images with an embedded ICC profile, like wide-gamut or
+    /// linear-light exports, are converted to sRGB before they're signed, so that they sign like
+    /// their sRGB exports. Color images of more than 8 bits per channel are brought down to 8 bits
+    /// for the conversion. Only affects JPEG, PNG, WebP, and TIFF images decoded from files,
+    /// bytes, or streams with the `img` feature, and never those signed with [Compat::Goldberg],
+    /// since the Python library ignores profiles. Defaults to `true`.
+    pub fn icc_profiles(mut self, icc_profiles: bool) -> Self {
+        self.icc_profiles = icc_profiles;
+        self
+    }
+
+    /// Whether decoded images are turned upright, see [SignatureConfig::exif_orientation].
+    #[cfg(feature = "img")]
+    pub(crate) fn orients_images(&self) -> bool {
+        self.exif_orientation && self.compat == Compat::Native
+    }
+
+    /// Whether decoded images are converted to sRGB, see [SignatureConfig::icc_profiles].
+    #[cfg(feature = "img")]
+    pub(crate) fn converts_profiles(&self) -> bool {
+        self.icc_profiles && self.compat == Compat::Native
+    }
+
+    /// The largest image, in millions of pixels, that's signed at full size. Larger images are
+    /// shrunk by a whole factor with a box filter, averaging each square of pixels into one, until
+    /// they're within the limit, e.g. a 100 megapixel scan by 3 along both sides for a limit of 12.
+    /// The grid averages squares far larger than the filter's, so signatures barely change, while
+    /// the rest of the pipeline reads a fraction of the pixels. Images are never shrunk below four
+    /// pixels per grid cell along their shorter side. Defaults to `None`, signing every image at
+    /// full size.
+    pub fn max_megapixels(mut self, max_megapixels: Option<f32>) -> Self {
+        self.max_megapixels = max_megapixels;
+        self
+    }
+
+    /// Checks the tuning parameters are within the ranges signatures can be computed with: `crop`
+    /// in [0, 0.5), `grid_size` at least 3, and `max_megapixels` positive. Every computation
+    /// checks them as well, returning the same [SignatureError::InvalidParameter].
+    pub fn validate(&self) -> Result<(), SignatureError> {
+        if !(0.0..0.5).contains(&self.crop) {
+            return Err(SignatureError::InvalidParameter {
+                name: "crop",
+                value: self.crop.to_string(),
+                supported: "[0, 0.5)",
+            });
+        }
+        // The signature's length must also be representable, which only rules out absurd grids.
+        let representable = self.grid_size.checked_pow(2).and_then(|n| n.checked_mul(8)).is_some();
+        if self.grid_size < MIN_GRID_SIZE || !representable {
+            return Err(SignatureError::InvalidParameter {
+                name: "grid_size",
+                value: self.grid_size.to_string(),
+                supported: "3 or more",
+            });
+        }
+        let unsupported = |max: &f32| max.is_nan() || *max <= 0.0;
+        if let Some(max_megapixels) = self.max_megapixels.filter(unsupported) {
+            return Err(SignatureError::InvalidParameter {
+                name: "max_megapixels",
+                value: max_megapixels.to_string(),
+                supported: "greater than 0",
+            });
+        }
+        Ok(())
+    }
+
+    /// The length of signatures computed with this config, which depends on the grid size and
+    /// [NeighborPadding]. Like [signature_length](crate::signature_length), grids too large for
+    /// [SignatureConfig::validate] give `usize::MAX`.
+    pub fn signature_length(&self) -> usize {
+        match (self.compat, self.neighbor_padding) {
+            (Compat::Native, NeighborPadding::Omit) => signature_length(self.grid_size),
+            _ => {
+                let side = self.grid_size.saturating_sub(1);
+                side.checked_mul(side).and_then(|n| n.checked_mul(8)).unwrap_or(usize::MAX)
+            }
+        }
+    }
+
+    /// Controls the size of the box around each grid point that's averaged to produce that grid
+    /// point's brightness value. The function receives the cropped width and height and returns
+    /// the half-width of the box. The paper proposes
+    /// `max(2, floor(0.5 + min(cropped_width, cropped_height) / 20))` but provides no information
+    /// about how that was chosen. Functions can't be serialized, so configs deserialized with the
+    /// `serde` feature always use the paper's.
+    pub fn average_square_width_fn(
+        mut self,
+        average_square_width_fn: fn(width: usize, height: usize) -> usize,
+    ) -> Self {
+        self.average_square_width_fn = average_square_width_fn;
+        self
+    }
+
+    /// Produces a signature for a provided image that's encoded as an array of conceptually
+    /// grouped bytes in the configured [PixelFormat], RGBA by default, with the provided width. The
+    /// result is designed to be compared to other signatures computed with an identical config
+    /// using [cosine-similarity(a, b)].
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute] for details.
+    pub fn compute(&self, rgba_buffer: &[u8], width: usize) -> Vec<i8> {
+        self.try_compute(rgba_buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute]. Returns an error if the tuning
+    /// parameters are invalid, see [SignatureConfig::validate], if the buffer doesn't hold whole
+    /// rows of pixels, or if the image is too small or too featureless to place the signature grid
+    /// on.
+    pub fn try_compute(
+        &self,
+        rgba_buffer: &[u8],
+        width: usize,
+    ) -> Result<Vec<i8>, SignatureError> {
+        if self.compat == Compat::Goldberg {
+            self.validate()?;
+            let mut signature = vec![0; self.signature_length()];
+            goldberg::compute_into(rgba_buffer, width, self, &mut signature)?;
+            return Ok(signature);
+        }
+
+        let gray = grayscale_buffer(rgba_buffer, width, self.pixel_format, self)?;
+        compute_from_gray(&gray, self)
+    }
+
+    /// Like [SignatureConfig::compute], but also checks the buffer holds exactly `height` rows, so
+    /// that a truncated read is caught rather than signed as a shorter image.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_sized] for
+    /// details.
+    pub fn compute_sized(&self, buffer: &[u8], width: usize, height: usize) -> Vec<i8> {
+        self.try_compute_sized(buffer, width, height).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_sized]. In addition to the errors of
+    /// [SignatureConfig::try_compute], returns [SignatureError::BufferLengthMismatch] if the
+    /// buffer isn't exactly as long as `width` by `height` pixels in the configured format.
+    pub fn try_compute_sized(
+        &self,
+        buffer: &[u8],
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<i8>, SignatureError> {
+        let expected = self
+            .pixel_format
+            .channels()
+            .checked_mul(width)
+            .and_then(|row_len| row_len.checked_mul(height))
+            .ok_or(SignatureError::BadBufferLength { len: buffer.len(), width })?;
+        if buffer.len() != expected {
+            return Err(SignatureError::BufferLengthMismatch { expected, actual: buffer.len() });
+        }
+
+        self.try_compute(buffer, width)
+    }
+
+    /// Like [SignatureConfig::compute], but writes the signature to `out` rather than allocating a
+    /// vector for it.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_into] for
+    /// details.
+    pub fn compute_into(&self, buffer: &[u8], width: usize, out: &mut [i8]) {
+        self.try_compute_into(buffer, width, out).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_into]. In addition to the errors of
+    /// [SignatureConfig::try_compute], returns [SignatureError::MismatchedLengths] if `out` isn't
+    /// [SignatureConfig::signature_length] long. The contents of `out` are unspecified after an
+    /// error.
+    pub fn try_compute_into(
+        &self,
+        buffer: &[u8],
+        width: usize,
+        out: &mut [i8],
+    ) -> Result<(), SignatureError> {
+        if self.compat == Compat::Goldberg {
+            return goldberg::compute_into(buffer, width, self, out);
+        }
+
+        let gray = grayscale_buffer(buffer, width, self.pixel_format, self)?;
+        compute_from_gray_with(&gray, self, &mut Scratch::default(), out)
+    }
+
+    /// Produces a signature for a provided image that's encoded as rows of conceptually grouped
+    /// bytes in the configured [PixelFormat], where each row starts `stride` bytes after the start
+    /// of the previous one.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_strided] for
+    /// details.
+    pub fn compute_strided(
+        &self,
+        rgba_buffer: &[u8],
+        width: usize,
+        height: usize,
+        stride: usize,
+    ) -> Vec<i8> {
+        self.try_compute_strided(rgba_buffer, width, height, stride)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_strided]. Returns an error if the
+    /// stride is shorter than a row of pixels, if the buffer is too short to hold `height`
+    /// rows, or if the image is too small or too featureless to place the signature grid on.
+    pub fn try_compute_strided(
+        &self,
+        rgba_buffer: &[u8],
+        width: usize,
+        height: usize,
+        stride: usize,
+    ) -> Result<Vec<i8>, SignatureError> {
+        let gray = grayscale_strided_buffer(
+            rgba_buffer, width, height, stride, self.pixel_format, self,
+        )?;
+        compute_from_gray(&gray, self)
+    }
+
+    /// Produces a signature for a provided image that's encoded as an array of conceptually
+    /// grouped RGB bytes, without an alpha channel, with the provided width.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_rgb] for
+    /// details.
+    pub fn compute_rgb(&self, rgb_buffer: &[u8], width: usize) -> Vec<i8> {
+        self.try_compute_rgb(rgb_buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_rgb]. Returns an error if the buffer
+    /// doesn't hold whole rows of RGB pixels, or if the image is too small or too featureless to
+    /// place the signature grid on.
+    pub fn try_compute_rgb(
+        &self,
+        rgb_buffer: &[u8],
+        width: usize,
+    ) -> Result<Vec<i8>, SignatureError> {
+        let gray = grayscale_buffer(rgb_buffer, width, PixelFormat::Rgb, self)?;
+        compute_from_gray(&gray, self)
+    }
+
+    /// Produces a signature for a provided image that's encoded as an array of conceptually
+    /// grouped 16-bit channels in the configured [PixelFormat] with the provided width.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_u16] for
+    /// details.
+    pub fn compute_u16(&self, rgba_buffer: &[u16], width: usize) -> Vec<i8> {
+        self.try_compute_u16(rgba_buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_u16]. Returns an error if the buffer
+    /// doesn't hold whole rows of pixels, or if the image is too small or too featureless to
+    /// place the signature grid on.
+    pub fn try_compute_u16(
+        &self,
+        rgba_buffer: &[u16],
+        width: usize,
+    ) -> Result<Vec<i8>, SignatureError> {
+        let gray = grayscale_buffer(rgba_buffer, width, self.pixel_format, self)?;
+        compute_from_gray(&gray, self)
+    }
+
+    /// Produces a signature for a provided image that's encoded as an array of 8-bit gray levels,
+    /// one byte per pixel, with the provided width.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_gray] for
+    /// details.
+    pub fn compute_gray(&self, gray_buffer: &[u8], width: usize) -> Vec<i8> {
+        self.try_compute_gray(gray_buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_gray]. Returns an error if the buffer
+    /// doesn't hold whole rows of gray pixels, or if the image is too small or too featureless to
+    /// place the signature grid on.
+    pub fn try_compute_gray(
+        &self,
+        gray_buffer: &[u8],
+        width: usize,
+    ) -> Result<Vec<i8>, SignatureError> {
+        if self.compat == Compat::Goldberg {
+            let mut signature = vec![0; self.signature_length()];
+            goldberg::compute_gray_into(gray_buffer, width, self, &mut signature)?;
+            return Ok(signature);
+        }
+
+        let gray = gray_buffer_rows(gray_buffer, width)?;
+        compute_from_gray(&gray, self)
+    }
+
+    /// Produces a signature for a provided image that's encoded as an array of 16-bit gray levels,
+    /// one per pixel, with the provided width.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_gray_u16] for
+    /// details.
+    pub fn compute_gray_u16(&self, gray_buffer: &[u16], width: usize) -> Vec<i8> {
+        self.try_compute_gray_u16(gray_buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_gray_u16]. Returns an error if the
+    /// buffer doesn't hold whole rows of gray pixels, or if the image is too small or too
+    /// featureless to place the signature grid on.
+    pub fn try_compute_gray_u16(
+        &self,
+        gray_buffer: &[u16],
+        width: usize,
+    ) -> Result<Vec<i8>, SignatureError> {
+        if self.compat == Compat::Goldberg {
+            let mut signature = vec![0; self.signature_length()];
+            goldberg::compute_gray_into(gray_buffer, width, self, &mut signature)?;
+            return Ok(signature);
+        }
+
+        let gray = gray_buffer_rows(gray_buffer, width)?;
+        compute_from_gray(&gray, self)
+    }
+
+    /// Produces a signature for a provided high dynamic range image that's encoded as an array of
+    /// conceptually grouped linear `f32` channels in the configured [PixelFormat] with the
+    /// provided width.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_f32] for
+    /// details.
+    pub fn compute_f32(&self, rgba_buffer: &[f32], width: usize) -> Vec<i8> {
+        self.try_compute_f32(rgba_buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_f32]. Returns an error if the buffer
+    /// doesn't hold whole rows of pixels, or if the image is too small or too featureless to
+    /// place the signature grid on.
+    pub fn try_compute_f32(
+        &self,
+        rgba_buffer: &[f32],
+        width: usize,
+    ) -> Result<Vec<i8>, SignatureError> {
+        let mapped = tonemap_buffer(
+            rgba_buffer,
+            width,
+            self.pixel_format.channels(),
+            self.pixel_format.alpha_offset(),
+            self.tonemap,
+        )?;
+        self.try_compute_u16(&mapped, width)
+    }
+
+    /// Produces a signature for a provided high dynamic range image that's encoded as an array of
+    /// linear `f32` gray levels, one per pixel, with the provided width.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_gray_f32] for
+    /// details.
+    pub fn compute_gray_f32(&self, gray_buffer: &[f32], width: usize) -> Vec<i8> {
+        self.try_compute_gray_f32(gray_buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_gray_f32]. Returns an error if the
+    /// buffer doesn't hold whole rows of gray pixels, or if the image is too small or too
+    /// featureless to place the signature grid on.
+    pub fn try_compute_gray_f32(
+        &self,
+        gray_buffer: &[f32],
+        width: usize,
+    ) -> Result<Vec<i8>, SignatureError> {
+        let mapped = tonemap_buffer(gray_buffer, width, 1, None, self.tonemap)?;
+        self.try_compute_gray_u16(&mapped, width)
+    }
+}
+
+impl Default for SignatureConfig {
+    fn default() -> Self {
+        SignatureConfig::new()
+    }
+}
+
+/// The order of the channels making up each pixel of a color buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PixelFormat {
+    #[default]
+    Rgba,
+    Bgra,
+    Argb,
+    /// RGB followed by an unused byte, which is ignored rather than treated as alpha.
+    Rgbx,
+    /// BGR followed by an unused byte, as in most Windows GDI captures.
+    Bgrx,
+    /// An unused byte followed by RGB.
+    Xrgb,
+    Rgb,
+    Bgr,
+}
+
+impl PixelFormat {
+    /// The number of channels making up each pixel.
+    pub fn channels(self) -> usize {
+        match self {
+            PixelFormat::Rgb | PixelFormat::Bgr => 3,
+            _ => 4,
+        }
+    }
+
+    /// The offsets of the red, green, and blue channels within a pixel.
+    pub(crate) fn color_offsets(self) -> [usize; 3] {
+        match self {
+            PixelFormat::Rgba | PixelFormat::Rgbx | PixelFormat::Rgb => [0, 1, 2],
+            PixelFormat::Bgra | PixelFormat::Bgrx | PixelFormat::Bgr => [2, 1, 0],
+            PixelFormat::Argb | PixelFormat::Xrgb => [1, 2, 3],
+        }
+    }
+
+    /// The offset of the alpha channel within a pixel, if there is one.
+    pub(crate) fn alpha_offset(self) -> Option<usize> {
+        match self {
+            PixelFormat::Rgba | PixelFormat::Bgra => Some(3),
+            PixelFormat::Argb => Some(0),
+            _ => None,
+        }
+    }
+}
+
+/// How a color pixel's red, green, and blue values are weighted to produce its gray level.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum GrayscaleMode {
+    /// The plain average of the three channels.
+    #[default]
+    Average,
+    /// Luma weights from ITU-R BT.601, as used by JPEG and most image libraries.
+    Rec601,
+    /// Luma weights from ITU-R BT.709, the sRGB and HDTV standard.
+    Rec709,
+    /// Red, green, and blue weights, which should sum to 1.
+    Custom([f32; 3]),
+}
+
+impl GrayscaleMode {
+    /// The red, green, and blue weights of weighted modes, or `None` for a plain average.
+    pub(crate) fn weights(self) -> Option<[f32; 3]> {
+        match self {
+            GrayscaleMode::Average => None,
+            GrayscaleMode::Rec601 => Some([0.299, 0.587, 0.114]),
+            GrayscaleMode::Rec709 => Some([0.2126, 0.7152, 0.0722]),
+            GrayscaleMode::Custom(weights) => Some(weights),
+        }
+    }
+
+    /// [GrayscaleMode::weights] as multiples of `1 / 2^FIXED_POINT_BITS`. The standard weights are
+    /// rounded so they still sum to exactly 1, while custom ones are rounded individually, with
+    /// negative weights treated as 0.
+    pub(crate) fn fixed_weights(self) -> Option<[u64; 3]> {
+        match self {
+            GrayscaleMode::Average => None,
+            GrayscaleMode::Rec601 => Some([19595, 38470, 7471]),
+            GrayscaleMode::Rec709 => Some([13933, 46871, 4732]),
+            GrayscaleMode::Custom(weights) => {
+                Some(weights.map(|w| (w * (1 << FIXED_POINT_BITS) as f32).round() as u64))
+            }
+        }
+    }
+}
+
+/// The number of fractional bits in fixed-point weights and fractions.
+pub(crate) const FIXED_POINT_BITS: u32 = 16;
+
+/// The kind of arithmetic used where the pipeline has a choice. Grid averages, the differences
+/// between them, and the thresholds they're quantized with are always computed with integers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum Arithmetic {
+    /// Floating point luma weights, alpha compositing, and crop thresholds. Each step is a single
+    /// IEEE operation in a fixed order, so results are bit-identical on every target with IEEE
+    /// single precision, including wasm32, though not x87-only ones like `i586`.
+    #[default]
+    Float,
+    /// Fixed-point luma weights, alpha compositing, and crop thresholds, so that signatures are
+    /// bit-identical on every platform and can be computed without an FPU. Results differ
+    /// slightly from [Arithmetic::Float] wherever the two round differently. `f32` inputs are
+    /// still tonemapped with floating point.
+    Integer,
+}
+
+/// Where grid points are placed. Either way they're spaced evenly by dividing the cropped region
+/// into `grid_size` blocks along each side.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum GridPlacement {
+    /// Counts the spacing from the image's top left corner rather than the crop's, so the grid
+    /// covers the cropped region only when nothing was cropped from the top and left. Kept for
+    /// compatibility with existing signatures.
+    #[default]
+    ImageOrigin,
+    /// Counts the spacing from the crop's top left corner, centering the grid on the cropped region
+    /// as the paper intends. Signatures are more robust to borders and padding added around an
+    /// image, but aren't comparable with [GridPlacement::ImageOrigin] ones.
+    CropOrigin,
+}
+
+/// Implementations whose signatures can be reproduced exactly, for searching signatures computed
+/// elsewhere.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum Compat {
+    /// This crate's own pipeline, tuned by the rest of the config.
+    #[default]
+    Native,
+    /// The Python `image_match` library's `ImageSignature`, as used by its Elasticsearch
+    /// integration. Its defaults correspond to the default config here; `grid_size`, `crop`,
+    /// `identical_tolerance`, and `pixel_format` still apply, but every other option is ignored.
+    /// Gray levels are computed the way scikit-image does with NumPy built without a BLAS library
+    /// using fused multiply-add, and alpha is dropped. Signatures are `8 * (grid_size - 1)^2`
+    /// long, with missing neighbors padded with zeros. 8-bit color buffers are supported through
+    /// [SignatureConfig::compute] and [SignatureConfig::compute_into], 8 and 16-bit gray buffers
+    /// through [SignatureConfig::compute_gray] and [SignatureConfig::compute_gray_u16], and
+    /// decoded images and files through the APIs built on them; other inputs return
+    /// [SignatureError::CompatUnsupported]. Gray images are signed on the scale of their levels
+    /// rather than of 0 to 1, as the library does.
+    Goldberg,
+}
+
+/// How the missing neighbors of grid points in the first and last rows and columns of the grid are
+/// represented in the signature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum NeighborPadding {
+    /// Leaves them out, so the signature is
+    /// [signature_length(grid_size)](crate::signature_length) long.
+    #[default]
+    Omit,
+    /// Fills their positions with 0, as the paper's authors do, so that every grid point has 8
+    /// elements and the signature is `8 * (grid_size - 1)^2` long. This matches the layout of
+    /// signatures from other implementations of the paper.
+    Zeros,
+}
+
+/// How images too small for the signature grid are handled. An image needs more pixels than
+/// `grid_size` along both sides for the grid points to be distinct. Squares around grid points
+/// near the border of small images are filled in with copies of the edge pixels, so signatures of
+/// images only slightly larger than the grid capture less of the image than usual.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SmallImagePolicy {
+    /// Returns [SignatureError::ImageTooSmall] for any image the grid can't be placed on.
+    #[default]
+    Error,
+    /// Enlarges images whose shorter side is under four pixels per grid cell by repeating each
+    /// pixel, so that their shorter side is at least that long. Very narrow images are enlarged
+    /// along both sides and can become large. Images the grid still can't be placed on return
+    /// [SignatureError::ImageTooSmall] with their original size.
+    Upscale,
+}
+
+impl SignatureConfig {
+    /// How many times each pixel of a `width` by `height` image is repeated along both sides
+    /// before the grid is placed, which is 1 unless the image is upscaled.
+    pub(crate) fn upscale_factor(&self, width: usize, height: usize) -> usize {
+        let target = UPSCALED_CELL_SIDE.saturating_mul(self.grid_size);
+        let side = min(width, height);
+        match self.small_image_policy {
+            SmallImagePolicy::Upscale if side > 0 && side < target => target.div_ceil(side),
+            _ => 1,
+        }
+    }
+
+    /// How many pixels along both sides of a `width` by `height` image are averaged into one
+    /// before the grid is placed, which is 1 unless the image is over the megapixel limit.
+    pub(crate) fn downscale_factor(&self, width: usize, height: usize) -> usize {
+        let Some(max_megapixels) = self.max_megapixels else {
+            return 1;
+        };
+        let max_pixels = (max_megapixels as f64 * 1e6).max(1.0);
+        let pixels = |factor: usize| (width.div_ceil(factor) * height.div_ceil(factor)) as f64;
+        let shrink = (width as f64 * height as f64 / max_pixels).sqrt();
+        let mut factor = shrink.ceil().max(1.0) as usize;
+        while pixels(factor) > max_pixels {
+            factor += 1;
+        }
+        // A factor that shrinks the image below the upscaling target is capped just above it.
+        let target = UPSCALED_CELL_SIDE.saturating_mul(self.grid_size).max(1);
+        factor.min(min(width, height) / target).max(1)
+    }
+}
+
+/// How a pixel's alpha channel affects its gray level.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum AlphaMode {
+    /// Treats every pixel as opaque.
+    Ignore,
+    /// Scales gray levels by alpha, equivalent to compositing over black. Transparent regions of
+    /// logos and icons will match black rather than the background they're usually displayed on.
+    #[default]
+    Multiply,
+    /// Composites over a solid background color given as RGB, e.g. `[255, 255, 255]` to match
+    /// images that were flattened onto white.
+    Background([u8; 3]),
+    /// Composites over a white and light gray checkerboard of 8 pixel squares, the way most image
+    /// editors display transparency.
+    Checkerboard,
+}
+
+/// Operators mapping linear high dynamic range values, where 1.0 is nominal white, into [0, 1].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Tonemap {
+    /// Clips values outside of [0, 1]. Best for float buffers that are already display-referred.
+    #[default]
+    Clamp,
+    /// The Reinhard operator `x / (1 + x)`, which compresses highlights instead of clipping them.
+    Reinhard,
+    /// Scales values by the given exposure multiplier, then clips them to [0, 1].
+    Exposure(f32),
+}
+
+impl Tonemap {
+    pub(crate) fn apply(self, value: f32) -> f32 {
+        let value = value.max(0.0);
+        match self {
+            Tonemap::Clamp => value.min(1.0),
+            Tonemap::Reinhard => value / (1.0 + value),
+            Tonemap::Exposure(exposure) => (value * exposure).min(1.0),
+        }
+    }
+}
+
+/// The paper's `max(2, floor(0.5 + min(n, m) / 20))` square edge, halved to get the distance from
+/// the grid point to the edge of the square.
+pub(crate) fn default_average_square_width(width: usize, height: usize) -> usize {
+    // Integer division rounding half up, identical to the paper's floating point expression for
+    // any realistic image size.
+    max(2_usize, (min(width, height) + 10) / 20) / 2
+}
@@ -0,0 +1,243 @@
+//! CSV and TSV interchange of signature sets, for small pipelines and spreadsheets.
+
+use std::io::{self, BufRead, ErrorKind, Write};
+
+use crate::{
+    AlphaMode, Arithmetic, Compat, GrayscaleMode, GridPlacement, NeighborPadding, Signature,
+    SignatureConfig, SignatureError,
+};
+
+/// The header row, naming the id, the signature as in [Signature::to_base64], and its parameters.
+const HEADER: [&str; 11] = [
+    "id",
+    "signature",
+    "crop",
+    "grid_size",
+    "identical_tolerance",
+    "grid_placement",
+    "neighbor_padding",
+    "compat",
+    "grayscale_mode",
+    "alpha_mode",
+    "arithmetic",
+];
+
+/// Writes ids and signatures as rows of delimited text with a header row, e.g. with a `delimiter`
+/// of `b','` for CSV or `b'\t'` for TSV. Each row holds an id, its signature as in
+/// [Signature::to_base64], and the signature's parameters, with enums by variant name and the
+/// values of `Custom` weights and `Background` colors in brackets, e.g. `Custom([0.3, 0.6, 0.1])`.
+/// Fields are
+/// quoted when they contain the delimiter, a quote, or a line break, with quotes doubled, as in
+/// RFC 4180.
+pub fn export_csv<'a, W, I>(mut writer: W, delimiter: u8, records: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = (&'a str, &'a Signature)>,
+{
+    let delimiter = delimiter as char;
+    write_row(&mut writer, delimiter, HEADER.iter().map(|field| field.to_string()))?;
+    for (id, signature) in records {
+        let params = signature.params();
+        let row = [
+            id.to_string(),
+            signature.to_base64(),
+            params.crop().to_string(),
+            params.grid_size().to_string(),
+            params.identical_tolerance().to_string(),
+            format!("{:?}", params.grid_placement()),
+            format!("{:?}", params.neighbor_padding()),
+            format!("{:?}", params.compat()),
+            format!("{:?}", params.grayscale_mode()),
+            format!("{:?}", params.alpha_mode()),
+            format!("{:?}", params.arithmetic()),
+        ];
+        write_row(&mut writer, delimiter, row)?;
+    }
+    writer.flush()
+}
+
+/// Reads ids and signatures written by [export_csv] with the same `delimiter`. Line breaks may be
+/// `\n` or `\r\n`, and a leading byte order mark, as spreadsheet applications write, and blank
+/// lines are skipped.
+///
+/// Returns an error of kind [ErrorKind::InvalidData] naming the line if the header or a row is
+/// malformed, or a signature doesn't decode.
+pub fn import_csv<R: BufRead>(
+    mut reader: R,
+    delimiter: u8,
+) -> io::Result<Vec<(String, Signature)>> {
+    let delimiter = delimiter as char;
+    let mut line = 1;
+    let header = read_row(&mut reader, delimiter, &mut line)?;
+    let header = header.map(|mut fields| {
+        if let Some(first) = fields.first_mut() {
+            *first = first.trim_start_matches('\u{feff}').to_string();
+        }
+        fields
+    });
+    if header.as_deref() != Some(&HEADER.map(String::from)[..]) {
+        return Err(invalid_row(1, "the header doesn't name the expected columns"));
+    }
+
+    let mut records = Vec::new();
+    loop {
+        let start = line;
+        let Some(row) = read_row(&mut reader, delimiter, &mut line)? else {
+            return Ok(records);
+        };
+        if row.len() == 1 && row[0].is_empty() {
+            continue;
+        }
+        let fields = row.len();
+        let Ok(row) = <[String; HEADER.len()]>::try_from(row) else {
+            let message = format!("expected {} fields, found {}", HEADER.len(), fields);
+            return Err(invalid_row(start, message));
+        };
+        records.push(parse_record(row).map_err(|e| invalid_row(start, e))?);
+    }
+}
+
+fn parse_record(row: [String; HEADER.len()]) -> Result<(String, Signature), SignatureError> {
+    let [
+        id, signature, crop, grid_size, tolerance, placement, padding, compat, grayscale, alpha,
+        arithmetic,
+    ] = row;
+
+    let crop = crop.parse().map_err(|_| invalid("crop", &crop, "a number"))?;
+    let grid_size = grid_size.parse().map_err(|_| invalid("grid_size", &grid_size, "a number"))?;
+    let identical_tolerance =
+        tolerance.parse().map_err(|_| invalid("identical_tolerance", &tolerance, "0 to 255"))?;
+    let grid_placement = match placement.as_str() {
+        "ImageOrigin" => GridPlacement::ImageOrigin,
+        "CropOrigin" => GridPlacement::CropOrigin,
+        _ => return Err(invalid("grid_placement", &placement, "ImageOrigin or CropOrigin")),
+    };
+    let neighbor_padding = match padding.as_str() {
+        "Omit" => NeighborPadding::Omit,
+        "Zeros" => NeighborPadding::Zeros,
+        _ => return Err(invalid("neighbor_padding", &padding, "Omit or Zeros")),
+    };
+    let compat = match compat.as_str() {
+        "Native" => Compat::Native,
+        "Goldberg" => Compat::Goldberg,
+        _ => return Err(invalid("compat", &compat, "Native or Goldberg")),
+    };
+    let grayscale_mode = match grayscale.as_str() {
+        "Average" => GrayscaleMode::Average,
+        "Rec601" => GrayscaleMode::Rec601,
+        "Rec709" => GrayscaleMode::Rec709,
+        custom => {
+            let supported = "Average, Rec601, Rec709, or Custom([r, g, b])";
+            let mode = parse_list(custom, "Custom").map(GrayscaleMode::Custom);
+            mode.ok_or_else(|| invalid("grayscale_mode", custom, supported))?
+        }
+    };
+    let alpha_mode = match alpha.as_str() {
+        "Ignore" => AlphaMode::Ignore,
+        "Multiply" => AlphaMode::Multiply,
+        "Checkerboard" => AlphaMode::Checkerboard,
+        background => {
+            let supported = "Ignore, Multiply, Background([r, g, b]), or Checkerboard";
+            let mode = parse_list(background, "Background").map(AlphaMode::Background);
+            mode.ok_or_else(|| invalid("alpha_mode", background, supported))?
+        }
+    };
+    let arithmetic = match arithmetic.as_str() {
+        "Float" => Arithmetic::Float,
+        "Integer" => Arithmetic::Integer,
+        _ => return Err(invalid("arithmetic", &arithmetic, "Float or Integer")),
+    };
+
+    let config = SignatureConfig::new()
+        .crop(crop)
+        .grid_size(grid_size)
+        .identical_tolerance(identical_tolerance)
+        .grid_placement(grid_placement)
+        .neighbor_padding(neighbor_padding)
+        .compat(compat)
+        .grayscale_mode(grayscale_mode)
+        .alpha_mode(alpha_mode)
+        .arithmetic(arithmetic);
+    config.validate()?;
+    Ok((id, Signature::from_base64(config.params(), &signature)?))
+}
+
+/// Parses the values of a variant like `Custom([0.3, 0.6, 0.1])`, as its `Debug` form writes them.
+fn parse_list<T: std::str::FromStr, const N: usize>(value: &str, variant: &str) -> Option<[T; N]> {
+    let list = value.strip_prefix(variant)?.strip_prefix("([")?.strip_suffix("])")?;
+    let values: Option<Vec<T>> = list.split(", ").map(|value| value.parse().ok()).collect();
+    values?.try_into().ok()
+}
+
+fn invalid(name: &'static str, value: &str, supported: &'static str) -> SignatureError {
+    SignatureError::InvalidParameter { name, value: value.to_string(), supported }
+}
+
+fn invalid_row<E: std::fmt::Display>(line: usize, error: E) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("line {}: {}", line, error))
+}
+
+fn write_row<W, I>(writer: &mut W, delimiter: char, fields: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = String>,
+{
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            write!(writer, "{}", delimiter)?;
+        }
+        if field.contains([delimiter, '"', '\n', '\r']) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(field.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\n")
+}
+
+/// Reads the fields of the next row, which spans several lines when a quoted field contains line
+/// breaks, counting them in `line`. Returns `None` at the end of the input.
+fn read_row<R: BufRead>(
+    reader: &mut R,
+    delimiter: char,
+    line: &mut usize,
+) -> io::Result<Option<Vec<String>>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut text = String::new();
+    loop {
+        text.clear();
+        if reader.read_line(&mut text)? == 0 {
+            if quoted {
+                return Err(invalid_row(*line, "a quoted field isn't closed"));
+            }
+            if fields.is_empty() && field.is_empty() {
+                return Ok(None);
+            }
+            fields.push(field);
+            return Ok(Some(fields));
+        }
+        *line += 1;
+
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' if quoted => quoted = false,
+                '"' if field.is_empty() => quoted = true,
+                c if quoted => field.push(c),
+                c if c == delimiter => fields.push(std::mem::take(&mut field)),
+                '\r' if chars.peek() == Some(&'\n') => {}
+                '\n' => {
+                    fields.push(field);
+                    return Ok(Some(fields));
+                }
+                c => field.push(c),
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Documents in the layout the Python `image_match` library's Elasticsearch backend indexes, so that
+//! signatures computed here can be added to an index it populated.
+
+use std::fmt::Write;
+
+use crate::{try_signature_words, SignatureError, DEFAULT_WORD_COUNT, DEFAULT_WORD_LENGTH};
+
+/// Renders the document the Python `image_match` library's `make_record` builds for an image at
+/// `path`: the path, the signature, and `simple_word_0` to `simple_word_62`, the
+/// [signature_words](crate::signature_words) its Elasticsearch backend searches on, as integers.
+/// The backend adds a `timestamp` when inserting records, which isn't included.
+///
+/// Only signatures computed with [Compat::Goldberg](crate::Compat::Goldberg) and otherwise
+/// default parameters are comparable with the Python library's.
+///
+/// Panics if the signature is shorter than a word, see [try_elasticsearch_document] for a
+/// non-panicking version.
+pub fn elasticsearch_document<S: AsRef<[i8]>>(path: &str, signature: S) -> String {
+    try_elasticsearch_document(path, signature, DEFAULT_WORD_LENGTH, DEFAULT_WORD_COUNT)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// A non-panicking version of [elasticsearch_document] for indexes built with a different
+/// `word_length` and `word_count`. Returns [SignatureError::InvalidParameter] if either is zero
+/// or longer than the signature.
+pub fn try_elasticsearch_document<S: AsRef<[i8]>>(
+    path: &str,
+    signature: S,
+    word_length: usize,
+    word_count: usize,
+) -> Result<String, SignatureError> {
+    let signature = signature.as_ref();
+    let words = try_signature_words(signature, word_length, word_count)?;
+
+    let mut document = String::from("{\"path\":");
+    write_json_string(&mut document, path);
+    document.push_str(",\"signature\":[");
+    for (i, element) in signature.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "," };
+        write!(document, "{}{}", separator, element).unwrap();
+    }
+    document.push(']');
+    for (i, word) in words.iter().enumerate() {
+        write!(document, ",\"simple_word_{}\":{}", i, word.to_int()).unwrap();
+    }
+    document.push('}');
+    Ok(document)
+}
+
+/// Writes `value` as a JSON string literal.
+fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
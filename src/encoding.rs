@@ -0,0 +1,92 @@
+//! Compact encodings of signature elements for storage and interchange.
+
+/// Signature elements packed into each byte. Elements take 5 values, and 5^3 = 125 fits in a byte.
+const ELEMENTS_PER_BYTE: usize = 3;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Packs elements in -2 to 2 three to a byte as base 5 digits, first element most significant. A
+/// short last group is padded with elements of -2, which encode as 0.
+pub(crate) fn pack(elements: &[i8]) -> Vec<u8> {
+    elements.chunks(ELEMENTS_PER_BYTE)
+        .map(|group| {
+            (0..ELEMENTS_PER_BYTE)
+                .map(|i| group.get(i).map_or(0, |element| (element + 2) as u8))
+                .fold(0, |byte, digit| byte * 5 + digit)
+        })
+        .collect()
+}
+
+/// Unpacks every element of bytes produced by [pack], including the padding of a short last
+/// group. Bytes above 124 unpack to elements above 2.
+pub(crate) fn unpack(bytes: &[u8]) -> Vec<i8> {
+    bytes.iter()
+        .flat_map(|byte| [byte / 25, byte / 5 % 5, byte % 5].map(|digit| digit as i8 - 2))
+        .collect()
+}
+
+/// The number of bytes [pack] packs `len` elements into.
+pub(crate) fn packed_len(len: usize) -> usize {
+    len.div_ceil(ELEMENTS_PER_BYTE)
+}
+
+/// URL-safe base64 without padding, which fits in URLs and text columns without escaping.
+pub(crate) fn to_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 4).div_ceil(3));
+    for group in bytes.chunks(3) {
+        let bits = group.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..=group.len() {
+            encoded.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// Decodes URL-safe base64 without padding, or returns `None` if it isn't the canonical encoding
+/// of some bytes.
+pub(crate) fn from_base64(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+    if encoded.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    for group in encoded.chunks(4) {
+        let mut bits = 0u32;
+        for c in group {
+            let value = BASE64_ALPHABET.iter().position(|a| a == c)?;
+            bits = bits << 6 | value as u32;
+        }
+        bits <<= 6 * (4 - group.len());
+        let decoded = [(bits >> 16) as u8, (bits >> 8) as u8, bits as u8];
+        // Bits past the last byte must be zero, so that every signature has a single encoding.
+        let len = group.len() - 1;
+        if decoded[len..].iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        bytes.extend_from_slice(&decoded[..len]);
+    }
+    Some(bytes)
+}
+
+/// Elements as lowercase hex digits in 4-bit two's complement, so 0 is `0` and -1 is `f`.
+pub(crate) fn to_hex(elements: &[i8]) -> String {
+    elements.iter().map(|element| HEX_DIGITS[(*element as u8 & 0xF) as usize] as char).collect()
+}
+
+/// Decodes every hex digit, in either case, to an element, or returns `None` if `encoded` isn't
+/// whole bytes of hex.
+pub(crate) fn from_hex(encoded: &str) -> Option<Vec<i8>> {
+    if encoded.len() % 2 == 1 {
+        return None;
+    }
+
+    encoded.chars()
+        .map(|c| c.to_digit(16).map(|nibble| ((nibble as u8) << 4) as i8 >> 4))
+        .collect()
+}
@@ -0,0 +1,123 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use SignatureError::{
+    BadBufferLength, BadStride, BufferLengthMismatch, DegenerateCrop, ElementOutOfRange,
+    CompatUnsupported, FlatImage, ImageTooSmall, IncompatibleSignatures, InvalidEncoding,
+    InvalidParameter, MismatchedLengths, RegionOutOfBounds, UnsupportedVersion,
+};
+
+/// Reasons a signature couldn't be computed or compared.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The buffer is empty, the width is zero, or the buffer's length isn't a whole number of rows
+    /// of the provided width. Also returned when the width or stride is too large for the buffer
+    /// to possibly hold.
+    BadBufferLength { len: usize, width: usize },
+    /// The buffer's length doesn't match the dimensions it was provided with, e.g. because a read
+    /// was cut short.
+    BufferLengthMismatch { expected: usize, actual: usize },
+    /// The distance between the starts of consecutive rows is shorter than a row of pixels.
+    BadStride { stride: usize, row_len: usize },
+    /// The image is too small to place the grid and sample the area around each grid point.
+    ImageTooSmall { width: usize, height: usize },
+    /// Two compared signatures have different lengths, meaning they weren't computed with the same
+    /// tuning parameters.
+    MismatchedLengths { left: usize, right: usize },
+    /// Two compared [Signature](crate::Signature)s were computed with a different value of the
+    /// named tuning parameter.
+    IncompatibleSignatures { name: &'static str, left: String, right: String },
+    /// Cropping left nothing of the image to place a grid on, either because the crop is close to
+    /// 0.5 or because all of the image's detail is concentrated in a line or two that the crop
+    /// takes in from both sides. Empty images are reported as [ImageTooSmall](Self::ImageTooSmall).
+    DegenerateCrop,
+    /// Every grid point is within tolerance of its neighbors, so the signature would be all zeros.
+    /// Only returned when flat images are rejected, see
+    /// [SignatureConfig::reject_flat_images](crate::SignatureConfig::reject_flat_images).
+    FlatImage,
+    /// A tuning parameter is outside the range signatures can be computed with, see
+    /// [SignatureConfig::validate](crate::SignatureConfig::validate).
+    InvalidParameter { name: &'static str, value: String, supported: &'static str },
+    /// The input can't be signed in the configured [Compat](crate::Compat) mode. Goldberg
+    /// signatures are computed from whole 8-bit color and 8 or 16-bit gray buffers, and from
+    /// decoded images and files, but not from 16-bit color, floating point, strided, region, tile,
+    /// view, or other pixel sources.
+    CompatUnsupported,
+    /// An encoded signature isn't valid in the named format.
+    InvalidEncoding { format: &'static str },
+    /// An encoded signature was written by a version of its format this release of the crate
+    /// can't read, either a newer one or an old one missing parameters.
+    UnsupportedVersion { version: u8 },
+    /// A signature element is outside the range of -2 to 2 that signatures are made of.
+    ElementOutOfRange { index: usize, value: i8 },
+    /// A [Region](crate::Region) to sign is empty or extends past the edges of an image of the
+    /// given size.
+    RegionOutOfBounds { region: String, width: usize, height: usize },
+}
+
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BadBufferLength { len, width } => write!(
+                f,
+                "Buffer of length {} does not hold a whole number of rows of width {}",
+                len, width
+            ),
+            BufferLengthMismatch { expected, actual } => write!(
+                f,
+                "Buffer of length {} does not match the expected length of {}",
+                actual, expected
+            ),
+            BadStride { stride, row_len } => write!(
+                f,
+                "Stride of {} bytes is shorter than a row of {} bytes",
+                stride, row_len
+            ),
+            ImageTooSmall { width, height } => write!(
+                f,
+                "Image of size {}x{} is too small to place the signature grid",
+                width, height
+            ),
+            MismatchedLengths { left, right } => write!(
+                f,
+                "Compared vectors must be of equal length, got {} and {}",
+                left, right
+            ),
+            IncompatibleSignatures { name, left, right } => write!(
+                f,
+                "Compared signatures were computed with different {}, {} and {}",
+                name, left, right
+            ),
+            DegenerateCrop => write!(f, "Cropping left no area to place the signature grid"),
+            FlatImage => write!(f, "Image has no detail for the signature to capture"),
+            InvalidParameter { name, value, supported } => write!(
+                f,
+                "{} of {} is outside the supported range of {}",
+                name, value, supported
+            ),
+            CompatUnsupported => {
+                write!(
+                    f,
+                    "Compatibility mode only supports whole 8-bit color and 8 or 16-bit gray \
+                     buffers, and decoded images and files"
+                )
+            }
+            InvalidEncoding { format } => write!(f, "Signature is not valid {}", format),
+            UnsupportedVersion { version } => {
+                write!(f, "Signature format version {} is not supported", version)
+            }
+            ElementOutOfRange { index, value } => write!(
+                f,
+                "Signature element {} at index {} is outside the range of -2 to 2",
+                value, index
+            ),
+            RegionOutOfBounds { region, width, height } => write!(
+                f,
+                "{} does not fit in an image of size {}x{}",
+                region, width, height
+            ),
+        }
+    }
+}
+
+impl Error for SignatureError {}
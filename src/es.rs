@@ -0,0 +1,165 @@
+//! A [SignatureStore] in Elasticsearch or OpenSearch, shared with the Python `image_match`
+//! library.
+
+use reqwest::blocking::{Client, Response};
+use reqwest::{StatusCode, Url};
+use serde_json::{json, Value};
+
+use crate::store::rank;
+use crate::{
+    try_elasticsearch_document, try_signature_words, Signature, SignatureError, SignatureParams,
+    SignatureStore, StoreError, DEFAULT_WORD_COUNT, DEFAULT_WORD_LENGTH,
+};
+
+/// The index [EsSignatureStore::new] keeps signatures in, the Python library's default.
+pub const DEFAULT_ES_INDEX: &str = "images";
+
+/// The number of candidates a search fetches by default, the Python library's default `size`.
+pub const DEFAULT_ES_SEARCH_SIZE: usize = 100;
+
+/// A [SignatureStore] in an Elasticsearch or OpenSearch index, laid out like the Python
+/// `image_match` library's `SignatureES`, so that both can add to and search the same index.
+///
+/// Documents are those of [elasticsearch_document](crate::elasticsearch_document), with the
+/// store's id as the document id and the `path`. A search asks the cluster for the documents
+/// sharing the most words with the query, up to the [search size](EsSignatureStore::search_size),
+/// and rescores them with cosine similarity here, returning their paths as ids. Documents the
+/// Python library added have generated document ids, so they're found by searches but not by
+/// [SignatureStore::get] or [SignatureStore::delete].
+///
+/// Documents don't record the parameters their signatures were computed with; they're read as
+/// computed with the store's. Only signatures computed with
+/// [Compat::Goldberg](crate::Compat::Goldberg) and otherwise default parameters are comparable
+/// with the Python library's.
+pub struct EsSignatureStore {
+    client: Client,
+    url: Url,
+    index: String,
+    params: SignatureParams,
+    search_size: usize,
+}
+
+impl EsSignatureStore {
+    /// Stores signatures computed with `params` in the [DEFAULT_ES_INDEX] of the cluster at
+    /// `url`, sending requests through `client`, which carries any authentication.
+    pub fn new(client: Client, url: Url, params: SignatureParams) -> Self {
+        Self::with_index(client, url, DEFAULT_ES_INDEX, params)
+    }
+
+    /// Stores signatures computed with `params` in `index`.
+    pub fn with_index(client: Client, url: Url, index: &str, params: SignatureParams) -> Self {
+        EsSignatureStore {
+            client,
+            url,
+            index: index.to_string(),
+            params,
+            search_size: DEFAULT_ES_SEARCH_SIZE,
+        }
+    }
+
+    /// How many candidates a search fetches before rescoring, [DEFAULT_ES_SEARCH_SIZE] by
+    /// default. Larger sizes find more matches in indexes of many similar images, at the cost of
+    /// slower searches.
+    pub fn search_size(mut self, search_size: usize) -> Self {
+        self.search_size = search_size;
+        self
+    }
+
+    /// Makes the documents added so far visible to searches, rather than waiting for the index's
+    /// refresh interval.
+    pub fn refresh(&mut self) -> Result<(), StoreError<reqwest::Error>> {
+        let url = self.endpoint(&["_refresh"]);
+        send(self.client.post(url))?;
+        Ok(())
+    }
+
+    /// The URL of `segments` under the index.
+    fn endpoint(&self, segments: &[&str]) -> Url {
+        let mut url = self.url.clone();
+        url.path_segments_mut()
+            .expect("Elasticsearch URLs are http or https")
+            .pop_if_empty()
+            .push(&self.index)
+            .extend(segments);
+        url
+    }
+
+    /// Reads the signature of a document's source.
+    fn read_source(&self, source: &Value) -> Result<(String, Signature), SignatureError> {
+        let malformed = SignatureError::InvalidEncoding { format: "Elasticsearch document" };
+        let path = source["path"].as_str().ok_or(malformed.clone())?;
+        let elements = source["signature"].as_array().ok_or(malformed.clone())?;
+        let elements = elements.iter()
+            .map(|element| element.as_i64().and_then(|e| i8::try_from(e).ok()))
+            .collect::<Option<Vec<i8>>>()
+            .ok_or(malformed)?;
+        Ok((path.to_string(), Signature::try_new(self.params, elements)?))
+    }
+}
+
+impl SignatureStore for EsSignatureStore {
+    type Error = StoreError<reqwest::Error>;
+
+    fn insert(&mut self, id: &str, signature: &Signature) -> Result<(), Self::Error> {
+        let document =
+            try_elasticsearch_document(id, signature, DEFAULT_WORD_LENGTH, DEFAULT_WORD_COUNT)?;
+        let url = self.endpoint(&["_doc", id]);
+        let request = self.client.put(url).header("Content-Type", "application/json");
+        send(request.body(document))?;
+        Ok(())
+    }
+
+    fn get(&mut self, id: &str) -> Result<Option<Signature>, Self::Error> {
+        let response = self.client.get(self.endpoint(&["_doc", id])).send();
+        let response = response.map_err(StoreError::Backend)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let document: Value = response.error_for_status()
+            .and_then(Response::json)
+            .map_err(StoreError::Backend)?;
+        Ok(Some(self.read_source(&document["_source"])?.1))
+    }
+
+    fn delete(&mut self, id: &str) -> Result<bool, Self::Error> {
+        let response = self.client.delete(self.endpoint(&["_doc", id])).send();
+        let response = response.map_err(StoreError::Backend)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        response.error_for_status().map_err(StoreError::Backend)?;
+        Ok(true)
+    }
+
+    fn search_similar(
+        &mut self,
+        query: &Signature,
+        threshold: f64,
+        limit: usize,
+    ) -> Result<Vec<(String, f64)>, Self::Error> {
+        let words = try_signature_words(query, DEFAULT_WORD_LENGTH, DEFAULT_WORD_COUNT)?;
+        let should: Vec<Value> = words.iter().enumerate()
+            .map(|(i, word)| json!({ "term": { format!("simple_word_{}", i): word.to_int() } }))
+            .collect();
+        let body = json!({
+            "size": self.search_size,
+            "query": { "bool": { "should": should } },
+            "_source": { "excludes": ["simple_word_*"] },
+        });
+
+        let response: Value = send(self.client.post(self.endpoint(&["_search"])).json(&body))?;
+        let hits = response["hits"]["hits"].as_array().map(Vec::as_slice).unwrap_or_default();
+        let candidates = hits.iter()
+            .map(|hit| self.read_source(&hit["_source"]))
+            .collect::<Result<Vec<_>, SignatureError>>()?;
+        Ok(rank(query, candidates, threshold, limit))
+    }
+}
+
+/// Sends a request, treating error statuses as errors, and reads the response's JSON.
+fn send(request: reqwest::blocking::RequestBuilder) -> Result<Value, StoreError<reqwest::Error>> {
+    request.send()
+        .and_then(Response::error_for_status)
+        .and_then(Response::json)
+        .map_err(StoreError::Backend)
+}
@@ -0,0 +1,271 @@
+//! An append-only file format for keeping signatures between runs.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::{Signature, SignatureError};
+
+/// Identifies signature files.
+const FILE_MAGIC: &[u8; 4] = b"IMSF";
+
+/// The version of the file layout, incremented whenever it changes.
+const FILE_VERSION: u8 = 1;
+
+/// The magic, the version, and three reserved bytes.
+const HEADER_LEN: u64 = 8;
+
+/// Ends the index footer, so that a file whose footer wasn't written can be told apart.
+const FOOTER_MAGIC: &[u8; 4] = b"IMSX";
+
+/// The record count and the footer magic that end the footer.
+const FOOTER_TAIL_LEN: u64 = 12;
+
+/// An append-only file of signatures, each stored with an id and its parameters, for tools that
+/// need to keep a few hundred thousand signatures between runs without a database server.
+///
+/// The file is a header, then one record per signature, then an index footer of each record's
+/// offset. A record is the id's length and UTF-8 bytes, then the length and bytes of the
+/// signature's [Signature::encode] container, with lengths as little endian `u32`s. Appending
+/// removes the footer, and it's written back by [SignatureFile::flush] or when the file is
+/// dropped. A file whose footer is missing, e.g. because the process was killed, is recovered by
+/// scanning its records on open, dropping a partially written last record.
+#[derive(Debug)]
+pub struct SignatureFile {
+    file: File,
+    offsets: Vec<u64>,
+    /// Where the records end, and the footer starts once it's written.
+    end: u64,
+    /// Whether the footer on disk is missing or out of date.
+    dirty: bool,
+}
+
+impl SignatureFile {
+    /// Opens the signature file at `path`, creating it if it doesn't exist. Returns an error of
+    /// kind [ErrorKind::InvalidData] if the file isn't a signature file.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false)
+            .open(path)?;
+        let len = file.metadata()?.len();
+        if len == 0 {
+            let mut header = [0; HEADER_LEN as usize];
+            header[..4].copy_from_slice(FILE_MAGIC);
+            header[4] = FILE_VERSION;
+            file.write_all(&header)?;
+            return Ok(SignatureFile { file, offsets: Vec::new(), end: HEADER_LEN, dirty: true });
+        }
+
+        let mut header = [0; HEADER_LEN as usize];
+        file.read_exact(&mut header).map_err(|_| invalid_data("not a signature file"))?;
+        if &header[..4] != FILE_MAGIC {
+            return Err(invalid_data("not a signature file"));
+        }
+        if header[4] != FILE_VERSION {
+            return Err(invalid_data(SignatureError::UnsupportedVersion { version: header[4] }));
+        }
+
+        let mut opened = SignatureFile { file, offsets: Vec::new(), end: len, dirty: false };
+        if !opened.read_footer(len)? {
+            opened.scan(len)?;
+        }
+        Ok(opened)
+    }
+
+    /// The number of signatures in the file.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Whether the file holds no signatures.
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Appends a signature with an id, which doesn't have to be unique.
+    pub fn append(&mut self, id: &str, signature: &Signature) -> io::Result<()> {
+        let container = signature.encode();
+        let too_long = |_| io::Error::new(ErrorKind::InvalidInput, "id is too long");
+        let mut record = Vec::with_capacity(8 + id.len() + container.len());
+        record.extend(u32::try_from(id.len()).map_err(too_long)?.to_le_bytes());
+        record.extend_from_slice(id.as_bytes());
+        record.extend((container.len() as u32).to_le_bytes());
+        record.extend(container);
+
+        if !self.dirty {
+            self.file.set_len(self.end)?;
+            self.dirty = true;
+        }
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&record)?;
+        self.offsets.push(self.end);
+        self.end += record.len() as u64;
+        Ok(())
+    }
+
+    /// The id and signature of the record at `index`, in the order they were appended, or `None`
+    /// if it's out of bounds.
+    pub fn get(&mut self, index: usize) -> io::Result<Option<(String, Signature)>> {
+        let Some(offset) = self.offsets.get(index) else {
+            return Ok(None);
+        };
+        self.file.seek(SeekFrom::Start(*offset))?;
+        read_record(&mut self.file).map(Some)
+    }
+
+    /// Iterates over the ids and signatures in the file, in the order they were appended.
+    pub fn iter(&mut self) -> io::Result<Records<'_>> {
+        self.file.seek(SeekFrom::Start(HEADER_LEN))?;
+        Ok(Records { reader: BufReader::new(&self.file), remaining: self.offsets.len() })
+    }
+
+    /// Writes the file's records as delimited text, see [export_csv](crate::export_csv).
+    pub fn export_csv<W: Write>(&mut self, writer: W, delimiter: u8) -> io::Result<()> {
+        let records = self.iter()?.collect::<io::Result<Vec<_>>>()?;
+        crate::export_csv(writer, delimiter, records.iter().map(|(id, sig)| (id.as_str(), sig)))
+    }
+
+    /// Appends the records of delimited text, see [import_csv](crate::import_csv), and returns
+    /// how many there were. Nothing is appended if any of them is malformed.
+    pub fn import_csv<R: BufRead>(&mut self, reader: R, delimiter: u8) -> io::Result<usize> {
+        let records = crate::import_csv(reader, delimiter)?;
+        for (id, signature) in &records {
+            self.append(id, signature)?;
+        }
+        Ok(records.len())
+    }
+
+    /// Writes the index footer if records were appended since it was last written, and flushes
+    /// the file to disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut footer = Vec::with_capacity(8 * self.offsets.len() + FOOTER_TAIL_LEN as usize);
+        for offset in &self.offsets {
+            footer.extend(offset.to_le_bytes());
+        }
+        footer.extend((self.offsets.len() as u64).to_le_bytes());
+        footer.extend_from_slice(FOOTER_MAGIC);
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&footer)?;
+        self.file.set_len(self.end + footer.len() as u64)?;
+        self.file.sync_data()?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Reads the offsets from the footer of a file `len` bytes long, or returns `false` if it
+    /// doesn't end in a footer.
+    fn read_footer(&mut self, len: u64) -> io::Result<bool> {
+        if len < HEADER_LEN + FOOTER_TAIL_LEN {
+            return Ok(false);
+        }
+        let mut tail = [0; FOOTER_TAIL_LEN as usize];
+        self.file.seek(SeekFrom::Start(len - FOOTER_TAIL_LEN))?;
+        self.file.read_exact(&mut tail)?;
+        let count = u64::from_le_bytes(tail[..8].try_into().unwrap());
+        let footer_len = count.checked_mul(8).and_then(|len| len.checked_add(FOOTER_TAIL_LEN));
+        let Some(end) = footer_len.and_then(|footer_len| len.checked_sub(footer_len)) else {
+            return Ok(false);
+        };
+        if &tail[8..] != FOOTER_MAGIC || end < HEADER_LEN {
+            return Ok(false);
+        }
+
+        let mut offsets = vec![0; 8 * count as usize];
+        self.file.seek(SeekFrom::Start(end))?;
+        self.file.read_exact(&mut offsets)?;
+        let offsets: Vec<u64> = offsets.chunks_exact(8)
+            .map(|offset| u64::from_le_bytes(offset.try_into().unwrap()))
+            .collect();
+        let in_order = offsets.first().is_none_or(|first| *first == HEADER_LEN)
+            && offsets.windows(2).all(|pair| pair[0] < pair[1])
+            && offsets.last().is_none_or(|last| *last < end);
+        if !in_order {
+            return Ok(false);
+        }
+
+        self.offsets = offsets;
+        self.end = end;
+        Ok(true)
+    }
+
+    /// Finds the records of a file `len` bytes long without a footer by reading them one after
+    /// another, stopping at the first that's incomplete or corrupt.
+    fn scan(&mut self, len: u64) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(HEADER_LEN))?;
+        let mut reader = BufReader::new(&self.file);
+        let mut offset = HEADER_LEN;
+        self.offsets.clear();
+        while offset < len {
+            match read_record(&mut reader) {
+                Ok(_) => {
+                    self.offsets.push(offset);
+                    offset = reader.stream_position()?;
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::UnexpectedEof | ErrorKind::InvalidData) => {
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        self.end = offset;
+        self.dirty = true;
+        Ok(())
+    }
+}
+
+impl Drop for SignatureFile {
+    fn drop(&mut self) {
+        // Errors can't be reported from here; the records are already written, and the next open
+        // recovers them without the footer.
+        let _ = self.flush();
+    }
+}
+
+/// An iterator over the records of a [SignatureFile].
+pub struct Records<'a> {
+    reader: BufReader<&'a File>,
+    remaining: usize,
+}
+
+impl Iterator for Records<'_> {
+    type Item = io::Result<(String, Signature)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(read_record(&mut self.reader))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+fn read_record<R: Read>(reader: &mut R) -> io::Result<(String, Signature)> {
+    let read_chunk = |reader: &mut R| -> io::Result<Vec<u8>> {
+        let mut len = [0; 4];
+        reader.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as u64;
+        // Reads through `take` rather than into a buffer of `len` bytes, so that a corrupt length
+        // doesn't allocate gigabytes.
+        let mut chunk = Vec::new();
+        reader.take(len).read_to_end(&mut chunk)?;
+        if (chunk.len() as u64) < len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        Ok(chunk)
+    };
+
+    let id = String::from_utf8(read_chunk(reader)?).map_err(invalid_data)?;
+    let signature = Signature::decode(&read_chunk(reader)?).map_err(invalid_data)?;
+    Ok((id, signature))
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error)
+}
@@ -0,0 +1,72 @@
+use std::ops::Deref;
+
+use crate::{signature_length, SignatureConfig, SignatureError};
+
+/// The length of signatures computed with the default grid size.
+pub const DEFAULT_SIGNATURE_LENGTH: usize = signature_length(crate::config::DEFAULT_GRID_SIZE);
+
+/// A signature stored inline in a fixed size array rather than on the heap, which makes large
+/// collections of signatures compact and cache friendly. `N` defaults to the length of signatures
+/// computed with the default grid size. Compare with [cosine-similarity(a, b)] like any other
+/// signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FixedSignature<const N: usize = DEFAULT_SIGNATURE_LENGTH>(pub [i8; N]);
+
+impl<const N: usize> Deref for FixedSignature<N> {
+    type Target = [i8; N];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> AsRef<[i8]> for FixedSignature<N> {
+    fn as_ref(&self) -> &[i8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> From<[i8; N]> for FixedSignature<N> {
+    fn from(elements: [i8; N]) -> Self {
+        FixedSignature(elements)
+    }
+}
+
+impl<const N: usize> TryFrom<&[i8]> for FixedSignature<N> {
+    type Error = SignatureError;
+
+    fn try_from(elements: &[i8]) -> Result<Self, Self::Error> {
+        elements.try_into()
+            .map(FixedSignature)
+            .map_err(|_| SignatureError::MismatchedLengths { left: N, right: elements.len() })
+    }
+}
+
+impl SignatureConfig {
+    /// Produces a signature stored in a [FixedSignature] for a provided image that's encoded as an
+    /// array of conceptually grouped bytes in the configured pixel format. `N` must be the
+    /// config's [SignatureConfig::signature_length].
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_fixed] for
+    /// details.
+    pub fn compute_fixed<const N: usize>(&self, buffer: &[u8], width: usize) -> FixedSignature<N> {
+        self.try_compute_fixed(buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_fixed]. In addition to the errors of
+    /// [SignatureConfig::try_compute], returns [SignatureError::MismatchedLengths] if `N` doesn't
+    /// match the config's signature length.
+    pub fn try_compute_fixed<const N: usize>(
+        &self,
+        buffer: &[u8],
+        width: usize,
+    ) -> Result<FixedSignature<N>, SignatureError> {
+        self.validate()?;
+        let length = self.signature_length();
+        if length != N {
+            return Err(SignatureError::MismatchedLengths { left: N, right: length });
+        }
+
+        FixedSignature::try_from(self.try_compute(buffer, width)?.as_slice())
+    }
+}
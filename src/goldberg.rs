@@ -0,0 +1,327 @@
+//! Signatures identical to those of the Python `image_match` library's `ImageSignature`, the
+//! reference implementation of the paper used by its Elasticsearch integration. Every step follows
+//! that library and the NumPy and scikit-image routines it calls, in 64-bit floating point and in
+//! the same order of operations, so that signatures stored by one can be searched with the other.
+
+use crate::{check_buffer_length, SignatureConfig, SignatureError};
+
+/// The `rgb2gray` weights scikit-image uses for red, green, and blue.
+const GRAY_WEIGHTS: [f64; 3] = [0.2125, 0.7154, 0.0721];
+
+/// Below this many elements, NumPy's pairwise summation adds elements one at a time.
+const PAIRWISE_UNROLL: usize = 8;
+
+/// Above this many elements, NumPy's pairwise summation splits the elements in two.
+const PAIRWISE_BLOCK: usize = 128;
+
+/// Writes the signature of an image encoded as bytes in the configured pixel format to `out`,
+/// which must be [SignatureConfig::signature_length] long.
+pub(crate) fn compute_into(
+    buffer: &[u8],
+    width: usize,
+    config: &SignatureConfig,
+    out: &mut [i8],
+) -> Result<(), SignatureError> {
+    check_output(config, out)?;
+    check_buffer_length(buffer.len(), width, config.pixel_format.channels())?;
+    signature_into(&grayscale(buffer, config), width, None, config, out)
+}
+
+/// Writes the signature of an image encoded as 8 or 16-bit gray levels to `out`, which must be
+/// [SignatureConfig::signature_length] long. scikit-image's `imread` only converts color images
+/// to gray, leaving the levels of gray images as they were read, integers of the image's depth
+/// rather than fractions of 1. So the library signs gray images on that scale, differences of
+/// levels wrap around as NumPy's differences of unsigned integers do, and the tolerance isn't
+/// scaled to match.
+pub(crate) fn compute_gray_into<T: Copy + Into<f64>>(
+    gray_buffer: &[T],
+    width: usize,
+    config: &SignatureConfig,
+    out: &mut [i8],
+) -> Result<(), SignatureError> {
+    check_output(config, out)?;
+    check_buffer_length(gray_buffer.len(), width, 1)?;
+    let gray: Vec<f64> = gray_buffer.iter().map(|&level| level.into()).collect();
+    let levels = 2f64.powi(8 * size_of::<T>() as i32);
+    signature_into(&gray, width, Some(levels), config, out)
+}
+
+fn check_output(config: &SignatureConfig, out: &[i8]) -> Result<(), SignatureError> {
+    config.validate()?;
+    let length = config.signature_length();
+    if out.len() != length {
+        return Err(SignatureError::MismatchedLengths { left: out.len(), right: length });
+    }
+    Ok(())
+}
+
+/// Signs gray levels, whose differences wrap around at `wrap` levels, if given.
+fn signature_into(
+    gray: &[f64],
+    width: usize,
+    wrap: Option<f64>,
+    config: &SignatureConfig,
+    out: &mut [i8],
+) -> Result<(), SignatureError> {
+    let height = gray.len() / width;
+    let crop = crop_percentile(config.crop);
+    let (rows, columns) = crop_limits(gray, width, height, wrap, (crop, 100.0 - crop));
+
+    let points = config.grid_size - 1;
+    let row_points = grid_coordinates(rows, points);
+    let column_points = grid_coordinates(columns, points);
+    let averages = mean_levels(gray, (width, height), &row_points, &column_points)
+        .ok_or(SignatureError::ImageTooSmall { width, height })?;
+
+    let tolerance = config.identical_tolerance as f64 / 255.0;
+    let mut diffs = differentials(&averages, points);
+    normalize_and_threshold(&mut diffs, tolerance);
+    for (out, diff) in out.iter_mut().zip(diffs) {
+        *out = diff as i8;
+    }
+    Ok(())
+}
+
+/// The lower crop percentile for a crop, as Python takes it: a decimal like 5 for a crop of 0.05.
+/// Widening the `f32` gives 5.000000074505806 instead, so the percentile is rounded to 4 decimal
+/// places, well within the 7 significant digits an `f32` holds, provided that decimal is the one
+/// the crop was rounded from. Any other crop keeps its exact value.
+fn crop_percentile(crop: f32) -> f64 {
+    let exact = f64::from(crop) * 100.0;
+    let decimal = (exact * 1e4).round() / 1e4;
+    if (decimal / 100.0) as f32 == crop {
+        decimal
+    } else {
+        exact
+    }
+}
+
+/// `rgb2gray` on the color channels, which scikit-image scales to [0, 1] by multiplying with the
+/// reciprocal of 255. Alpha is dropped, as the library does when converting images to RGB.
+fn grayscale(buffer: &[u8], config: &SignatureConfig) -> Vec<f64> {
+    let [r, g, b] = config.pixel_format.color_offsets();
+    let scale = 1.0 / 255.0;
+    buffer.chunks_exact(config.pixel_format.channels())
+        .map(|pixel| {
+            let [r, g, b] = [pixel[r], pixel[g], pixel[b]].map(|channel| channel as f64 * scale);
+            r * GRAY_WEIGHTS[0] + g * GRAY_WEIGHTS[1] + b * GRAY_WEIGHTS[2]
+        })
+        .collect()
+}
+
+/// The rows and columns bounding the crop, found by locating the percentiles of the cumulative sums
+/// of differences between adjacent pixels. Featureless images fall back to cropping the same
+/// percentiles of their size.
+fn crop_limits(
+    gray: &[f64],
+    width: usize,
+    height: usize,
+    wrap: Option<f64>,
+    (lower, upper): (f64, f64),
+) -> ((usize, usize), (usize, usize)) {
+    // The sums of differences of integer levels are exact, so they're the same whatever the order
+    // NumPy adds them in.
+    let difference = |earlier: f64, later: f64| match wrap {
+        Some(levels) => (later - earlier).rem_euclid(levels),
+        None => (later - earlier).abs(),
+    };
+    // Each row's differences are summed pairwise, and each column's row after row, like NumPy's
+    // reductions over the last and first axis.
+    let mut row_sums = Vec::with_capacity(height);
+    let mut column_sums = vec![0.0; width];
+    let mut row_diffs = Vec::with_capacity(width);
+    for (y, row) in gray.chunks_exact(width).enumerate() {
+        row_diffs.clear();
+        row_diffs.extend(row.windows(2).map(|pair| difference(pair[0], pair[1])));
+        row_sums.push(pairwise_sum(&row_diffs));
+
+        if y > 0 {
+            let above = &gray[(y - 1) * width..y * width];
+            for ((sum, below), above) in column_sums.iter_mut().zip(row).zip(above) {
+                *sum += difference(*above, *below);
+            }
+        }
+    }
+
+    let limits = |sums: Vec<f64>, size: usize| {
+        let cumulative = cumulative_sum(sums);
+        let upper_value = percentile(&cumulative, upper);
+        let lower_value = percentile(&cumulative, lower);
+        let upper_limit = cumulative.partition_point(|sum| *sum < upper_value);
+        let lower_limit = cumulative.partition_point(|sum| *sum <= lower_value);
+        if lower_limit > upper_limit {
+            ((lower / 100.0 * size as f64) as usize, (upper / 100.0 * size as f64) as usize)
+        } else {
+            (lower_limit, upper_limit)
+        }
+    };
+    (limits(row_sums, height), limits(column_sums, width))
+}
+
+fn cumulative_sum(mut values: Vec<f64>) -> Vec<f64> {
+    let mut total = 0.0;
+    for value in values.iter_mut() {
+        total += *value;
+        *value = total;
+    }
+    values
+}
+
+/// `np.linspace(lower, upper, points + 2, dtype=int)[1:-1]`, the interior points of the grid along
+/// one axis.
+fn grid_coordinates((lower, upper): (usize, usize), points: usize) -> Vec<usize> {
+    let step = (upper as f64 - lower as f64) / (points + 1) as f64;
+    (1..=points)
+        .map(|i| {
+            if step == 0.0 {
+                lower
+            } else {
+                (i as f64 * step + lower as f64).floor() as usize
+            }
+        })
+        .collect()
+}
+
+/// The mean gray level of the `P`x`P` square at each grid point, rows outer, with `P` chosen from
+/// the size of the whole image rather than the crop. Returns `None` if a square is empty.
+fn mean_levels(
+    gray: &[f64],
+    (width, height): (usize, usize),
+    row_points: &[usize],
+    column_points: &[usize],
+) -> Option<Vec<f64>> {
+    let square = 2.max((0.5 + width.min(height) as f64 / 20.0) as usize);
+    let range = |point: usize, size: usize| {
+        let lower = (point as f64 - square as f64 / 2.0).max(0.0) as usize;
+        (lower, (lower + square).min(size))
+    };
+
+    let mut averages = Vec::with_capacity(row_points.len() * column_points.len());
+    for &row in row_points {
+        let (top, bottom) = range(row, height);
+        for &column in column_points {
+            let (left, right) = range(column, width);
+            if top >= bottom || left >= right {
+                return None;
+            }
+
+            // NumPy sums squares spanning whole rows in one go, as they're contiguous, and squares
+            // one column wide down the column.
+            let sum = if right - left == width {
+                pairwise_sum(&gray[top * width..bottom * width])
+            } else if right - left == 1 {
+                let column: Vec<f64> = (top..bottom).map(|y| gray[y * width + left]).collect();
+                pairwise_sum(&column)
+            } else {
+                (top..bottom)
+                    .map(|y| pairwise_sum(&gray[y * width + left..y * width + right]))
+                    .fold(0.0, |total, row| total + row)
+            };
+            averages.push(sum / ((bottom - top) * (right - left)) as f64);
+        }
+    }
+    Some(averages)
+}
+
+/// Each grid point's gray level minus those of its eight neighbors, in the signature's order, with
+/// missing neighbors as 0.
+fn differentials(averages: &[f64], points: usize) -> Vec<f64> {
+    const DELTAS: [(isize, isize); 8] =
+        [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+    let points = points as isize;
+    let mut diffs = Vec::with_capacity(averages.len() * DELTAS.len());
+    for y in 0..points {
+        for x in 0..points {
+            let level = averages[(y * points + x) as usize];
+            diffs.extend(DELTAS.iter().map(|(delta_x, delta_y)| {
+                let (x, y) = (x + delta_x, y + delta_y);
+                if (0..points).contains(&x) && (0..points).contains(&y) {
+                    level - averages[(y * points + x) as usize]
+                } else {
+                    0.0
+                }
+            }));
+        }
+    }
+    diffs
+}
+
+/// Zeroes differences within `tolerance`, then bins the rest into two levels each side of zero at
+/// the median. Bins are assigned one after another, overwriting earlier ones where they overlap,
+/// exactly as the library does.
+fn normalize_and_threshold(diffs: &mut [f64], tolerance: f64) {
+    for diff in diffs.iter_mut() {
+        if diff.abs() < tolerance {
+            *diff = 0.0;
+        }
+    }
+
+    let mut positive: Vec<f64> = diffs.iter().copied().filter(|diff| *diff > 0.0).collect();
+    let mut negative: Vec<f64> = diffs.iter().copied().filter(|diff| *diff < 0.0).collect();
+    positive.sort_by(f64::total_cmp);
+    negative.sort_by(f64::total_cmp);
+
+    if !positive.is_empty() {
+        let cutoffs = [0.0, 50.0, 100.0].map(|q| percentile(&positive, q));
+        for (level, bounds) in cutoffs.windows(2).enumerate() {
+            for diff in diffs.iter_mut() {
+                if *diff >= bounds[0] && *diff <= bounds[1] {
+                    *diff = (level + 1) as f64;
+                }
+            }
+        }
+    }
+    if !negative.is_empty() {
+        let cutoffs = [100.0, 50.0, 0.0].map(|q| percentile(&negative, q));
+        for (level, bounds) in cutoffs.windows(2).enumerate() {
+            for diff in diffs.iter_mut() {
+                if *diff <= bounds[0] && *diff >= bounds[1] {
+                    *diff = -((level + 1) as f64);
+                }
+            }
+        }
+    }
+}
+
+/// `np.percentile` of sorted values with linear interpolation.
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let last = sorted.len() - 1;
+    let index = last as f64 * (percent / 100.0);
+    if index >= last as f64 {
+        return sorted[last];
+    }
+
+    let below = index.floor().max(0.0);
+    let (a, b) = (sorted[below as usize], sorted[below as usize + 1]);
+    let t = index - below;
+    let diff = b - a;
+    if t >= 0.5 {
+        b - diff * (1.0 - t)
+    } else {
+        a + diff * t
+    }
+}
+
+/// NumPy's pairwise summation of contiguous values.
+fn pairwise_sum(values: &[f64]) -> f64 {
+    let n = values.len();
+    if n < PAIRWISE_UNROLL {
+        values.iter().fold(0.0, |total, value| total + value)
+    } else if n <= PAIRWISE_BLOCK {
+        let mut partial = [0.0; PAIRWISE_UNROLL];
+        partial.copy_from_slice(&values[..PAIRWISE_UNROLL]);
+        let blocks = n - n % PAIRWISE_UNROLL;
+        for block in values[PAIRWISE_UNROLL..blocks].chunks_exact(PAIRWISE_UNROLL) {
+            for (partial, value) in partial.iter_mut().zip(block) {
+                *partial += value;
+            }
+        }
+        let total = ((partial[0] + partial[1]) + (partial[2] + partial[3]))
+            + ((partial[4] + partial[5]) + (partial[6] + partial[7]));
+        values[blocks..].iter().fold(total, |total, value| total + value)
+    } else {
+        let half = n / 2;
+        let half = half - half % PAIRWISE_UNROLL;
+        pairwise_sum(&values[..half]) + pairwise_sum(&values[half..])
+    }
+}
@@ -0,0 +1,491 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    check_buffer_length, check_flat, compute_signature, get_bounds, pixel_luma_fixed, place_grid,
+    AlphaMode, Arithmetic, Bounds, Compat, SignatureConfig, SignatureError,
+};
+
+/// Invocations per workgroup, matching the shaders.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// The most workgroups a dispatch may have along one dimension.
+const MAX_WORKGROUPS: u32 = 65535;
+
+/// The most bytes of pixels uploaded for one batch before its signatures are finished, which
+/// bounds the GPU memory a batch holds at once.
+const BATCH_BYTES: usize = 256 << 20;
+
+/// Reasons a GPU couldn't be set up for computing signatures.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GpuError {
+    /// No GPU adapter is available.
+    NoAdapter,
+    /// The adapter refused to provide a device, with the reason it gave.
+    NoDevice(String),
+}
+
+impl Display for GpuError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpuError::NoAdapter => write!(f, "No GPU adapter is available"),
+            GpuError::NoDevice(reason) => write!(f, "Could not get a GPU device: {}", reason),
+        }
+    }
+}
+
+impl Error for GpuError {}
+
+/// Computes signatures on the GPU, which pays off for batches of many images, such as the frames
+/// of a video. The grayscale conversion, the sums that decide the crop, and the grid averages run
+/// in compute shaders, while the few steps in between run on the CPU.
+///
+/// The shaders use integer arithmetic throughout, so signatures are identical to those computed
+/// on the CPU by the same config with [Arithmetic::Integer], whatever arithmetic the config asks
+/// for. Images too large for the device's buffers, and configs whose custom grayscale weights
+/// could overflow 32-bit arithmetic, are computed on the CPU instead, as are images too small for
+/// the grid and configs reproducing another implementation's signatures.
+pub struct GpuSigner {
+    config: SignatureConfig,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    grayscale: wgpu::ComputePipeline,
+    diff_sums: wgpu::ComputePipeline,
+    grid_sums: wgpu::ComputePipeline,
+}
+
+/// How far along an image of a batch is.
+enum Progress {
+    /// The image's gray levels and diff sums are being computed.
+    Gray(Pending),
+    /// The sums of the image's grid, with the given square edge, are being computed.
+    Grid(usize, wgpu::Buffer),
+    Done(Vec<i8>),
+}
+
+/// An image whose gray levels and diff sums are computed on the GPU.
+struct Pending {
+    width: usize,
+    height: usize,
+    params: Params,
+    gray: wgpu::Buffer,
+    sums: wgpu::Buffer,
+}
+
+/// The shaders' uniform parameters, in declaration order.
+#[derive(Clone, Copy, Default)]
+struct Params {
+    width: u32,
+    height: u32,
+    channels: u32,
+    color_offsets: [u32; 3],
+    alpha_offset: u32,
+    weighted: u32,
+    weights: [u32; 3],
+    alpha_mode: u32,
+    background: u32,
+    square_edge: u32,
+    point_count: u32,
+}
+
+impl Params {
+    fn to_bytes(self) -> Vec<u8> {
+        let [r_offset, g_offset, b_offset] = self.color_offsets;
+        let [r_weight, g_weight, b_weight] = self.weights;
+        [
+            self.width, self.height, self.channels, r_offset, g_offset, b_offset,
+            self.alpha_offset, self.weighted, r_weight, g_weight, b_weight, self.alpha_mode,
+            self.background, self.square_edge, self.point_count, 0,
+        ].iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+}
+
+impl GpuSigner {
+    /// Sets up the first available GPU to compute signatures with default settings.
+    pub fn new() -> Result<Self, GpuError> {
+        SignatureConfig::default().gpu_signer()
+    }
+
+    /// Produces a signature for a provided image that's encoded as an array of conceptually
+    /// grouped bytes in the configured [crate::PixelFormat], RGBA by default, with the provided
+    /// width.
+    ///
+    /// Panics if the signature can't be computed, see [GpuSigner::try_compute] for details.
+    pub fn compute(&self, buffer: &[u8], width: usize) -> Vec<i8> {
+        self.try_compute(buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [GpuSigner::compute]. Returns an error if the buffer doesn't
+    /// hold whole rows of pixels, or if the image is too small or too featureless to place the
+    /// signature grid on.
+    pub fn try_compute(&self, buffer: &[u8], width: usize) -> Result<Vec<i8>, SignatureError> {
+        self.compute_batch(&[(buffer, width)]).remove(0)
+    }
+
+    /// Computes the signatures of many images, submitting each step for the whole batch at once.
+    /// Each image is a buffer in the configured [crate::PixelFormat] and its width. Results are in
+    /// the order of `images`, each as [GpuSigner::try_compute] would return it.
+    pub fn compute_batch<B: AsRef<[u8]>>(
+        &self,
+        images: &[(B, usize)],
+    ) -> Vec<Result<Vec<i8>, SignatureError>> {
+        let mut results = Vec::with_capacity(images.len());
+        let mut start = 0;
+        while start < images.len() {
+            // Batches are cut once they've uploaded enough, always taking at least one image.
+            let mut bytes = 0;
+            let end = start + images[start..].iter()
+                .take_while(|(buffer, _)| {
+                    bytes += buffer.as_ref().len();
+                    bytes <= BATCH_BYTES
+                })
+                .count()
+                .max(1);
+            results.extend(self.compute_chunk(&images[start..end]));
+            start = end;
+        }
+        results
+    }
+
+    fn compute_chunk<B: AsRef<[u8]>>(
+        &self,
+        images: &[(B, usize)],
+    ) -> Vec<Result<Vec<i8>, SignatureError>> {
+        let channels = self.config.pixel_format.channels();
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        let progress: Vec<Result<Progress, SignatureError>> = images.iter()
+            .map(|(buffer, width)| {
+                let buffer = buffer.as_ref();
+                self.config.validate()?;
+                check_buffer_length(buffer.len(), *width, channels)?;
+                let height = buffer.len().checked_div(channels * width).unwrap_or(0);
+                if self.fits(buffer.len()) && !self.cpu_only(*width, height) {
+                    Ok(Progress::Gray(self.encode_gray(&mut encoder, buffer, *width)))
+                } else {
+                    Ok(Progress::Done(self.cpu_config().try_compute(buffer, *width)?))
+                }
+            })
+            .collect();
+        self.queue.submit([encoder.finish()]);
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        let progress = self.advance(progress, |image, sums| {
+            let (square_edge, grid_sums) = self.encode_grid(&mut encoder, image, sums)?;
+            Ok(Progress::Grid(square_edge, grid_sums))
+        });
+        self.queue.submit([encoder.finish()]);
+
+        self.advance(progress, |_, _| unreachable!("every image's grid has been encoded"))
+            .into_iter()
+            .map(|progress| match progress? {
+                Progress::Done(signature) => Ok(signature),
+                _ => unreachable!("every image has been finished"),
+            })
+            .collect()
+    }
+
+    /// Reads back the sums of every image waiting on the GPU, and moves each to its next step:
+    /// images with diff sums through `place_grid`, and images with grid sums to their signature.
+    fn advance(
+        &self,
+        progress: Vec<Result<Progress, SignatureError>>,
+        mut place_grid: impl FnMut(Pending, &[u64]) -> Result<Progress, SignatureError>,
+    ) -> Vec<Result<Progress, SignatureError>> {
+        let waiting = progress.iter().flatten().filter_map(|progress| match progress {
+            Progress::Gray(image) => Some(&image.sums),
+            Progress::Grid(_, sums) => Some(sums),
+            Progress::Done(_) => None,
+        });
+        let mut sums = self.read(waiting).into_iter();
+
+        progress.into_iter()
+            .map(|progress| match progress? {
+                Progress::Gray(image) => {
+                    place_grid(image, &sums.next().expect("sums were read for every image"))
+                }
+                Progress::Grid(square_edge, _) => {
+                    let grid_sums = sums.next().expect("sums were read for every image");
+                    let signature = self.finish_signature(square_edge, &grid_sums);
+                    check_flat(&signature, &self.config)?;
+                    Ok(Progress::Done(signature))
+                }
+                done => Ok(done),
+            })
+            .collect()
+    }
+
+    /// Whether an image of `len` bytes fits the device's buffers, and its gray levels can be
+    /// computed in 32-bit arithmetic.
+    fn fits(&self, len: usize) -> bool {
+        let limits = self.device.limits();
+        let max_len = limits.max_storage_buffer_binding_size.min(limits.max_buffer_size);
+        let weights_fit = self.config.grayscale_mode.fixed_weights().is_none_or(|weights| {
+            weights.iter().sum::<u64>() * 255 + (1 << 15) <= u32::MAX as u64
+        });
+        weights_fit && (len.next_multiple_of(4) as u64) <= max_len
+    }
+
+    /// Encodes the grayscale conversion and diff sums of an image.
+    fn encode_gray(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        buffer: &[u8],
+        width: usize,
+    ) -> Pending {
+        let height = buffer.len() / (self.config.pixel_format.channels() * width);
+        let params = self.params(width, height);
+
+        let mut padded;
+        let contents = if buffer.len().is_multiple_of(4) {
+            buffer
+        } else {
+            padded = buffer.to_vec();
+            padded.resize(buffer.len().next_multiple_of(4), 0);
+            &padded
+        };
+        let pixels = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents,
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let gray = self.storage((width * height).div_ceil(4).max(1));
+        let sums = self.storage(2 * (width + height));
+
+        let uniform = self.uniform(params);
+        let gray_group =
+            self.bind_group(&self.grayscale, &[(0, &uniform), (1, &pixels), (2, &gray)]);
+        self.dispatch(encoder, &self.grayscale, &gray_group, (width * height).div_ceil(4));
+        let sums_group = self.bind_group(&self.diff_sums, &[(0, &uniform), (2, &gray), (3, &sums)]);
+        self.dispatch(encoder, &self.diff_sums, &sums_group, width + height);
+
+        Pending { width, height, params, gray, sums }
+    }
+
+    /// Crops and places the grid of an image from its diff sums, and encodes the grid sums.
+    /// Returns the square edge and the buffer the grid sums will be written to.
+    fn encode_grid(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        image: Pending,
+        diff_sums: &[u64],
+    ) -> Result<(usize, wgpu::Buffer), SignatureError> {
+        let Pending { width, height, mut params, gray, .. } = image;
+        let config = self.cpu_config();
+        let (top, bottom) = get_bounds(&diff_sums[..height], config.crop, config.arithmetic);
+        let (left, right) = get_bounds(&diff_sums[height..], config.crop, config.arithmetic);
+        let bounds = Bounds { lower_x: left, upper_x: right, lower_y: top, upper_y: bottom };
+
+        let mut points = Vec::new();
+        let square_edge = place_grid(&bounds, (width, height), &config, &mut points)?;
+        params.square_edge = square_edge as u32;
+        params.point_count = points.len() as u32;
+
+        let point_words: Vec<u8> = points.iter()
+            .flat_map(|(x, y)| [*x as u32, *y as u32])
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        let points_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            // Storage bindings can't be empty, which they would be for a grid of one.
+            contents: if point_words.is_empty() { &[0; 8] } else { &point_words },
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let sums = self.storage(2 * points.len().max(1));
+
+        let uniform = self.uniform(params);
+        let group = self.bind_group(
+            &self.grid_sums,
+            &[(0, &uniform), (2, &gray), (3, &sums), (4, &points_buffer)],
+        );
+        self.dispatch(encoder, &self.grid_sums, &group, points.len());
+
+        Ok((square_edge, sums))
+    }
+
+    /// Reduces the grid sums to averages and quantizes their differences into a signature.
+    fn finish_signature(&self, square_edge: usize, grid_sums: &[u64]) -> Vec<i8> {
+        let side = (2 * square_edge + 1) as u64;
+        let points = (self.config.grid_size.saturating_sub(1)).pow(2);
+        let averages: Vec<u8> = grid_sums[..points].iter()
+            .map(|sum| (sum / (9 * side * side)) as u8)
+            .collect();
+
+        let mut signature = vec![0; self.config.signature_length()];
+        compute_signature(
+            &averages,
+            self.config.grid_size,
+            self.config.identical_tolerance,
+            self.config.neighbor_padding,
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut signature,
+        );
+        signature
+    }
+
+    /// Whether an image is left to the CPU, because it's rejected or upscaled for being too small,
+    /// downscaled for being too large, or because the config reproduces another implementation.
+    fn cpu_only(&self, width: usize, height: usize) -> bool {
+        width.min(height) <= self.config.grid_size
+            || self.config.upscale_factor(width, height) > 1
+            || self.config.downscale_factor(width, height) > 1
+            || self.config.compat != Compat::Native
+    }
+
+    /// The config computing the same signatures on the CPU.
+    fn cpu_config(&self) -> SignatureConfig {
+        self.config.arithmetic(Arithmetic::Integer)
+    }
+
+    fn params(&self, width: usize, height: usize) -> Params {
+        let format = self.config.pixel_format;
+        let mode = self.config.grayscale_mode;
+        let (alpha_mode, background) = match self.config.alpha_mode {
+            AlphaMode::Ignore => (0, 0),
+            AlphaMode::Multiply => (1, 0),
+            AlphaMode::Background([r, g, b]) => {
+                (2, pixel_luma_fixed(r as u64, g as u64, b as u64, 255, mode) as u32)
+            }
+            AlphaMode::Checkerboard => (3, 0),
+        };
+        let weights = mode.fixed_weights();
+
+        Params {
+            width: width as u32,
+            height: height as u32,
+            channels: format.channels() as u32,
+            color_offsets: format.color_offsets().map(|offset| offset as u32),
+            alpha_offset: format.alpha_offset().map_or(u32::MAX, |offset| offset as u32),
+            weighted: weights.is_some() as u32,
+            weights: weights.unwrap_or_default().map(|weight| weight as u32),
+            alpha_mode,
+            background,
+            ..Params::default()
+        }
+    }
+
+    fn storage(&self, words: usize) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 4 * words as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn uniform(&self, params: Params) -> wgpu::Buffer {
+        self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &params.to_bytes(),
+            usage: wgpu::BufferUsages::UNIFORM,
+        })
+    }
+
+    fn bind_group(
+        &self,
+        pipeline: &wgpu::ComputePipeline,
+        buffers: &[(u32, &wgpu::Buffer)],
+    ) -> wgpu::BindGroup {
+        let entries: Vec<_> = buffers.iter()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: *binding,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        })
+    }
+
+    /// Dispatches enough workgroups for `invocations`, spread over two dimensions when there are
+    /// more than one dimension allows.
+    fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::ComputePipeline,
+        group: &wgpu::BindGroup,
+        invocations: usize,
+    ) {
+        let groups = (invocations as u32).div_ceil(WORKGROUP_SIZE).max(1);
+        let x = groups.min(MAX_WORKGROUPS);
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, group, &[]);
+        pass.dispatch_workgroups(x, groups.div_ceil(x), 1);
+    }
+
+    /// Copies buffers of 64-bit sums, stored as pairs of low and high words, back from the GPU.
+    fn read<'a>(&self, buffers: impl Iterator<Item = &'a wgpu::Buffer>) -> Vec<Vec<u64>> {
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        let staging: Vec<wgpu::Buffer> = buffers
+            .map(|buffer| {
+                let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: None,
+                    size: buffer.size(),
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+                staging
+            })
+            .collect();
+        self.queue.submit([encoder.finish()]);
+
+        for buffer in &staging {
+            buffer.slice(..).map_async(wgpu::MapMode::Read, |result| {
+                result.expect("GPU buffer could not be read")
+            });
+        }
+        self.device.poll(wgpu::PollType::wait_indefinitely()).expect("GPU device was lost");
+
+        staging.iter()
+            .map(|buffer| {
+                let words = buffer.slice(..).get_mapped_range().expect("GPU buffer was mapped");
+                words.chunks_exact(8)
+                    .map(|pair| u64::from_le_bytes(pair.try_into().unwrap()))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl SignatureConfig {
+    /// Sets up the first available GPU to compute signatures with this config, see [GpuSigner].
+    pub fn gpu_signer(&self) -> Result<GpuSigner, GpuError> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default()))
+            .map_err(|_| GpuError::NoAdapter)?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            required_limits: adapter.limits(),
+            ..Default::default()
+        })).map_err(|e| GpuError::NoDevice(e.to_string()))?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("image-match"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+        let pipeline = |entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: None,
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        Ok(GpuSigner {
+            config: *self,
+            grayscale: pipeline("grayscale"),
+            diff_sums: pipeline("diff_sums"),
+            grid_sums: pipeline("grid_sums"),
+            device,
+            queue,
+        })
+    }
+}
@@ -0,0 +1,190 @@
+// Compute shaders for the GPU backend. Every step mirrors the integer arithmetic of the CPU
+// pipeline, so signatures are bit-identical to those computed with `Arithmetic::Integer`.
+
+struct Params {
+    width: u32,
+    height: u32,
+    channels: u32,
+    r_offset: u32,
+    g_offset: u32,
+    b_offset: u32,
+    // NO_ALPHA when the format has no alpha channel.
+    alpha_offset: u32,
+    // Fixed-point luma weights, used when `weighted` is 1.
+    weighted: u32,
+    r_weight: u32,
+    g_weight: u32,
+    b_weight: u32,
+    // 0 ignore, 1 multiply, 2 background, 3 checkerboard.
+    alpha_mode: u32,
+    background: u32,
+    square_edge: u32,
+    point_count: u32,
+    _padding: u32,
+}
+
+const NO_ALPHA: u32 = 0xFFFFFFFFu;
+const CHECKER_SIZE: u32 = 8u;
+const WORKGROUP_SIZE: u32 = 64u;
+
+@group(0) @binding(0) var<uniform> params: Params;
+// Pixel bytes, four to a word, little-endian.
+@group(0) @binding(1) var<storage, read> pixels: array<u32>;
+// Gray levels, four to a word, little-endian.
+@group(0) @binding(2) var<storage, read_write> gray: array<u32>;
+// 64-bit sums as pairs of low and high words.
+@group(0) @binding(3) var<storage, read_write> sums: array<u32>;
+// Grid points as pairs of x and y.
+@group(0) @binding(4) var<storage, read> points: array<u32>;
+
+fn pixel_byte(index: u32) -> u32 {
+    return (pixels[index >> 2u] >> ((index & 3u) * 8u)) & 0xFFu;
+}
+
+fn gray_at(x: u32, y: u32) -> u32 {
+    let index = y * params.width + x;
+    return (gray[index >> 2u] >> ((index & 3u) * 8u)) & 0xFFu;
+}
+
+// The gray level at the given coordinates, clamped to the nearest pixel of the image.
+fn gray_clamped(x: i32, y: i32) -> u32 {
+    let last = vec2<i32>(i32(params.width) - 1, i32(params.height) - 1);
+    return gray_at(u32(clamp(x, 0, last.x)), u32(clamp(y, 0, last.y)));
+}
+
+fn invocation_index(id: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return id.y * groups.x * WORKGROUP_SIZE + id.x;
+}
+
+// Adds `value` to the 64-bit sum in `total`, carrying into the high word.
+fn add_wide(total: vec2<u32>, value: u32) -> vec2<u32> {
+    let low = total.x + value;
+    return vec2<u32>(low, total.y + select(0u, 1u, low < value));
+}
+
+fn pixel_luma(r: u32, g: u32, b: u32) -> u32 {
+    if params.weighted == 0u {
+        return (r + g + b) / 3u;
+    }
+    let weighted = r * params.r_weight + g * params.g_weight + b * params.b_weight + 32768u;
+    return min(weighted >> 16u, 255u);
+}
+
+fn pixel_gray(index: u32) -> u32 {
+    let start = index * params.channels;
+    let luma = pixel_luma(
+        pixel_byte(start + params.r_offset),
+        pixel_byte(start + params.g_offset),
+        pixel_byte(start + params.b_offset),
+    );
+    var alpha = 255u;
+    if params.alpha_offset != NO_ALPHA {
+        alpha = pixel_byte(start + params.alpha_offset);
+    }
+
+    var background = params.background;
+    switch params.alpha_mode {
+        case 0u: {
+            return luma;
+        }
+        case 1u: {
+            return luma * alpha / 255u;
+        }
+        case 3u: {
+            let x = index % params.width;
+            let y = index / params.width;
+            background = select(204u, 255u, (x / CHECKER_SIZE + y / CHECKER_SIZE) % 2u == 0u);
+        }
+        default: {}
+    }
+    return (luma * alpha + background * (255u - alpha)) / 255u;
+}
+
+// Converts four consecutive pixels to gray per invocation, packing them into one word.
+@compute @workgroup_size(64)
+fn grayscale(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let word = invocation_index(id, groups);
+    let pixel_count = params.width * params.height;
+    if word * 4u >= pixel_count {
+        return;
+    }
+
+    var packed = 0u;
+    for (var i = 0u; i < 4u; i++) {
+        let index = word * 4u + i;
+        if index < pixel_count {
+            packed |= pixel_gray(index) << (i * 8u);
+        }
+    }
+    gray[word] = packed;
+}
+
+// Sums the differences between adjacent pixels of one row, for the first `height` invocations, or
+// of one column, for the next `width`.
+@compute @workgroup_size(64)
+fn diff_sums(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let index = invocation_index(id, groups);
+    var total = vec2<u32>(0u, 0u);
+    if index < params.height {
+        for (var x = 1u; x < params.width; x++) {
+            let left = gray_at(x - 1u, index);
+            let right = gray_at(x, index);
+            total = add_wide(total, max(left, right) - min(left, right));
+        }
+    } else if index < params.height + params.width {
+        let x = index - params.height;
+        for (var y = 1u; y < params.height; y++) {
+            let above = gray_at(x, y - 1u);
+            let below = gray_at(x, y);
+            total = add_wide(total, max(above, below) - min(above, below));
+        }
+    } else {
+        return;
+    }
+    sums[2u * index] = total.x;
+    sums[2u * index + 1u] = total.y;
+}
+
+// The number of the nine shifted squares around a point that cover the pixel `offset` pixels into
+// the area they span together, along one axis.
+fn coverage(offset: u32, side: u32) -> u32 {
+    let first = select(0u, offset + 1u - side, offset + 1u > side);
+    return min(2u, offset) + 1u - first;
+}
+
+// Sums the nine shifted squares around one grid point per invocation, as a weighted sum over the
+// area they span together. Pixels past the border are sampled from the nearest pixel on it.
+@compute @workgroup_size(64)
+fn grid_sums(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let index = invocation_index(id, groups);
+    if index >= params.point_count {
+        return;
+    }
+
+    let side = 2u * params.square_edge + 1u;
+    let left = i32(points[2u * index]) - i32(params.square_edge) - 1;
+    let top = i32(points[2u * index + 1u]) - i32(params.square_edge) - 1;
+    var total = vec2<u32>(0u, 0u);
+    for (var offset_y = 0u; offset_y < side + 2u; offset_y++) {
+        var row = 0u;
+        for (var offset_x = 0u; offset_x < side + 2u; offset_x++) {
+            let level = gray_clamped(left + i32(offset_x), top + i32(offset_y));
+            row += level * coverage(offset_x, side);
+        }
+        let weight = coverage(offset_y, side);
+        for (var i = 0u; i < weight; i++) {
+            total = add_wide(total, row);
+        }
+    }
+    sums[2u * index] = total.x;
+    sums[2u * index + 1u] = total.y;
+}
@@ -0,0 +1,117 @@
+use crate::{check_buffer_length, Sample, SignatureError};
+
+/// A grayscale image stored as a single buffer of rows, top to bottom, where pure white is
+/// represented by the sample type's maximum and pure black by 0. This is the form every input is
+/// brought into before the grid is placed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrayImage<T = u8> {
+    data: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+// Parts of the API are only used by the `pipeline` module.
+#[cfg_attr(not(feature = "internals"), allow(dead_code))]
+impl<T: Copy> GrayImage<T> {
+    /// Wraps a buffer of gray levels with the provided width. Returns an error if the buffer
+    /// doesn't hold a whole number of rows.
+    pub fn new(data: Vec<T>, width: usize) -> Result<Self, SignatureError> {
+        check_buffer_length(data.len(), width, 1)?;
+        let height = data.len() / width;
+        Ok(GrayImage { data, width, height })
+    }
+
+    /// Wraps a buffer already known to hold exactly `height` rows of `width` gray levels.
+    pub(crate) fn from_raw(data: Vec<T>, width: usize, height: usize) -> Self {
+        debug_assert_eq!(data.len(), width * height);
+        GrayImage { data, width, height }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The gray level of the pixel at column `x` and row `y`.
+    pub fn get(&self, x: usize, y: usize) -> T {
+        self.data[y * self.width + x]
+    }
+
+    /// The gray levels of row `y`.
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.data[y * self.width..(y + 1) * self.width]
+    }
+
+    /// All gray levels, row after row.
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    pub(crate) fn data_mut(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    /// A copy of the image with every pixel repeated `factor` times along both sides.
+    pub(crate) fn upscale(&self, factor: usize) -> Self {
+        let width = self.width * factor;
+        let mut data = Vec::with_capacity(width * self.height * factor);
+        for y in 0..self.height {
+            let start = data.len();
+            data.extend(self.row(y).iter().flat_map(|&level| std::iter::repeat_n(level, factor)));
+            for _ in 1..factor {
+                data.extend_from_within(start..start + width);
+            }
+        }
+        GrayImage { data, width, height: self.height * factor }
+    }
+
+    /// A copy of the image with every `factor` by `factor` square of pixels averaged into one, the
+    /// squares along the right and bottom edges over the pixels they cover.
+    pub(crate) fn downscale(&self, factor: usize) -> Self
+    where
+        T: Sample,
+    {
+        let width = self.width.div_ceil(factor);
+        let height = self.height.div_ceil(factor);
+        let mut data = Vec::with_capacity(width * height);
+        let mut sums = vec![0_u64; width];
+        for rows in self.data.chunks(self.width * factor) {
+            sums.fill(0);
+            for row in rows.chunks_exact(self.width) {
+                for (sum, square) in sums.iter_mut().zip(row.chunks(factor)) {
+                    *sum += square.iter().map(|&level| level.into()).sum::<u64>();
+                }
+            }
+            let rows = (rows.len() / self.width) as u64;
+            data.extend(sums.iter().enumerate().map(|(x, &sum)| {
+                let columns = (self.width - x * factor).min(factor) as u64;
+                let count = rows * columns;
+                T::from_integer((sum + count / 2) / count)
+            }));
+        }
+        GrayImage { data, width, height }
+    }
+
+    /// A copy of the image surrounded by `margin` copies of its outermost rows and columns, and of
+    /// its corner pixels beyond them.
+    pub(crate) fn pad(&self, margin: usize) -> Self {
+        let width = self.width + 2 * margin;
+        let height = self.height + 2 * margin;
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row = self.row(y.saturating_sub(margin).min(self.height - 1));
+            data.extend(std::iter::repeat_n(row[0], margin));
+            data.extend_from_slice(row);
+            data.extend(std::iter::repeat_n(row[self.width - 1], margin));
+        }
+        GrayImage { data, width, height }
+    }
+
+    /// Unwraps the buffer of gray levels.
+    pub fn into_data(self) -> Vec<T> {
+        self.data
+    }
+}
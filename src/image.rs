@@ -1,35 +1,100 @@
-use std::cmp::{max, min};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
-use std::io;
+use std::fs;
+use std::io::{self, BufRead, Cursor, Read, Seek};
 use std::path::Path;
 
-use image::{GenericImageView, ImageError, Pixel};
-use image::io::Reader as ImageReader;
-use num::ToPrimitive;
+use exif::{Exif, In, Tag};
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::tiff::TiffDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{
+    imageops, DynamicImage, GenericImageView, ImageDecoder, ImageError, ImageFormat, ImageResult,
+    Pixel, Primitive,
+};
+use qcms::{DataType, Intent, Profile, Transform};
 
-use ImageReadError::{DecodeError, IoError};
+use ImageReadError::{ComputeError, DecodeError, IoError};
 
-use crate::{compute_from_gray, DEFAULT_CROP, DEFAULT_GRID_SIZE, pixel_gray};
+pub use animation::{
+    animation_signature, get_animation_signatures, get_gif_signatures, FrameSignature,
+};
+#[cfg(feature = "pdf")]
+pub use pages::{get_pdf_page_signatures, DEFAULT_PDF_DPI};
+pub use pages::get_tiff_page_signatures;
+#[cfg(all(feature = "tokio", feature = "http"))]
+pub use nonblocking::get_url_signature_async;
+#[cfg(feature = "tokio")]
+pub use nonblocking::get_file_signature_async;
+#[cfg(feature = "http")]
+pub use url::{
+    get_url_signature, UrlLimits, UrlReadError, DEFAULT_URL_MAX_BYTES, DEFAULT_URL_MAX_PIXELS,
+    DEFAULT_URL_TIMEOUT,
+};
+pub use thumbnail::{get_thumbnail_signature, thumbnail_candidates, DEFAULT_THUMBNAIL_THRESHOLD};
+
+use crate::{
+    compute_from_gray, pixel_gray, Compat, GrayImage, PixelFormat, Region, Signature,
+    SignatureConfig, SignatureError, TiledSignature,
+};
+
+mod animation;
+mod pages;
+#[cfg(feature = "tokio")]
+mod nonblocking;
+mod thumbnail;
+#[cfg(feature = "http")]
+mod url;
 
 /// Produces a 544 signed byte signature for a provided image. The result is designed to be compared
 /// to other vectors computed by a call to this method using [cosine-similarity(a, b)].
 pub fn get_image_signature<I: GenericImageView>(img: I) -> Vec<i8> {
-    let gray = grayscale_image(img);
+    SignatureConfig::default().compute_image(img)
+}
 
-    let average_square_width_fn = |width, height| {
-        max(
-            2_usize,
-            (0.5 + min(width, height) as f32 / 20.0).floor() as usize,
-        ) / 2
-    };
+/// Produces a 544 signed byte signature for a borrowed view of an image, such as a [SubImage] of a
+/// larger one, a custom image type, or a view from another crate, without converting it into an
+/// owned buffer first. The result is designed to be compared to other vectors computed by a call
+/// to this method using [cosine-similarity(a, b)]. A [SubImage] is a view through [Deref], so
+/// it's signed as `get_view_signature(&*sub_image)`.
+///
+/// [SubImage]: image::SubImage
+/// [Deref]: std::ops::Deref
+pub fn get_view_signature<I: GenericImageView>(view: &I) -> Vec<i8> {
+    SignatureConfig::default().compute_view(view)
+}
 
-    compute_from_gray(gray, DEFAULT_CROP, DEFAULT_GRID_SIZE, average_square_width_fn)
+/// Produces a 544 signed byte signature for a region of a provided image, such as a face or
+/// product an upstream detector found, given in pixels or relative to the image's size. The result
+/// is designed to be compared to other vectors computed by a call to this method or
+/// [get_region_signature](crate::get_region_signature) using [cosine-similarity(a, b)].
+pub fn get_image_region_signature<I: GenericImageView>(img: &I, region: Region) -> Vec<i8> {
+    SignatureConfig::default().compute_image_region(img, region)
+}
+
+/// Splits a provided image into `columns` by `rows` even tiles and produces a signature for each,
+/// so that [TiledSignature::matches] can report which tiles of two images match, such as a known
+/// photo within a collage or a screenshot.
+pub fn get_image_tiled_signature<I: GenericImageView>(
+    img: &I,
+    columns: usize,
+    rows: usize,
+) -> TiledSignature {
+    SignatureConfig::default().compute_image_tiles(img, columns, rows)
+}
+
+/// Produces a 544 signed byte signature for a decoded image of any color type, reading its pixels
+/// in their own layout and depth rather than converting them to 8-bit RGBA first. The result is
+/// designed to be compared to other vectors computed by a call to this method using
+/// [cosine-similarity(a, b)].
+pub fn get_dynamic_image_signature(img: &DynamicImage) -> Vec<i8> {
+    SignatureConfig::default().compute_dynamic_image(img)
 }
 
 /// Produces a variable length signed byte signature for a provided image. The result is designed to
 /// be compared to other vectors computed by a call to this method with identical tuning parameters
-/// using [cosine-similarity(a, b)]. `crop` is a value in [0, 0.5] indicating what percentage of the
+/// using [cosine-similarity(a, b)]. `crop` is a value in [0, 0.5) indicating what percentage of the
 /// image to crop on all sides before grid placement. Note that this percentage is based not on the
 /// raw width but a calculation of color density. `grid_size` indicates how many points to place on
 /// the image for measurement in the resulting signature. Changing `grid_size` will alter the length
@@ -38,26 +103,45 @@ pub fn get_image_signature<I: GenericImageView>(img: I) -> Vec<i8> {
 /// to produce that grid point's brightness value. The paper proposes
 /// `max(2, floor(0.5 + min(cropped_width, cropped_height) / 20))` but provides no information about
 /// how that was chosen.
+#[deprecated(since = "0.2.4", note = "use `SignatureConfig::compute_image` instead")]
 pub fn get_tuned_image_signature<I: GenericImageView>(
     img: I,
     crop: f32,
     grid_size: usize,
     average_square_width_fn: fn(width: usize, height: usize) -> usize,
 ) -> Vec<i8> {
-    let gray = grayscale_image(img);
-    compute_from_gray(gray, crop, grid_size, average_square_width_fn)
+    SignatureConfig::new()
+        .crop(crop)
+        .grid_size(grid_size)
+        .average_square_width_fn(average_square_width_fn)
+        .compute_image(img)
 }
 
 /// Produces a 544 signed byte signature for a provided image file. The result is designed to be
 /// compared to other vectors computed by a call to this method using [cosine-similarity(a, b)].
+/// See [SignatureConfig::compute_file_signature] for a [Signature] tagged with its parameters.
 pub fn get_file_signature<P: AsRef<Path>>(path: P) -> Result<Vec<i8>> {
-    let image = ImageReader::open(path)?.decode()?;
-    Ok(get_image_signature(image))
+    SignatureConfig::default().compute_file(path)
+}
+
+/// Produces a 544 signed byte signature for an encoded image held in memory, such as an upload,
+/// whose format is guessed from its first bytes. The result is designed to be compared to other
+/// vectors computed by a call to this method using [cosine-similarity(a, b)].
+pub fn get_bytes_signature(bytes: &[u8]) -> Result<Vec<i8>> {
+    SignatureConfig::default().compute_bytes(bytes)
+}
+
+/// Produces a 544 signed byte signature for an encoded image read from a stream, such as an HTTP
+/// body or an object store download, whose format is guessed from its first bytes. The result is
+/// designed to be compared to other vectors computed by a call to this method using
+/// [cosine-similarity(a, b)].
+pub fn get_reader_signature<R: Read>(reader: R) -> Result<Vec<i8>> {
+    SignatureConfig::default().compute_reader(reader)
 }
 
 /// Produces a variable length signed byte signature for a provided image file. The result is
 /// designed to be compared to other vectors computed by a call to this method with identical tuning
-/// parameters using [cosine-similarity(a, b)]. `crop` is a value in [0, 0.5] indicating what
+/// parameters using [cosine-similarity(a, b)]. `crop` is a value in [0, 0.5) indicating what
 /// percentage of the image to crop on all sides before grid placement. Note that this percentage is
 /// based not on the raw width but a calculation of color density. `grid_size` indicates how many
 /// points to place on the image for measurement in the resulting signature. Changing `grid_size`
@@ -66,19 +150,233 @@ pub fn get_file_signature<P: AsRef<Path>>(path: P) -> Result<Vec<i8>> {
 /// averaged to produce that grid point's brightness value. The paper proposes
 /// `max(2, floor(0.5 + min(cropped_width, cropped_height) / 20))` but provides no information about
 /// how that was chosen.
+#[deprecated(since = "0.2.4", note = "use `SignatureConfig::compute_file` instead")]
 pub fn get_tuned_file_signature<P: AsRef<Path>>(
     path: P,
     crop: f32,
     grid_size: usize,
     average_square_width_fn: fn(width: usize, height: usize) -> usize,
 ) -> Result<Vec<i8>> {
-    let image = ImageReader::open(path)?.decode()?;
-    Ok(get_tuned_image_signature(image, crop, grid_size, average_square_width_fn))
+    SignatureConfig::new()
+        .crop(crop)
+        .grid_size(grid_size)
+        .average_square_width_fn(average_square_width_fn)
+        .compute_file(path)
+}
+
+impl SignatureConfig {
+    /// Produces a signature for a provided image. The result is designed to be compared to other
+    /// signatures computed with an identical config using [cosine-similarity(a, b)].
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_image] for
+    /// details.
+    pub fn compute_image<I: GenericImageView>(&self, img: I) -> Vec<i8> {
+        self.try_compute_image(img).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_image]. Returns an error if the image
+    /// is too small or too featureless to place the signature grid on.
+    pub fn try_compute_image<I: GenericImageView>(
+        &self,
+        img: I,
+    ) -> std::result::Result<Vec<i8>, SignatureError> {
+        self.try_compute_view(&img)
+    }
+
+    /// Like [SignatureConfig::compute_image], but borrows the image, so that views like a
+    /// [SubImage](image::SubImage) of a larger image are signed in place, reading only the pixels
+    /// they show.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_view] for
+    /// details.
+    pub fn compute_view<I: GenericImageView>(&self, view: &I) -> Vec<i8> {
+        self.try_compute_view(view).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_view]. Returns an error if the view is
+    /// too small or too featureless to place the signature grid on.
+    pub fn try_compute_view<I: GenericImageView>(
+        &self,
+        view: &I,
+    ) -> std::result::Result<Vec<i8>, SignatureError> {
+        compute_from_gray(&grayscale_image(view, self), self)
+    }
+
+    /// Like [SignatureConfig::compute_view], but signs only a region of the image, reading its
+    /// pixels in place as if the image had been cropped to it first.
+    ///
+    /// Panics if the signature can't be computed, see
+    /// [SignatureConfig::try_compute_image_region] for details.
+    pub fn compute_image_region<I: GenericImageView>(&self, img: &I, region: Region) -> Vec<i8> {
+        self.try_compute_image_region(img, region).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_image_region]. Returns an error if
+    /// the region doesn't fit in the image, or if it's too small or too featureless to place the
+    /// signature grid on.
+    pub fn try_compute_image_region<I: GenericImageView>(
+        &self,
+        img: &I,
+        region: Region,
+    ) -> std::result::Result<Vec<i8>, SignatureError> {
+        let (width, height) = img.dimensions();
+        let (x, y, width, height) = region.bounds(width as usize, height as usize)?;
+        let view = imageops::crop_imm(img, x as u32, y as u32, width as u32, height as u32);
+        self.try_compute_view(&*view)
+    }
+
+    /// Splits a provided image into `columns` by `rows` even tiles and signs each of them, as if
+    /// it were cropped out with [SignatureConfig::compute_image_region].
+    ///
+    /// Panics if a tile can't be signed, see [SignatureConfig::try_compute_image_tiles] for
+    /// details.
+    pub fn compute_image_tiles<I: GenericImageView>(
+        &self,
+        img: &I,
+        columns: usize,
+        rows: usize,
+    ) -> TiledSignature {
+        self.try_compute_image_tiles(img, columns, rows).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_image_tiles]. Returns an error if
+    /// there are no columns or rows or more of them than pixels, or if a tile is too small or too
+    /// featureless to place the signature grid on.
+    pub fn try_compute_image_tiles<I: GenericImageView>(
+        &self,
+        img: &I,
+        columns: usize,
+        rows: usize,
+    ) -> std::result::Result<TiledSignature, SignatureError> {
+        let (width, height) = img.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        TiledSignature::try_from_tiles(self, columns, rows, width, height, |region| {
+            self.try_compute_image_region(img, region)
+        })
+    }
+
+    /// Produces a signature for a decoded image of any color type. Gray, RGB, and RGBA images of 8
+    /// or 16 bits, and RGB and RGBA images of `f32` channels, are read through the buffer of
+    /// matching layout, like [SignatureConfig::compute_gray_u16] or
+    /// [SignatureConfig::compute_f32], without a conversion to 8-bit RGBA. Other color types are
+    /// read pixel by pixel, like [SignatureConfig::compute_image]. The configured [PixelFormat]
+    /// is ignored, since the image's own layout is known. With [Compat::Goldberg], 8 and 16-bit
+    /// gray images are read as gray levels and 8-bit color ones as is, and every other image is
+    /// converted to 8-bit RGBA first.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_dynamic_image]
+    /// for details.
+    pub fn compute_dynamic_image(&self, img: &DynamicImage) -> Vec<i8> {
+        self.try_compute_dynamic_image(img).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_dynamic_image]. Returns an error if
+    /// the image is too small or too featureless to place the signature grid on.
+    pub fn try_compute_dynamic_image(
+        &self,
+        img: &DynamicImage,
+    ) -> std::result::Result<Vec<i8>, SignatureError> {
+        let width = img.width() as usize;
+        let rgb = self.pixel_format(PixelFormat::Rgb);
+        let rgba = self.pixel_format(PixelFormat::Rgba);
+        if self.compat == Compat::Goldberg {
+            // Python's imaging library converts gray images with alpha to RGBA, like every other
+            // image that isn't 8-bit gray or color, which scikit-image then converts to gray.
+            return match img {
+                DynamicImage::ImageLuma8(img) => self.try_compute_gray(img.as_raw(), width),
+                DynamicImage::ImageLuma16(img) => self.try_compute_gray_u16(img.as_raw(), width),
+                DynamicImage::ImageRgb8(img) => rgb.try_compute(img.as_raw(), width),
+                DynamicImage::ImageRgba8(img) => rgba.try_compute(img.as_raw(), width),
+                img => rgba.try_compute(img.to_rgba8().as_raw(), width),
+            };
+        }
+        match img {
+            DynamicImage::ImageLuma8(img) => self.try_compute_gray(img.as_raw(), width),
+            DynamicImage::ImageRgb8(img) => rgb.try_compute(img.as_raw(), width),
+            DynamicImage::ImageRgba8(img) => rgba.try_compute(img.as_raw(), width),
+            DynamicImage::ImageLuma16(img) => self.try_compute_gray_u16(img.as_raw(), width),
+            DynamicImage::ImageRgb16(img) => rgb.try_compute_u16(img.as_raw(), width),
+            DynamicImage::ImageRgba16(img) => rgba.try_compute_u16(img.as_raw(), width),
+            DynamicImage::ImageRgb32F(img) => rgb.try_compute_f32(img.as_raw(), width),
+            DynamicImage::ImageRgba32F(img) => rgba.try_compute_f32(img.as_raw(), width),
+            img => compute_from_gray(&grayscale_image(img, self), self),
+        }
+    }
+
+    /// Produces a signature for a provided image file. The result is designed to be compared to
+    /// other signatures computed with an identical config using [cosine-similarity(a, b)]. The
+    /// image is converted to sRGB by its ICC profile and turned upright by its EXIF orientation,
+    /// see [SignatureConfig::icc_profiles] and [SignatureConfig::exif_orientation].
+    pub fn compute_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<i8>> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let format = ImageFormat::from_path(path).or_else(|_| image::guess_format(&bytes))?;
+        self.compute_encoded(&bytes, format)
+    }
+
+    /// Produces a signature for an encoded image held in memory, in any format the image library
+    /// can decode, guessed from its first bytes, without writing it to a file first. The result
+    /// is designed to be compared to other signatures computed with an identical config using
+    /// [cosine-similarity(a, b)]. The image is converted to sRGB and turned upright, as with
+    /// [SignatureConfig::compute_file].
+    pub fn compute_bytes(&self, bytes: &[u8]) -> Result<Vec<i8>> {
+        self.compute_encoded(bytes, image::guess_format(bytes)?)
+    }
+
+    /// Like [SignatureConfig::compute_bytes], but tags the result with the config's parameters so
+    /// that comparisons can check them.
+    pub fn compute_bytes_signature(&self, bytes: &[u8]) -> Result<Signature> {
+        Ok(Signature::new(self.params(), self.compute_bytes(bytes)?))
+    }
+
+    /// Produces a signature for an encoded image read from a stream to its end, in any format the
+    /// image library can decode, guessed from its first bytes. Streams that can't seek, like
+    /// network bodies, are read into memory first, since most formats can't be decoded in one
+    /// pass; wrap them in [Read::take] to bound how much is read.
+    pub fn compute_reader<R: Read>(&self, mut reader: R) -> Result<Vec<i8>> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        self.compute_bytes(&bytes)
+    }
+
+    /// Like [SignatureConfig::compute_reader], but tags the result with the config's parameters
+    /// so that comparisons can check them.
+    pub fn compute_reader_signature<R: Read>(&self, reader: R) -> Result<Signature> {
+        Ok(Signature::new(self.params(), self.compute_reader(reader)?))
+    }
+
+    /// Decodes `bytes` in `format`, converted to sRGB and turned upright as configured, and signs
+    /// the image.
+    fn compute_encoded(&self, bytes: &[u8], format: ImageFormat) -> Result<Vec<i8>> {
+        let mut image = if self.converts_profiles() {
+            let (image, profile) = decode_with_profile(bytes, format)?;
+            match profile {
+                Some(profile) => to_srgb(image, &profile),
+                None => image,
+            }
+        } else {
+            image::load_from_memory_with_format(bytes, format)?
+        };
+        if self.orients_images() {
+            image = orient(image, Cursor::new(bytes));
+        }
+        Ok(self.try_compute_dynamic_image(&image)?)
+    }
+
+    /// Like [SignatureConfig::compute_file], but tags the result with the config's parameters so
+    /// that comparisons can check them. Opening, decoding, and signing the file all fail with the
+    /// one [ImageReadError].
+    pub fn compute_file_signature<P: AsRef<Path>>(&self, path: P) -> Result<Signature> {
+        Ok(Signature::new(self.params(), self.compute_file(path)?))
+    }
 }
 
+/// The error of reading the signature of an image file: opening it, decoding it, or computing the
+/// signature of the decoded image.
 pub enum ImageReadError {
     IoError(io::Error),
     DecodeError(ImageError),
+    ComputeError(SignatureError),
 }
 
 impl Debug for ImageReadError {
@@ -86,6 +384,7 @@ impl Debug for ImageReadError {
         match self {
             IoError(e) => Debug::fmt(e, f),
             DecodeError(e) => Debug::fmt(e, f),
+            ComputeError(e) => Debug::fmt(e, f),
         }
     }
 }
@@ -95,15 +394,17 @@ impl Display for ImageReadError {
         match self {
             IoError(e) => Display::fmt(e, f),
             DecodeError(e) => Display::fmt(e, f),
+            ComputeError(e) => Display::fmt(e, f),
         }
     }
 }
 
 impl Error for ImageReadError {
-    fn cause(&self) -> Option<&dyn Error> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             IoError(e) => Some(e),
-            DecodeError(e) => Some(e)
+            DecodeError(e) => Some(e),
+            ComputeError(e) => Some(e),
         }
     }
 }
@@ -120,29 +421,111 @@ impl From<ImageError> for ImageReadError {
     }
 }
 
+impl From<SignatureError> for ImageReadError {
+    fn from(e: SignatureError) -> Self {
+        ComputeError(e)
+    }
+}
+
 pub type Result<R> = std::result::Result<R, ImageReadError>;
 
-fn grayscale_image<I: GenericImageView>(img: I) -> Vec<Vec<u8>> {
-    let pixels = img.pixels()
-        .map(|(_, _, p)| p.to_rgba().0);
-
-    let mut result = Vec::with_capacity(img.width() as usize);
-    let mut row = Vec::with_capacity(img.height() as usize);
-    let mut col = 0;
-    for pixel in pixels {
-        row.push(pixel_gray(
-            pixel[0].to_u8().unwrap(),
-            pixel[1].to_u8().unwrap(),
-            pixel[2].to_u8().unwrap(),
-            pixel[3].to_u8().unwrap(),
-        ));
-        col += 1;
-        if col >= img.width() {
-            result.push(row);
-            row = Vec::with_capacity(img.height() as usize);
-            col = 0;
-        }
+fn grayscale_image<I: GenericImageView>(img: &I, config: &SignatureConfig) -> GrayImage {
+    let data = img.pixels()
+        .map(|(x, y, p)| {
+            let pixel = p.to_rgba().0;
+            pixel_gray(
+                to_u8(pixel[0]),
+                to_u8(pixel[1]),
+                to_u8(pixel[2]),
+                to_u8(pixel[3]),
+                (x as usize, y as usize),
+                config,
+            )
+        })
+        .collect();
+
+    GrayImage::from_raw(data, img.width() as usize, img.height() as usize)
+}
+
+/// Decodes `bytes` in `format`, along with the ICC profile embedded in them, for the formats that
+/// carry one.
+fn decode_with_profile(
+    bytes: &[u8],
+    format: ImageFormat,
+) -> ImageResult<(DynamicImage, Option<Vec<u8>>)> {
+    let reader = Cursor::new(bytes);
+    match format {
+        ImageFormat::Jpeg => with_profile(JpegDecoder::new(reader)?),
+        ImageFormat::Png => with_profile(PngDecoder::new(reader)?),
+        ImageFormat::WebP => with_profile(WebPDecoder::new(reader)?),
+        ImageFormat::Tiff => with_profile(TiffDecoder::new(reader)?),
+        format => Ok((image::load_from_memory_with_format(bytes, format)?, None)),
+    }
+}
+
+fn with_profile<'a, D: ImageDecoder<'a>>(
+    mut decoder: D,
+) -> ImageResult<(DynamicImage, Option<Vec<u8>>)> {
+    let profile = decoder.icc_profile();
+    Ok((DynamicImage::from_decoder(decoder)?, profile))
+}
+
+/// `image` converted from the colors of the ICC `profile` to sRGB, the colors every other image is
+/// taken to be in. Gray images, and profiles that are sRGB already, can't be read, or don't
+/// describe RGB colors, leave it as it is.
+fn to_srgb(image: DynamicImage, profile: &[u8]) -> DynamicImage {
+    let Some(input) = Profile::new_from_slice(profile, false) else {
+        return image;
+    };
+    if input.is_sRGB() || !image.color().has_color() {
+        return image;
+    }
+    let alpha = image.color().has_alpha();
+    let data_type = if alpha { DataType::RGBA8 } else { DataType::RGB8 };
+    let Some(transform) = Transform::new(&input, &Profile::new_sRGB(), data_type, Intent::default())
+    else {
+        return image;
+    };
+    if alpha {
+        let mut rgba = image.into_rgba8();
+        transform.apply(&mut rgba);
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        let mut rgb = image.into_rgb8();
+        transform.apply(&mut rgb);
+        DynamicImage::ImageRgb8(rgb)
     }
+}
+
+/// `image` turned upright by the EXIF Orientation tag of the container it was decoded from, if
+/// it has one. Unreadable or missing metadata leaves it as it is, as decoders do.
+fn orient<R: BufRead + Seek>(image: DynamicImage, mut container: R) -> DynamicImage {
+    let exif = exif::Reader::new().read_from_container(&mut container).ok();
+    apply_orientation(image, exif.as_ref().and_then(orientation))
+}
+
+/// The value of the Orientation tag, 1 to 8, if there is one.
+fn orientation(exif: &Exif) -> Option<u32> {
+    exif.get_field(Tag::Orientation, In::PRIMARY).and_then(|field| field.value.get_uint(0))
+}
+
+/// `image` turned upright from `orientation`, 1 or missing for an image that's upright already.
+fn apply_orientation(image: DynamicImage, orientation: Option<u32>) -> DynamicImage {
+    match orientation {
+        Some(2) => image.fliph(),
+        Some(3) => image.rotate180(),
+        Some(4) => image.flipv(),
+        Some(5) => image.rotate90().fliph(),
+        Some(6) => image.rotate90(),
+        Some(7) => image.rotate270().fliph(),
+        Some(8) => image.rotate270(),
+        _ => image,
+    }
+}
 
-    result
+/// A channel of any depth scaled to 8 bits, e.g. 65535 or 1.0 to 255.
+fn to_u8<S: Primitive>(value: S) -> u8 {
+    let max = S::DEFAULT_MAX_VALUE.to_f32().unwrap_or(1.0);
+    let value = value.to_f32().unwrap_or(0.0) / max * u8::MAX as f32;
+    value.round().clamp(0.0, u8::MAX as f32) as u8
 }
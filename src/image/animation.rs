@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::Duration;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageFormat};
+
+use crate::image::Result;
+use crate::{Signature, SignatureConfig};
+
+/// The signature of one frame of an animation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameSignature {
+    /// The frame's position in the animation, from 0.
+    pub index: usize,
+    /// How long the frame is shown for.
+    pub delay: Duration,
+    /// The signature of the whole canvas as the frame shows it.
+    pub signature: Signature,
+}
+
+/// Produces a 544 signed byte signature for every frame of an animated GIF file, in order. Frames
+/// are composited onto the canvas the way viewers show them, following each frame's disposal, so
+/// frames that only redraw part of the canvas sign like the whole picture they show. A still GIF
+/// has a single frame. See [animation_signature] for one signature of the whole animation.
+pub fn get_gif_signatures<P: AsRef<Path>>(path: P) -> Result<Vec<FrameSignature>> {
+    SignatureConfig::default().compute_gif_file(path)
+}
+
+/// Produces a 544 signed byte signature for every frame of an animated GIF, WebP, or PNG (APNG)
+/// file, in order, guessing its format from its first bytes. Frames are composited as with
+/// [get_gif_signatures]. Still images, in these formats or any other the image library decodes,
+/// are a single frame without a delay, so that an animation and its thumbnail go through the
+/// same call.
+pub fn get_animation_signatures<P: AsRef<Path>>(path: P) -> Result<Vec<FrameSignature>> {
+    SignatureConfig::default().compute_animation_file(path)
+}
+
+/// A signature representing a whole animation, to match it against a still thumbnail or against
+/// re-encodes that dropped or merged frames. Each element is the median of the frames' elements
+/// at its position, weighted by how long each frame is shown, so that it reflects what's on
+/// screen most of the time. Frames without a delay weigh as much as a millisecond. With the
+/// weights split evenly between two levels, it's their mean rounded toward zero, as with
+/// [centroid](crate::centroid).
+///
+/// The frames are assumed to be signed with the same config, as those of one animation are. The
+/// signature of no frames is `None`.
+pub fn animation_signature(frames: &[FrameSignature]) -> Option<Signature> {
+    let first = frames.first()?;
+    // How long each of -2 to 2 is shown at each position.
+    let mut weights = vec![[0_u64; 5]; first.signature.len()];
+    let mut total = 0;
+    for frame in frames {
+        let weight = u64::try_from(frame.delay.as_millis()).unwrap_or(u64::MAX).max(1);
+        total += weight;
+        for (levels, &element) in weights.iter_mut().zip(frame.signature.iter()) {
+            levels[(element.clamp(-2, 2) + 2) as usize] += weight;
+        }
+    }
+    let elements = weights.iter().map(|levels| weighted_median(levels, total)).collect();
+    Some(Signature::new(*first.signature.params(), elements))
+}
+
+impl SignatureConfig {
+    /// Produces a signature for every frame of an animated GIF read from a stream, such as a
+    /// `&[u8]` of one held in memory. See [get_gif_signatures] for how frames are composited.
+    pub fn compute_gif<R: Read>(&self, reader: R) -> Result<Vec<FrameSignature>> {
+        self.compute_frames(GifDecoder::new(reader)?)
+    }
+
+    /// Produces a signature for every frame of an animated GIF file, see [get_gif_signatures].
+    pub fn compute_gif_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<FrameSignature>> {
+        self.compute_gif(BufReader::new(File::open(path)?))
+    }
+
+    /// Produces a signature for every frame of an animated GIF, WebP, or PNG read from a stream,
+    /// such as a `&[u8]` of one held in memory. See [get_animation_signatures] for how formats
+    /// are told apart and still images signed. The stream is read into memory first, as with
+    /// [SignatureConfig::compute_reader].
+    pub fn compute_animation<R: Read>(&self, mut reader: R) -> Result<Vec<FrameSignature>> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let frames = match image::guess_format(&bytes)? {
+            ImageFormat::Gif => self.compute_frames(GifDecoder::new(bytes.as_slice())?)?,
+            ImageFormat::WebP => self.compute_frames(WebPDecoder::new(bytes.as_slice())?)?,
+            ImageFormat::Png => self.compute_frames(PngDecoder::new(bytes.as_slice())?.apng())?,
+            _ => Vec::new(),
+        };
+        if !frames.is_empty() {
+            return Ok(frames);
+        }
+        // Animation decoders find no frames in still images.
+        let signature = self.compute_bytes_signature(&bytes)?;
+        Ok(vec![FrameSignature { index: 0, delay: Duration::ZERO, signature }])
+    }
+
+    /// Produces a signature for every frame of an animated GIF, WebP, or PNG file, see
+    /// [get_animation_signatures].
+    pub fn compute_animation_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<FrameSignature>> {
+        self.compute_animation(BufReader::new(File::open(path)?))
+    }
+
+    /// Signs the composited frames of `decoder`, in order.
+    fn compute_frames<'a, D: AnimationDecoder<'a>>(
+        &self,
+        decoder: D,
+    ) -> Result<Vec<FrameSignature>> {
+        let params = self.params();
+        decoder.into_frames()
+            .enumerate()
+            .map(|(index, frame)| {
+                let frame = frame?;
+                let delay = Duration::from(frame.delay());
+                let image = DynamicImage::ImageRgba8(frame.into_buffer());
+                let signature = Signature::new(params, self.try_compute_dynamic_image(&image)?);
+                Ok(FrameSignature { index, delay, signature })
+            })
+            .collect()
+    }
+}
+
+/// The median of the levels -2 to 2 shown for `weights[level + 2]` each, out of `total`.
+fn weighted_median(weights: &[u64; 5], total: u64) -> i8 {
+    let level = |past: fn(u64, u64) -> bool| {
+        let mut shown = 0;
+        let position = weights.iter().position(|&weight| {
+            shown += weight;
+            past(2 * shown, total)
+        });
+        position.unwrap_or(2) as i8 - 2
+    };
+    // The levels either side of the middle, which differ when it falls between them.
+    let lower = level(|shown, total| shown >= total);
+    let upper = level(|shown, total| shown > total);
+    (lower + upper) / 2
+}
@@ -0,0 +1,86 @@
+use std::io;
+use std::panic;
+use std::path::Path;
+
+use image::ImageFormat;
+use tokio::task::{self, JoinError};
+
+#[cfg(feature = "http")]
+use super::url::{UrlLimits, UrlReadError, MAX_REDIRECTS};
+#[cfg(feature = "http")]
+use crate::image::ImageReadError;
+use crate::image::Result;
+use crate::SignatureConfig;
+#[cfg(feature = "http")]
+use crate::Signature;
+
+/// An async version of [get_file_signature](crate::image::get_file_signature) for use on a tokio
+/// runtime. The file is read without blocking, and its decoding and signing, which are CPU bound,
+/// run on the runtime's blocking thread pool rather than stalling its workers.
+pub async fn get_file_signature_async<P: AsRef<Path>>(path: P) -> Result<Vec<i8>> {
+    SignatureConfig::default().compute_file_async(path).await
+}
+
+/// An async version of [get_url_signature](crate::image::get_url_signature) for use on a tokio
+/// runtime. The download is async, and the image is decoded and signed on the runtime's blocking
+/// thread pool.
+#[cfg(feature = "http")]
+pub async fn get_url_signature_async<U: reqwest::IntoUrl>(
+    url: U,
+) -> std::result::Result<Signature, UrlReadError> {
+    let policy = reqwest::redirect::Policy::limited(MAX_REDIRECTS);
+    let client = reqwest::Client::builder().redirect(policy).build()?;
+    SignatureConfig::default().compute_url_async(&client, url, UrlLimits::new()).await
+}
+
+impl SignatureConfig {
+    /// An async version of [SignatureConfig::compute_file], see [get_file_signature_async].
+    pub async fn compute_file_async<P: AsRef<Path>>(&self, path: P) -> Result<Vec<i8>> {
+        let path = path.as_ref();
+        let bytes = tokio::fs::read(path).await?;
+        let format = ImageFormat::from_path(path).or_else(|_| image::guess_format(&bytes))?;
+        let config = *self;
+        blocking(move || config.compute_encoded(&bytes, format)).await?
+    }
+
+    /// An async version of [SignatureConfig::compute_bytes], decoding and signing `bytes` on the
+    /// runtime's blocking thread pool.
+    pub async fn compute_bytes_async(&self, bytes: Vec<u8>) -> Result<Vec<i8>> {
+        let config = *self;
+        blocking(move || config.compute_bytes(&bytes)).await?
+    }
+
+    /// An async version of [SignatureConfig::compute_url], see [get_url_signature_async].
+    #[cfg(feature = "http")]
+    pub async fn compute_url_async<U: reqwest::IntoUrl>(
+        &self,
+        client: &reqwest::Client,
+        url: U,
+        limits: UrlLimits,
+    ) -> std::result::Result<Signature, UrlReadError> {
+        let request = client.get(url).timeout(limits.timeout);
+        let mut response = request.send().await?.error_for_status()?;
+        limits.check_length(response.content_length())?;
+        // As with the blocking download, the announced length isn't trusted.
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+            limits.check_length(Some(bytes.len() as u64))?;
+        }
+        let config = *self;
+        blocking(move || config.sign_download(&bytes, limits)).await.map_err(ImageReadError::from)?
+    }
+}
+
+/// Runs `f` on the blocking thread pool, resuming its panic if it panicked. The task can only be
+/// cancelled by the runtime shutting down, which is reported as an IO error.
+async fn blocking<F, T>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    task::spawn_blocking(f).await.map_err(|e: JoinError| match e.try_into_panic() {
+        Ok(payload) => panic::resume_unwind(payload),
+        Err(e) => io::Error::from(e),
+    })
+}
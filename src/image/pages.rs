@@ -0,0 +1,144 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+use image::error::{DecodingError, UnsupportedError, UnsupportedErrorKind};
+use image::{DynamicImage, ImageBuffer, ImageError, ImageFormat, Pixel};
+#[cfg(feature = "pdf")]
+use image::error::ImageFormatHint;
+#[cfg(feature = "pdf")]
+use pdfium_render::prelude::{Pdfium, PdfiumError, PdfRenderConfig};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::{ColorType, TiffError};
+
+use crate::image::ImageReadError::{DecodeError, IoError};
+use crate::image::{ImageReadError, Result};
+#[cfg(feature = "pdf")]
+use crate::PixelFormat;
+use crate::{Signature, SignatureConfig};
+
+/// The resolution PDF pages are rendered at by [get_pdf_page_signatures]: one pixel per point,
+/// PDF's own unit, so that an A4 page is 595 by 842 pixels.
+#[cfg(feature = "pdf")]
+pub const DEFAULT_PDF_DPI: f32 = 72.0;
+
+/// Produces a 544 signed byte signature for every page of a TIFF file, in order, such as the
+/// pages of a scanned document. Pages may differ in size and color type; gray, RGB, and RGBA pages
+/// of 8 or 16 bits, with or without alpha, and RGB and RGBA pages of `f32` channels are supported.
+pub fn get_tiff_page_signatures<P: AsRef<Path>>(path: P) -> Result<Vec<Signature>> {
+    SignatureConfig::default().compute_tiff_file_pages(path)
+}
+
+/// Produces a 544 signed byte signature for every page of a PDF file, in order, rendered at
+/// [DEFAULT_PDF_DPI] by the [Pdfium](https://pdfium.googlesource.com/pdfium/) library installed
+/// on the system, which is bound on every call. See [SignatureConfig::compute_pdf_pages] to bind
+/// it once, from a library of one's own, or to render at another resolution.
+#[cfg(feature = "pdf")]
+pub fn get_pdf_page_signatures<P: AsRef<Path>>(path: P) -> Result<Vec<Signature>> {
+    let pdfium = Pdfium::new(Pdfium::bind_to_system_library().map_err(pdf_error)?);
+    SignatureConfig::default().compute_pdf_pages(&pdfium, &std::fs::read(path)?, DEFAULT_PDF_DPI)
+}
+
+impl SignatureConfig {
+    /// Produces a signature for every page of a TIFF read from a stream, such as a
+    /// [Cursor](std::io::Cursor) over one held in memory, see [get_tiff_page_signatures].
+    pub fn compute_tiff_pages<R: Read + Seek>(&self, reader: R) -> Result<Vec<Signature>> {
+        let params = self.params();
+        let mut decoder = Decoder::new(reader).map_err(tiff_error)?;
+        let mut signatures = Vec::new();
+        loop {
+            let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+            let color = decoder.colortype().map_err(tiff_error)?;
+            let pixels = decoder.read_image().map_err(tiff_error)?;
+            let page = tiff_page(color, pixels, width, height)?;
+            signatures.push(Signature::new(params, self.try_compute_dynamic_image(&page)?));
+            if !decoder.more_images() {
+                return Ok(signatures);
+            }
+            decoder.next_image().map_err(tiff_error)?;
+        }
+    }
+
+    /// Produces a signature for every page of a TIFF file, see [get_tiff_page_signatures].
+    pub fn compute_tiff_file_pages<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Signature>> {
+        self.compute_tiff_pages(BufReader::new(File::open(path)?))
+    }
+
+    /// Produces a signature for every page of a PDF held in memory, in order, rendered by
+    /// `pdfium` at `dpi` pixels per inch. Signatures are largely independent of resolution, so a
+    /// low one that keeps the grid squares many pixels wide renders quickly without losing much.
+    /// Documents that can't be opened or pages that can't be rendered return a
+    /// [DecodeError](ImageReadError::DecodeError).
+    #[cfg(feature = "pdf")]
+    pub fn compute_pdf_pages(
+        &self,
+        pdfium: &Pdfium,
+        bytes: &[u8],
+        dpi: f32,
+    ) -> Result<Vec<Signature>> {
+        let params = self.params();
+        let rgba = self.pixel_format(PixelFormat::Rgba);
+        let render = PdfRenderConfig::new().scale_page_by_factor(dpi / DEFAULT_PDF_DPI);
+        let document = pdfium.load_pdf_from_byte_slice(bytes, None).map_err(pdf_error)?;
+        let pages = document.pages().iter().map(|page| {
+            let bitmap = page.render_with_config(&render).map_err(pdf_error)?;
+            let width = bitmap.width() as usize;
+            Ok(Signature::new(params, rgba.try_compute(&bitmap.as_rgba_bytes(), width)?))
+        });
+        pages.collect()
+    }
+}
+
+/// A decoded TIFF page as an image of the matching color type, if it's one that's supported.
+fn tiff_page(
+    color: ColorType,
+    pixels: DecodingResult,
+    width: u32,
+    height: u32,
+) -> Result<DynamicImage> {
+    use DecodingResult::{F32, U16, U8};
+    use DynamicImage::{ImageLuma16, ImageLuma8, ImageLumaA16, ImageLumaA8, ImageRgb16};
+    use DynamicImage::{ImageRgb32F, ImageRgb8, ImageRgba16, ImageRgba32F, ImageRgba8};
+    let size = (width, height);
+    let page = match (color, pixels) {
+        (ColorType::Gray(8), U8(p)) => from_raw(size, p, ImageLuma8),
+        (ColorType::GrayA(8), U8(p)) => from_raw(size, p, ImageLumaA8),
+        (ColorType::RGB(8), U8(p)) => from_raw(size, p, ImageRgb8),
+        (ColorType::RGBA(8), U8(p)) => from_raw(size, p, ImageRgba8),
+        (ColorType::Gray(16), U16(p)) => from_raw(size, p, ImageLuma16),
+        (ColorType::GrayA(16), U16(p)) => from_raw(size, p, ImageLumaA16),
+        (ColorType::RGB(16), U16(p)) => from_raw(size, p, ImageRgb16),
+        (ColorType::RGBA(16), U16(p)) => from_raw(size, p, ImageRgba16),
+        (ColorType::RGB(32), F32(p)) => from_raw(size, p, ImageRgb32F),
+        (ColorType::RGBA(32), F32(p)) => from_raw(size, p, ImageRgba32F),
+        _ => {
+            let feature = UnsupportedErrorKind::GenericFeature(format!("{:?} pages", color));
+            let error = UnsupportedError::from_format_and_kind(ImageFormat::Tiff.into(), feature);
+            return Err(DecodeError(ImageError::Unsupported(error)));
+        }
+    };
+    let short = || DecodingError::new(ImageFormat::Tiff.into(), "page is missing pixels");
+    page.ok_or_else(|| DecodeError(ImageError::Decoding(short())))
+}
+
+/// The image of `variant` holding `pixels`, if there are as many as its size calls for.
+fn from_raw<P: Pixel>(
+    (width, height): (u32, u32),
+    pixels: Vec<P::Subpixel>,
+    variant: fn(ImageBuffer<P, Vec<P::Subpixel>>) -> DynamicImage,
+) -> Option<DynamicImage> {
+    ImageBuffer::from_raw(width, height, pixels).map(variant)
+}
+
+fn tiff_error(e: TiffError) -> ImageReadError {
+    match e {
+        TiffError::IoError(e) => IoError(e),
+        e => DecodeError(ImageError::Decoding(DecodingError::new(ImageFormat::Tiff.into(), e))),
+    }
+}
+
+#[cfg(feature = "pdf")]
+fn pdf_error(e: PdfiumError) -> ImageReadError {
+    let format = ImageFormatHint::Name("PDF".to_string());
+    DecodeError(ImageError::Decoding(DecodingError::new(format, e.to_string())))
+}
@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek};
+use std::path::Path;
+
+use exif::{Exif, In, Tag};
+use image::ImageFormat;
+
+use crate::image::ImageReadError::IoError;
+use crate::image::{apply_orientation, orientation, Result};
+use crate::{Signature, SignatureConfig};
+
+/// The similarity two thumbnails need for [thumbnail_candidates] to pair their images, well below
+/// [DEFAULT_MATCH_THRESHOLD](crate::DEFAULT_MATCH_THRESHOLD), since thumbnails are small and
+/// heavily compressed, so that images matching at full size aren't left out.
+pub const DEFAULT_THUMBNAIL_THRESHOLD: f64 = 0.4;
+
+/// Produces a 544 signed byte signature for the thumbnail embedded in the EXIF metadata of an
+/// image file, such as the one most cameras store in their JPEGs, as a cheap first pass over large
+/// archives. Only the metadata is read, not the image itself, so it takes a fraction of the time
+/// of a full decode, most of all from slow disks.
+///
+/// Returns `None` for files without a thumbnail, or with one that can't be decoded or signed, so
+/// that they're decoded in full instead. Fails only if the file can't be read. Thumbnail
+/// signatures are designed to be compared to each other, with [thumbnail_candidates], rather than
+/// to signatures of full images.
+pub fn get_thumbnail_signature<P: AsRef<Path>>(path: P) -> Result<Option<Signature>> {
+    SignatureConfig::default().compute_thumbnail_file(path)
+}
+
+/// The pairs of images, by their index in `thumbnails`, that are worth comparing at full size:
+/// those whose thumbnails are at least `threshold` similar, see [DEFAULT_THUMBNAIL_THRESHOLD], and
+/// those where either has no thumbnail signature to rule them out. Pairs are in order, the first
+/// index less than the second.
+pub fn thumbnail_candidates(
+    thumbnails: &[Option<Signature>],
+    threshold: f64,
+) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (i, a) in thumbnails.iter().enumerate() {
+        for (j, b) in thumbnails.iter().enumerate().skip(i + 1) {
+            let similar = match (a, b) {
+                (Some(a), Some(b)) => a.try_similarity(b).map_or(true, |s| s >= threshold),
+                _ => true,
+            };
+            if similar {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+impl SignatureConfig {
+    /// Produces a signature for the EXIF thumbnail of an image read from a seekable stream, see
+    /// [get_thumbnail_signature]. The thumbnail is turned upright like the image, as configured by
+    /// [SignatureConfig::exif_orientation].
+    pub fn compute_thumbnail<R: BufRead + Seek>(&self, mut reader: R) -> Result<Option<Signature>> {
+        let exif = match exif::Reader::new().read_from_container(&mut reader) {
+            Ok(exif) => exif,
+            Err(exif::Error::Io(e)) => return Err(IoError(e)),
+            Err(_) => return Ok(None),
+        };
+        let Some(thumbnail) = thumbnail(&exif) else {
+            return Ok(None);
+        };
+        let Ok(mut image) = image::load_from_memory_with_format(thumbnail, ImageFormat::Jpeg) else {
+            return Ok(None);
+        };
+        if self.orients_images() {
+            image = apply_orientation(image, orientation(&exif));
+        }
+        let signature = self.try_compute_dynamic_image(&image).ok();
+        Ok(signature.map(|signature| Signature::new(self.params(), signature)))
+    }
+
+    /// Produces a signature for the EXIF thumbnail of an image file, see
+    /// [get_thumbnail_signature].
+    pub fn compute_thumbnail_file<P: AsRef<Path>>(&self, path: P) -> Result<Option<Signature>> {
+        self.compute_thumbnail(BufReader::new(File::open(path)?))
+    }
+}
+
+/// The JPEG thumbnail in `exif`'s second IFD, if there is one, whose offset is from the start of
+/// the TIFF data.
+fn thumbnail(exif: &Exif) -> Option<&[u8]> {
+    let field = |tag| exif.get_field(tag, In::THUMBNAIL).and_then(|f| f.value.get_uint(0));
+    let offset = field(Tag::JPEGInterchangeFormat)? as usize;
+    let length = field(Tag::JPEGInterchangeFormatLength)? as usize;
+    exif.buf().get(offset..offset.checked_add(length)?)
+}
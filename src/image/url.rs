@@ -0,0 +1,180 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{Cursor, Read};
+use std::time::Duration;
+
+use image::io::Reader as ImageReader;
+use reqwest::blocking::Client;
+use reqwest::redirect::Policy;
+use reqwest::IntoUrl;
+
+use crate::image::ImageReadError;
+use crate::{Signature, SignatureConfig};
+
+/// The largest response [UrlLimits] accepts unless set otherwise, 32 MiB.
+pub const DEFAULT_URL_MAX_BYTES: u64 = 32 * 1024 * 1024;
+
+/// How long [UrlLimits] allows a download to take unless set otherwise.
+pub const DEFAULT_URL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The most pixels [UrlLimits] allows a downloaded image to decode to unless set otherwise, which
+/// covers the largest camera photos.
+pub const DEFAULT_URL_MAX_PIXELS: u64 = 200_000_000;
+
+/// The number of redirects [get_url_signature] follows.
+pub(super) const MAX_REDIRECTS: usize = 5;
+
+/// Safety limits on downloading and decoding remote images, which may be hostile: slow to
+/// respond, endless, or small files that decode to enormous images.
+///
+/// ```
+/// use std::time::Duration;
+/// use image_match::image::UrlLimits;
+///
+/// let limits = UrlLimits::new().max_bytes(8 * 1024 * 1024).timeout(Duration::from_secs(5));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UrlLimits {
+    pub(super) max_bytes: u64,
+    pub(super) timeout: Duration,
+    pub(super) max_pixels: u64,
+}
+
+impl UrlLimits {
+    /// Limits of [DEFAULT_URL_MAX_BYTES], [DEFAULT_URL_TIMEOUT], and [DEFAULT_URL_MAX_PIXELS].
+    pub fn new() -> Self {
+        UrlLimits {
+            max_bytes: DEFAULT_URL_MAX_BYTES,
+            timeout: DEFAULT_URL_TIMEOUT,
+            max_pixels: DEFAULT_URL_MAX_PIXELS,
+        }
+    }
+
+    /// The largest response body read, whether or not the server announces its length.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// How long the whole download may take, from connecting to the end of the body.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The most pixels the image may have, checked from its header before it's decoded.
+    pub fn max_pixels(mut self, max_pixels: u64) -> Self {
+        self.max_pixels = max_pixels;
+        self
+    }
+
+    /// Rejects a response whose length is known to be over the limit.
+    pub(super) fn check_length(&self, length: Option<u64>) -> Result<(), UrlReadError> {
+        match length {
+            Some(length) if length > self.max_bytes => {
+                Err(UrlReadError::TooLarge { max_bytes: self.max_bytes })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for UrlLimits {
+    fn default() -> Self {
+        UrlLimits::new()
+    }
+}
+
+/// The error of signing a remote image: downloading it, a limit it exceeds, or reading the image.
+#[derive(Debug)]
+pub enum UrlReadError {
+    /// The request failed, timed out, or was answered with an error status.
+    Http(reqwest::Error),
+    /// The response was longer than [UrlLimits::max_bytes].
+    TooLarge { max_bytes: u64 },
+    /// The image has more pixels than [UrlLimits::max_pixels].
+    TooManyPixels { width: u32, height: u32 },
+    /// The response couldn't be decoded or signed.
+    Image(ImageReadError),
+}
+
+impl Display for UrlReadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UrlReadError::Http(e) => Display::fmt(e, f),
+            UrlReadError::TooLarge { max_bytes } => {
+                write!(f, "Response is longer than the limit of {} bytes", max_bytes)
+            }
+            UrlReadError::TooManyPixels { width, height } => {
+                write!(f, "Image of {}x{} pixels is larger than the limit", width, height)
+            }
+            UrlReadError::Image(e) => Display::fmt(e, f),
+        }
+    }
+}
+
+impl Error for UrlReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UrlReadError::Http(e) => Some(e),
+            UrlReadError::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for UrlReadError {
+    fn from(e: reqwest::Error) -> Self {
+        UrlReadError::Http(e)
+    }
+}
+
+impl<E: Into<ImageReadError>> From<E> for UrlReadError {
+    fn from(e: E) -> Self {
+        UrlReadError::Image(e.into())
+    }
+}
+
+/// Downloads, decodes, and signs a remote image within the default [UrlLimits], following up to
+/// 5 redirects, and returns a [Signature] tagged with the default parameters. The image's format
+/// is guessed from its first bytes rather than trusted from the response's headers. See
+/// [SignatureConfig::compute_url] to reuse a client or set other limits.
+pub fn get_url_signature<U: IntoUrl>(url: U) -> Result<Signature, UrlReadError> {
+    let client = Client::builder().redirect(Policy::limited(MAX_REDIRECTS)).build()?;
+    SignatureConfig::default().compute_url(&client, url, UrlLimits::new())
+}
+
+impl SignatureConfig {
+    /// Downloads an image with `client`, within `limits`, then decodes and signs it like
+    /// [SignatureConfig::compute_bytes_signature]. Redirects are followed as the client is
+    /// configured to.
+    pub fn compute_url<U: IntoUrl>(
+        &self,
+        client: &Client,
+        url: U,
+        limits: UrlLimits,
+    ) -> Result<Signature, UrlReadError> {
+        let response = client.get(url).timeout(limits.timeout).send()?.error_for_status()?;
+        limits.check_length(response.content_length())?;
+        // The announced length may be missing or false, so the body is cut off past the limit.
+        let mut bytes = Vec::new();
+        let mut body = response.take(limits.max_bytes.saturating_add(1));
+        body.read_to_end(&mut bytes).map_err(ImageReadError::from)?;
+        self.sign_download(&bytes, limits)
+    }
+
+    /// Checks a downloaded image against `limits` before decoding and signing it.
+    pub(super) fn sign_download(
+        &self,
+        bytes: &[u8],
+        limits: UrlLimits,
+    ) -> Result<Signature, UrlReadError> {
+        limits.check_length(Some(bytes.len() as u64))?;
+        let header = ImageReader::new(Cursor::new(&bytes)).with_guessed_format()?;
+        let (width, height) = header.into_dimensions()?;
+        if width as u64 * height as u64 > limits.max_pixels {
+            return Err(UrlReadError::TooManyPixels { width, height });
+        }
+        Ok(self.compute_bytes_signature(bytes)?)
+    }
+}
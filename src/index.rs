@@ -0,0 +1,752 @@
+//! In-memory structures that find the signatures near a query without comparing it against every
+//! stored signature.
+//!
+//! ```
+//! use image_match::index::BkTree;
+//!
+//! let mut tree = BkTree::new();
+//! let first = tree.insert([1i8, 0, -1, 2]);
+//! let second = tree.insert([1i8, 1, -1, 2]);
+//! tree.insert([-2i8, 2, 2, -2]);
+//!
+//! assert_eq!(tree.find_within([1i8, 0, -1, 2], 1), vec![(first, 0), (second, 1)]);
+//! ```
+
+use std::fs;
+use std::io::{self, Write};
+use std::mem::size_of;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::SignatureError;
+
+pub use bktree::BkTree;
+pub use hnsw::{
+    HnswIndex, DEFAULT_HNSW_CONNECTIONS, DEFAULT_HNSW_EF_CONSTRUCTION, DEFAULT_HNSW_EF_SEARCH,
+};
+pub use linear::LinearIndex;
+pub use lsh::LshIndex;
+#[cfg(feature = "mmap")]
+pub use mapped::MappedLshIndex;
+pub use ranked::{RankedIndex, DEFAULT_RANKED_CANDIDATES};
+pub use sharded::ShardedIndex;
+pub use shared::{SharedIndex, DEFAULT_SHARED_BATCH_SIZE};
+
+mod bktree;
+mod hnsw;
+mod linear;
+mod lsh;
+#[cfg(feature = "mmap")]
+mod mapped;
+mod ranked;
+mod sharded;
+mod shared;
+mod snapshot;
+
+/// A way of searching signatures by cosine similarity, so that collections like
+/// [MemorySignatureStore](crate::MemorySignatureStore) can pick the search that suits their size:
+/// an exact [LinearIndex], a [LshIndex] on words, or an approximate [HnswIndex].
+///
+/// Signatures are keyed by the order they were inserted in, starting at 0, and must all be of the
+/// same length. Matches are pairs of key and cosine similarity, most similar first, with ties
+/// broken in favor of the smaller key.
+///
+/// Removing a signature leaves a tombstone: searches skip it right away, but it keeps its key and
+/// its memory until the index is [compacted](Index::compact), which renumbers the signatures left
+/// in order. Long-running services compact once tombstones make up a good part of the index.
+///
+/// An index [saved](Index::save) to a snapshot file [loads](Index::load) with its search structure
+/// as it was, tombstones included, rather than being rebuilt from its signatures.
+///
+/// Indexes hold bare elements, not the [parameters](crate::SignatureParams) they were computed
+/// with, so they can't compute queries from images themselves. A
+/// [MemorySignatureStore](crate::MemorySignatureStore) over an index can, with
+/// [query_buffer](crate::MemorySignatureStore::query_buffer) and `query_image`.
+pub trait Index {
+    /// The number of signatures in the index, not counting removed ones.
+    fn len(&self) -> usize;
+
+    /// Whether the index holds no signatures, not counting removed ones.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The signature inserted with `key`, unless there's none or it's been removed.
+    fn get(&self, key: usize) -> Option<&[i8]>;
+
+    /// The number of signatures removed since the index was last compacted.
+    fn removed(&self) -> usize;
+
+    /// Removes the signature with `key` from searches, returning whether there was one. It keeps
+    /// its key until the index is compacted.
+    fn remove(&mut self, key: usize) -> bool;
+
+    /// Drops removed signatures, renumbering the others in order, and returns the new key of each
+    /// previous key, `None` for removed ones.
+    fn compact(&mut self) -> Vec<Option<usize>>;
+
+    /// Adds a signature to the index, returning its key. Panics if it's not of the same length as
+    /// the signatures already in the index, see [Index::try_insert] for a non-panicking version.
+    fn insert(&mut self, signature: &[i8]) -> usize {
+        self.try_insert(signature).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [Index::insert] which returns an error rather than crashing
+    /// when the signature is of a different length than those already in the index.
+    fn try_insert(&mut self, signature: &[i8]) -> Result<usize, SignatureError>;
+
+    /// Adds all of `signatures` to the index at once, keyed in order, e.g. to build an index from
+    /// a collection, in less time than inserting them one by one. Panics if they're not all of the
+    /// same length as the signatures already in the index, see [Index::try_build_from] for a
+    /// non-panicking version.
+    ///
+    /// ```
+    /// use image_match::index::{HnswIndex, Index};
+    ///
+    /// let index = HnswIndex::new().build_from([[1i8, 0, -1, 2], [-2, 2, 2, -2]]);
+    /// assert_eq!(index.nearest(&[1, 0, -1, 2], 1), [(0, 1.0)]);
+    /// ```
+    fn build_from<I, S>(self, signatures: I) -> Self
+    where
+        Self: Sized,
+        I: IntoIterator<Item = S>,
+        S: AsRef<[i8]>,
+    {
+        self.try_build_from(signatures).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [Index::build_from] which returns an error rather than crashing
+    /// when the signatures aren't all of the same length as those already in the index, in which
+    /// case none of them are added.
+    ///
+    /// Indexes build in a way suited to each: an [LshIndex] sorts each posting list once, and an
+    /// [HnswIndex] searches for the neighbors of a batch of signatures at a time, in parallel with
+    /// the `rayon` feature. The default inserts signatures one by one.
+    fn try_build_from<I, S>(mut self, signatures: I) -> Result<Self, SignatureError>
+    where
+        Self: Sized,
+        I: IntoIterator<Item = S>,
+        S: AsRef<[i8]>,
+    {
+        let signatures: Vec<S> = signatures.into_iter().collect();
+        let signatures: Vec<&[i8]> = signatures.iter().map(AsRef::as_ref).collect();
+        // With the signatures all of one length, either the first fails to insert or none do.
+        check_lengths(&signatures)?;
+        for signature in signatures {
+            self.try_insert(signature)?;
+        }
+        Ok(self)
+    }
+
+    /// Finds the signatures with a cosine similarity of at least `threshold` to `query`. Panics if
+    /// the query is not of the same length as the signatures in the index, see
+    /// [Index::try_find_similar] for a non-panicking version.
+    fn find_similar(&self, query: &[i8], threshold: f64) -> Vec<(usize, f64)> {
+        self.try_find_similar(query, threshold).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [Index::find_similar] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    fn try_find_similar(
+        &self,
+        query: &[i8],
+        threshold: f64,
+    ) -> Result<Vec<(usize, f64)>, SignatureError>;
+
+    /// Streams the signatures with a cosine similarity of at least `min_similarity` to `query`, in
+    /// no particular order, without collecting them first, e.g. to page through the thousands of
+    /// copies of a common logo. Panics if the query is not of the same length as the signatures
+    /// in the index, see [Index::try_find_all_within] for a non-panicking version.
+    fn find_all_within(
+        &self,
+        query: &[i8],
+        min_similarity: f64,
+    ) -> Box<dyn Iterator<Item = (usize, f64)> + '_> {
+        self.try_find_all_within(query, min_similarity).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [Index::find_all_within] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    fn try_find_all_within(
+        &self,
+        query: &[i8],
+        min_similarity: f64,
+    ) -> Result<Box<dyn Iterator<Item = (usize, f64)> + '_>, SignatureError>;
+
+    /// Finds the `k` signatures most similar to `query`. Panics if the query is not of the same
+    /// length as the signatures in the index, see [Index::try_nearest] for a non-panicking
+    /// version.
+    fn nearest(&self, query: &[i8], k: usize) -> Vec<(usize, f64)> {
+        self.try_nearest(query, k).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [Index::nearest] which returns an error rather than crashing
+    /// when the query is of a different length than the signatures in the index.
+    fn try_nearest(&self, query: &[i8], k: usize) -> Result<Vec<(usize, f64)>, SignatureError>;
+
+    /// Finds the signatures with a cosine similarity of at least `threshold` to `query` like
+    /// [Index::find_similar], but stops once `budget` runs out, returning the matches found so
+    /// far. Panics if the query is not of the same length as the signatures in the index, see
+    /// [Index::try_find_similar_bounded] for a non-panicking version.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use image_match::index::{Index, LinearIndex, SearchBudget};
+    ///
+    /// let index = LinearIndex::new().build_from([[1i8, 0, -1, 2], [1, 1, -1, 2], [1, 0, -1, 1]]);
+    /// let budget = SearchBudget::new().time(Duration::from_millis(5)).candidates(2);
+    /// let found = index.find_similar_bounded(&[1, 0, -1, 2], 0.5, &budget);
+    /// assert_eq!(found.matches.len(), 2);
+    /// assert!(found.truncated);
+    /// ```
+    fn find_similar_bounded(
+        &self,
+        query: &[i8],
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> BoundedMatches {
+        self.try_find_similar_bounded(query, threshold, budget)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [Index::find_similar_bounded] which returns an error rather
+    /// than crashing when the query is of a different length than the signatures in the index.
+    fn try_find_similar_bounded(
+        &self,
+        query: &[i8],
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError>;
+
+    /// Finds the `k` signatures most similar to `query` like [Index::nearest], but stops once
+    /// `budget` runs out, returning the nearest of those compared so far. Panics if the query is
+    /// not of the same length as the signatures in the index, see [Index::try_nearest_bounded]
+    /// for a non-panicking version.
+    fn nearest_bounded(&self, query: &[i8], k: usize, budget: &SearchBudget) -> BoundedMatches {
+        self.try_nearest_bounded(query, k, budget).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [Index::nearest_bounded] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    fn try_nearest_bounded(
+        &self,
+        query: &[i8],
+        k: usize,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError>;
+
+    /// The index's size, the memory it holds, and how much searching it's done, e.g. to plan the
+    /// capacity of a deployment.
+    fn stats(&self) -> IndexStats;
+
+    /// Writes the snapshot [Index::save] saves to `writer`, e.g. to nest it in the snapshot of an
+    /// index made of others.
+    fn write_snapshot(&self, writer: &mut dyn Write) -> io::Result<()>;
+
+    /// Reads a snapshot written by [Index::write_snapshot], with the errors of [Index::load].
+    fn read_snapshot(bytes: &[u8]) -> io::Result<Self>
+    where
+        Self: Sized;
+
+    /// Saves the index, its signatures and the structure that speeds up its searches, to a
+    /// snapshot file at `path`, replacing it once the snapshot is complete.
+    fn save(&self, path: &Path) -> io::Result<()> {
+        snapshot::save(path, |writer| self.write_snapshot(writer))
+    }
+
+    /// Loads an index saved with [Index::save]. Returns an error of kind
+    /// [ErrorKind::InvalidData](io::ErrorKind::InvalidData) if the file isn't a snapshot of this
+    /// kind of index, was saved by an incompatible version of this crate, or is corrupt.
+    fn load(path: &Path) -> io::Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::read_snapshot(&fs::read(path)?)
+    }
+}
+
+impl Index for HnswIndex {
+    fn len(&self) -> usize {
+        HnswIndex::len(self)
+    }
+
+    fn get(&self, key: usize) -> Option<&[i8]> {
+        HnswIndex::get(self, key)
+    }
+
+    fn removed(&self) -> usize {
+        HnswIndex::removed(self)
+    }
+
+    fn remove(&mut self, key: usize) -> bool {
+        HnswIndex::remove(self, key)
+    }
+
+    fn compact(&mut self) -> Vec<Option<usize>> {
+        HnswIndex::compact(self)
+    }
+
+    fn try_insert(&mut self, signature: &[i8]) -> Result<usize, SignatureError> {
+        HnswIndex::try_insert(self, signature)
+    }
+
+    fn try_build_from<I, S>(self, signatures: I) -> Result<Self, SignatureError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[i8]>,
+    {
+        HnswIndex::try_build_from(self, signatures)
+    }
+
+    fn try_find_similar(
+        &self,
+        query: &[i8],
+        threshold: f64,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        HnswIndex::try_find_similar(self, query, threshold)
+    }
+
+    fn try_find_all_within(
+        &self,
+        query: &[i8],
+        min_similarity: f64,
+    ) -> Result<Box<dyn Iterator<Item = (usize, f64)> + '_>, SignatureError> {
+        HnswIndex::try_find_all_within(self, query, min_similarity)
+    }
+
+    fn try_nearest(&self, query: &[i8], k: usize) -> Result<Vec<(usize, f64)>, SignatureError> {
+        HnswIndex::try_nearest(self, query, k)
+    }
+
+    fn try_find_similar_bounded(
+        &self,
+        query: &[i8],
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        HnswIndex::try_find_similar_bounded(self, query, threshold, budget)
+    }
+
+    fn try_nearest_bounded(
+        &self,
+        query: &[i8],
+        k: usize,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        HnswIndex::try_nearest_bounded(self, query, k, budget)
+    }
+
+    fn stats(&self) -> IndexStats {
+        HnswIndex::stats(self)
+    }
+
+    fn write_snapshot(&self, writer: &mut dyn Write) -> io::Result<()> {
+        HnswIndex::write_snapshot(self, writer)
+    }
+
+    fn read_snapshot(bytes: &[u8]) -> io::Result<Self> {
+        HnswIndex::read_snapshot(bytes)
+    }
+}
+
+impl Index for LshIndex {
+    fn len(&self) -> usize {
+        LshIndex::len(self)
+    }
+
+    fn get(&self, key: usize) -> Option<&[i8]> {
+        LshIndex::get(self, key)
+    }
+
+    fn removed(&self) -> usize {
+        LshIndex::removed(self)
+    }
+
+    fn remove(&mut self, key: usize) -> bool {
+        LshIndex::remove(self, key)
+    }
+
+    fn compact(&mut self) -> Vec<Option<usize>> {
+        LshIndex::compact(self)
+    }
+
+    fn try_insert(&mut self, signature: &[i8]) -> Result<usize, SignatureError> {
+        LshIndex::try_insert(self, signature)
+    }
+
+    fn try_build_from<I, S>(self, signatures: I) -> Result<Self, SignatureError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[i8]>,
+    {
+        LshIndex::try_build_from(self, signatures)
+    }
+
+    fn try_find_similar(
+        &self,
+        query: &[i8],
+        threshold: f64,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        LshIndex::try_find_similar(self, query, threshold)
+    }
+
+    fn try_find_all_within(
+        &self,
+        query: &[i8],
+        min_similarity: f64,
+    ) -> Result<Box<dyn Iterator<Item = (usize, f64)> + '_>, SignatureError> {
+        LshIndex::try_find_all_within(self, query, min_similarity)
+    }
+
+    fn try_nearest(&self, query: &[i8], k: usize) -> Result<Vec<(usize, f64)>, SignatureError> {
+        LshIndex::try_nearest(self, query, k)
+    }
+
+    fn try_find_similar_bounded(
+        &self,
+        query: &[i8],
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        LshIndex::try_find_similar_bounded(self, query, threshold, budget)
+    }
+
+    fn try_nearest_bounded(
+        &self,
+        query: &[i8],
+        k: usize,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        LshIndex::try_nearest_bounded(self, query, k, budget)
+    }
+
+    fn stats(&self) -> IndexStats {
+        LshIndex::stats(self)
+    }
+
+    fn write_snapshot(&self, writer: &mut dyn Write) -> io::Result<()> {
+        LshIndex::write_snapshot(self, writer)
+    }
+
+    fn read_snapshot(bytes: &[u8]) -> io::Result<Self> {
+        LshIndex::read_snapshot(bytes)
+    }
+}
+
+/// Limits on the work of one search, so that queries close to a great many signatures, like an
+/// extremely common logo, return in time with the best matches found so far rather than holding
+/// up a service. Unlimited by default.
+///
+/// A budget of candidates limits how many signatures a search compares the query against, and a
+/// budget of time how long it spends comparing them, checked every few comparisons, so a search
+/// may run a little over it. Searches through a [ShardedIndex] share the budget between shards.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchBudget {
+    time: Option<Duration>,
+    deadline: Option<Instant>,
+    candidates: Option<usize>,
+}
+
+impl SearchBudget {
+    /// Creates an unlimited budget.
+    pub fn new() -> Self {
+        SearchBudget::default()
+    }
+
+    /// Limits the time a search takes, from when it starts.
+    pub fn time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Limits a search to end by `deadline`, e.g. that of the request it's for.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Limits the number of signatures a search compares the query against.
+    pub fn candidates(mut self, candidates: usize) -> Self {
+        self.candidates = Some(candidates);
+        self
+    }
+
+    /// Starts spending the budget on a search.
+    fn start(&self) -> Spending {
+        let deadline = match (self.time, self.deadline) {
+            (Some(time), deadline) => {
+                let end = Instant::now().checked_add(time);
+                end.into_iter().chain(deadline).min()
+            }
+            (None, deadline) => deadline,
+        };
+        Spending { deadline, candidates: self.candidates, spent: 0, exhausted: false }
+    }
+
+    /// The budget of each of `shards` shards searched side by side, each with its share of the
+    /// candidates, and all ending together.
+    fn share(&self, shards: usize) -> SearchBudget {
+        SearchBudget {
+            time: None,
+            deadline: self.start().deadline,
+            candidates: self.candidates.map(|candidates| candidates.div_ceil(shards.max(1))),
+        }
+    }
+}
+
+/// The matches of a search with a [SearchBudget], most similar first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BoundedMatches {
+    /// The matches found, as pairs of key and cosine similarity.
+    pub matches: Vec<(usize, f64)>,
+    /// Whether the budget ran out before the search was done, so better matches may have been
+    /// left out.
+    pub truncated: bool,
+}
+
+/// What's left of a [SearchBudget] during a search.
+#[derive(Debug)]
+struct Spending {
+    deadline: Option<Instant>,
+    candidates: Option<usize>,
+    spent: usize,
+    exhausted: bool,
+}
+
+impl Spending {
+    /// How many comparisons are made between checks of the time.
+    const CLOCK_INTERVAL: usize = 32;
+
+    /// A budget that never runs out, for searches without one.
+    fn unlimited() -> Self {
+        SearchBudget::new().start()
+    }
+
+    /// Spends the budget on comparing one more signature, returning whether there was any left.
+    fn spend(&mut self) -> bool {
+        if self.exhausted {
+            return false;
+        }
+        let out_of_candidates = self.candidates.is_some_and(|candidates| self.spent >= candidates);
+        let out_of_time = self.spent.is_multiple_of(Self::CLOCK_INTERVAL)
+            && self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if out_of_candidates || out_of_time {
+            self.exhausted = true;
+            return false;
+        }
+        self.spent += 1;
+        true
+    }
+}
+
+/// Collects the matches at least `threshold` similar among `scores`, a search's candidates
+/// scored as they're iterated, until `spending` runs out, most similar first.
+fn score_within(
+    scores: impl Iterator<Item = (usize, f64)>,
+    threshold: f64,
+    spending: &mut Spending,
+) -> BoundedMatches {
+    let mut matches: Vec<_> = scores
+        .take_while(|_| spending.spend())
+        .filter(|(_, similarity)| *similarity >= threshold)
+        .collect();
+    sort_matches(&mut matches);
+    BoundedMatches { matches, truncated: spending.exhausted }
+}
+
+/// The size of an [Index], the memory it holds, and how much searching it's done, from
+/// [Index::stats].
+///
+/// Memory is counted from the capacity of the index's collections, so it leaves out the
+/// allocator's own overhead, and the hash maps' by an estimate. Counters start at 0 when an index
+/// is created or loaded, and go on counting after it's cloned.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndexStats {
+    /// The number of signatures in the index, not counting removed ones.
+    pub len: usize,
+    /// The number of signatures removed since the index was last compacted.
+    pub removed: usize,
+    /// The bytes of memory holding the signatures, removed ones included.
+    pub signature_bytes: usize,
+    /// The bytes of memory holding the structure that speeds up searches, such as posting lists
+    /// or the links of a graph.
+    pub structure_bytes: usize,
+    /// The bytes of memory holding the rest, such as which signatures were removed.
+    pub bookkeeping_bytes: usize,
+    /// The number of posting lists of an [LshIndex], one per word of each position.
+    pub posting_lists: usize,
+    /// The number of keys in the posting lists of an [LshIndex].
+    pub postings: usize,
+    /// The number of nodes on each layer of an [HnswIndex], added together.
+    pub layer_nodes: usize,
+    /// The number of links from one node to another on each layer of an [HnswIndex], added
+    /// together.
+    pub links: usize,
+    /// The number of searches the index has answered.
+    pub queries: u64,
+    /// The number of signatures whose similarity to a query has been computed, and for an
+    /// [HnswIndex], to a signature being linked into the graph.
+    pub comparisons: u64,
+}
+
+impl IndexStats {
+    /// The bytes of memory the index holds, all together.
+    pub fn memory_bytes(&self) -> usize {
+        self.signature_bytes + self.structure_bytes + self.bookkeeping_bytes
+    }
+
+    /// The average number of keys in a posting list, if the index has any.
+    pub fn average_posting_length(&self) -> Option<f64> {
+        (self.posting_lists > 0).then(|| self.postings as f64 / self.posting_lists as f64)
+    }
+
+    /// The average number of links of a node on a layer, if the index has a graph.
+    pub fn average_degree(&self) -> Option<f64> {
+        (self.layer_nodes > 0).then(|| self.links as f64 / self.layer_nodes as f64)
+    }
+
+    /// Adds the sizes and counts of `other`, another part of the same index, to these.
+    fn add(&mut self, other: &IndexStats) {
+        self.len += other.len;
+        self.removed += other.removed;
+        self.signature_bytes += other.signature_bytes;
+        self.structure_bytes += other.structure_bytes;
+        self.bookkeeping_bytes += other.bookkeeping_bytes;
+        self.posting_lists += other.posting_lists;
+        self.postings += other.postings;
+        self.layer_nodes += other.layer_nodes;
+        self.links += other.links;
+        self.queries += other.queries;
+        self.comparisons += other.comparisons;
+    }
+}
+
+/// How much searching an index has done, counted from behind shared references so that searches
+/// don't need exclusive access to the index.
+#[derive(Debug, Default)]
+struct Counters {
+    queries: AtomicU64,
+    comparisons: AtomicU64,
+}
+
+impl Counters {
+    /// Counts a search.
+    fn queried(&self) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts `count` similarities computed.
+    fn compared(&self, count: usize) {
+        self.comparisons.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Fills in the counts of `stats`.
+    fn fill(&self, stats: &mut IndexStats) {
+        stats.queries = self.queries.load(Ordering::Relaxed);
+        stats.comparisons = self.comparisons.load(Ordering::Relaxed);
+    }
+}
+
+impl Clone for Counters {
+    fn clone(&self) -> Self {
+        Counters {
+            queries: AtomicU64::new(self.queries.load(Ordering::Relaxed)),
+            comparisons: AtomicU64::new(self.comparisons.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// The bytes of memory held by `signatures`, each with its precomputed sum of squares.
+fn signature_bytes(signatures: &Vec<(Box<[i8]>, i64)>) -> usize {
+    signatures.capacity() * size_of::<(Box<[i8]>, i64)>()
+        + signatures.iter().map(|(signature, _)| signature.len()).sum::<usize>()
+}
+
+/// Which keys of an index have been removed since it was last compacted.
+#[derive(Clone, Debug, Default)]
+struct Tombstones {
+    removed: Vec<bool>,
+    count: usize,
+}
+
+impl Tombstones {
+    /// Tracks a newly inserted key.
+    fn push(&mut self) {
+        self.removed.push(false);
+    }
+
+    /// Whether `key` was removed, or never inserted.
+    fn is_removed(&self, key: usize) -> bool {
+        self.removed.get(key).copied().unwrap_or(true)
+    }
+
+    /// Removes `key`, returning whether it was there to remove.
+    fn remove(&mut self, key: usize) -> bool {
+        match self.removed.get_mut(key) {
+            Some(removed) if !*removed => {
+                *removed = true;
+                self.count += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The bytes of memory holding the tombstones.
+    fn bytes(&self) -> usize {
+        self.removed.capacity()
+    }
+
+    /// Drops the items of removed keys from `items`, which holds one per key, and returns the new
+    /// key of each key.
+    fn compact<T>(&mut self, items: &mut Vec<T>) -> Vec<Option<usize>> {
+        let mut live = 0;
+        let keys: Vec<Option<usize>> = self.removed.iter()
+            .map(|&removed| {
+                (!removed).then(|| {
+                    live += 1;
+                    live - 1
+                })
+            })
+            .collect();
+        let mut key = 0;
+        items.retain(|_| {
+            key += 1;
+            keys[key - 1].is_some()
+        });
+        *self = Tombstones { removed: vec![false; live], count: 0 };
+        keys
+    }
+}
+
+/// Removes and compacts away any signatures in `index`.
+pub(crate) fn emptied<I: Index>(mut index: I) -> I {
+    for key in 0..index.len() + index.removed() {
+        index.remove(key);
+    }
+    index.compact();
+    index
+}
+
+/// Checks that `signatures` are all of the same length, that of the first.
+fn check_lengths(signatures: &[&[i8]]) -> Result<(), SignatureError> {
+    let Some(first) = signatures.first() else {
+        return Ok(());
+    };
+    match signatures.iter().find(|signature| signature.len() != first.len()) {
+        Some(other) => {
+            Err(SignatureError::MismatchedLengths { left: first.len(), right: other.len() })
+        }
+        None => Ok(()),
+    }
+}
+
+/// Sorts matches most similar first, breaking ties in favor of the smaller key.
+fn sort_matches(matches: &mut [(usize, f64)]) {
+    matches.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+}
@@ -0,0 +1,125 @@
+use crate::{manhattan_distance, SignatureError};
+
+/// A BK-tree of signatures under the [Manhattan](crate::Manhattan) distance, which finds every
+/// stored signature within a distance of a query while skipping the subtrees the triangle
+/// inequality rules out.
+///
+/// Signatures are keyed by the order they were inserted in, starting at 0. All signatures in a
+/// tree must be of the same length, that of the first one inserted. Queries close to many stored
+/// signatures visit most of the tree; the tree is most effective at small distances, such as
+/// finding near-duplicates.
+#[derive(Clone, Debug, Default)]
+pub struct BkTree {
+    nodes: Vec<Node>,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    signature: Box<[i8]>,
+    /// The nodes below this one, each paired with its distance to this node, which is unique
+    /// among them.
+    children: Vec<(u64, usize)>,
+}
+
+impl BkTree {
+    /// Creates an empty tree.
+    pub fn new() -> Self {
+        BkTree { nodes: Vec::new() }
+    }
+
+    /// The number of signatures in the tree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether no signatures have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The signature inserted with `key`, if any.
+    pub fn get(&self, key: usize) -> Option<&[i8]> {
+        self.nodes.get(key).map(|node| &*node.signature)
+    }
+
+    /// Adds a signature to the tree, returning its key. Panics if it's not of the same length as
+    /// the signatures already in the tree, see [BkTree::try_insert] for a non-panicking version.
+    pub fn insert<S: AsRef<[i8]>>(&mut self, signature: S) -> usize {
+        self.try_insert(signature).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [BkTree::insert] which returns an error rather than crashing
+    /// when the signature is of a different length than those already in the tree.
+    pub fn try_insert<S: AsRef<[i8]>>(&mut self, signature: S) -> Result<usize, SignatureError> {
+        let signature = signature.as_ref();
+        self.check_length(signature)?;
+
+        let key = self.nodes.len();
+        if key > 0 {
+            let mut parent = 0;
+            loop {
+                let distance = manhattan_distance(&self.nodes[parent].signature, signature) as u64;
+                let children = &mut self.nodes[parent].children;
+                match children.iter().find(|(d, _)| *d == distance) {
+                    Some(&(_, child)) => parent = child,
+                    None => {
+                        children.push((distance, key));
+                        break;
+                    }
+                }
+            }
+        }
+        self.nodes.push(Node { signature: signature.into(), children: Vec::new() });
+        Ok(key)
+    }
+
+    /// Finds the signatures within a Manhattan distance of `max_distance` of `query`, as pairs of
+    /// key and distance, nearest first. Ties are broken in favor of the smaller key. Panics if
+    /// the query is not of the same length as the signatures in the tree, see
+    /// [BkTree::try_find_within] for a non-panicking version.
+    pub fn find_within<S: AsRef<[i8]>>(&self, query: S, max_distance: u64) -> Vec<(usize, u64)> {
+        self.try_find_within(query, max_distance).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [BkTree::find_within] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the tree.
+    pub fn try_find_within<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        max_distance: u64,
+    ) -> Result<Vec<(usize, u64)>, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+
+        let mut found = Vec::new();
+        let mut pending = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(key) = pending.pop() {
+            let node = &self.nodes[key];
+            let distance = manhattan_distance(&node.signature, query) as u64;
+            if distance <= max_distance {
+                found.push((key, distance));
+            }
+            // Every signature under the child at distance `d` is `d` from this node, so by the
+            // triangle inequality it's at least `|d - distance|` from the query.
+            let low = distance.saturating_sub(max_distance);
+            let high = distance.saturating_add(max_distance);
+            pending.extend(node.children.iter()
+                .filter(|(d, _)| (low..=high).contains(d))
+                .map(|(_, child)| *child));
+        }
+        found.sort_by_key(|&(key, distance)| (distance, key));
+        Ok(found)
+    }
+
+    fn check_length(&self, signature: &[i8]) -> Result<(), SignatureError> {
+        match self.nodes.first() {
+            Some(root) if root.signature.len() != signature.len() => {
+                Err(SignatureError::MismatchedLengths {
+                    left: root.signature.len(),
+                    right: signature.len(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,882 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::mem::size_of;
+use std::path::Path;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::index::snapshot::{self, corrupt, SnapshotKind};
+use crate::index::{
+    check_lengths, BoundedMatches, Counters, IndexStats, SearchBudget, Spending, Tombstones,
+};
+use crate::{cosine_with_squares, sum_of_squares, SignatureError};
+
+/// The number of neighbors [HnswIndex::new] links each signature to on the upper layers. The
+/// bottom layer links twice as many.
+pub const DEFAULT_HNSW_CONNECTIONS: usize = 16;
+
+/// The number of candidates [HnswIndex::new] considers when linking a new signature.
+pub const DEFAULT_HNSW_EF_CONSTRUCTION: usize = 200;
+
+/// The number of candidates [HnswIndex::new] considers when searching.
+pub const DEFAULT_HNSW_EF_SEARCH: usize = 64;
+
+/// [HnswIndex::build_from] inserts batches of up to one in this many of the nodes already in the
+/// graph, so that the graph a batch is searched through is nearly as complete as it would be
+/// inserting one by one.
+const BATCH_FRACTION: usize = 16;
+
+/// The most nodes [HnswIndex::build_from] inserts in a batch.
+const MAX_BATCH: usize = 256;
+
+/// An approximate nearest neighbor index of signatures under cosine similarity, a hierarchical
+/// navigable small world graph, which finds the signatures most similar to a query in roughly
+/// logarithmic time.
+///
+/// Signatures are keyed by the order they were inserted in, starting at 0, and must all be of the
+/// same length, that of the first one inserted. Searches are approximate: they may miss some of
+/// the most similar signatures, less often the more candidates they consider, see
+/// [HnswIndex::ef_search]. Similarities are those of
+/// [cosine_similarity(a, b)](crate::cosine_similarity), to the last bit.
+///
+/// Levels are drawn from a generator seeded the same way for every index, so inserting the same
+/// signatures in the same order always builds the same graph.
+///
+/// Removed signatures stay in the graph, routing searches through their links without being
+/// returned, until [HnswIndex::compact] drops them and relinks their neighbors.
+#[derive(Clone, Debug)]
+pub struct HnswIndex {
+    nodes: Vec<Node>,
+    tombstones: Tombstones,
+    entry: Option<usize>,
+    connections: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    rng: u64,
+    counters: Counters,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    signature: Box<[i8]>,
+    squares: i64,
+    /// The node's neighbors on each layer it's on, from the bottom up.
+    layers: Vec<Vec<usize>>,
+}
+
+/// A node paired with its distance from whatever is being searched for, ordered by distance. The
+/// distance is the negated cosine similarity, so that it's exact and nearer nodes come first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Candidate {
+    distance: f64,
+    key: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.total_cmp(&other.distance).then(self.key.cmp(&other.key))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Default for HnswIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HnswIndex {
+    /// Creates an empty index with [DEFAULT_HNSW_CONNECTIONS], [DEFAULT_HNSW_EF_CONSTRUCTION] and
+    /// [DEFAULT_HNSW_EF_SEARCH].
+    pub fn new() -> Self {
+        HnswIndex {
+            nodes: Vec::new(),
+            tombstones: Tombstones::default(),
+            entry: None,
+            connections: DEFAULT_HNSW_CONNECTIONS,
+            ef_construction: DEFAULT_HNSW_EF_CONSTRUCTION,
+            ef_search: DEFAULT_HNSW_EF_SEARCH,
+            rng: 0x9e37_79b9_7f4a_7c15,
+            counters: Counters::default(),
+        }
+    }
+
+    /// How many neighbors each signature is linked to, at least 2. More connections find the
+    /// most similar signatures more reliably, at the cost of memory and slower inserts. Only
+    /// affects signatures inserted afterwards.
+    pub fn connections(mut self, connections: usize) -> Self {
+        self.connections = connections.max(2);
+        self
+    }
+
+    /// How many candidates to consider when linking a new signature, at least 1. Larger values
+    /// build a better connected graph, at the cost of slower inserts.
+    pub fn ef_construction(mut self, ef_construction: usize) -> Self {
+        self.ef_construction = ef_construction.max(1);
+        self
+    }
+
+    /// How many candidates to consider when searching, at least 1, and never fewer than the
+    /// number of results asked for. Larger values miss fewer of the most similar signatures, at
+    /// the cost of slower searches.
+    pub fn ef_search(mut self, ef_search: usize) -> Self {
+        self.ef_search = ef_search.max(1);
+        self
+    }
+
+    /// The number of signatures in the index, not counting removed ones.
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.tombstones.count()
+    }
+
+    /// Whether the index holds no signatures, not counting removed ones.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The signature inserted with `key`, unless there's none or it's been removed.
+    pub fn get(&self, key: usize) -> Option<&[i8]> {
+        if self.tombstones.is_removed(key) {
+            return None;
+        }
+        self.nodes.get(key).map(|node| &*node.signature)
+    }
+
+    /// The number of signatures removed since the index was last compacted.
+    pub fn removed(&self) -> usize {
+        self.tombstones.count()
+    }
+
+    /// Removes the signature with `key` from searches, returning whether there was one. It keeps
+    /// its key, and its place in the graph, until the index is compacted.
+    pub fn remove(&mut self, key: usize) -> bool {
+        self.tombstones.remove(key)
+    }
+
+    /// Drops removed signatures from the graph, renumbering the others in order, and returns the
+    /// new key of each previous key, `None` for removed ones.
+    ///
+    /// Each link to a removed signature is replaced by the best of that signature's own links,
+    /// so that the graph stays navigable.
+    pub fn compact(&mut self) -> Vec<Option<usize>> {
+        let mut relinked = Vec::new();
+        for (key, node) in self.nodes.iter().enumerate() {
+            if self.tombstones.is_removed(key) {
+                continue;
+            }
+            for (layer, neighbors) in node.layers.iter().enumerate() {
+                if neighbors.iter().all(|&neighbor| !self.tombstones.is_removed(neighbor)) {
+                    continue;
+                }
+                let mut candidates: Vec<usize> = neighbors.iter()
+                    .flat_map(|&neighbor| {
+                        if self.tombstones.is_removed(neighbor) {
+                            self.nodes[neighbor].layers[layer].clone()
+                        } else {
+                            vec![neighbor]
+                        }
+                    })
+                    .filter(|&candidate| candidate != key && !self.tombstones.is_removed(candidate))
+                    .collect();
+                candidates.sort_unstable();
+                candidates.dedup();
+                let mut candidates: Vec<Candidate> = candidates.into_iter()
+                    .map(|candidate| self.candidate(&node.signature, node.squares, candidate))
+                    .collect();
+                candidates.sort();
+                let neighbors = self.select_neighbors(&candidates, self.max_connections(layer));
+                relinked.push((key, layer, neighbors));
+            }
+        }
+        for (key, layer, neighbors) in relinked {
+            self.nodes[key].layers[layer] = neighbors;
+        }
+
+        let keys = self.tombstones.compact(&mut self.nodes);
+        for neighbors in self.nodes.iter_mut().flat_map(|node| node.layers.iter_mut()) {
+            for neighbor in neighbors {
+                *neighbor = keys[*neighbor].expect("links to removed nodes were replaced");
+            }
+        }
+        // The first of the nodes on the most layers, as if the graph had been built without the
+        // removed nodes.
+        self.entry = self.nodes.iter()
+            .enumerate()
+            .max_by_key(|(key, node)| (node.layers.len(), Reverse(*key)))
+            .map(|(key, _)| key);
+        keys
+    }
+
+    /// Adds a signature to the index, returning its key. Panics if it's not of the same length as
+    /// the signatures already in the index, see [HnswIndex::try_insert] for a non-panicking
+    /// version.
+    pub fn insert<S: AsRef<[i8]>>(&mut self, signature: S) -> usize {
+        self.try_insert(signature).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [HnswIndex::insert] which returns an error rather than crashing
+    /// when the signature is of a different length than those already in the index.
+    pub fn try_insert<S: AsRef<[i8]>>(&mut self, signature: S) -> Result<usize, SignatureError> {
+        let signature = signature.as_ref();
+        self.check_length(signature)?;
+
+        let key = self.nodes.len();
+        let level = self.random_level();
+        let squares = sum_of_squares(signature);
+        self.nodes.push(Node {
+            signature: signature.into(),
+            squares,
+            layers: vec![Vec::new(); level + 1],
+        });
+        self.tombstones.push();
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(key);
+            return Ok(key);
+        };
+        let top = self.nodes[entry].layers.len() - 1;
+        let mut nearest = vec![self.candidate(signature, squares, entry)];
+        for layer in (level + 1..=top).rev() {
+            nearest = self.search_layer(signature, squares, &nearest, 1, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            nearest = self.search_layer(signature, squares, &nearest, self.ef_construction, layer);
+            self.link_neighbors(key, layer, &nearest);
+        }
+        if level > top {
+            self.entry = Some(key);
+        }
+        Ok(key)
+    }
+
+    /// Adds all of `signatures` to the index at once, keyed in order. Panics if they're not all of
+    /// the same length as the signatures already in the index, see [HnswIndex::try_build_from]
+    /// for a non-panicking version.
+    pub fn build_from<I, S>(self, signatures: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[i8]>,
+    {
+        self.try_build_from(signatures).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [HnswIndex::build_from] which returns an error rather than
+    /// crashing when the signatures aren't all of the same length as those already in the index.
+    ///
+    /// Signatures are inserted in batches whose neighbors are searched for through the graph as
+    /// it was before the batch, in parallel with the `rayon` feature, then among the batch, and
+    /// linked to them one after another. The graph is as good as one built one by one, and the
+    /// same whatever the number of threads.
+    pub fn try_build_from<I, S>(mut self, signatures: I) -> Result<Self, SignatureError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[i8]>,
+    {
+        let signatures: Vec<S> = signatures.into_iter().collect();
+        let signatures: Vec<&[i8]> = signatures.iter().map(AsRef::as_ref).collect();
+        check_lengths(&signatures)?;
+        if let Some(first) = signatures.first() {
+            self.check_length(first)?;
+        }
+
+        self.nodes.reserve_exact(signatures.len());
+        let mut remaining = &signatures[..];
+        while !remaining.is_empty() {
+            let batch = (self.nodes.len() / BATCH_FRACTION).clamp(1, MAX_BATCH);
+            let (inserted, rest) = remaining.split_at(batch.min(remaining.len()));
+            if inserted.len() == 1 {
+                self.try_insert(inserted[0])?;
+            } else {
+                self.insert_batch(inserted);
+            }
+            remaining = rest;
+        }
+        Ok(self)
+    }
+
+    /// Finds the `k` signatures most similar to `query`, as pairs of key and cosine similarity,
+    /// most similar first. Ties are broken in favor of the smaller key. Panics if the query is
+    /// not of the same length as the signatures in the index, see [HnswIndex::try_nearest] for a
+    /// non-panicking version.
+    pub fn nearest<S: AsRef<[i8]>>(&self, query: S, k: usize) -> Vec<(usize, f64)> {
+        self.try_nearest(query, k).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [HnswIndex::nearest] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    pub fn try_nearest<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        k: usize,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+        Ok(self.nearest_spending(query, k, &mut Spending::unlimited()))
+    }
+
+    /// Finds the `k` signatures most similar to `query` like [HnswIndex::nearest], but stops
+    /// walking the graph once `budget` runs out, returning the nearest of the nodes reached so
+    /// far. Panics if the query is not of the same length as the signatures in the index, see
+    /// [HnswIndex::try_nearest_bounded] for a non-panicking version.
+    pub fn nearest_bounded<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        k: usize,
+        budget: &SearchBudget,
+    ) -> BoundedMatches {
+        self.try_nearest_bounded(query, k, budget).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [HnswIndex::nearest_bounded] which returns an error rather
+    /// than crashing when the query is of a different length than the signatures in the index.
+    pub fn try_nearest_bounded<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        k: usize,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+        let mut spending = budget.start();
+        let matches = self.nearest_spending(query, k, &mut spending);
+        Ok(BoundedMatches { matches, truncated: spending.exhausted })
+    }
+
+    /// The `k` nodes nearest `query` that haven't been removed, among those reached before
+    /// `spending` runs out.
+    fn nearest_spending(
+        &self,
+        query: &[i8],
+        k: usize,
+        spending: &mut Spending,
+    ) -> Vec<(usize, f64)> {
+        if k == 0 {
+            return Vec::new();
+        }
+        // Removed nodes may take places among the nearest, so consider as many more candidates.
+        let ef = self.ef_search.max(k).saturating_add(self.tombstones.count());
+        let nearest = self.search(query, sum_of_squares(query), ef, spending);
+        nearest.into_iter()
+            .filter(|c| !self.tombstones.is_removed(c.key))
+            .take(k)
+            .map(|c| (c.key, -c.distance))
+            .collect()
+    }
+
+    /// Finds the signatures with a cosine similarity of at least `threshold` to `query`, as pairs
+    /// of key and similarity, most similar first. Ties are broken in favor of the smaller key.
+    /// Panics if the query is not of the same length as the signatures in the index, see
+    /// [HnswIndex::try_find_similar] for a non-panicking version.
+    ///
+    /// The search spreads out from the nearest signatures through their links to every linked
+    /// signature over the threshold, so it may miss matches only reachable through signatures
+    /// under it.
+    pub fn find_similar<S: AsRef<[i8]>>(&self, query: S, threshold: f64) -> Vec<(usize, f64)> {
+        self.try_find_similar(query, threshold).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [HnswIndex::find_similar] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    pub fn try_find_similar<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        threshold: f64,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+
+        let within = self.within(query, threshold, Spending::unlimited());
+        let mut found: Vec<Candidate> = within.collect();
+        found.sort();
+        Ok(found.into_iter().map(|c| (c.key, -c.distance)).collect())
+    }
+
+    /// Finds the signatures with a cosine similarity of at least `threshold` to `query` like
+    /// [HnswIndex::find_similar], but stops spreading out through the graph once `budget` runs
+    /// out, returning the matches found so far, which are those nearest the query's nearest.
+    /// Panics if the query is not of the same length as the signatures in the index, see
+    /// [HnswIndex::try_find_similar_bounded] for a non-panicking version.
+    pub fn find_similar_bounded<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> BoundedMatches {
+        self.try_find_similar_bounded(query, threshold, budget).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [HnswIndex::find_similar_bounded] which returns an error
+    /// rather than crashing when the query is of a different length than the signatures in the
+    /// index.
+    pub fn try_find_similar_bounded<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+
+        let mut within = self.within(query, threshold, budget.start());
+        let mut found: Vec<Candidate> = within.by_ref().collect();
+        found.sort();
+        Ok(BoundedMatches {
+            matches: found.into_iter().map(|c| (c.key, -c.distance)).collect(),
+            truncated: within.spending.exhausted,
+        })
+    }
+
+    /// Streams the signatures with a cosine similarity of at least `min_similarity` to `query`,
+    /// as pairs of key and similarity, in no particular order, without collecting them first.
+    /// Panics if the query is not of the same length as the signatures in the index, see
+    /// [HnswIndex::try_find_all_within] for a non-panicking version.
+    ///
+    /// Finds the same signatures as [HnswIndex::find_similar].
+    pub fn find_all_within<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        min_similarity: f64,
+    ) -> Box<dyn Iterator<Item = (usize, f64)> + '_> {
+        self.try_find_all_within(query, min_similarity).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [HnswIndex::find_all_within] which returns an error rather
+    /// than crashing when the query is of a different length than the signatures in the index.
+    pub fn try_find_all_within<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        min_similarity: f64,
+    ) -> Result<Box<dyn Iterator<Item = (usize, f64)> + '_>, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+        let within = self.within(query, min_similarity, Spending::unlimited());
+        Ok(Box::new(within.map(|c| (c.key, -c.distance))))
+    }
+
+    /// The index's size, the memory it holds, and how much searching it's done, see
+    /// [Index::stats](crate::index::Index::stats).
+    pub fn stats(&self) -> IndexStats {
+        let layers = || self.nodes.iter().flat_map(|node| &node.layers);
+        let layer_lists: usize =
+            self.nodes.iter().map(|node| node.layers.capacity() * size_of::<Vec<usize>>()).sum();
+        let links: usize = layers().map(|neighbors| neighbors.capacity()).sum::<usize>()
+            * size_of::<usize>();
+        let mut stats = IndexStats {
+            len: self.len(),
+            removed: self.removed(),
+            signature_bytes: self.nodes.capacity() * (size_of::<Box<[i8]>>() + size_of::<i64>())
+                + self.nodes.iter().map(|node| node.signature.len()).sum::<usize>(),
+            structure_bytes: self.nodes.capacity() * size_of::<Vec<Vec<usize>>>()
+                + layer_lists
+                + links,
+            bookkeeping_bytes: self.tombstones.bytes(),
+            layer_nodes: layers().count(),
+            links: layers().map(Vec::len).sum(),
+            ..IndexStats::default()
+        };
+        self.counters.fill(&mut stats);
+        stats
+    }
+
+    /// Saves the index, its signatures and graph, to a snapshot file at `path`, see
+    /// [Index::save](crate::index::Index::save). The snapshot keeps the index's settings and the
+    /// state of its level generator, so a loaded index goes on to build the same graph.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        snapshot::save(path.as_ref(), |writer| self.write_snapshot(writer))
+    }
+
+    /// Loads an index saved with [HnswIndex::save], see [Index::load](crate::index::Index::load).
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_snapshot(&fs::read(path)?)
+    }
+
+    pub(super) fn write_snapshot(&self, writer: &mut dyn Write) -> io::Result<()> {
+        snapshot::write(writer, SnapshotKind::Hnsw, |writer| {
+            let signatures = self.nodes.iter().map(|node| &*node.signature);
+            writer.signatures(signatures, &self.tombstones)?;
+            writer.usize(self.connections)?;
+            writer.usize(self.ef_construction)?;
+            writer.usize(self.ef_search)?;
+            writer.u64(self.rng)?;
+            // The entry's key plus one, 0 for an empty graph.
+            writer.usize(self.entry.map_or(0, |entry| entry + 1))?;
+            for node in &self.nodes {
+                writer.usize(node.layers.len())?;
+                for neighbors in &node.layers {
+                    writer.usize(neighbors.len())?;
+                    for neighbor in neighbors {
+                        writer.usize(*neighbor)?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub(super) fn read_snapshot(bytes: &[u8]) -> io::Result<Self> {
+        snapshot::read(bytes, SnapshotKind::Hnsw, |reader| {
+            let (signatures, tombstones) = reader.signatures()?;
+            let (connections, ef_construction, ef_search) =
+                (reader.usize()?, reader.usize()?, reader.usize()?);
+            let rng = reader.u64()?;
+            let entry = reader.usize()?.checked_sub(1);
+            let mut nodes = Vec::with_capacity(signatures.len());
+            for signature in signatures {
+                let mut layers = vec![Vec::new(); reader.count(8)?];
+                for neighbors in &mut layers {
+                    *neighbors = (0..reader.count(8)?)
+                        .map(|_| reader.usize())
+                        .collect::<io::Result<_>>()?;
+                }
+                let squares = sum_of_squares(&signature);
+                nodes.push(Node { signature, squares, layers });
+            }
+
+            // Searches follow links without checking them, so they must all lead to nodes on the
+            // layers they're on.
+            let linked = nodes.iter().all(|node| {
+                !node.layers.is_empty()
+                    && node.layers.iter().enumerate().all(|(layer, neighbors)| {
+                        neighbors.iter().all(|&neighbor| {
+                            nodes.get(neighbor).is_some_and(|node| node.layers.len() > layer)
+                        })
+                    })
+            });
+            let entered = match entry {
+                Some(entry) => entry < nodes.len(),
+                None => nodes.is_empty(),
+            };
+            if !linked || !entered || connections < 2 || ef_construction == 0 || ef_search == 0
+                || rng == 0
+            {
+                return Err(corrupt());
+            }
+            Ok(HnswIndex {
+                nodes,
+                tombstones,
+                entry,
+                connections,
+                ef_construction,
+                ef_search,
+                rng,
+                counters: Counters::default(),
+            })
+        })
+    }
+
+    /// Spreads out from the nodes nearest the query through the bottom layer's links, yielding
+    /// every node reached that's at least `threshold` similar to it, until `spending` runs out.
+    fn within(&self, query: &[i8], threshold: f64, mut spending: Spending) -> Within<'_> {
+        let squares = sum_of_squares(query);
+        let nearest = self.search(query, squares, self.ef_search, &mut spending);
+        Within {
+            index: self,
+            visited: nearest.iter().map(|c| c.key).collect(),
+            ready: nearest.into_iter().filter(|c| -c.distance >= threshold).collect(),
+            pending: Vec::new(),
+            query: query.into(),
+            squares,
+            threshold,
+            compared: 0,
+            spending,
+        }
+    }
+
+    /// Finds up to `ef` of the nodes nearest the query on the bottom layer, nearest first, after
+    /// descending greedily through the layers above, until `spending` runs out.
+    fn search(
+        &self,
+        query: &[i8],
+        squares: i64,
+        ef: usize,
+        spending: &mut Spending,
+    ) -> Vec<Candidate> {
+        self.counters.queried();
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        let mut nearest = vec![self.candidate(query, squares, entry)];
+        self.counters.compared(1);
+        for layer in (1..self.nodes[entry].layers.len()).rev() {
+            nearest = self.search_layer_spending(query, squares, &nearest, 1, layer, spending);
+        }
+        self.search_layer_spending(query, squares, &nearest, ef, 0, spending)
+    }
+
+    /// Draws the top layer of a new node from an exponentially decaying distribution, so that
+    /// each layer holds about `1 / connections` of the nodes of the one below.
+    fn random_level(&mut self) -> usize {
+        // xorshift64*, which is plenty for spreading nodes across layers.
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        let uniform = (bits as f64 + 1.0) / (1u64 << 53) as f64;
+        (-uniform.ln() / (self.connections as f64).ln()) as usize
+    }
+
+    /// The most neighbors a node may have on `layer`.
+    fn max_connections(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.connections * 2
+        } else {
+            self.connections
+        }
+    }
+
+    fn candidate(&self, signature: &[i8], squares: i64, key: usize) -> Candidate {
+        let node = &self.nodes[key];
+        let similarity = cosine_with_squares(signature, &node.signature, squares, node.squares);
+        Candidate { distance: -similarity, key }
+    }
+
+    /// Finds up to `ef` nodes of `layer` nearest the query by walking the graph greedily from
+    /// `entries`, returned nearest first.
+    fn search_layer(
+        &self,
+        query: &[i8],
+        squares: i64,
+        entries: &[Candidate],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        self.search_layer_spending(query, squares, entries, ef, layer, &mut Spending::unlimited())
+    }
+
+    /// Like [HnswIndex::search_layer], but stops walking once `spending` runs out.
+    fn search_layer_spending(
+        &self,
+        query: &[i8],
+        squares: i64,
+        entries: &[Candidate],
+        ef: usize,
+        layer: usize,
+        spending: &mut Spending,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entries.iter().map(|c| c.key).collect();
+        let mut pending: BinaryHeap<_> = entries.iter().copied().map(Reverse).collect();
+        let mut found: BinaryHeap<Candidate> = entries.iter().copied().collect();
+        while found.len() > ef {
+            found.pop();
+        }
+
+        'walk: while let Some(Reverse(closest)) = pending.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|furthest| closest > *furthest) {
+                break;
+            }
+            for &neighbor in &self.nodes[closest.key].layers[layer] {
+                if visited.contains(&neighbor) {
+                    continue;
+                }
+                if !spending.spend() {
+                    break 'walk;
+                }
+                visited.insert(neighbor);
+                let candidate = self.candidate(query, squares, neighbor);
+                if found.len() < ef || found.peek().is_some_and(|furthest| candidate < *furthest) {
+                    pending.push(Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        self.counters.compared(visited.len() - entries.len());
+        found.into_sorted_vec()
+    }
+
+    /// Inserts a batch of signatures into a graph with an entry. Each signature's nearest nodes
+    /// on each of its layers are found independently of the others: through the graph, then
+    /// among the signatures before it in the batch.
+    fn insert_batch(&mut self, signatures: &[&[i8]]) {
+        let entry = self.entry.expect("batches are inserted into a graph with nodes");
+        let top = self.nodes[entry].layers.len() - 1;
+        let first = self.nodes.len();
+        let levels: Vec<usize> = signatures.iter().map(|_| self.random_level()).collect();
+        let squares: Vec<i64> = signatures.iter().map(|s| sum_of_squares(s)).collect();
+
+        let search = |position: usize| -> Vec<Vec<Candidate>> {
+            let (signature, level) = (signatures[position], levels[position]);
+            let own_squares = squares[position];
+            let mut nearest = vec![self.candidate(signature, own_squares, entry)];
+            for layer in (level + 1..=top).rev() {
+                nearest = self.search_layer(signature, own_squares, &nearest, 1, layer);
+            }
+            let mut layers = vec![Vec::new(); level + 1];
+            for layer in (0..=level.min(top)).rev() {
+                let ef = self.ef_construction;
+                nearest = self.search_layer(signature, own_squares, &nearest, ef, layer);
+                layers[layer].clone_from(&nearest);
+            }
+            for earlier in 0..position {
+                let (earlier_signature, earlier_squares) = (signatures[earlier], squares[earlier]);
+                let similarity =
+                    cosine_with_squares(signature, earlier_signature, own_squares, earlier_squares);
+                let candidate = Candidate { distance: -similarity, key: first + earlier };
+                for layer in &mut layers[..=levels[earlier].min(level)] {
+                    layer.push(candidate);
+                }
+            }
+            self.counters.compared(position);
+            for layer in &mut layers {
+                layer.sort();
+            }
+            layers
+        };
+        #[cfg(feature = "rayon")]
+        let nearest: Vec<Vec<Vec<Candidate>>> =
+            (0..signatures.len()).into_par_iter().map(search).collect();
+        #[cfg(not(feature = "rayon"))]
+        let nearest: Vec<Vec<Vec<Candidate>>> = (0..signatures.len()).map(search).collect();
+
+        for ((signature, squares), (level, nearest)) in
+            signatures.iter().zip(squares).zip(levels.into_iter().zip(nearest))
+        {
+            let key = self.nodes.len();
+            self.nodes.push(Node {
+                signature: (*signature).into(),
+                squares,
+                layers: vec![Vec::new(); level + 1],
+            });
+            self.tombstones.push();
+            for (layer, nearest) in nearest.iter().enumerate() {
+                self.link_neighbors(key, layer, nearest);
+            }
+            let top = self.entry.map_or(0, |entry| self.nodes[entry].layers.len() - 1);
+            if level > top {
+                self.entry = Some(key);
+            }
+        }
+    }
+
+    /// Links the node with `key` on `layer` to the best of `nearest`, sorted nearest first, and
+    /// them back to it.
+    fn link_neighbors(&mut self, key: usize, layer: usize, nearest: &[Candidate]) {
+        let neighbors = self.select_neighbors(nearest, self.connections);
+        for &neighbor in &neighbors {
+            self.link(neighbor, key, layer);
+        }
+        self.nodes[key].layers[layer] = neighbors;
+    }
+
+    /// Picks up to `count` of `candidates`, sorted nearest first, to link a node to, preferring
+    /// those not closer to an already picked neighbor than to the node, so that links reach out
+    /// in every direction rather than into a single cluster. Skipped candidates fill any
+    /// remaining places.
+    fn select_neighbors(&self, candidates: &[Candidate], count: usize) -> Vec<usize> {
+        let mut picked: Vec<usize> = Vec::with_capacity(count);
+        let mut skipped = Vec::new();
+        for candidate in candidates {
+            if picked.len() == count {
+                break;
+            }
+            let Node { signature, squares, .. } = &self.nodes[candidate.key];
+            let diverse = picked.iter().all(|&neighbor| {
+                self.candidate(signature, *squares, neighbor).distance > candidate.distance
+            });
+            if diverse {
+                picked.push(candidate.key);
+            } else {
+                skipped.push(candidate.key);
+            }
+        }
+        let remaining = count - picked.len();
+        picked.extend(skipped.into_iter().take(remaining));
+        picked
+    }
+
+    /// Links `from` to `to` on `layer`, dropping the links of `from` that are no longer among the
+    /// best once it has too many.
+    fn link(&mut self, from: usize, to: usize, layer: usize) {
+        let max_connections = self.max_connections(layer);
+        self.nodes[from].layers[layer].push(to);
+        if self.nodes[from].layers[layer].len() <= max_connections {
+            return;
+        }
+
+        let node = &self.nodes[from];
+        let mut candidates: Vec<Candidate> = node.layers[layer].iter()
+            .map(|&key| self.candidate(&node.signature, node.squares, key))
+            .collect();
+        candidates.sort();
+        self.nodes[from].layers[layer] = self.select_neighbors(&candidates, max_connections);
+    }
+
+    fn check_length(&self, signature: &[i8]) -> Result<(), SignatureError> {
+        match self.nodes.first() {
+            Some(first) if first.signature.len() != signature.len() => {
+                Err(SignatureError::MismatchedLengths {
+                    left: first.signature.len(),
+                    right: signature.len(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The nodes found by [HnswIndex::within], yielded as they're found.
+struct Within<'a> {
+    index: &'a HnswIndex,
+    visited: HashSet<usize>,
+    /// Nodes over the threshold that are yet to be yielded.
+    ready: Vec<Candidate>,
+    /// Yielded nodes whose links are yet to be followed.
+    pending: Vec<usize>,
+    query: Box<[i8]>,
+    squares: i64,
+    threshold: f64,
+    /// The similarities computed since the search, counted once the iterator is dropped.
+    compared: usize,
+    spending: Spending,
+}
+
+impl Drop for Within<'_> {
+    fn drop(&mut self) {
+        self.index.counters.compared(self.compared);
+    }
+}
+
+impl Iterator for Within<'_> {
+    type Item = Candidate;
+
+    fn next(&mut self) -> Option<Candidate> {
+        loop {
+            if let Some(candidate) = self.ready.pop() {
+                self.pending.push(candidate.key);
+                if self.index.tombstones.is_removed(candidate.key) {
+                    continue;
+                }
+                return Some(candidate);
+            }
+            let key = self.pending.pop()?;
+            for &neighbor in &self.index.nodes[key].layers[0] {
+                if self.visited.contains(&neighbor) {
+                    continue;
+                }
+                if !self.spending.spend() {
+                    // Yield what's ready, without following any more links.
+                    self.pending.clear();
+                    break;
+                }
+                self.visited.insert(neighbor);
+                self.compared += 1;
+                let candidate = self.index.candidate(&self.query, self.squares, neighbor);
+                if -candidate.distance >= self.threshold {
+                    self.ready.push(candidate);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::io::{self, Write};
+
+use crate::index::snapshot::{self, SnapshotKind};
+use crate::index::{
+    score_within, signature_bytes, sort_matches, BoundedMatches, Counters, Index, IndexStats,
+    SearchBudget, Tombstones,
+};
+use crate::{cosine_with_squares, sum_of_squares, SignatureError};
+
+/// Signatures in a list that every query is compared against, the exact search approximate
+/// indexes are measured against, and the quickest for collections of up to a few thousand.
+///
+/// Signatures are keyed by the order they were inserted in, starting at 0, and must all be of the
+/// same length, that of the first one inserted.
+#[derive(Clone, Debug, Default)]
+pub struct LinearIndex {
+    signatures: Vec<(Box<[i8]>, i64)>,
+    tombstones: Tombstones,
+    counters: Counters,
+}
+
+impl LinearIndex {
+    /// Creates an empty index.
+    pub fn new() -> Self {
+        LinearIndex::default()
+    }
+
+    /// Scores the signatures that haven't been removed against `query`, in order of key.
+    fn scores<'a>(
+        &'a self,
+        query: &[i8],
+    ) -> Result<impl Iterator<Item = (usize, f64)> + 'a, SignatureError> {
+        self.check_length(query)?;
+        self.counters.queried();
+        self.counters.compared(Index::len(self));
+        Ok(self.scores_unchecked(query))
+    }
+
+    /// Scores the signatures that haven't been removed against `query`, in order of key, as
+    /// they're iterated, without counting the search.
+    fn scores_unchecked<'a>(&'a self, query: &[i8]) -> impl Iterator<Item = (usize, f64)> + 'a {
+        let squares = sum_of_squares(query);
+        let query: Box<[i8]> = query.into();
+        self.signatures.iter()
+            .enumerate()
+            .filter(|(key, _)| !self.tombstones.is_removed(*key))
+            .map(move |(key, (signature, signature_squares))| {
+                (key, cosine_with_squares(&query, signature, squares, *signature_squares))
+            })
+    }
+
+    /// Scores signatures in order of key until `budget` runs out, keeping those at least
+    /// `threshold` similar, most similar first.
+    fn bounded_scores(
+        &self,
+        query: &[i8],
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        self.check_length(query)?;
+        self.counters.queried();
+        let mut spending = budget.start();
+        let found = score_within(self.scores_unchecked(query), threshold, &mut spending);
+        self.counters.compared(spending.spent);
+        Ok(found)
+    }
+
+    fn check_length(&self, signature: &[i8]) -> Result<(), SignatureError> {
+        match self.signatures.first() {
+            Some((first, _)) if first.len() != signature.len() => {
+                Err(SignatureError::MismatchedLengths { left: first.len(), right: signature.len() })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Index for LinearIndex {
+    fn len(&self) -> usize {
+        self.signatures.len() - self.tombstones.count()
+    }
+
+    fn get(&self, key: usize) -> Option<&[i8]> {
+        if self.tombstones.is_removed(key) {
+            return None;
+        }
+        self.signatures.get(key).map(|(signature, _)| &**signature)
+    }
+
+    fn removed(&self) -> usize {
+        self.tombstones.count()
+    }
+
+    fn remove(&mut self, key: usize) -> bool {
+        self.tombstones.remove(key)
+    }
+
+    fn compact(&mut self) -> Vec<Option<usize>> {
+        self.tombstones.compact(&mut self.signatures)
+    }
+
+    fn try_insert(&mut self, signature: &[i8]) -> Result<usize, SignatureError> {
+        self.check_length(signature)?;
+        self.signatures.push((signature.into(), sum_of_squares(signature)));
+        self.tombstones.push();
+        Ok(self.signatures.len() - 1)
+    }
+
+    fn try_find_similar(
+        &self,
+        query: &[i8],
+        threshold: f64,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        let mut matches: Vec<_> = self.scores(query)?
+            .filter(|(_, similarity)| *similarity >= threshold)
+            .collect();
+        sort_matches(&mut matches);
+        Ok(matches)
+    }
+
+    fn try_find_all_within(
+        &self,
+        query: &[i8],
+        min_similarity: f64,
+    ) -> Result<Box<dyn Iterator<Item = (usize, f64)> + '_>, SignatureError> {
+        let matches = self.scores(query)?;
+        Ok(Box::new(matches.filter(move |(_, similarity)| *similarity >= min_similarity)))
+    }
+
+    fn try_nearest(&self, query: &[i8], k: usize) -> Result<Vec<(usize, f64)>, SignatureError> {
+        let mut matches: Vec<_> = self.scores(query)?.collect();
+        sort_matches(&mut matches);
+        matches.truncate(k);
+        Ok(matches)
+    }
+
+    fn try_find_similar_bounded(
+        &self,
+        query: &[i8],
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        self.bounded_scores(query, threshold, budget)
+    }
+
+    fn try_nearest_bounded(
+        &self,
+        query: &[i8],
+        k: usize,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        let mut found = self.bounded_scores(query, f64::NEG_INFINITY, budget)?;
+        found.matches.truncate(k);
+        Ok(found)
+    }
+
+    fn stats(&self) -> IndexStats {
+        let mut stats = IndexStats {
+            len: Index::len(self),
+            removed: self.removed(),
+            signature_bytes: signature_bytes(&self.signatures),
+            bookkeeping_bytes: self.tombstones.bytes(),
+            ..IndexStats::default()
+        };
+        self.counters.fill(&mut stats);
+        stats
+    }
+
+    fn write_snapshot(&self, writer: &mut dyn Write) -> io::Result<()> {
+        snapshot::write(writer, SnapshotKind::Linear, |writer| {
+            let signatures = self.signatures.iter().map(|(signature, _)| &**signature);
+            writer.signatures(signatures, &self.tombstones)
+        })
+    }
+
+    fn read_snapshot(bytes: &[u8]) -> io::Result<Self> {
+        snapshot::read(bytes, SnapshotKind::Linear, |reader| {
+            let (signatures, tombstones) = reader.signatures()?;
+            let signatures = signatures.into_iter()
+                .map(|signature| {
+                    let squares = sum_of_squares(&signature);
+                    (signature, squares)
+                })
+                .collect();
+            Ok(LinearIndex { signatures, tombstones, counters: Counters::default() })
+        })
+    }
+}
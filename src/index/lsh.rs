@@ -0,0 +1,511 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::mem::size_of;
+use std::path::Path;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::index::snapshot::{self, corrupt, SnapshotKind};
+use crate::index::{
+    check_lengths, score_within, signature_bytes, sort_matches, BoundedMatches, Counters,
+    IndexStats, SearchBudget, Tombstones,
+};
+use crate::{
+    cosine_with_squares, sum_of_squares, try_signature_words, SignatureError, DEFAULT_WORD_COUNT,
+    DEFAULT_WORD_LENGTH,
+};
+
+/// An index of signatures by their [words](crate::signature_words), the source paper's way of
+/// scaling searches: a query is only compared against the signatures sharing at least one word
+/// with it, gathered from a posting list per word, and those are rescored with cosine similarity.
+///
+/// Signatures are keyed by the order they were inserted in, starting at 0, and must all be of the
+/// same length, that of the first one inserted. Words are of [DEFAULT_WORD_LENGTH] elements and
+/// [DEFAULT_WORD_COUNT] are taken from each signature unless set with [LshIndex::words], both
+/// limited to the signature's length like the words of a [SignatureStore](crate::SignatureStore).
+/// Signatures sharing no word with a query are never found, however similar, which only happens
+/// to the most heavily edited images.
+///
+/// Removed signatures stay in the posting lists, skipped by searches, until
+/// [LshIndex::compact] drops them.
+#[derive(Clone, Debug)]
+pub struct LshIndex {
+    pub(super) signatures: Vec<(Box<[i8]>, i64)>,
+    pub(super) word_length: usize,
+    pub(super) word_count: usize,
+    /// The keys of the signatures with each word, a map per word position.
+    pub(super) postings: Vec<HashMap<i64, Vec<usize>>>,
+    tombstones: Tombstones,
+    counters: Counters,
+}
+
+impl Default for LshIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LshIndex {
+    /// Creates an empty index, taking [DEFAULT_WORD_COUNT] words of [DEFAULT_WORD_LENGTH]
+    /// elements from each signature.
+    pub fn new() -> Self {
+        LshIndex {
+            signatures: Vec::new(),
+            word_length: DEFAULT_WORD_LENGTH,
+            word_count: DEFAULT_WORD_COUNT,
+            postings: Vec::new(),
+            tombstones: Tombstones::default(),
+            counters: Counters::default(),
+        }
+    }
+
+    /// Takes `word_count` words of `word_length` elements from each signature, each at least 1.
+    /// Shorter words and more of them find more heavily edited images, at the cost of more
+    /// candidates to rescore. Has no effect once signatures have been inserted.
+    pub fn words(mut self, word_length: usize, word_count: usize) -> Self {
+        if self.signatures.is_empty() {
+            self.word_length = word_length.max(1);
+            self.word_count = word_count.max(1);
+        }
+        self
+    }
+
+    /// The number of signatures in the index, not counting removed ones.
+    pub fn len(&self) -> usize {
+        self.signatures.len() - self.tombstones.count()
+    }
+
+    /// Whether the index holds no signatures, not counting removed ones.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The signature inserted with `key`, unless there's none or it's been removed.
+    pub fn get(&self, key: usize) -> Option<&[i8]> {
+        if self.tombstones.is_removed(key) {
+            return None;
+        }
+        self.signatures.get(key).map(|(signature, _)| &**signature)
+    }
+
+    /// The number of signatures removed since the index was last compacted.
+    pub fn removed(&self) -> usize {
+        self.tombstones.count()
+    }
+
+    /// Removes the signature with `key` from searches, returning whether there was one. It keeps
+    /// its key, and its place in the posting lists, until the index is compacted.
+    pub fn remove(&mut self, key: usize) -> bool {
+        self.tombstones.remove(key)
+    }
+
+    /// Drops removed signatures from the index and its posting lists, renumbering the others in
+    /// order, and returns the new key of each previous key, `None` for removed ones.
+    pub fn compact(&mut self) -> Vec<Option<usize>> {
+        let keys = self.tombstones.compact(&mut self.signatures);
+        for postings in &mut self.postings {
+            postings.retain(|_, list| {
+                *list = list.iter().filter_map(|key| keys[*key]).collect();
+                !list.is_empty()
+            });
+        }
+        keys
+    }
+
+    /// Adds a signature to the index, returning its key. Panics if it's not of the same length as
+    /// the signatures already in the index, see [LshIndex::try_insert] for a non-panicking
+    /// version.
+    pub fn insert<S: AsRef<[i8]>>(&mut self, signature: S) -> usize {
+        self.try_insert(signature).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [LshIndex::insert] which returns an error rather than crashing
+    /// when the signature is of a different length than those already in the index.
+    pub fn try_insert<S: AsRef<[i8]>>(&mut self, signature: S) -> Result<usize, SignatureError> {
+        let signature = signature.as_ref();
+        self.check_length(signature)?;
+
+        let key = self.signatures.len();
+        let words = words_of(signature, self.word_length, self.word_count);
+        if self.postings.len() < words.len() {
+            self.postings.resize_with(words.len(), HashMap::new);
+        }
+        for (postings, word) in self.postings.iter_mut().zip(words) {
+            postings.entry(word).or_default().push(key);
+        }
+        self.signatures.push((signature.into(), sum_of_squares(signature)));
+        self.tombstones.push();
+        Ok(key)
+    }
+
+    /// Adds all of `signatures` to the index at once, keyed in order. Panics if they're not all of
+    /// the same length as the signatures already in the index, see [LshIndex::try_build_from] for
+    /// a non-panicking version.
+    pub fn build_from<I, S>(self, signatures: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[i8]>,
+    {
+        self.try_build_from(signatures).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [LshIndex::build_from] which returns an error rather than
+    /// crashing when the signatures aren't all of the same length as those already in the index.
+    ///
+    /// The signatures' words are computed first, in parallel with the `rayon` feature, then each
+    /// position's words are sorted once to lay out every posting list at its final size.
+    pub fn try_build_from<I, S>(mut self, signatures: I) -> Result<Self, SignatureError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[i8]>,
+    {
+        let signatures: Vec<S> = signatures.into_iter().collect();
+        let signatures: Vec<&[i8]> = signatures.iter().map(AsRef::as_ref).collect();
+        check_lengths(&signatures)?;
+        let Some(first) = signatures.first() else {
+            return Ok(self);
+        };
+        self.check_length(first)?;
+
+        let (word_length, word_count) = (self.word_length, self.word_count);
+        let prepare = |signature: &&[i8]| {
+            (words_of(signature, word_length, word_count), sum_of_squares(signature))
+        };
+        #[cfg(feature = "rayon")]
+        let prepared: Vec<(Vec<i64>, i64)> = signatures.par_iter().map(prepare).collect();
+        #[cfg(not(feature = "rayon"))]
+        let prepared: Vec<(Vec<i64>, i64)> = signatures.iter().map(prepare).collect();
+
+        let start = self.signatures.len();
+        let positions = prepared.iter().map(|(words, _)| words.len()).max().unwrap_or(0);
+        // Each position's words with the keys they're from, sorted by word and then key, so that
+        // each word's keys are in a run to copy into its posting list.
+        let postings_at = |position: usize| -> HashMap<i64, Vec<usize>> {
+            let mut words: Vec<(i64, usize)> = prepared.iter()
+                .enumerate()
+                .filter_map(|(key, (words, _))| Some((*words.get(position)?, start + key)))
+                .collect();
+            words.sort_unstable();
+            let runs: Vec<&[(i64, usize)]> = words.chunk_by(|a, b| a.0 == b.0).collect();
+            let mut postings = HashMap::with_capacity(runs.len());
+            for run in runs {
+                postings.insert(run[0].0, run.iter().map(|(_, key)| *key).collect());
+            }
+            postings
+        };
+        #[cfg(feature = "rayon")]
+        let new_postings: Vec<_> = (0..positions).into_par_iter().map(postings_at).collect();
+        #[cfg(not(feature = "rayon"))]
+        let new_postings: Vec<_> = (0..positions).map(postings_at).collect();
+
+        if self.postings.len() < positions {
+            self.postings.resize_with(positions, HashMap::new);
+        }
+        for (postings, new_postings) in self.postings.iter_mut().zip(new_postings) {
+            if postings.is_empty() {
+                *postings = new_postings;
+                continue;
+            }
+            for (word, keys) in new_postings {
+                postings.entry(word).or_default().extend(keys);
+            }
+        }
+        self.signatures.reserve_exact(signatures.len());
+        for (signature, (_, squares)) in signatures.iter().zip(prepared) {
+            self.signatures.push(((*signature).into(), squares));
+            self.tombstones.push();
+        }
+        Ok(self)
+    }
+
+    /// Finds the signatures sharing a word with `query` with a cosine similarity of at least
+    /// `threshold` to it like [LshIndex::find_similar], but stops once `budget` runs out,
+    /// returning the matches found so far. Panics if the query is not of the same length as the
+    /// signatures in the index, see [LshIndex::try_find_similar_bounded] for a non-panicking
+    /// version.
+    ///
+    /// Candidates are compared in order of key, so a truncated search finds the matches among
+    /// the signatures inserted first.
+    pub fn find_similar_bounded<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> BoundedMatches {
+        self.try_find_similar_bounded(query, threshold, budget).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [LshIndex::find_similar_bounded] which returns an error rather
+    /// than crashing when the query is of a different length than the signatures in the index.
+    pub fn try_find_similar_bounded<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+        let mut spending = budget.start();
+        let candidates = self.candidates(query);
+        let found = score_within(self.scores(query, candidates), threshold, &mut spending);
+        self.counters.compared(spending.spent);
+        Ok(found)
+    }
+
+    /// Finds the `k` signatures sharing a word with `query` most similar to it like
+    /// [LshIndex::nearest], but stops once `budget` runs out, returning the nearest of those
+    /// compared so far. Panics if the query is not of the same length as the signatures in the
+    /// index, see [LshIndex::try_nearest_bounded] for a non-panicking version.
+    pub fn nearest_bounded<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        k: usize,
+        budget: &SearchBudget,
+    ) -> BoundedMatches {
+        self.try_nearest_bounded(query, k, budget).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [LshIndex::nearest_bounded] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    pub fn try_nearest_bounded<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        k: usize,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        let mut found = self.try_find_similar_bounded(query, f64::NEG_INFINITY, budget)?;
+        found.matches.truncate(k);
+        Ok(found)
+    }
+
+    /// Finds the signatures sharing a word with `query` with a cosine similarity of at least
+    /// `threshold` to it, as pairs of key and similarity, most similar first. Ties are broken in
+    /// favor of the smaller key. Panics if the query is not of the same length as the signatures
+    /// in the index, see [LshIndex::try_find_similar] for a non-panicking version.
+    pub fn find_similar<S: AsRef<[i8]>>(&self, query: S, threshold: f64) -> Vec<(usize, f64)> {
+        self.try_find_similar(query, threshold).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [LshIndex::find_similar] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    pub fn try_find_similar<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        threshold: f64,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+        Ok(self.rescore(query, threshold))
+    }
+
+    /// Finds the `k` signatures sharing a word with `query` most similar to it, as pairs of key
+    /// and cosine similarity, most similar first. Ties are broken in favor of the smaller key.
+    /// Panics if the query is not of the same length as the signatures in the index, see
+    /// [LshIndex::try_nearest] for a non-panicking version.
+    pub fn nearest<S: AsRef<[i8]>>(&self, query: S, k: usize) -> Vec<(usize, f64)> {
+        self.try_nearest(query, k).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [LshIndex::nearest] which returns an error rather than crashing
+    /// when the query is of a different length than the signatures in the index.
+    pub fn try_nearest<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        k: usize,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+        let mut matches = self.rescore(query, f64::NEG_INFINITY);
+        matches.truncate(k);
+        Ok(matches)
+    }
+
+    /// Streams the signatures sharing a word with `query` with a cosine similarity of at least
+    /// `min_similarity` to it, as pairs of key and similarity, in no particular order, without
+    /// collecting them first. Panics if the query is not of the same length as the signatures in
+    /// the index, see [LshIndex::try_find_all_within] for a non-panicking version.
+    pub fn find_all_within<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        min_similarity: f64,
+    ) -> Box<dyn Iterator<Item = (usize, f64)> + '_> {
+        self.try_find_all_within(query, min_similarity).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [LshIndex::find_all_within] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    pub fn try_find_all_within<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        min_similarity: f64,
+    ) -> Result<Box<dyn Iterator<Item = (usize, f64)> + '_>, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+        Ok(Box::new(self.within(query, min_similarity)))
+    }
+
+    /// Scores the signatures sharing a word with `query`, keeping those at least `threshold`
+    /// similar, most similar first.
+    fn rescore(&self, query: &[i8], threshold: f64) -> Vec<(usize, f64)> {
+        let mut matches: Vec<(usize, f64)> = self.within(query, threshold).collect();
+        sort_matches(&mut matches);
+        matches
+    }
+
+    /// Scores the signatures sharing a word with `query` as they're iterated, in order of key,
+    /// yielding those at least `threshold` similar.
+    fn within(&self, query: &[i8], threshold: f64) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let candidates = self.candidates(query);
+        self.counters.compared(candidates.len());
+        self.scores(query, candidates)
+            .filter(move |(_, similarity)| *similarity >= threshold)
+    }
+
+    /// The keys of the signatures sharing a word with `query`, in order, counting the search.
+    fn candidates(&self, query: &[i8]) -> Vec<usize> {
+        let mut candidates: Vec<usize> = self.postings.iter()
+            .zip(words_of(query, self.word_length, self.word_count))
+            .filter_map(|(postings, word)| postings.get(&word))
+            .flatten()
+            .copied()
+            .filter(|key| !self.tombstones.is_removed(*key))
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        self.counters.queried();
+        candidates
+    }
+
+    /// Scores `candidates` against `query` as they're iterated.
+    fn scores(
+        &self,
+        query: &[i8],
+        candidates: Vec<usize>,
+    ) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let squares = sum_of_squares(query);
+        let query: Box<[i8]> = query.into();
+        candidates.into_iter().map(move |key| {
+            let (signature, signature_squares) = &self.signatures[key];
+            (key, cosine_with_squares(&query, signature, squares, *signature_squares))
+        })
+    }
+
+    /// The index's size, the memory it holds, and how much searching it's done, see
+    /// [Index::stats](crate::index::Index::stats).
+    pub fn stats(&self) -> IndexStats {
+        let lists = || self.postings.iter().flat_map(HashMap::values);
+        // A hash map holds a control byte per entry besides the entry itself.
+        let tables: usize = self.postings.iter()
+            .map(|postings| postings.capacity() * (size_of::<(i64, Vec<usize>)>() + 1))
+            .sum();
+        let keys: usize = lists().map(|keys| keys.capacity() * size_of::<usize>()).sum();
+        let mut stats = IndexStats {
+            len: self.len(),
+            removed: self.removed(),
+            signature_bytes: signature_bytes(&self.signatures),
+            structure_bytes: self.postings.capacity() * size_of::<HashMap<i64, Vec<usize>>>()
+                + tables
+                + keys,
+            bookkeeping_bytes: self.tombstones.bytes(),
+            posting_lists: lists().count(),
+            postings: lists().map(Vec::len).sum(),
+            ..IndexStats::default()
+        };
+        self.counters.fill(&mut stats);
+        stats
+    }
+
+    /// Saves the index, its signatures and posting lists, to a snapshot file at `path`, see
+    /// [Index::save](crate::index::Index::save).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        snapshot::save(path.as_ref(), |writer| self.write_snapshot(writer))
+    }
+
+    /// Loads an index saved with [LshIndex::save], see [Index::load](crate::index::Index::load).
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_snapshot(&fs::read(path)?)
+    }
+
+    pub(super) fn write_snapshot(&self, writer: &mut dyn Write) -> io::Result<()> {
+        snapshot::write(writer, SnapshotKind::Lsh, |writer| {
+            let signatures = self.signatures.iter().map(|(signature, _)| &**signature);
+            writer.signatures(signatures, &self.tombstones)?;
+            writer.usize(self.word_length)?;
+            writer.usize(self.word_count)?;
+            writer.usize(self.postings.len())?;
+            for postings in &self.postings {
+                // Sorted by word, so that saving the same index twice writes the same file.
+                let mut words: Vec<_> = postings.iter().collect();
+                words.sort_unstable_by_key(|(word, _)| **word);
+                writer.usize(words.len())?;
+                for (word, keys) in words {
+                    writer.i64(*word)?;
+                    writer.usize(keys.len())?;
+                    for key in keys {
+                        writer.usize(*key)?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub(super) fn read_snapshot(bytes: &[u8]) -> io::Result<Self> {
+        snapshot::read(bytes, SnapshotKind::Lsh, |reader| {
+            let (signatures, tombstones) = reader.signatures()?;
+            let (word_length, word_count) = (reader.usize()?, reader.usize()?);
+            if word_length == 0 || word_count == 0 {
+                return Err(corrupt());
+            }
+            let positions = reader.count(8)?;
+            let mut postings = Vec::with_capacity(positions);
+            for _ in 0..positions {
+                let words = reader.count(16)?;
+                let mut position = HashMap::with_capacity(words);
+                for _ in 0..words {
+                    let word = reader.i64()?;
+                    let len = reader.count(8)?;
+                    let mut keys = Vec::with_capacity(len);
+                    for _ in 0..len {
+                        let key = reader.usize()?;
+                        if key >= signatures.len() {
+                            return Err(corrupt());
+                        }
+                        keys.push(key);
+                    }
+                    position.insert(word, keys);
+                }
+                postings.push(position);
+            }
+            let signatures = signatures.into_iter()
+                .map(|signature| {
+                    let squares = sum_of_squares(&signature);
+                    (signature, squares)
+                })
+                .collect();
+            let counters = Counters::default();
+            Ok(LshIndex { signatures, word_length, word_count, postings, tombstones, counters })
+        })
+    }
+
+    fn check_length(&self, signature: &[i8]) -> Result<(), SignatureError> {
+        match self.signatures.first() {
+            Some((first, _)) if first.len() != signature.len() => {
+                Err(SignatureError::MismatchedLengths { left: first.len(), right: signature.len() })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The words of a signature as integers, in order of position, with the word length and count
+/// limited to the signature's length.
+pub(super) fn words_of(signature: &[i8], word_length: usize, word_count: usize) -> Vec<i64> {
+    let word_length = word_length.min(signature.len());
+    let word_count = word_count.min(signature.len());
+    try_signature_words(signature, word_length, word_count)
+        .map(|words| words.iter().map(|word| word.to_int()).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,365 @@
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::index::lsh::words_of;
+use crate::index::{sort_matches, LshIndex};
+use crate::{cosine_with_squares, sum_of_squares, SignatureError};
+
+/// Identifies mapped index files.
+const INDEX_MAGIC: &[u8; 4] = b"IMLI";
+
+/// The version of the file layout, incremented whenever it changes.
+const INDEX_VERSION: u8 = 1;
+
+/// The magic, the version, three reserved bytes, the signature length, word length, word count
+/// and number of word positions as `u32`s, then the signature, word and posting counts as `u64`s.
+const HEADER_LEN: usize = 48;
+
+/// A word and the start of its posting list.
+const ENTRY_LEN: usize = 16;
+
+impl LshIndex {
+    /// Writes the index in the layout [MappedLshIndex] maps. Returns an error of kind
+    /// [ErrorKind::InvalidInput] if it holds more than `u32::MAX` signatures, or signatures were
+    /// removed from it since it was last [compacted](LshIndex::compact).
+    pub fn write_mapped<W: Write>(&self, writer: W) -> io::Result<()> {
+        let count = self.signatures.len();
+        if u32::try_from(count).is_err() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "too many signatures to map"));
+        }
+        if self.removed() > 0 {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "compact the index to map it"));
+        }
+        let signature_length = self.signatures.first().map_or(0, |(signature, _)| signature.len());
+        let tables: Vec<Vec<(i64, &Vec<usize>)>> = self.postings.iter()
+            .map(|postings| {
+                let mut table: Vec<_> = postings.iter().map(|(word, keys)| (*word, keys)).collect();
+                table.sort_unstable_by_key(|(word, _)| *word);
+                table
+            })
+            .collect();
+        let word_total: usize = tables.iter().map(Vec::len).sum();
+        let posting_total: usize = tables.iter().flatten().map(|(_, keys)| keys.len()).sum();
+
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_all(&[INDEX_VERSION, 0, 0, 0])?;
+        for value in [signature_length, self.word_length, self.word_count, tables.len()] {
+            writer.write_all(&(value as u32).to_le_bytes())?;
+        }
+        for value in [count, word_total, posting_total] {
+            writer.write_all(&(value as u64).to_le_bytes())?;
+        }
+
+        for (signature, _) in &self.signatures {
+            let bytes: Vec<u8> = signature.iter().map(|&element| element as u8).collect();
+            writer.write_all(&bytes)?;
+        }
+        let padding = padding(count * signature_length);
+        writer.write_all(&[0; 8][..padding])?;
+        for (_, squares) in &self.signatures {
+            writer.write_all(&squares.to_le_bytes())?;
+        }
+
+        // Where each position's words start among all the words, and where they all end.
+        let mut start = 0u64;
+        for table in &tables {
+            writer.write_all(&start.to_le_bytes())?;
+            start += table.len() as u64;
+        }
+        writer.write_all(&start.to_le_bytes())?;
+        // Each word with where its posting list starts among all the postings, ending with the
+        // end of the last list, so that every list ends where the next entry's starts.
+        let mut start = 0u64;
+        for (word, keys) in tables.iter().flatten() {
+            writer.write_all(&word.to_le_bytes())?;
+            writer.write_all(&start.to_le_bytes())?;
+            start += keys.len() as u64;
+        }
+        writer.write_all(&0i64.to_le_bytes())?;
+        writer.write_all(&start.to_le_bytes())?;
+        for key in tables.iter().flatten().flat_map(|(_, keys)| keys.iter()) {
+            writer.write_all(&(*key as u32).to_le_bytes())?;
+        }
+        writer.flush()
+    }
+}
+
+/// An [LshIndex] written with [LshIndex::write_mapped] and memory-mapped rather than read, so
+/// that an index of millions of signatures can be queried as soon as it's opened, with its pages
+/// loaded as queries touch them and shared between processes mapping the same file.
+///
+/// Searches find the same signatures with the same similarities as the index that was written.
+/// Its posting lists are sorted by word, so each word of a query is looked up with a binary
+/// search. The index is read-only; write it again to add signatures.
+#[derive(Debug)]
+pub struct MappedLshIndex {
+    map: Mmap,
+    signature_length: usize,
+    word_length: usize,
+    word_count: usize,
+    positions: usize,
+    count: usize,
+    /// The byte offsets of the sections after the signatures.
+    squares: usize,
+    directory: usize,
+    entries: usize,
+    postings: usize,
+}
+
+impl MappedLshIndex {
+    /// Maps the index file at `path`. Returns an error of kind [ErrorKind::InvalidData] if it's
+    /// not an index file, not of the length its header describes, or its word directory or posting
+    /// lists are out of order.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it's mapped, by this process or any other.
+    /// Doing so is undefined behavior, see [memmap2::Mmap::map].
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let map = Mmap::map(&File::open(path)?)?;
+        // Queries touch a few scattered pages each. The advice is only a hint, so failing to give
+        // it changes nothing.
+        #[cfg(unix)]
+        let _ = map.advise(memmap2::Advice::Random);
+
+        if map.len() < HEADER_LEN || &map[..4] != INDEX_MAGIC {
+            return Err(invalid_data("not a mapped index file"));
+        }
+        if map[4] != INDEX_VERSION {
+            return Err(invalid_data(format!("unsupported mapped index version {}", map[4])));
+        }
+        let u32_at = |offset: usize| read_u32(&map, offset) as usize;
+        let (signature_length, word_length, word_count, positions) =
+            (u32_at(8), u32_at(12), u32_at(16), u32_at(20));
+        let u64_at = |offset: usize| usize::try_from(read_u64(&map, offset)).ok();
+        let sections = (|| {
+            let (count, word_total, posting_total) = (u64_at(24)?, u64_at(32)?, u64_at(40)?);
+            let signatures = count.checked_mul(signature_length)?;
+            let squares = HEADER_LEN.checked_add(signatures)?.checked_add(padding(signatures))?;
+            let directory = squares.checked_add(count.checked_mul(8)?)?;
+            let entries = directory.checked_add(positions.checked_add(1)?.checked_mul(8)?)?;
+            let postings = entries.checked_add(word_total.checked_add(1)?.checked_mul(ENTRY_LEN)?)?;
+            let end = postings.checked_add(posting_total.checked_mul(4)?)?;
+            Some((count, word_total, posting_total, squares, directory, entries, postings, end))
+        })();
+        let Some((count, word_total, posting_total, squares, directory, entries, postings, end)) =
+            sections
+        else {
+            return Err(invalid_data("mapped index sections are too large"));
+        };
+        if map.len() != end {
+            return Err(invalid_data("mapped index is not of the length its header describes"));
+        }
+
+        let index = MappedLshIndex {
+            map,
+            signature_length,
+            word_length,
+            word_count,
+            positions,
+            count,
+            squares,
+            directory,
+            entries,
+            postings,
+        };
+        let starts: Vec<u64> = (0..=positions).map(|p| index.u64_at(directory + p * 8)).collect();
+        let sorted = starts.windows(2).all(|pair| pair[0] <= pair[1]);
+        if !sorted || starts[positions] != word_total as u64 {
+            return Err(invalid_data("mapped index directory is corrupt"));
+        }
+        // Likewise, every posting list must end where the next begins, within the postings. This
+        // reads the entries once, sequentially, so that queries needn't check them.
+        let mut previous = 0;
+        for entry in 0..=word_total {
+            let start = index.u64_at(entries + entry * ENTRY_LEN + 8);
+            if start < previous {
+                return Err(invalid_data("mapped index posting lists are corrupt"));
+            }
+            previous = start;
+        }
+        if previous != posting_total as u64 {
+            return Err(invalid_data("mapped index posting lists are corrupt"));
+        }
+        Ok(index)
+    }
+
+    /// The number of signatures in the index.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Whether the index holds no signatures.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The signature with `key`, if any.
+    pub fn get(&self, key: usize) -> Option<&[i8]> {
+        if key >= self.count {
+            return None;
+        }
+        let start = HEADER_LEN + key * self.signature_length;
+        let bytes = &self.map[start..start + self.signature_length];
+        // Safety: i8 and u8 have the same size and alignment, and every bit pattern is valid for
+        // both.
+        Some(unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast::<i8>(), bytes.len()) })
+    }
+
+    /// Finds the signatures sharing a word with `query` with a cosine similarity of at least
+    /// `threshold` to it, as pairs of key and similarity, most similar first. Ties are broken in
+    /// favor of the smaller key. Panics if the query is not of the same length as the signatures
+    /// in the index, see [MappedLshIndex::try_find_similar] for a non-panicking version.
+    pub fn find_similar<S: AsRef<[i8]>>(&self, query: S, threshold: f64) -> Vec<(usize, f64)> {
+        self.try_find_similar(query, threshold).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [MappedLshIndex::find_similar] which returns an error rather
+    /// than crashing when the query is of a different length than the signatures in the index.
+    pub fn try_find_similar<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        threshold: f64,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+        Ok(self.rescore(query, threshold))
+    }
+
+    /// Finds the `k` signatures sharing a word with `query` most similar to it, as pairs of key
+    /// and cosine similarity, most similar first. Ties are broken in favor of the smaller key.
+    /// Panics if the query is not of the same length as the signatures in the index, see
+    /// [MappedLshIndex::try_nearest] for a non-panicking version.
+    pub fn nearest<S: AsRef<[i8]>>(&self, query: S, k: usize) -> Vec<(usize, f64)> {
+        self.try_nearest(query, k).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [MappedLshIndex::nearest] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    pub fn try_nearest<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        k: usize,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+        let mut matches = self.rescore(query, f64::NEG_INFINITY);
+        matches.truncate(k);
+        Ok(matches)
+    }
+
+    /// Streams the signatures sharing a word with `query` with a cosine similarity of at least
+    /// `min_similarity` to it, as pairs of key and similarity, in no particular order, without
+    /// collecting them first. Panics if the query is not of the same length as the signatures in
+    /// the index, see [MappedLshIndex::try_find_all_within] for a non-panicking version.
+    pub fn find_all_within<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        min_similarity: f64,
+    ) -> Box<dyn Iterator<Item = (usize, f64)> + '_> {
+        self.try_find_all_within(query, min_similarity).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [MappedLshIndex::find_all_within] which returns an error
+    /// rather than crashing when the query is of a different length than the signatures in the
+    /// index.
+    pub fn try_find_all_within<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        min_similarity: f64,
+    ) -> Result<Box<dyn Iterator<Item = (usize, f64)> + '_>, SignatureError> {
+        let query = query.as_ref();
+        self.check_length(query)?;
+        Ok(Box::new(self.within(query, min_similarity)))
+    }
+
+    fn rescore(&self, query: &[i8], threshold: f64) -> Vec<(usize, f64)> {
+        let mut matches: Vec<(usize, f64)> = self.within(query, threshold).collect();
+        sort_matches(&mut matches);
+        matches
+    }
+
+    /// Scores the signatures sharing a word with `query` as they're iterated, in order of key,
+    /// yielding those at least `threshold` similar.
+    fn within(&self, query: &[i8], threshold: f64) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let words = words_of(query, self.word_length, self.word_count);
+        let mut candidates: Vec<usize> = words.iter()
+            .take(self.positions)
+            .enumerate()
+            .flat_map(|(position, word)| self.postings_of(position, *word))
+            .filter(|&key| key < self.count)
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let squares = sum_of_squares(query);
+        let query: Box<[i8]> = query.into();
+        candidates.into_iter().filter_map(move |key| {
+            let signature = self.get(key)?;
+            let signature_squares = self.u64_at(self.squares + key * 8) as i64;
+            let similarity = cosine_with_squares(&query, signature, squares, signature_squares);
+            (similarity >= threshold).then_some((key, similarity))
+        })
+    }
+
+    /// The keys of the signatures with `word` at `position`, found by a binary search of the
+    /// position's words.
+    fn postings_of(&self, position: usize, word: i64) -> impl Iterator<Item = usize> + '_ {
+        let word_at = |entry: usize| self.u64_at(self.entries + entry * ENTRY_LEN) as i64;
+        let last = self.u64_at(self.directory + (position + 1) * 8) as usize;
+        let (mut low, mut high) = (self.u64_at(self.directory + position * 8) as usize, last);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if word_at(middle) < word {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        // The list's bounds were checked to be in order and within the postings when the file was
+        // opened.
+        let (mut start, mut end) = (0, 0);
+        if low < last && word_at(low) == word {
+            let entry = self.entries + low * ENTRY_LEN;
+            start = self.u64_at(entry + 8) as usize;
+            end = self.u64_at(entry + ENTRY_LEN + 8) as usize;
+        }
+        (start..end).map(move |i| read_u32(&self.map, self.postings + i * 4) as usize)
+    }
+
+    fn u64_at(&self, offset: usize) -> u64 {
+        read_u64(&self.map, offset)
+    }
+
+    fn check_length(&self, signature: &[i8]) -> Result<(), SignatureError> {
+        if self.count > 0 && signature.len() != self.signature_length {
+            return Err(SignatureError::MismatchedLengths {
+                left: self.signature_length,
+                right: signature.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The zero bytes after `len` bytes of signatures that align the following sections to 8 bytes.
+fn padding(len: usize) -> usize {
+    (8 - len % 8) % 8
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().expect("four bytes"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().expect("eight bytes"))
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error)
+}
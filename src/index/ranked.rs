@@ -0,0 +1,134 @@
+use crate::index::Index;
+use crate::{DistanceMetric, SignatureError};
+
+/// The number of candidates a [RankedIndex] asks its index for per match wanted, unless set
+/// otherwise.
+pub const DEFAULT_RANKED_CANDIDATES: usize = 4;
+
+/// An [Index] whose matches are ranked by a [DistanceMetric] rather than by cosine similarity, so
+/// that the same structure ranks by [NormalizedDistance](crate::NormalizedDistance), by
+/// [Manhattan](crate::Manhattan) distance, or by a custom weighted metric.
+///
+/// The index finds candidates by cosine similarity as usual, and the metric ranks them. Matches
+/// are pairs of key and distance, nearest first, with ties broken in favor of the smaller key.
+/// [RankedIndex::nearest] ranks [candidates](RankedIndex::candidates) times as many of the
+/// index's nearest as it returns, which finds the nearest under metrics that mostly agree with
+/// cosine similarity, as those on signatures do. [RankedIndex::find_within] ranks every signature
+/// the index finds over [min_similarity](RankedIndex::min_similarity), by default every one, so
+/// it's exact over a [LinearIndex](crate::index::LinearIndex).
+///
+/// ```
+/// use image_match::index::{Index, LinearIndex, RankedIndex};
+/// use image_match::NormalizedDistance;
+///
+/// let mut index = RankedIndex::new(LinearIndex::new(), NormalizedDistance);
+/// let first = index.index_mut().insert(&[1, 0, -1, 2]);
+/// index.index_mut().insert(&[-2, 2, 2, -2]);
+/// assert_eq!(index.nearest(&[1, 0, -1, 2], 1), [(first, 0.0)]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RankedIndex<I, M> {
+    index: I,
+    metric: M,
+    candidates: usize,
+    min_similarity: f64,
+}
+
+impl<I: Index, M: DistanceMetric> RankedIndex<I, M> {
+    /// Ranks the matches of `index` by `metric`.
+    pub fn new(index: I, metric: M) -> Self {
+        RankedIndex { index, metric, candidates: DEFAULT_RANKED_CANDIDATES, min_similarity: -1.0 }
+    }
+
+    /// Sets how many candidates [RankedIndex::nearest] ranks per match it returns, at least 1.
+    pub fn candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates.max(1);
+        self
+    }
+
+    /// Sets the cosine similarity that [RankedIndex::find_within] asks the index for candidates
+    /// over, -1 for every signature. Raising it skips ranking signatures too dissimilar to be
+    /// within any distance of interest, but may miss some under metrics that disagree with
+    /// cosine similarity.
+    pub fn min_similarity(mut self, min_similarity: f64) -> Self {
+        self.min_similarity = min_similarity;
+        self
+    }
+
+    /// The index the candidates come from.
+    pub fn index(&self) -> &I {
+        &self.index
+    }
+
+    /// The index the candidates come from, to insert into or remove from.
+    pub fn index_mut(&mut self) -> &mut I {
+        &mut self.index
+    }
+
+    /// The metric matches are ranked by.
+    pub fn metric(&self) -> &M {
+        &self.metric
+    }
+
+    /// The index, without the metric.
+    pub fn into_inner(self) -> I {
+        self.index
+    }
+
+    /// Finds the `k` signatures nearest to `query` under the metric, as pairs of key and
+    /// distance, nearest first. Panics if the query is not of the same length as the signatures
+    /// in the index, see [RankedIndex::try_nearest] for a non-panicking version.
+    pub fn nearest<S: AsRef<[i8]>>(&self, query: S, k: usize) -> Vec<(usize, f64)> {
+        self.try_nearest(query, k).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [RankedIndex::nearest] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    pub fn try_nearest<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        k: usize,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        let query = query.as_ref();
+        let candidates = self.index.try_nearest(query, k.saturating_mul(self.candidates))?;
+        let mut matches = self.rank(query, candidates.into_iter());
+        matches.truncate(k);
+        Ok(matches)
+    }
+
+    /// Finds the signatures within `max_distance` of `query` under the metric, as pairs of key
+    /// and distance, nearest first. Panics if the query is not of the same length as the
+    /// signatures in the index, see [RankedIndex::try_find_within] for a non-panicking version.
+    pub fn find_within<S: AsRef<[i8]>>(&self, query: S, max_distance: f64) -> Vec<(usize, f64)> {
+        self.try_find_within(query, max_distance).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [RankedIndex::find_within] which returns an error rather than
+    /// crashing when the query is of a different length than the signatures in the index.
+    pub fn try_find_within<S: AsRef<[i8]>>(
+        &self,
+        query: S,
+        max_distance: f64,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        let query = query.as_ref();
+        let candidates = self.index.try_find_all_within(query, self.min_similarity)?;
+        let mut matches = self.rank(query, candidates);
+        matches.retain(|&(_, distance)| distance <= max_distance);
+        Ok(matches)
+    }
+
+    /// The distances of `candidates` to `query`, nearest first.
+    fn rank<C>(&self, query: &[i8], candidates: C) -> Vec<(usize, f64)>
+    where
+        C: Iterator<Item = (usize, f64)>,
+    {
+        let mut matches: Vec<(usize, f64)> = candidates
+            .map(|(key, _)| {
+                let signature = self.index.get(key).expect("candidates are in the index");
+                (key, self.metric.distance(query, signature))
+            })
+            .collect();
+        matches.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        matches
+    }
+}
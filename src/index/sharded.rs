@@ -0,0 +1,367 @@
+use std::io::{self, Write};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::index::snapshot::{self, corrupt, SnapshotKind};
+use crate::index::{
+    check_lengths, emptied, sort_matches, BoundedMatches, Counters, Index, IndexStats, SearchBudget,
+};
+use crate::SignatureError;
+
+/// An [Index] split into shards searched side by side, so that one logical index keeps every core
+/// of a large machine busy rather than serializing its searches on one.
+///
+/// Each signature goes to the shard holding the fewest, keyed in the order signatures were
+/// inserted into the whole index, like any other [Index]. Searches ask every shard at once, on
+/// rayon's pool with the `rayon` feature or on a thread per shard otherwise, and merge their
+/// matches; [Index::nearest] takes the `k` nearest of each shard's `k` nearest. Shards like
+/// [HnswIndex](crate::index::HnswIndex) are as approximate as they'd be on their own.
+/// [Index::find_all_within] streams the shards' matches one shard after another.
+///
+/// ```
+/// use image_match::index::{HnswIndex, Index, ShardedIndex};
+///
+/// let mut index = ShardedIndex::with_shards(4, || HnswIndex::new().ef_search(128));
+/// let first = index.insert(&[1, 0, -1, 2]);
+/// index.insert(&[-2, 2, 2, -2]);
+/// assert_eq!(index.nearest(&[1, 0, -1, 2], 1), [(first, 1.0)]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ShardedIndex<I> {
+    shards: Vec<I>,
+    /// The shard of each key of the index, and the signature's key within it.
+    keys: Vec<(usize, usize)>,
+    /// The key of the index of each key of each shard.
+    globals: Vec<Vec<usize>>,
+    /// The length of the signatures, that of the first one inserted.
+    length: Option<usize>,
+    counters: Counters,
+}
+
+impl<I: Index + Default> ShardedIndex<I> {
+    /// Creates an index of a default shard per available core.
+    pub fn new() -> Self {
+        let shards = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(shards, I::default)
+    }
+}
+
+impl<I: Index + Default> Default for ShardedIndex<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I: Index> ShardedIndex<I> {
+    /// Creates an index of `count` shards, at least 1, each made by `shard`, dropping any
+    /// signatures already in them.
+    pub fn with_shards<F: FnMut() -> I>(count: usize, mut shard: F) -> Self {
+        let count = count.max(1);
+        ShardedIndex {
+            shards: (0..count).map(|_| emptied(shard())).collect(),
+            keys: Vec::new(),
+            globals: vec![Vec::new(); count],
+            length: None,
+            counters: Counters::default(),
+        }
+    }
+
+    /// The shards, each with its own keys.
+    pub fn shards(&self) -> &[I] {
+        &self.shards
+    }
+
+    fn check_length(&self, signature: &[i8]) -> Result<(), SignatureError> {
+        match self.length {
+            Some(length) if length != signature.len() => {
+                Err(SignatureError::MismatchedLengths { left: length, right: signature.len() })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs `search` on every shard at once, and merges the shards' matches as keys of the index,
+    /// most similar first.
+    fn fan_out<F>(&self, search: F) -> Result<Vec<(usize, f64)>, SignatureError>
+    where
+        I: Sync,
+        F: Fn(&I) -> Result<Vec<(usize, f64)>, SignatureError> + Sync,
+    {
+        let search = |shard: usize| -> Result<Vec<(usize, f64)>, SignatureError> {
+            let globals = &self.globals[shard];
+            let matches = search(&self.shards[shard])?;
+            Ok(matches.into_iter().map(|(key, similarity)| (globals[key], similarity)).collect())
+        };
+
+        #[cfg(feature = "rayon")]
+        let matches: Vec<_> = (0..self.shards.len()).into_par_iter().map(search).collect();
+
+        #[cfg(not(feature = "rayon"))]
+        let matches: Vec<_> = std::thread::scope(|scope| {
+            let search = &search;
+            let workers: Vec<_> = (1..self.shards.len())
+                .map(|shard| scope.spawn(move || search(shard)))
+                .collect();
+            // The first shard is searched on this thread, which would otherwise only wait.
+            let first = search(0);
+            std::iter::once(first)
+                .chain(workers.into_iter().map(|worker| {
+                    worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+                }))
+                .collect()
+        });
+
+        let mut merged = Vec::new();
+        for shard_matches in matches {
+            merged.extend(shard_matches?);
+        }
+        sort_matches(&mut merged);
+        Ok(merged)
+    }
+
+    /// Runs `search` on every shard at once with its share of `budget`, and merges the shards'
+    /// matches, truncated if any shard's were.
+    fn fan_out_bounded<F>(
+        &self,
+        budget: &SearchBudget,
+        search: F,
+    ) -> Result<BoundedMatches, SignatureError>
+    where
+        I: Sync,
+        F: Fn(&I, &SearchBudget) -> Result<BoundedMatches, SignatureError> + Sync,
+    {
+        let budget = budget.share(self.shards.len());
+        let truncated = AtomicBool::new(false);
+        let matches = self.fan_out(|shard| {
+            let found = search(shard, &budget)?;
+            if found.truncated {
+                truncated.store(true, Ordering::Relaxed);
+            }
+            Ok(found.matches)
+        })?;
+        Ok(BoundedMatches { matches, truncated: truncated.into_inner() })
+    }
+}
+
+impl<I: Index + Sync> Index for ShardedIndex<I> {
+    fn len(&self) -> usize {
+        self.shards.iter().map(Index::len).sum()
+    }
+
+    fn get(&self, key: usize) -> Option<&[i8]> {
+        let &(shard, key) = self.keys.get(key)?;
+        self.shards[shard].get(key)
+    }
+
+    fn removed(&self) -> usize {
+        self.shards.iter().map(Index::removed).sum()
+    }
+
+    fn remove(&mut self, key: usize) -> bool {
+        match self.keys.get(key) {
+            Some(&(shard, key)) => self.shards[shard].remove(key),
+            None => false,
+        }
+    }
+
+    fn compact(&mut self) -> Vec<Option<usize>> {
+        let shard_keys: Vec<Vec<Option<usize>>> =
+            self.shards.iter_mut().map(Index::compact).collect();
+        let mut keys = Vec::with_capacity(self.len());
+        let mut globals = vec![Vec::new(); self.shards.len()];
+        // Keys of the index are in the order of the shards' keys within each shard, so the new
+        // keys of each shard are reached in order.
+        let mapping = self.keys.iter()
+            .map(|&(shard, key)| {
+                shard_keys[shard][key].map(|key| {
+                    globals[shard].push(keys.len());
+                    keys.push((shard, key));
+                    keys.len() - 1
+                })
+            })
+            .collect();
+        if keys.is_empty() {
+            self.length = None;
+        }
+        self.keys = keys;
+        self.globals = globals;
+        mapping
+    }
+
+    fn try_insert(&mut self, signature: &[i8]) -> Result<usize, SignatureError> {
+        self.check_length(signature)?;
+        let shard = (0..self.shards.len())
+            .min_by_key(|&shard| self.globals[shard].len())
+            .expect("there's at least one shard");
+        let key = self.shards[shard].try_insert(signature)?;
+        debug_assert_eq!(key, self.globals[shard].len());
+        self.globals[shard].push(self.keys.len());
+        self.keys.push((shard, key));
+        self.length = Some(signature.len());
+        Ok(self.keys.len() - 1)
+    }
+
+    /// Spreads the signatures over the shards as inserting them would, then builds each shard's
+    /// share at once.
+    fn try_build_from<T, S>(mut self, signatures: T) -> Result<Self, SignatureError>
+    where
+        T: IntoIterator<Item = S>,
+        S: AsRef<[i8]>,
+    {
+        let signatures: Vec<S> = signatures.into_iter().collect();
+        let signatures: Vec<&[i8]> = signatures.iter().map(AsRef::as_ref).collect();
+        check_lengths(&signatures)?;
+        let Some(first) = signatures.first() else {
+            return Ok(self);
+        };
+        self.check_length(first)?;
+
+        let mut shares = vec![Vec::new(); self.shards.len()];
+        for signature in &signatures {
+            let shard = (0..self.shards.len())
+                .min_by_key(|&shard| self.globals[shard].len())
+                .expect("there's at least one shard");
+            self.globals[shard].push(self.keys.len());
+            self.keys.push((shard, self.globals[shard].len() - 1));
+            shares[shard].push(*signature);
+        }
+        self.shards = std::mem::take(&mut self.shards).into_iter()
+            .zip(shares)
+            .map(|(shard, share)| shard.try_build_from(share))
+            .collect::<Result<_, _>>()?;
+        self.length = Some(first.len());
+        Ok(self)
+    }
+
+    fn try_find_similar(
+        &self,
+        query: &[i8],
+        threshold: f64,
+    ) -> Result<Vec<(usize, f64)>, SignatureError> {
+        self.check_length(query)?;
+        self.counters.queried();
+        self.fan_out(|shard| shard.try_find_similar(query, threshold))
+    }
+
+    fn try_find_all_within(
+        &self,
+        query: &[i8],
+        min_similarity: f64,
+    ) -> Result<Box<dyn Iterator<Item = (usize, f64)> + '_>, SignatureError> {
+        self.check_length(query)?;
+        self.counters.queried();
+        let shards = self.shards.iter()
+            .map(|shard| shard.try_find_all_within(query, min_similarity))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Box::new(shards.into_iter().enumerate().flat_map(move |(shard, matches)| {
+            matches.map(move |(key, similarity)| (self.globals[shard][key], similarity))
+        })))
+    }
+
+    fn try_nearest(&self, query: &[i8], k: usize) -> Result<Vec<(usize, f64)>, SignatureError> {
+        self.check_length(query)?;
+        self.counters.queried();
+        let mut matches = self.fan_out(|shard| shard.try_nearest(query, k))?;
+        matches.truncate(k);
+        Ok(matches)
+    }
+
+    fn try_find_similar_bounded(
+        &self,
+        query: &[i8],
+        threshold: f64,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        self.check_length(query)?;
+        self.counters.queried();
+        self.fan_out_bounded(budget, |shard, budget| {
+            shard.try_find_similar_bounded(query, threshold, budget)
+        })
+    }
+
+    fn try_nearest_bounded(
+        &self,
+        query: &[i8],
+        k: usize,
+        budget: &SearchBudget,
+    ) -> Result<BoundedMatches, SignatureError> {
+        self.check_length(query)?;
+        self.counters.queried();
+        let mut found = self.fan_out_bounded(budget, |shard, budget| {
+            shard.try_nearest_bounded(query, k, budget)
+        })?;
+        found.matches.truncate(k);
+        Ok(found)
+    }
+
+    /// Adds up the stats of the shards, but counts each search once, rather than once per shard.
+    fn stats(&self) -> IndexStats {
+        let mut stats = IndexStats::default();
+        for shard in &self.shards {
+            stats.add(&shard.stats());
+        }
+        let globals: usize = self.globals.iter().map(Vec::capacity).sum();
+        stats.bookkeeping_bytes +=
+            self.keys.capacity() * size_of::<(usize, usize)>() + globals * size_of::<usize>();
+        let comparisons = stats.comparisons;
+        self.counters.fill(&mut stats);
+        stats.comparisons = comparisons;
+        stats
+    }
+
+    /// Writes which shard each key is in, then the snapshot of each shard, into one snapshot, so
+    /// that saving replaces the whole index at once.
+    fn write_snapshot(&self, writer: &mut dyn Write) -> io::Result<()> {
+        snapshot::write(writer, SnapshotKind::Sharded, |writer| {
+            writer.usize(self.shards.len())?;
+            writer.usize(self.length.map_or(0, |length| length + 1))?;
+            writer.usize(self.keys.len())?;
+            for &(shard, key) in &self.keys {
+                writer.usize(shard)?;
+                writer.usize(key)?;
+            }
+            let mut bytes = Vec::new();
+            for shard in &self.shards {
+                bytes.clear();
+                shard.write_snapshot(&mut bytes)?;
+                writer.blob(&bytes)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn read_snapshot(bytes: &[u8]) -> io::Result<Self> {
+        let (length, keys, shards) = snapshot::read(bytes, SnapshotKind::Sharded, |reader| {
+            let count = reader.count(8)?;
+            let length = reader.usize()?.checked_sub(1);
+            let keys = (0..reader.count(16)?)
+                .map(|_| Ok((reader.usize()?, reader.usize()?)))
+                .collect::<io::Result<Vec<_>>>()?;
+            let shards = (0..count)
+                .map(|_| I::read_snapshot(reader.blob()?))
+                .collect::<io::Result<Vec<_>>>()?;
+            Ok((length, keys, shards))
+        })?;
+        let count = shards.len();
+
+        // Each shard's keys must appear in order, and cover the whole shard.
+        let mut globals = vec![Vec::new(); count];
+        for (global, &(shard, key)) in keys.iter().enumerate() {
+            match globals.get_mut(shard) {
+                Some(shard_globals) if shard_globals.len() == key => shard_globals.push(global),
+                _ => return Err(corrupt()),
+            }
+        }
+        let complete = shards.iter()
+            .zip(&globals)
+            .all(|(shard, shard_globals)| shard.len() + shard.removed() == shard_globals.len());
+        if count == 0 || !complete {
+            return Err(corrupt());
+        }
+        Ok(ShardedIndex { shards, keys, globals, length, counters: Counters::default() })
+    }
+}
+
@@ -0,0 +1,167 @@
+use std::mem;
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::index::Index;
+use crate::SignatureError;
+
+/// The number of inserts a [SharedIndex] queues before applying them, unless set otherwise.
+pub const DEFAULT_SHARED_BATCH_SIZE: usize = 256;
+
+/// An [Index] shared between threads, e.g. behind an [Arc](std::sync::Arc) in a web service,
+/// which any number of threads search at once while others insert into it.
+///
+/// Searches go through a [read](SharedIndex::read) lock, which readers hold side by side, so they
+/// never wait on each other. Inserts don't take the write lock one by one: they're checked and
+/// given their key right away, then queued, and applied together once
+/// [batch_size](SharedIndex::batch_size) of them are waiting, or on [flush](SharedIndex::flush),
+/// so that readers are held up once per batch rather than once per insert. Queued signatures
+/// aren't found by searches until they're applied.
+///
+/// ```
+/// use std::sync::Arc;
+/// use image_match::index::{HnswIndex, Index, SharedIndex};
+///
+/// let index = Arc::new(SharedIndex::new(HnswIndex::new()).batch_size(64));
+/// let key = index.insert(&[1, 0, -1, 2]);
+/// index.flush();
+///
+/// let reader = Arc::clone(&index);
+/// let nearest = std::thread::spawn(move || reader.read().nearest(&[1, 0, -1, 2], 1));
+/// assert_eq!(nearest.join().unwrap(), [(key, 1.0)]);
+/// ```
+#[derive(Debug)]
+pub struct SharedIndex<I> {
+    index: RwLock<I>,
+    pending: Mutex<Pending>,
+    batch_size: usize,
+}
+
+/// Inserts waiting to be applied.
+#[derive(Debug)]
+struct Pending {
+    signatures: Vec<Box<[i8]>>,
+    /// The key of the next signature inserted, while there are signatures queued.
+    next_key: usize,
+    /// The length of the signatures, once known.
+    length: Option<usize>,
+}
+
+impl<I: Index> SharedIndex<I> {
+    /// Shares `index`, keeping any signatures already in it.
+    pub fn new(index: I) -> Self {
+        let keys = index.len() + index.removed();
+        let length = (0..keys).find_map(|key| index.get(key)).map(<[i8]>::len);
+        SharedIndex {
+            index: RwLock::new(index),
+            pending: Mutex::new(Pending { signatures: Vec::new(), next_key: keys, length }),
+            batch_size: DEFAULT_SHARED_BATCH_SIZE,
+        }
+    }
+
+    /// Sets how many inserts are queued before they're applied, at least 1. Larger batches hold
+    /// up searches less often, but leave signatures unsearchable for longer.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Locks the index for searching, alongside any other readers, with any [Index] method that
+    /// takes `&self`. Inserts still queued aren't in it.
+    pub fn read(&self) -> RwLockReadGuard<'_, I> {
+        self.index.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Applies the queued inserts, then locks the index on its own, e.g. to
+    /// [compact](Index::compact) it. Later inserts are keyed after whatever the index holds once
+    /// the guard is dropped, so a thread must drop it before inserting itself.
+    pub fn write(&self) -> RwLockWriteGuard<'_, I> {
+        let mut pending = self.pending();
+        let mut index = self.index.write().unwrap_or_else(PoisonError::into_inner);
+        apply(&mut *index, &mut pending);
+        index
+    }
+
+    /// Queues `signature` for inserting, applying the queue if it's full, and returns its key.
+    ///
+    /// Panics if the signature isn't the same length as the others, see
+    /// [try_insert](SharedIndex::try_insert) for a non-panicking version.
+    pub fn insert<S: AsRef<[i8]>>(&self, signature: S) -> usize {
+        self.try_insert(signature).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [insert(signature)](SharedIndex::insert) which returns an error
+    /// rather than crashing when the signature isn't the same length as the others.
+    pub fn try_insert<S: AsRef<[i8]>>(&self, signature: S) -> Result<usize, SignatureError> {
+        let signature = signature.as_ref();
+        let mut pending = self.pending();
+        let Some(length) = pending.length else {
+            // The index decides whether the first signature fits, e.g. when all of those in it
+            // were removed.
+            let mut index = self.index.write().unwrap_or_else(PoisonError::into_inner);
+            let key = index.try_insert(signature)?;
+            pending.length = Some(signature.len());
+            return Ok(key);
+        };
+        if signature.len() != length {
+            return Err(SignatureError::MismatchedLengths { left: length, right: signature.len() });
+        }
+
+        if pending.signatures.is_empty() {
+            // The index may have been compacted through a write guard since the last batch,
+            // which waits for the guard to be dropped.
+            let index = self.read();
+            pending.next_key = index.len() + index.removed();
+        }
+        let key = pending.next_key;
+        pending.signatures.push(signature.into());
+        pending.next_key += 1;
+        if pending.signatures.len() >= self.batch_size {
+            let mut index = self.index.write().unwrap_or_else(PoisonError::into_inner);
+            apply(&mut *index, &mut pending);
+        }
+        Ok(key)
+    }
+
+    /// Applies the queued inserts, so that searches find them.
+    pub fn flush(&self) {
+        drop(self.write());
+    }
+
+    /// The number of inserts queued.
+    pub fn queued(&self) -> usize {
+        self.pending().signatures.len()
+    }
+
+    /// Removes the signature under `key` once the queued inserts are applied, returning whether
+    /// it was there to remove.
+    pub fn remove(&self, key: usize) -> bool {
+        self.write().remove(key)
+    }
+
+    /// The index, with the queued inserts applied.
+    pub fn into_inner(self) -> I {
+        let mut index = self.index.into_inner().unwrap_or_else(PoisonError::into_inner);
+        let mut pending = self.pending.into_inner().unwrap_or_else(PoisonError::into_inner);
+        apply(&mut index, &mut pending);
+        index
+    }
+
+    // The queue is always locked before the index, so that batches are applied in the order
+    // their keys were given out.
+    fn pending(&self) -> MutexGuard<'_, Pending> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<I: Index + Default> Default for SharedIndex<I> {
+    fn default() -> Self {
+        SharedIndex::new(I::default())
+    }
+}
+
+/// Inserts the queued signatures into `index`.
+fn apply<I: Index>(index: &mut I, pending: &mut Pending) {
+    for signature in mem::take(&mut pending.signatures) {
+        index.try_insert(&signature).expect("queued signatures are of the index's length");
+    }
+}
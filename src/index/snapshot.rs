@@ -0,0 +1,234 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::Path;
+
+use crate::index::Tombstones;
+use crate::SignatureError;
+
+/// Identifies index snapshots.
+const SNAPSHOT_MAGIC: &[u8; 4] = b"IMIX";
+
+/// The version of the snapshot layout, incremented whenever it changes.
+const SNAPSHOT_VERSION: u8 = 2;
+
+/// The magic, the version, the kind of index, and two reserved bytes.
+const HEADER_LEN: usize = 8;
+
+/// The checksum of everything before it, which ends a snapshot.
+const CHECKSUM_LEN: usize = 8;
+
+/// The kind of index a snapshot holds, so that one kind isn't loaded as another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum SnapshotKind {
+    Linear = 0,
+    Lsh = 1,
+    Hnsw = 2,
+    Sharded = 3,
+}
+
+/// Saves the snapshot `write` writes at `path`. It's written next to `path` and renamed over it
+/// once complete, so that a save that fails midway, or a crash, leaves the previous snapshot in
+/// place.
+pub(super) fn save<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    let mut temporary = OsString::from(path);
+    temporary.push(".tmp");
+    let written = File::create(&temporary).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()
+    });
+    match written {
+        Ok(()) => fs::rename(&temporary, path),
+        Err(e) => {
+            let _ = fs::remove_file(&temporary);
+            Err(e)
+        }
+    }
+}
+
+/// Writes a snapshot of an index of `kind` to `writer`, with `write` writing what the index holds.
+///
+/// A snapshot is a header, then the index's contents as little endian integers, so that it loads
+/// on any platform, then an FNV-1a checksum of everything before it.
+pub(super) fn write<F>(writer: &mut dyn Write, kind: SnapshotKind, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut SnapshotWriter) -> io::Result<()>,
+{
+    let mut writer = SnapshotWriter { writer, checksum: FNV_OFFSET };
+    writer.bytes(SNAPSHOT_MAGIC)?;
+    writer.bytes(&[SNAPSHOT_VERSION, kind as u8, 0, 0])?;
+    write(&mut writer)?;
+    let checksum = writer.checksum;
+    writer.writer.write_all(&checksum.to_le_bytes())
+}
+
+/// Reads the snapshot of an index of `kind` in `bytes`, with `read` reading what the index holds.
+/// Returns an error of kind [ErrorKind::InvalidData] if the bytes aren't a snapshot of that kind of
+/// index, were saved by another version of the layout, or don't match their checksum.
+pub(super) fn read<T, F>(bytes: &[u8], kind: SnapshotKind, read: F) -> io::Result<T>
+where
+    F: FnOnce(&mut SnapshotReader) -> io::Result<T>,
+{
+    if bytes.len() < HEADER_LEN + CHECKSUM_LEN || &bytes[..4] != SNAPSHOT_MAGIC {
+        return Err(invalid_data("not an index snapshot"));
+    }
+    if bytes[4] != SNAPSHOT_VERSION {
+        return Err(invalid_data(SignatureError::UnsupportedVersion { version: bytes[4] }));
+    }
+    if bytes[5] != kind as u8 {
+        return Err(invalid_data("snapshot is of another kind of index"));
+    }
+    let (contents, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    if fnv1a(FNV_OFFSET, contents) != u64::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err(invalid_data("snapshot doesn't match its checksum"));
+    }
+
+    let mut reader = SnapshotReader { bytes: &contents[HEADER_LEN..] };
+    let index = read(&mut reader)?;
+    if !reader.bytes.is_empty() {
+        return Err(corrupt());
+    }
+    Ok(index)
+}
+
+/// Writes the contents of a snapshot, keeping their checksum.
+pub(super) struct SnapshotWriter<'a> {
+    writer: &'a mut dyn Write,
+    checksum: u64,
+}
+
+impl SnapshotWriter<'_> {
+    pub(super) fn i64(&mut self, value: i64) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub(super) fn u64(&mut self, value: u64) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    /// Writes a count or a key, as a `u64`.
+    pub(super) fn usize(&mut self, value: usize) -> io::Result<()> {
+        self.u64(value as u64)
+    }
+
+    /// Writes the number of signatures and their length, then their elements, then whether each
+    /// was removed.
+    pub(super) fn signatures<'a, I>(
+        &mut self,
+        signatures: I,
+        tombstones: &Tombstones,
+    ) -> io::Result<()>
+    where
+        I: ExactSizeIterator<Item = &'a [i8]> + Clone,
+    {
+        self.usize(signatures.len())?;
+        self.usize(signatures.clone().next().map_or(0, <[i8]>::len))?;
+        for signature in signatures {
+            let bytes: Vec<u8> = signature.iter().map(|&element| element as u8).collect();
+            self.bytes(&bytes)?;
+        }
+        let removed: Vec<u8> = tombstones.removed.iter().map(|&removed| removed as u8).collect();
+        self.bytes(&removed)
+    }
+
+    /// Writes the length of `bytes`, then the bytes, e.g. the snapshot of a nested index.
+    pub(super) fn blob(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.usize(bytes.len())?;
+        self.bytes(bytes)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.checksum = fnv1a(self.checksum, bytes);
+        self.writer.write_all(bytes)
+    }
+}
+
+/// Reads the contents of a snapshot, already checked against their checksum.
+pub(super) struct SnapshotReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> SnapshotReader<'a> {
+    pub(super) fn i64(&mut self) -> io::Result<i64> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub(super) fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Reads a count or a key.
+    pub(super) fn usize(&mut self) -> io::Result<usize> {
+        usize::try_from(self.u64()?).map_err(|_| corrupt())
+    }
+
+    /// Reads the number of items of at least `item_len` bytes each that follow, checked against
+    /// the bytes left, so that a corrupt count doesn't allocate gigabytes.
+    pub(super) fn count(&mut self, item_len: usize) -> io::Result<usize> {
+        let count = self.usize()?;
+        match count.checked_mul(item_len) {
+            Some(len) if len <= self.bytes.len() => Ok(count),
+            _ => Err(corrupt()),
+        }
+    }
+
+    /// Reads signatures written by [SnapshotWriter::signatures], and which of them were removed.
+    pub(super) fn signatures(&mut self) -> io::Result<(Vec<Box<[i8]>>, Tombstones)> {
+        let count = self.count(1)?;
+        let length = self.usize()?;
+        let elements = self.take(count.checked_mul(length).ok_or_else(corrupt)?)?;
+        let signatures = (0..count)
+            .map(|key| {
+                let signature = &elements[key * length..(key + 1) * length];
+                signature.iter().map(|&element| element as i8).collect()
+            })
+            .collect();
+
+        let removed: Vec<bool> = self.take(count)?.iter()
+            .map(|&removed| match removed {
+                0 => Ok(false),
+                1 => Ok(true),
+                _ => Err(corrupt()),
+            })
+            .collect::<io::Result<_>>()?;
+        let removed_count = removed.iter().filter(|&&removed| removed).count();
+        Ok((signatures, Tombstones { removed, count: removed_count }))
+    }
+
+    /// Reads bytes written by [SnapshotWriter::blob].
+    pub(super) fn blob(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.count(1)?;
+        self.take(len)
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.bytes.len() {
+            return Err(corrupt());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Continues the 64-bit FNV-1a hash `hash` with `bytes`.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// The error of a snapshot that matches its checksum, but not the layout of the index it's of.
+pub(super) fn corrupt() -> io::Error {
+    invalid_data("snapshot is corrupt")
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error)
+}
@@ -0,0 +1,48 @@
+use crate::{GrayImage, Sample};
+
+/// A summed-area table over a window of a gray image, from which the sum of the gray levels in any
+/// rectangle within the window is found with four lookups, however large the rectangle.
+pub(crate) struct IntegralImage {
+    left: usize,
+    top: usize,
+    /// One more than the window's width, for the row and column of zeros leading the table.
+    stride: usize,
+    sums: Vec<u64>,
+}
+
+impl IntegralImage {
+    /// Builds the table over columns `left..right` and rows `top..bottom` of `pixels`, storing it
+    /// in `sums` so that the allocation of an earlier table can be reused.
+    pub(crate) fn new<T: Sample>(
+        pixels: &GrayImage<T>,
+        (left, right): (usize, usize),
+        (top, bottom): (usize, usize),
+        mut sums: Vec<u64>,
+    ) -> Self {
+        let stride = right - left + 1;
+        sums.clear();
+        sums.resize(stride * (bottom - top + 1), 0);
+        for (y, row) in (top..bottom).map(|y| &pixels.row(y)[left..right]).enumerate() {
+            let (above, current) = sums[y * stride..(y + 2) * stride].split_at_mut(stride);
+            let mut row_sum = 0;
+            for (x, pixel) in row.iter().enumerate() {
+                row_sum += (*pixel).into();
+                current[x + 1] = above[x + 1] + row_sum;
+            }
+        }
+
+        IntegralImage { left, top, stride, sums }
+    }
+
+    /// The sum of the gray levels in columns `left..right` and rows `top..bottom`, which must lie
+    /// within the table's window.
+    pub(crate) fn sum(&self, (left, right): (usize, usize), (top, bottom): (usize, usize)) -> u64 {
+        let at = |x: usize, y: usize| self.sums[(y - self.top) * self.stride + (x - self.left)];
+        at(right, bottom) + at(left, top) - at(left, bottom) - at(right, top)
+    }
+
+    /// Gives back the table's storage for reuse.
+    pub(crate) fn into_table(self) -> Vec<u64> {
+        self.sums
+    }
+}
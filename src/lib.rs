@@ -1,35 +1,404 @@
-use std::cmp::{max, min};
-use std::collections::HashMap;
-
 #[allow(unused_imports)] // It's actually used, I promise
 use num::Signed;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use computer::Scratch;
+use config::FIXED_POINT_BITS;
+use gray::GrayImage;
+use integral::IntegralImage;
+use simd::{dot_product, sum_of_squares};
+
+#[cfg(feature = "arrow")]
+pub use arrow::{
+    signature_array, signature_record_batch, signature_schema, write_parquet, ID_COLUMN,
+    SIGNATURE_COLUMN,
+};
+pub use batch::compute_batch;
+pub use builder::SignatureBuilder;
+pub use cluster::{
+    centroid, cluster_duplicates, cluster_duplicates_with, try_centroid, try_cluster_duplicates,
+    try_cluster_duplicates_with,
+};
+pub use computer::SignatureComputer;
+pub use config::{
+    AlphaMode, Arithmetic, Compat, GrayscaleMode, GridPlacement, NeighborPadding, PixelFormat,
+    SignatureConfig, SmallImagePolicy, Tonemap, CLEAN_RENDER_TOLERANCE, DEFAULT_IDENTICAL_TOLERANCE,
+    NOISY_SCAN_TOLERANCE,
+};
+pub use csv::{export_csv, import_csv};
+pub use elasticsearch::{elasticsearch_document, try_elasticsearch_document};
+pub use error::SignatureError;
+#[cfg(feature = "elasticsearch")]
+pub use es::{EsSignatureStore, DEFAULT_ES_INDEX, DEFAULT_ES_SEARCH_SIZE};
+pub use file::{Records, SignatureFile};
+pub use fixed::{FixedSignature, DEFAULT_SIGNATURE_LENGTH};
+#[cfg(feature = "gpu")]
+pub use gpu::{GpuError, GpuSigner};
+pub use memory::MemorySignatureStore;
+pub use metric::{Cosine, DistanceMetric, Euclidean, Manhattan, NormalizedDistance};
+#[cfg(feature = "mmap")]
+pub use mmap::MappedImage;
+pub use normalized::NormalizedSignature;
+pub use packed::PackedSignature;
+#[cfg(feature = "postgres")]
+pub use pg::{PgSignatureStore, DEFAULT_PG_TABLE};
+pub use policy::{MatchClass, MatchPolicy, DEFAULT_MATCH_THRESHOLD, NEAR_DUPLICATE_THRESHOLD};
+#[cfg(feature = "redis")]
+pub use redis_store::{RedisSignatureStore, DEFAULT_REDIS_PREFIX};
+pub use region::Region;
+pub use signature::{Signature, SignatureParams};
+#[cfg(feature = "rkyv")]
+pub use signature::{ArchivedSignature, ArchivedSignatureParams};
+pub use similarity::{
+    similarities, similarity_at_least, similarity_matrix, top_k, try_similarities,
+    try_similarity_at_least, try_similarity_matrix, try_top_k,
+};
+#[cfg(feature = "sled")]
+pub use sled_store::{SledSignatureStore, DEFAULT_SLED_TREE};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteSignatureStore, DEFAULT_SQLITE_TABLE};
+pub use source::{GrayBuffer, PixelSource, RgbaBuffer};
+pub use store::{SignatureStore, StoreError};
+pub use tiles::{TileMatch, TiledSignature};
+pub use words::{
+    signature_words, try_signature_words, Word, DEFAULT_WORD_COUNT, DEFAULT_WORD_LENGTH,
+};
+pub use yuv::{YuvFrame, YuvPlanes, YuvRange};
 
+#[cfg(feature = "arrow")]
+mod arrow;
+mod batch;
+mod builder;
+mod cluster;
+mod computer;
+mod config;
+mod csv;
+mod elasticsearch;
+mod encoding;
+mod error;
+#[cfg(feature = "elasticsearch")]
+mod es;
+mod file;
+mod fixed;
+#[cfg(feature = "gpu")]
+mod gpu;
+mod goldberg;
+mod gray;
 #[cfg(feature = "img")]
 pub mod image;
-
-const DEFAULT_CROP: f32 = 0.05;
-const DEFAULT_GRID_SIZE: usize = 10;
+pub mod index;
+mod integral;
+mod memory;
+mod metric;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod normalized;
+mod packed;
+#[cfg(feature = "internals")]
+pub mod pipeline;
+#[cfg(feature = "postgres")]
+mod pg;
+mod policy;
+#[cfg(feature = "redis")]
+mod redis_store;
+mod region;
+mod signature;
+mod similarity;
+mod simd;
+#[cfg(feature = "sled")]
+mod sled_store;
+mod source;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod store;
+mod tiles;
+mod words;
+mod yuv;
 
 /// Produces a 544 signed byte signature for a provided image that's encoded as an array of
 /// conceptually grouped RGBA bytes with the provided width. The result is designed to be compared
 /// to other vectors computed by a call to this method using [cosine-similarity(a, b)].
 pub fn get_buffer_signature(rgba_buffer: &[u8], width: usize) -> Vec<i8> {
-    let gray = grayscale_buffer(rgba_buffer, width);
+    SignatureConfig::default().compute(rgba_buffer, width)
+}
 
-    let average_square_width_fn = |width, height| {
-        max(
-            2_usize,
-            (0.5 + min(width, height) as f32 / 20.0).floor() as usize,
-        ) / 2
-    };
+/// Produces a 544 signed byte signature for a provided image that's encoded as an array of 8-bit
+/// gray levels, one byte per pixel, with the provided width. This skips the grayscale conversion
+/// entirely, e.g. for the luma plane of a decoded video frame. The result is designed to be
+/// compared to other vectors computed by a call to this method or [get_buffer_signature] using
+/// [cosine-similarity(a, b)].
+pub fn get_gray_buffer_signature(gray_buffer: &[u8], width: usize) -> Vec<i8> {
+    SignatureConfig::default().compute_gray(gray_buffer, width)
+}
+
+/// A non-panicking version of [get_gray_buffer_signature(gray_buffer, width)].
+pub fn try_get_gray_buffer_signature(
+    gray_buffer: &[u8],
+    width: usize,
+) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute_gray(gray_buffer, width)
+}
+
+/// Produces a 544 signed byte signature for a provided image that's encoded as an array of
+/// conceptually grouped RGB bytes, without an alpha channel, with the provided width. The result is
+/// designed to be compared to other vectors computed by a call to this method or
+/// [get_buffer_signature] using [cosine-similarity(a, b)].
+pub fn get_rgb_buffer_signature(rgb_buffer: &[u8], width: usize) -> Vec<i8> {
+    SignatureConfig::default().compute_rgb(rgb_buffer, width)
+}
+
+/// A non-panicking version of [get_rgb_buffer_signature(rgb_buffer, width)].
+pub fn try_get_rgb_buffer_signature(
+    rgb_buffer: &[u8],
+    width: usize,
+) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute_rgb(rgb_buffer, width)
+}
+
+/// Produces a 544 signed byte signature for a provided image that's encoded as rows of conceptually
+/// grouped RGBA bytes, where each row starts `stride` bytes after the start of the previous one.
+/// This allows signing padded buffers, like GPU readbacks or Windows DIBs, without first packing
+/// the rows together. The result is designed to be compared to other vectors computed by a call to
+/// this method or [get_buffer_signature] using [cosine-similarity(a, b)].
+pub fn get_strided_buffer_signature(
+    rgba_buffer: &[u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> Vec<i8> {
+    SignatureConfig::default().compute_strided(rgba_buffer, width, height, stride)
+}
+
+/// A non-panicking version of [get_strided_buffer_signature(rgba_buffer, width, height, stride)].
+pub fn try_get_strided_buffer_signature(
+    rgba_buffer: &[u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute_strided(rgba_buffer, width, height, stride)
+}
+
+/// Produces a 544 signed byte signature for a region of a provided image that's encoded as an
+/// array of conceptually grouped RGBA bytes with the provided width, such as a face or product an
+/// upstream detector found, given in pixels or relative to the image's size. The result is
+/// designed to be compared to other vectors computed by a call to this method or
+/// [get_buffer_signature] using [cosine-similarity(a, b)].
+pub fn get_region_signature(rgba_buffer: &[u8], width: usize, region: Region) -> Vec<i8> {
+    SignatureConfig::default().compute_region(rgba_buffer, width, region)
+}
+
+/// A non-panicking version of [get_region_signature(rgba_buffer, width, region)].
+pub fn try_get_region_signature(
+    rgba_buffer: &[u8],
+    width: usize,
+    region: Region,
+) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute_region(rgba_buffer, width, region)
+}
+
+/// Splits a provided image that's encoded as an array of conceptually grouped RGBA bytes with the
+/// provided width into `columns` by `rows` even tiles and produces a signature for each, so that
+/// [TiledSignature::matches] can report which tiles of two images match, such as a known photo
+/// within a collage or a screenshot.
+pub fn get_tiled_signature(
+    rgba_buffer: &[u8],
+    width: usize,
+    columns: usize,
+    rows: usize,
+) -> TiledSignature {
+    SignatureConfig::default().compute_tiles(rgba_buffer, width, columns, rows)
+}
+
+/// A non-panicking version of [get_tiled_signature(rgba_buffer, width, columns, rows)].
+pub fn try_get_tiled_signature(
+    rgba_buffer: &[u8],
+    width: usize,
+    columns: usize,
+    rows: usize,
+) -> Result<TiledSignature, SignatureError> {
+    SignatureConfig::default().try_compute_tiles(rgba_buffer, width, columns, rows)
+}
+
+/// Produces a 544 signed byte signature for a provided image that's encoded as an array of
+/// conceptually grouped 16-bit RGBA channels with the provided width. Gray levels keep their full
+/// precision until the grid averages are computed. The result is designed to be compared to other
+/// vectors computed by a call to this method or [get_buffer_signature] using
+/// [cosine-similarity(a, b)].
+pub fn get_buffer_signature_u16(rgba_buffer: &[u16], width: usize) -> Vec<i8> {
+    SignatureConfig::default().compute_u16(rgba_buffer, width)
+}
+
+/// A non-panicking version of [get_buffer_signature_u16(rgba_buffer, width)].
+pub fn try_get_buffer_signature_u16(
+    rgba_buffer: &[u16],
+    width: usize,
+) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute_u16(rgba_buffer, width)
+}
+
+/// Produces a 544 signed byte signature for a provided image that's encoded as an array of 16-bit
+/// gray levels, one per pixel, with the provided width. The result is designed to be compared to
+/// other vectors computed by a call to this method or [get_buffer_signature] using
+/// [cosine-similarity(a, b)].
+pub fn get_gray_buffer_signature_u16(gray_buffer: &[u16], width: usize) -> Vec<i8> {
+    SignatureConfig::default().compute_gray_u16(gray_buffer, width)
+}
+
+/// A non-panicking version of [get_gray_buffer_signature_u16(gray_buffer, width)].
+pub fn try_get_gray_buffer_signature_u16(
+    gray_buffer: &[u16],
+    width: usize,
+) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute_gray_u16(gray_buffer, width)
+}
+
+/// Produces a 544 signed byte signature for a provided high dynamic range image that's encoded as
+/// an array of conceptually grouped linear `f32` RGBA channels with the provided width. Colors are
+/// tonemapped with [Tonemap::Clamp] before the grayscale conversion, see
+/// [SignatureConfig::tonemap] to choose a different operator. The result is designed to be
+/// compared to other vectors computed by a call to this method using [cosine-similarity(a, b)].
+pub fn get_buffer_signature_f32(rgba_buffer: &[f32], width: usize) -> Vec<i8> {
+    SignatureConfig::default().compute_f32(rgba_buffer, width)
+}
+
+/// A non-panicking version of [get_buffer_signature_f32(rgba_buffer, width)].
+pub fn try_get_buffer_signature_f32(
+    rgba_buffer: &[f32],
+    width: usize,
+) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute_f32(rgba_buffer, width)
+}
+
+/// Produces a 544 signed byte signature for a provided high dynamic range image that's encoded as
+/// an array of linear `f32` gray levels, one per pixel, with the provided width. The result is
+/// designed to be compared to other vectors computed by a call to this method using
+/// [cosine-similarity(a, b)].
+pub fn get_gray_buffer_signature_f32(gray_buffer: &[f32], width: usize) -> Vec<i8> {
+    SignatureConfig::default().compute_gray_f32(gray_buffer, width)
+}
 
-    compute_from_gray(gray, DEFAULT_CROP, DEFAULT_GRID_SIZE, average_square_width_fn)
+/// A non-panicking version of [get_gray_buffer_signature_f32(gray_buffer, width)].
+pub fn try_get_gray_buffer_signature_f32(
+    gray_buffer: &[f32],
+    width: usize,
+) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute_gray_f32(gray_buffer, width)
+}
+
+/// Produces a 544 signed byte signature for a frame of YUV video, such as those produced by
+/// hardware decoders, using its luma plane as the grayscale image. The result is designed to be
+/// compared to other vectors computed by a call to this method or [get_buffer_signature] using
+/// [cosine-similarity(a, b)].
+pub fn get_yuv_signature(frame: &YuvFrame) -> Vec<i8> {
+    SignatureConfig::default().compute_yuv(frame)
+}
+
+/// A non-panicking version of [get_yuv_signature(frame)].
+pub fn try_get_yuv_signature(frame: &YuvFrame) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute_yuv(frame)
+}
+
+/// Produces a 544 signed byte signature for a provided image that's encoded as an array of
+/// conceptually grouped bytes in the given channel order, e.g. the BGRA of Windows screen captures
+/// and many GPU readbacks. The result is designed to be compared to other vectors computed by a
+/// call to this method or [get_buffer_signature] using [cosine-similarity(a, b)].
+pub fn get_formatted_buffer_signature(buffer: &[u8], width: usize, format: PixelFormat) -> Vec<i8> {
+    SignatureConfig::new().pixel_format(format).compute(buffer, width)
+}
+
+/// A non-panicking version of [get_formatted_buffer_signature(buffer, width, format)].
+pub fn try_get_formatted_buffer_signature(
+    buffer: &[u8],
+    width: usize,
+    format: PixelFormat,
+) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::new().pixel_format(format).try_compute(buffer, width)
+}
+
+/// Produces a 544 signed byte signature for an image read from a [PixelSource]. The result is
+/// designed to be compared to other vectors computed by a call to this method or
+/// [get_buffer_signature] using [cosine-similarity(a, b)].
+pub fn get_source_signature<S: PixelSource>(source: S) -> Vec<i8> {
+    SignatureConfig::default().compute_source(source)
+}
+
+/// A non-panicking version of [get_source_signature(source)].
+pub fn try_get_source_signature<S: PixelSource>(source: S) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute_source(source)
+}
+
+/// Produces a 544 signed byte signature for a provided image that's encoded as an array of
+/// conceptually grouped RGBA bytes with the provided width, stored inline in a [FixedSignature]
+/// rather than a heap allocated vector. The result is designed to be compared to other vectors
+/// computed by a call to this method or [get_buffer_signature] using [cosine-similarity(a, b)].
+pub fn compute_fixed_signature(rgba_buffer: &[u8], width: usize) -> FixedSignature {
+    SignatureConfig::default().compute_fixed(rgba_buffer, width)
+}
+
+/// A non-panicking version of [compute_fixed_signature(rgba_buffer, width)].
+pub fn try_compute_fixed_signature(
+    rgba_buffer: &[u8],
+    width: usize,
+) -> Result<FixedSignature, SignatureError> {
+    SignatureConfig::default().try_compute_fixed(rgba_buffer, width)
+}
+
+/// The length of signatures computed with the provided grid size:
+/// `8 * (grid_size - 1)^2 - 12 * (grid_size - 3) - 20`, or 544 for the default grid size of 10.
+/// Grids too large for the length to be representable, which no image could be signed with, give
+/// `usize::MAX` rather than overflowing.
+pub const fn signature_length(grid_size: usize) -> usize {
+    if grid_size < 2 {
+        return 0;
+    }
+    let side = grid_size - 1;
+    match side.checked_mul(side) {
+        // `8 * side^2 + 16 >= 12 * grid_size` for every grid of 2 or more.
+        Some(squared) => match squared.checked_mul(8) {
+            Some(length) => length + 16 - 12 * grid_size,
+            None => usize::MAX,
+        },
+        None => usize::MAX,
+    }
+}
+
+/// Writes the 544 signed byte signature of a provided image that's encoded as an array of
+/// conceptually grouped RGBA bytes with the provided width to `out`, e.g. a slot in a table of
+/// signatures, rather than allocating a vector for it. The result is identical to
+/// [get_buffer_signature]'s. See [SignatureComputer::compute_into] to also reuse the memory used
+/// while computing the signature.
+///
+/// Panics if `out` isn't [DEFAULT_SIGNATURE_LENGTH] long or the signature can't be computed, see
+/// [try_get_buffer_signature] for details.
+pub fn compute_signature_into(rgba_buffer: &[u8], width: usize, out: &mut [i8]) {
+    SignatureConfig::default().compute_into(rgba_buffer, width, out)
+}
+
+/// A non-panicking version of [compute_signature_into(rgba_buffer, width, out)].
+pub fn try_compute_signature_into(
+    rgba_buffer: &[u8],
+    width: usize,
+    out: &mut [i8],
+) -> Result<(), SignatureError> {
+    SignatureConfig::default().try_compute_into(rgba_buffer, width, out)
+}
+
+/// A non-panicking version of [get_buffer_signature(rgba_buffer, width)] suitable for untrusted
+/// input. Returns an error if the buffer doesn't hold whole rows of RGBA pixels, or if the image is
+/// too small or too featureless to place the signature grid on.
+pub fn try_get_buffer_signature(
+    rgba_buffer: &[u8],
+    width: usize,
+) -> Result<Vec<i8>, SignatureError> {
+    SignatureConfig::default().try_compute(rgba_buffer, width)
 }
 
 /// Produces a variable length signed byte signature for a provided image, encoded as an array of
 /// conceptually grouped RGBA bytes with the provided width. The result is designed to be compared
 /// to other vectors computed by a call to this method with identical tuning parameters using
-/// [cosine-similarity(a, b)]. `crop` is a value in [0, 0.5] indicating what percentage of the image
+/// [cosine-similarity(a, b)]. `crop` is a value in [0, 0.5) indicating what percentage of the image
 /// to crop on all sides before grid placement. Note that this percentage is based not on the raw
 /// width but a calculation of color density. `grid_size` indicates how many points to place on the
 /// image for measurement in the resulting signature. Changing `grid_size` will alter the length of
@@ -38,6 +407,7 @@ pub fn get_buffer_signature(rgba_buffer: &[u8], width: usize) -> Vec<i8> {
 /// to produce that grid point's brightness value. The paper proposes
 /// `max(2, floor(0.5 + min(cropped_width, cropped_height) / 20))` but provides no information about
 /// how that was chosen.
+#[deprecated(since = "0.2.4", note = "use `SignatureConfig` instead")]
 pub fn get_tuned_buffer_signature(
     rgba_buffer: &[u8],
     width: usize,
@@ -45,53 +415,311 @@ pub fn get_tuned_buffer_signature(
     grid_size: usize,
     average_square_width_fn: fn(width: usize, height: usize) -> usize,
 ) -> Vec<i8> {
-    let gray = grayscale_buffer(rgba_buffer, width);
-    compute_from_gray(gray, crop, grid_size, average_square_width_fn)
+    SignatureConfig::new()
+        .crop(crop)
+        .grid_size(grid_size)
+        .average_square_width_fn(average_square_width_fn)
+        .compute(rgba_buffer, width)
 }
 
 /// Computes the cosine of the angle between two feature vectors. Those vectors must have been both
 /// produced by calls to an un-tuned signature function or identical calls to a tuned version. Per
 /// the source paper and out own research, when using the un-tuned signature calculation a cosine of
 /// 0.6 or greater indicates significant similarity.
-/// If either vector is all zeros,
-pub fn cosine_similarity(a: &Vec<i8>, b: &Vec<i8>) -> f64 {
+/// If either vector is all zeros, the similarity is 0.0 unless both are, in which case it's 1.0;
+/// see [checked_cosine_similarity(a, b)] to tell these cases apart.
+///
+/// Any contiguous storage of signature elements can be compared, e.g. a `Vec<i8>`, a `Box<[i8]>`,
+/// a fixed size array, or a slice into a larger table.
+pub fn cosine_similarity<A: AsRef<[i8]>, B: AsRef<[i8]>>(a: A, b: B) -> f64 {
+    let (a, b) = (a.as_ref(), b.as_ref());
+
     // For our purposes here, unequal lengths are a sign of major issues in client code.
     // One of my favorite professors always said "Crash early, crash often."
     assert_eq!(a.len(), b.len(), "Compared vectors must be of equal length");
 
-    let a_length = vector_length(a);
-    let b_length = vector_length(b);
-    if a_length == 0.0 || b_length == 0.0 {
-        if a_length == 0.0 && b_length == 0.0 {
+    cosine(a, b)
+}
+
+/// Like [cosine_similarity(a, b)], but returns `None` rather than a conventional value when either
+/// vector is all zeros, as the signatures of flat images are, since the angle between them is
+/// undefined.
+pub fn checked_cosine_similarity<A: AsRef<[i8]>, B: AsRef<[i8]>>(a: A, b: B) -> Option<f64> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    assert_eq!(a.len(), b.len(), "Compared vectors must be of equal length");
+
+    if is_flat_signature(a) || is_flat_signature(b) {
+        None
+    } else {
+        Some(cosine(a, b))
+    }
+}
+
+/// Whether a signature is all zeros, meaning the image it was computed from had no detail at the
+/// scale of the grid. See [SignatureConfig::reject_flat_images] to reject such images up front.
+pub fn is_flat_signature<S: AsRef<[i8]>>(signature: S) -> bool {
+    signature.as_ref().iter().all(|&element| element == 0)
+}
+
+/// A non-panicking version of [cosine_similarity(a, b)] which returns an error rather than crashing
+/// when the compared vectors are of different lengths.
+pub fn try_cosine_similarity<A: AsRef<[i8]>, B: AsRef<[i8]>>(
+    a: A,
+    b: B,
+) -> Result<f64, SignatureError> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    if a.len() != b.len() {
+        return Err(SignatureError::MismatchedLengths { left: a.len(), right: b.len() });
+    }
+
+    Ok(cosine(a, b))
+}
+
+/// Computes the normalized distance `‖a - b‖ / (‖a‖ + ‖b‖)` between two feature vectors, as defined
+/// in the source paper. The result is in [0, 1], with 0 meaning identical. The paper suggests that a
+/// distance below 0.6 indicates significant similarity, but it ranks borderline pairs differently
+/// than [cosine_similarity(a, b)], so it's mostly useful for matching the behavior of other
+/// implementations. If both vectors are all zeros, the distance is 0.0.
+pub fn normalized_distance<A: AsRef<[i8]>, B: AsRef<[i8]>>(a: A, b: B) -> f64 {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    assert_eq!(a.len(), b.len(), "Compared vectors must be of equal length");
+
+    normalized(a, b)
+}
+
+/// A non-panicking version of [normalized_distance(a, b)] which returns an error rather than
+/// crashing when the compared vectors are of different lengths.
+pub fn try_normalized_distance<A: AsRef<[i8]>, B: AsRef<[i8]>>(
+    a: A,
+    b: B,
+) -> Result<f64, SignatureError> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    if a.len() != b.len() {
+        return Err(SignatureError::MismatchedLengths { left: a.len(), right: b.len() });
+    }
+
+    Ok(normalized(a, b))
+}
+
+/// Computes the average agreement of two feature vectors position by position, giving positions
+/// where both vectors are 0 a weight of `zero_weight` relative to every other position. Each
+/// position scores `1 - |a - b| / (|a| + |b|)`, so equal values score 1 and values of opposite
+/// sign score 0. The result is in [0, 1], with 1 meaning identical.
+///
+/// Zeros mark neighboring grid points of the same gray level, so images with large flat areas, such
+/// as scanned documents, share many of them whether they match or not. A `zero_weight` of 1.0
+/// counts them like any other agreement, while 0.0 ignores them entirely and only compares the
+/// parts of the images with detail. Shared zeros add nothing to [cosine_similarity(a, b)], but a
+/// zero opposite a non-zero value still counts against it; here such positions simply score 0. If
+/// every position is 0 in both vectors and `zero_weight` is 0.0, the vectors are considered
+/// identical.
+pub fn weighted_similarity<A: AsRef<[i8]>, B: AsRef<[i8]>>(a: A, b: B, zero_weight: f64) -> f64 {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    assert_eq!(a.len(), b.len(), "Compared vectors must be of equal length");
+
+    weighted(a, b, zero_weight)
+}
+
+/// A non-panicking version of [weighted_similarity(a, b, zero_weight)] which returns an error
+/// rather than crashing when the compared vectors are of different lengths.
+pub fn try_weighted_similarity<A: AsRef<[i8]>, B: AsRef<[i8]>>(
+    a: A,
+    b: B,
+    zero_weight: f64,
+) -> Result<f64, SignatureError> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    if a.len() != b.len() {
+        return Err(SignatureError::MismatchedLengths { left: a.len(), right: b.len() });
+    }
+
+    Ok(weighted(a, b, zero_weight))
+}
+
+fn weighted(a: &[i8], b: &[i8], zero_weight: f64) -> f64 {
+    let (mut agreement, mut total) = (0.0, 0.0);
+    for (av, bv) in a.iter().zip(b.iter()) {
+        let (av, bv) = (*av as i32, *bv as i32);
+        if av == 0 && bv == 0 {
+            agreement += zero_weight;
+            total += zero_weight;
+        } else {
+            agreement += 1.0 - (av - bv).abs() as f64 / (av.abs() + bv.abs()) as f64;
+            total += 1.0;
+        }
+    }
+
+    if total == 0.0 {
+        1.0
+    } else {
+        agreement / total
+    }
+}
+
+fn normalized(a: &[i8], b: &[i8]) -> f64 {
+    let norms = vector_length(a) + vector_length(b);
+    if norms == 0.0 {
+        return 0.0;
+    }
+
+    (squared_distance(a, b) as f64).sqrt() / norms
+}
+
+fn cosine(a: &[i8], b: &[i8]) -> f64 {
+    cosine_with_squares(a, b, sum_of_squares(a), sum_of_squares(b))
+}
+
+/// Cosine similarity given each vector's precomputed [sum_of_squares]. Taking a single square root
+/// of the product keeps the similarity of a vector with itself at exactly 1.0.
+fn cosine_with_squares(a: &[i8], b: &[i8], a_squares: i64, b_squares: i64) -> f64 {
+    cosine_from_parts(|| dot_product(a, b), a_squares, b_squares)
+}
+
+/// Cosine similarity from a lazily computed dot product and each vector's sum of squares, shared
+/// by every representation of a signature so they all agree to the last bit.
+fn cosine_from_parts(dot: impl FnOnce() -> i64, a_squares: i64, b_squares: i64) -> f64 {
+    if a_squares == 0 || b_squares == 0 {
+        if a_squares == 0 && b_squares == 0 {
             1.0
         } else {
             0.0
         }
     } else {
-        let dot_product: f64 = a.iter().zip(b.iter())
-            .map(|(av, bv)| *av as f64 * *bv as f64)
-            .sum();
-
-        dot_product / (a_length * b_length)
+        dot() as f64 / (a_squares as f64 * b_squares as f64).sqrt()
     }
 }
 
 fn vector_length(v: &[i8]) -> f64 {
-    v.iter().map(|vi| *vi as i32).map(|vi| (vi * vi) as f64).sum::<f64>().sqrt()
+    (sum_of_squares(v) as f64).sqrt()
+}
+
+// The comparison kernels below widen every element to i64 before any arithmetic, so no
+// intermediate can overflow whatever the signature's values, and sums are exact. Dot products and
+// sums of squares come from `simd`, which makes the same guarantee.
+
+fn squared_distance(a: &[i8], b: &[i8]) -> i64 {
+    a.iter().zip(b.iter()).map(|(av, bv)| (*av as i64 - *bv as i64).pow(2)).sum()
+}
+
+fn manhattan_distance(a: &[i8], b: &[i8]) -> i64 {
+    a.iter().zip(b.iter()).map(|(av, bv)| (*av as i64 - *bv as i64).abs()).sum()
 }
 
 /// Core computation steps of image signatures. Descriptions for each step can be found on the
 /// called functions and are pulled directly from the implemented paper.
-fn compute_from_gray(
-    gray: Vec<Vec<u8>>,
-    crop: f32,
-    grid_size: usize,
-    average_square_width_fn: fn(width: usize, height: usize) -> usize,
-) -> Vec<i8> {
-    let bounds = crop_boundaries(&gray, crop);
-    let points = grid_points(&bounds, grid_size);
-    let averages = grid_averages(gray, points, bounds, average_square_width_fn);
-    compute_signature(averages, grid_size)
+fn compute_from_gray<T: Sample>(
+    gray: &GrayImage<T>,
+    config: &SignatureConfig,
+) -> Result<Vec<i8>, SignatureError> {
+    config.validate()?;
+    let mut signature = vec![0; config.signature_length()];
+    compute_from_gray_with(gray, config, &mut Scratch::default(), &mut signature)?;
+    Ok(signature)
+}
+
+/// Like [compute_from_gray], but keeps intermediate results in `scratch` so that its buffers can be
+/// reused across signatures, and writes the signature to `out`, which must be exactly as long as
+/// the signature.
+fn compute_from_gray_with<T: Sample>(
+    gray: &GrayImage<T>,
+    config: &SignatureConfig,
+    scratch: &mut Scratch,
+    out: &mut [i8],
+) -> Result<(), SignatureError> {
+    config.validate()?;
+    if config.compat != Compat::Native {
+        return Err(SignatureError::CompatUnsupported);
+    }
+    let length = config.signature_length();
+    if out.len() != length {
+        return Err(SignatureError::MismatchedLengths { left: out.len(), right: length });
+    }
+
+    let (width, height) = (gray.width(), gray.height());
+    if width == 0 || height == 0 {
+        return Err(SignatureError::ImageTooSmall { width, height });
+    }
+
+    let factor = config.downscale_factor(width, height);
+    if factor > 1 {
+        return place_and_sign(&gray.downscale(factor), config, scratch, out);
+    }
+    let factor = config.upscale_factor(width, height);
+    if factor > 1 {
+        return place_and_sign(&gray.upscale(factor), config, scratch, out).map_err(|e| match e {
+            SignatureError::ImageTooSmall { .. } => SignatureError::ImageTooSmall { width, height },
+            e => e,
+        });
+    }
+    // Grid points are spaced `side / grid_size` pixels apart, so narrower images would place them
+    // on top of each other.
+    if width <= config.grid_size || height <= config.grid_size {
+        return Err(SignatureError::ImageTooSmall { width, height });
+    }
+    place_and_sign(gray, config, scratch, out)
+}
+
+/// Crops and places the grid on an image, then writes its signature to `out`.
+fn place_and_sign<T: Sample>(
+    gray: &GrayImage<T>,
+    config: &SignatureConfig,
+    scratch: &mut Scratch,
+    out: &mut [i8],
+) -> Result<(), SignatureError> {
+    let (width, height) = (gray.width(), gray.height());
+    let bounds = crop_boundaries(gray, config.crop, config.arithmetic, scratch);
+    let square_edge = place_grid(&bounds, (width, height), config, &mut scratch.points)?;
+
+    let Scratch { points, averages, raw_diffs, sorted_diffs, .. } = scratch;
+    grid_averages(gray, points, square_edge, averages);
+    compute_signature(
+        averages,
+        config.grid_size,
+        config.identical_tolerance,
+        config.neighbor_padding,
+        raw_diffs,
+        sorted_diffs,
+        out,
+    );
+    check_flat(out, config)
+}
+
+/// Returns [SignatureError::FlatImage] for an all-zero signature if the config rejects them.
+fn check_flat(signature: &[i8], config: &SignatureConfig) -> Result<(), SignatureError> {
+    if config.reject_flat_images && is_flat_signature(signature) {
+        Err(SignatureError::FlatImage)
+    } else {
+        Ok(())
+    }
+}
+
+/// Places the grid points on the cropped image and returns the half-width of the square averaged
+/// around each, after checking the square is smaller than the image.
+fn place_grid(
+    bounds: &Bounds,
+    (width, height): (usize, usize),
+    config: &SignatureConfig,
+    points: &mut Vec<(usize, usize)>,
+) -> Result<usize, SignatureError> {
+    if bounds.lower_x >= bounds.upper_x || bounds.lower_y >= bounds.upper_y {
+        return Err(SignatureError::DegenerateCrop);
+    }
+
+    grid_points(bounds, config.grid_size, config.grid_placement, points);
+    let square_edge = (config.average_square_width_fn)(
+        bounds.upper_x - bounds.lower_x,
+        bounds.upper_y - bounds.lower_y,
+    );
+
+    // Each grid point samples the square around it, and each pixel in the square is softened
+    // with its immediate neighbors, so everything within `square_edge + 1` is sampled. Squares
+    // reaching past the border are clamped to it, but one wider than the image would be mostly
+    // made up of copies of its edges.
+    let reach = square_edge.saturating_add(1);
+    if reach.saturating_mul(2) >= width.min(height) {
+        return Err(SignatureError::ImageTooSmall { width, height });
+    }
+
+    Ok(square_edge)
 }
 
 /*
@@ -99,32 +727,340 @@ Step 1.
 "If the image is color, we first convert it to 8-bit grayscale .. Pure white is represented by 255
 and pure black by 0."
  */
-fn grayscale_buffer(rgba_buffer: &[u8], width: usize) -> Vec<Vec<u8>> {
-    let height = (rgba_buffer.len() / 4) / width;
-    let mut result = Vec::with_capacity(height);
-    let mut idx: usize = 0;
-    while idx < rgba_buffer.len() {
-        let mut row = Vec::with_capacity(width);
-        for _ in 0..width {
-            let avg = pixel_gray(
-                rgba_buffer[idx],
-                rgba_buffer[idx + 1],
-                rgba_buffer[idx + 2],
-                rgba_buffer[idx + 3],
-            );
-
-            row.push(avg);
-            idx += 4;
+fn grayscale_buffer<T: Sample>(
+    buffer: &[T],
+    width: usize,
+    format: PixelFormat,
+    config: &SignatureConfig,
+) -> Result<GrayImage<T>, SignatureError> {
+    check_buffer_length(buffer.len(), width, format.channels())?;
+
+    let row_len = format.channels() * width;
+    let height = buffer.len() / row_len;
+    Ok(grayscale_rows(buffer.chunks_exact(row_len), width, height, format, config))
+}
+
+/// Like [grayscale_buffer], but for buffers where each row starts `stride` elements after the
+/// previous one. Padding at the end of each row, including the last, is never read.
+fn grayscale_strided_buffer<T: Sample>(
+    buffer: &[T],
+    width: usize,
+    height: usize,
+    stride: usize,
+    format: PixelFormat,
+    config: &SignatureConfig,
+) -> Result<GrayImage<T>, SignatureError> {
+    let rows = strided_rows(buffer, width, height, stride, format.channels())?;
+    Ok(grayscale_rows(rows, width, height, format, config))
+}
+
+fn gray_strided_rows<T: Sample>(
+    gray_buffer: &[T],
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> Result<GrayImage<T>, SignatureError> {
+    let rows = strided_rows(gray_buffer, width, height, stride, 1)?;
+    Ok(GrayImage::from_raw(rows.flatten().copied().collect(), width, height))
+}
+
+fn strided_rows<T>(
+    buffer: &[T],
+    width: usize,
+    height: usize,
+    stride: usize,
+    channels: usize,
+) -> Result<impl Iterator<Item = &[T]>, SignatureError> {
+    let Some(row_len) = channels.checked_mul(width) else {
+        return Err(SignatureError::BadBufferLength { len: buffer.len(), width });
+    };
+    if stride < row_len {
+        return Err(SignatureError::BadStride { stride, row_len });
+    }
+    // A stride so large that the rows' extent overflows can't fit in any buffer either.
+    let extent = stride.checked_mul(height.saturating_sub(1))
+        .and_then(|extent| extent.checked_add(row_len));
+    if width == 0 || height == 0 || extent.is_none_or(|extent| buffer.len() < extent) {
+        return Err(SignatureError::BadBufferLength { len: buffer.len(), width });
+    }
+
+    Ok((0..height).map(move |y| &buffer[y * stride..y * stride + row_len]))
+}
+
+/// Converts `height` rows of `width` pixels each. `width` must be non-zero.
+fn grayscale_rows<'a, T: Sample + 'a, R: Iterator<Item = &'a [T]>>(
+    rows: R,
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    config: &SignatureConfig,
+) -> GrayImage<T> {
+    let mut data = vec![T::MAX; width * height];
+    grayscale_rows_into(rows, 0, format, config, &mut data);
+    GrayImage::from_raw(data, width, height)
+}
+
+/// Converts rows of pixels into the rows of `gray`, which must be as long as the rows together have
+/// pixels. The first row is row `first_y` of the image.
+fn grayscale_rows_into<'a, T: Sample + 'a, R: Iterator<Item = &'a [T]>>(
+    rows: R,
+    first_y: usize,
+    format: PixelFormat,
+    config: &SignatureConfig,
+    gray: &mut [T],
+) {
+    let mut rows = rows.peekable();
+    let Some(width) = rows.peek().map(|row| row.len() / format.channels()) else {
+        return;
+    };
+    let gray_row = |(y, (gray, row)): (usize, (&mut [T], &[T]))| {
+        T::gray_row(row, first_y + y, format, config, gray)
+    };
+
+    #[cfg(feature = "rayon")]
+    gray.par_chunks_mut(width)
+        .zip(rows.collect::<Vec<_>>())
+        .enumerate()
+        .for_each(gray_row);
+    #[cfg(not(feature = "rayon"))]
+    gray.chunks_mut(width).zip(rows).enumerate().for_each(gray_row);
+}
+
+/// Gray buffers are already in the form the paper asks for and only need copying.
+/// 16-bit gray levels are kept as they are until the grid averages have been computed.
+fn gray_buffer_rows<T: Sample>(
+    gray_buffer: &[T],
+    width: usize,
+) -> Result<GrayImage<T>, SignatureError> {
+    GrayImage::new(gray_buffer.to_vec(), width)
+}
+
+/// Maps float channels into 16-bit ones so they can go through the same pipeline as other deep
+/// inputs. Color channels are tonemapped, while alpha, at `alpha_offset` within each pixel, is only
+/// clamped.
+fn tonemap_buffer(
+    buffer: &[f32],
+    width: usize,
+    channels: usize,
+    alpha_offset: Option<usize>,
+    tonemap: Tonemap,
+) -> Result<Vec<u16>, SignatureError> {
+    check_buffer_length(buffer.len(), width, channels)?;
+
+    Ok(buffer.iter().enumerate().map(|(idx, v)| {
+        let mapped = if alpha_offset == Some(idx % channels) {
+            v.clamp(0.0, 1.0)
+        } else {
+            tonemap.apply(*v)
+        };
+        (mapped * u16::MAX as f32).round() as u16
+    }).collect())
+}
+
+fn check_buffer_length(len: usize, width: usize, channels: usize) -> Result<(), SignatureError> {
+    // A row too long to count in bytes can't be held in any buffer either.
+    let whole_rows = channels.checked_mul(width).is_some_and(|row_len| len.is_multiple_of(row_len));
+    if width == 0 || len == 0 || !whole_rows {
+        Err(SignatureError::BadBufferLength { len, width })
+    } else {
+        Ok(())
+    }
+}
+
+fn pixel_gray<T: Sample>(
+    r: T,
+    g: T,
+    b: T,
+    a: T,
+    (x, y): (usize, usize),
+    config: &SignatureConfig,
+) -> T {
+    if config.arithmetic == Arithmetic::Integer {
+        return pixel_gray_fixed(r, g, b, a, (x, y), config);
+    }
+
+    let luma = pixel_luma::<T>(r.value(), g.value(), b.value(), config.grayscale_mode);
+    let alpha = a.value() / T::MAX.value();
+    let background = match config.alpha_mode {
+        AlphaMode::Ignore => return T::from_value(luma),
+        AlphaMode::Multiply => return T::from_value(luma * alpha),
+        AlphaMode::Background([r, g, b]) => {
+            pixel_luma::<u8>(r as f32, g as f32, b as f32, config.grayscale_mode) / 255.0
+        }
+        AlphaMode::Checkerboard => {
+            if ((x / CHECKER_SIZE) + (y / CHECKER_SIZE)).is_multiple_of(2) { 1.0 } else { 0.8 }
+        }
+    };
+    T::from_value(luma * alpha + background * T::MAX.value() * (1.0 - alpha))
+}
+
+/// [pixel_gray] in integer arithmetic, where each operation truncates the way its floating point
+/// counterpart does.
+fn pixel_gray_fixed<T: Sample>(
+    r: T,
+    g: T,
+    b: T,
+    a: T,
+    (x, y): (usize, usize),
+    config: &SignatureConfig,
+) -> T {
+    let max: u64 = T::MAX.into();
+    let luma = pixel_luma_fixed(r.into(), g.into(), b.into(), max, config.grayscale_mode);
+    let alpha: u64 = a.into();
+    let background = match config.alpha_mode {
+        AlphaMode::Ignore => return T::from_integer(luma),
+        AlphaMode::Multiply => return T::from_integer(luma * alpha / max),
+        AlphaMode::Background([r, g, b]) => {
+            let luma = pixel_luma_fixed(r as u64, g as u64, b as u64, 255, config.grayscale_mode);
+            luma * max / 255
         }
-        result.push(row);
+        AlphaMode::Checkerboard => {
+            let light = ((x / CHECKER_SIZE) + (y / CHECKER_SIZE)).is_multiple_of(2);
+            if light { max } else { max * 4 / 5 }
+        }
+    };
+    T::from_integer((luma * alpha + background * (max - alpha)) / max)
+}
+
+/// The edge length, in pixels, of the squares making up [AlphaMode::Checkerboard].
+const CHECKER_SIZE: usize = 8;
+
+fn pixel_luma<T: Sample>(r: f32, g: f32, b: f32, mode: GrayscaleMode) -> f32 {
+    match mode.weights() {
+        None => ((r + g + b) / 3.0).floor(),
+        Some(weights) => weighted_luma::<T>(r, g, b, weights),
     }
+}
 
-    result
+fn pixel_luma_fixed(r: u64, g: u64, b: u64, max: u64, mode: GrayscaleMode) -> u64 {
+    match mode.fixed_weights() {
+        None => (r + g + b) / 3,
+        Some([r_weight, g_weight, b_weight]) => {
+            let half = 1 << (FIXED_POINT_BITS - 1);
+            ((r * r_weight + g * g_weight + b * b_weight + half) >> FIXED_POINT_BITS).min(max)
+        }
+    }
 }
 
-fn pixel_gray(r: u8, g: u8, b: u8, a: u8) -> u8 {
-    let rgb_avg = (r as u16 + g as u16 + b as u16) / 3;
-    ((rgb_avg as f32) * (a as f32 / 255.0)) as u8
+fn weighted_luma<T: Sample>(
+    r: f32,
+    g: f32,
+    b: f32,
+    [r_weight, g_weight, b_weight]: [f32; 3],
+) -> f32 {
+    (r * r_weight + g * g_weight + b * b_weight)
+        .round()
+        .clamp(0.0, T::MAX.value())
+}
+
+/// Converts the pixels of `row`, in `format`, to gray levels in `gray`, starting from the pixel at
+/// index `start`. This lets vectorized conversions hand off whatever pixels they couldn't process.
+fn fill_gray_row<T: Sample>(
+    row: &[T],
+    y: usize,
+    format: PixelFormat,
+    config: &SignatureConfig,
+    gray: &mut [T],
+    start: usize,
+) {
+    // Pixels without an alpha channel, or whose fourth channel is padding, are treated as opaque.
+    let [r, g, b] = format.color_offsets();
+    let alpha = format.alpha_offset();
+    let pixels = row.chunks_exact(format.channels()).zip(gray.iter_mut()).enumerate();
+    for (x, (p, level)) in pixels.skip(start) {
+        let a = alpha.map_or(T::MAX, |a| p[a]);
+        *level = pixel_gray(p[r], p[g], p[b], a, (x, y), config);
+    }
+}
+
+/// A channel or gray level the pipeline can work with directly, so that deeper inputs keep their
+/// precision until grid averages are reduced to the paper's 0 to 255 scale.
+trait Sample: Copy + Send + Sync + Into<u64> {
+    const MAX: Self;
+
+    fn value(self) -> f32;
+
+    /// Truncates a value in `[0, MAX]` to a sample.
+    fn from_value(value: f32) -> Self;
+
+    /// Converts a value in `[0, MAX]` to a sample.
+    fn from_integer(value: u64) -> Self;
+
+    fn abs_diff(self, other: Self) -> u64;
+
+    /// Reduces the sum of `count` samples to their average as an 8-bit gray level, truncated.
+    fn to_level(sum: u64, count: u64) -> u8;
+
+    /// Converts row `y` of an image in `format` to gray levels in `gray`, which is exactly as long
+    /// as the row has pixels.
+    fn gray_row(
+        row: &[Self],
+        y: usize,
+        format: PixelFormat,
+        config: &SignatureConfig,
+        gray: &mut [Self],
+    ) {
+        fill_gray_row(row, y, format, config, gray, 0);
+    }
+}
+
+impl Sample for u8 {
+    const MAX: Self = u8::MAX;
+
+    fn value(self) -> f32 {
+        self as f32
+    }
+
+    fn from_value(value: f32) -> Self {
+        value as u8
+    }
+
+    fn from_integer(value: u64) -> Self {
+        value as u8
+    }
+
+    fn abs_diff(self, other: Self) -> u64 {
+        u8::abs_diff(self, other) as u64
+    }
+
+    fn to_level(sum: u64, count: u64) -> u8 {
+        (sum / count) as u8
+    }
+
+    fn gray_row(
+        row: &[Self],
+        y: usize,
+        format: PixelFormat,
+        config: &SignatureConfig,
+        gray: &mut [Self],
+    ) {
+        let converted = simd::gray_pixels(row, format, config, gray);
+        fill_gray_row(row, y, format, config, gray, converted);
+    }
+}
+
+impl Sample for u16 {
+    const MAX: Self = u16::MAX;
+
+    fn value(self) -> f32 {
+        self as f32
+    }
+
+    fn from_value(value: f32) -> Self {
+        value as u16
+    }
+
+    fn from_integer(value: u64) -> Self {
+        value as u16
+    }
+
+    fn abs_diff(self, other: Self) -> u64 {
+        u16::abs_diff(self, other) as u64
+    }
+
+    fn to_level(sum: u64, count: u64) -> u8 {
+        (sum / (count * 257)) as u8
+    }
 }
 
 #[derive(Debug)]
@@ -152,20 +1088,52 @@ the 5% and 95% columns, that is, the columns such that 5% of the total sum of di
 lies on either side of the cropped image. We crop the rows of the image the same way"
 (using the sums of original uncropped rows).
  */
-fn crop_boundaries(pixels: &Vec<Vec<u8>>, crop: f32) -> Bounds {
-    let row_diff_sums: Vec<i32> = (0..pixels.len()).map(|y|
-        (1..pixels[y].len()).map(|x|
-            pixels[y][x].abs_diff(pixels[y][x - 1]) as i32).sum()
-    ).collect();
+fn crop_boundaries<T: Sample>(
+    pixels: &GrayImage<T>,
+    crop: f32,
+    arithmetic: Arithmetic,
+    scratch: &mut Scratch,
+) -> Bounds {
+    let (width, height) = (pixels.width(), pixels.height());
+    let row_diff_sum = |y: usize| -> u64 {
+        pixels.row(y).windows(2).map(|pair| pair[1].abs_diff(pair[0])).sum()
+    };
+    // Columns are summed a row at a time to walk the buffer in order.
+    let add_col_diffs = |sums: &mut [u64], y: usize| {
+        let (above, row) = (pixels.row(y - 1), pixels.row(y));
+        for ((sum, above), pixel) in sums.iter_mut().zip(above).zip(row) {
+            *sum += pixel.abs_diff(*above);
+        }
+    };
 
-    let (top, bottom) = get_bounds(row_diff_sums, crop);
+    let row_diff_sums = &mut scratch.row_diff_sums;
+    row_diff_sums.clear();
+    #[cfg(feature = "rayon")]
+    row_diff_sums.par_extend((0..height).into_par_iter().map(row_diff_sum));
+    #[cfg(not(feature = "rayon"))]
+    row_diff_sums.extend((0..height).map(row_diff_sum));
 
-    let col_diff_sums: Vec<i32> = (0..pixels[0].len()).map(|x|
-        (1..pixels.len()).map(|y|
-            pixels[y][x].abs_diff(pixels[y - 1][x]) as i32).sum()
-    ).collect();
+    let (top, bottom) = get_bounds(row_diff_sums, crop, arithmetic);
+
+    let col_diff_sums = &mut scratch.col_diff_sums;
+    col_diff_sums.clear();
+    col_diff_sums.resize(width, 0);
+    #[cfg(feature = "rayon")]
+    {
+        let partial_sums = (1..height).into_par_iter()
+            .fold(|| vec![0; width], |mut sums, y| {
+                add_col_diffs(&mut sums, y);
+                sums
+            })
+            .collect::<Vec<_>>();
+        for sums in partial_sums {
+            col_diff_sums.iter_mut().zip(sums).for_each(|(total, sum)| *total += sum);
+        }
+    }
+    #[cfg(not(feature = "rayon"))]
+    (1..height).for_each(|y| add_col_diffs(col_diff_sums, y));
 
-    let (left, right) = get_bounds(col_diff_sums, crop);
+    let (left, right) = get_bounds(col_diff_sums, crop, arithmetic);
 
     Bounds {
         lower_x: left,
@@ -175,19 +1143,31 @@ fn crop_boundaries(pixels: &Vec<Vec<u8>>, crop: f32) -> Bounds {
     }
 }
 
-fn get_bounds(diff_sums: Vec<i32>, crop: f32) -> (usize, usize) {
-    let total_diff_sum: i32 = diff_sums.iter().sum();
-    let threshold = (total_diff_sum as f32 * crop) as i32;
+fn get_bounds(diff_sums: &[u64], crop: f32, arithmetic: Arithmetic) -> (usize, usize) {
+    let total_diff_sum: u64 = diff_sums.iter().sum();
+    let threshold = match arithmetic {
+        Arithmetic::Float => (total_diff_sum as f32 * crop) as u64,
+        Arithmetic::Integer => {
+            let crop = (crop.clamp(0.0, 1.0) * (1 << FIXED_POINT_BITS) as f32) as u128;
+            ((total_diff_sum as u128 * crop) >> FIXED_POINT_BITS) as u64
+        }
+    };
+    let last = diff_sums.len().saturating_sub(1);
     let mut lower = 0;
-    let mut upper = diff_sums.len() - 1;
     let mut sum = 0;
 
-    while sum < threshold {
+    while sum < threshold && lower < diff_sums.len() {
         sum += diff_sums[lower];
         lower += 1;
     }
+    // When the crop takes in all of the detail, as with high crops or images whose detail is
+    // concentrated in a line or two, the scans would cross. Stopping the second where the first
+    // ended leaves an empty range instead, which placing the grid rejects, and keeps
+    // `upper - lower` from wrapping.
+    let lower = lower.min(last);
+    let mut upper = last;
     sum = 0;
-    while sum < threshold {
+    while sum < threshold && upper > lower {
         sum += diff_sums[upper];
         upper -= 1;
     }
@@ -203,18 +1183,25 @@ Conceptually, we then divide the cropped image into a 10x10 grid of blocks. We r
 grid point to the closest pixel (that is, integer coordinates), thereby setting a 9x9 grid of
 points on the image."
  */
-fn grid_points(bounds: &Bounds, grid_size: usize) -> HashMap<(i8, i8), (usize, usize)> {
+/// Points are returned left-to-right, top-to-bottom, so the point at grid coordinates `(x, y)`, both
+/// starting at 1, is at index `(y - 1) * (grid_size - 1) + (x - 1)`.
+fn grid_points(
+    bounds: &Bounds,
+    grid_size: usize,
+    placement: GridPlacement,
+    points: &mut Vec<(usize, usize)>,
+) {
     let x_width = (bounds.upper_x - bounds.lower_x) / grid_size;
     let y_width = (bounds.upper_y - bounds.lower_y) / grid_size;
+    let (left, top) = match placement {
+        GridPlacement::ImageOrigin => (0, 0),
+        GridPlacement::CropOrigin => (bounds.lower_x, bounds.lower_y),
+    };
 
-    let mut points = HashMap::new();
-    for x in 1..grid_size {
-        for y in 1..grid_size {
-            points.insert((x as i8, y as i8), (x * x_width, y * y_width));
-        }
-    }
-
-    points
+    points.clear();
+    points.extend((1..grid_size).flat_map(|y| {
+        (1..grid_size).map(move |x| (left + x * x_width, top + y * y_width))
+    }));
 }
 
 /*
@@ -224,35 +1211,81 @@ We ran our experiments with P = max(2, floor(0.5 + min(n, m) / 20)) where n and
 of the image in pixels. The squares are slightly soft-edged, meaning that instead of using the
 pixel’s gray levels themselves, we use an average of a 3x3 block centered at that pixel."
  */
-fn grid_averages(
-    pixels: Vec<Vec<u8>>,
-    points: HashMap<(i8, i8), (usize, usize)>,
-    bounds: Bounds,
-    average_square_width_fn: fn(width: usize, height: usize) -> usize,
-) -> HashMap<(i8, i8), u8> {
-    let width = bounds.upper_x - bounds.lower_x;
-    let height = bounds.upper_y - bounds.lower_y;
-    let square_edge = average_square_width_fn(width, height) as i32;
-
-    let mut result = HashMap::new();
-    for (grid_coord, (point_x, point_y)) in points {
-        let mut sum: f32 = 0.0;
-        for delta_x in -square_edge..=square_edge {
-            for delta_y in -square_edge..=square_edge {
-                let average = pixel_average(
-                    &pixels,
-                    (point_x as i32 + delta_x) as usize,
-                    (point_y as i32 + delta_y) as usize,
-                );
-                sum += average;
-            }
-        }
+/// Writes the average of each point's square to `averages`.
+fn grid_averages<T: Sample>(
+    pixels: &GrayImage<T>,
+    points: &[(usize, usize)],
+    square_edge: usize,
+    averages: &mut Vec<u8>,
+) {
+    let reach = square_edge + 1;
+    let (width, height) = (pixels.width(), pixels.height());
+    let in_range = |&(x, y): &(usize, usize)| {
+        x >= reach && y >= reach && x + reach < width && y + reach < height
+    };
+    if !points.iter().all(in_range) {
+        // Squares reaching past the border sample the nearest pixel on it instead, as if the image
+        // were surrounded by copies of its outermost rows and columns.
+        let shifted: Vec<_> = points.iter().map(|(x, y)| (x + reach, y + reach)).collect();
+        return grid_averages(&pixels.pad(reach), &shifted, square_edge, averages);
+    }
 
-        let i = sum / ((square_edge * 2 + 1) * (square_edge * 2 + 1)) as f32;
-        result.insert(grid_coord, i as u8);
+    // Points sharing a row are averaged from one integral image over the band of rows their
+    // squares span, which keeps each average's cost independent of the square's size without
+    // holding a table for the whole image. Each band's table reuses the storage of the last.
+    let band_averages = |table: &mut Vec<u64>, band: &[(usize, usize)]| -> Vec<u8> {
+        let y = band[0].1;
+        let (min_x, max_x) = band.iter().fold((usize::MAX, 0), |(min, max), (x, _)| {
+            (min.min(*x), max.max(*x))
+        });
+        let sums = IntegralImage::new(
+            pixels,
+            (min_x - reach, max_x + reach + 1),
+            (y - reach, y + reach + 1),
+            std::mem::take(table),
+        );
+
+        let band_averages = band.iter()
+            .map(|point| point_average::<T>(&sums, *point, square_edge))
+            .collect();
+        *table = sums.into_table();
+        band_averages
+    };
+
+    averages.clear();
+    let bands = points.chunk_by(|a, b| a.1 == b.1);
+    #[cfg(feature = "rayon")]
+    averages.par_extend(bands.collect::<Vec<_>>()
+        .into_par_iter()
+        .map_init(Vec::new, band_averages)
+        .flatten_iter());
+    #[cfg(not(feature = "rayon"))]
+    {
+        let mut table = Vec::new();
+        averages.extend(bands.flat_map(|band| band_averages(&mut table, band)));
     }
+}
+
+/// The average of the soft-edged square around a point: the square's pixels are each replaced with
+/// the average of the 3x3 block centered on them, which is the same as summing the square shifted
+/// by each of the nine offsets within such a block.
+fn point_average<T: Sample>(
+    sums: &IntegralImage,
+    (point_x, point_y): (usize, usize),
+    square_edge: usize,
+) -> u8 {
+    let side = 2 * square_edge + 1;
+    let (left, top) = (point_x - square_edge - 1, point_y - square_edge - 1);
 
-    result
+    let mut sum = 0;
+    for offset_y in 0..3 {
+        for offset_x in 0..3 {
+            let (x, y) = (left + offset_x, top + offset_y);
+            sum += sums.sum((x, x + side), (y, y + side));
+        }
+    }
+
+    T::to_level(sum, 9 * (side * side) as u64)
 }
 
 /*
@@ -267,49 +1300,69 @@ step is that “same” may be very common in images with flat backgrounds (such
 hence it should not be included in the histogram equalization applied to the other values. Grid
 points in the first or last rows or column have fewer than 8 neighbors..."
 
-(The authors pad missing neighbors with 0's, we just omit them.)
+(The authors pad missing neighbors with 0's, we just omit them unless asked to pad.)
 
 Step 5
 "The signature of an image is simply the concatenation of the 8-element arrays corresponding to the
 grid points, ordered left-to-right, top-to-bottom..."
 */
-const GRID_DELTAS: [(i8, i8); 9] = [
+const GRID_DELTAS: [(isize, isize); 8] = [
     (-1, -1), (0, -1), (1, -1),
-    (-1, 0), (0, 0), (1, 0),
+    (-1, 0), (1, 0),
     (-1, 1), (0, 1), (1, 1)
 ];
 
-fn compute_signature(point_averages: HashMap<(i8, i8), u8>, grid_size: usize) -> Vec<i8> {
-    let mut raw_diffs = Vec::with_capacity(grid_size * grid_size);
-    for grid_y in 1..(grid_size as i8) {
-        for grid_x in 1..(grid_size as i8) {
-            let gray = *point_averages.get(&(grid_x, grid_y)).unwrap();
-            let raw_point_diffs: Vec<i16> = GRID_DELTAS.iter()
+/// `point_averages` holds the `(grid_size - 1)^2` grid point averages ordered left-to-right,
+/// top-to-bottom, as produced by [grid_points]. The signature is written to `out`, which must be
+/// as long as `padding` makes the signatures of `grid_size`.
+fn compute_signature(
+    point_averages: &[u8],
+    grid_size: usize,
+    identical_tolerance: u8,
+    padding: NeighborPadding,
+    raw_diffs: &mut Vec<i16>,
+    sorted_diffs: &mut Vec<i16>,
+    out: &mut [i8],
+) {
+    let points_per_row = grid_size.saturating_sub(1) as isize;
+    let average_at = |x: isize, y: isize| -> Option<u8> {
+        let in_grid = (0..points_per_row).contains(&x) && (0..points_per_row).contains(&y);
+        in_grid.then(|| point_averages[(y * points_per_row + x) as usize])
+    };
+
+    // Each point's diffs are concatenated in order, which is already the order of the signature.
+    raw_diffs.clear();
+    for grid_y in 0..points_per_row {
+        for grid_x in 0..points_per_row {
+            let gray = average_at(grid_x, grid_y).unwrap();
+            raw_diffs.extend(GRID_DELTAS.iter()
                 .filter_map(|(delta_x, delta_y)| {
-                    point_averages.get(&(grid_x + delta_x, grid_y + delta_y))
-                        .map(|other| compute_diff(gray, *other))
-                }).collect();
-            raw_diffs.push(raw_point_diffs)
+                    match average_at(grid_x + delta_x, grid_y + delta_y) {
+                        Some(other) => Some(compute_diff(gray, other, identical_tolerance)),
+                        // Zeros are "same", so they don't shift the thresholds either.
+                        None => (padding == NeighborPadding::Zeros).then_some(0),
+                    }
+                }));
         }
     }
 
-    let (dark_threshold, light_threshold) = get_thresholds(&raw_diffs);
-    raw_diffs.into_iter().flat_map(|neighbors|
-        neighbors.into_iter()
-            .map(|v| {
-                match v {
-                    v if v > 0 => collapse(v, light_threshold),
-                    v if v < 0 => collapse(v, dark_threshold),
-                    _ => 0
-                }
-            })).collect()
+    let (dark_threshold, light_threshold) = get_thresholds(raw_diffs, sorted_diffs);
+    for (out, v) in out.iter_mut().zip(raw_diffs.iter()) {
+        *out = match *v {
+            v if v > 0 => collapse(v, light_threshold),
+            v if v < 0 => collapse(v, dark_threshold),
+            _ => 0
+        };
+    }
 }
 
+fn get_thresholds(raw_diffs: &[i16], sorted: &mut Vec<i16>) -> (i16, i16) {
+    sorted.clear();
+    sorted.extend_from_slice(raw_diffs);
+    sorted.sort_unstable();
 
-fn get_thresholds(raw_diffs: &[Vec<i16>]) -> (i16, i16) {
-    let (dark, light): (Vec<i16>, Vec<i16>) = raw_diffs.iter().flatten()
-        .filter(|d| **d != 0)
-        .partition(|d| **d < 0);
+    let dark = &sorted[..sorted.partition_point(|d| *d < 0)];
+    let light = &sorted[sorted.partition_point(|d| *d <= 0)..];
 
     let dark_threshold = get_median(dark);
     let light_threshold = get_median(light);
@@ -325,9 +1378,9 @@ fn collapse(val: i16, threshold: i16) -> i8 {
     }
 }
 
-fn get_median(mut vec: Vec<i16>) -> i16 {
-    vec.sort();
-    if vec.len() % 2 == 0 {
+/// The median of an already sorted list.
+fn get_median(vec: &[i16]) -> i16 {
+    if vec.len().is_multiple_of(2) {
         if vec.is_empty() {
             0
         } else {
@@ -338,25 +1391,11 @@ fn get_median(mut vec: Vec<i16>) -> i16 {
     }
 }
 
-fn compute_diff(me: u8, other: u8) -> i16 {
+fn compute_diff(me: u8, other: u8, identical_tolerance: u8) -> i16 {
     let raw_result = me as i16 - other as i16;
-    if raw_result.abs() <= 2 {
+    if raw_result.abs() <= identical_tolerance as i16 {
         0
     } else {
         raw_result
     }
 }
-
-const PIXEL_DELTAS: [(i32, i32); 9] = [
-    (-1, -1), (0, -1), (1, -1),
-    (-1, 0), (0, 0), (1, 0),
-    (-1, 1), (0, 1), (1, 1)
-];
-
-fn pixel_average(pixels: &[Vec<u8>], x: usize, y: usize) -> f32 {
-    let sum: f32 = PIXEL_DELTAS.iter().map(|(delta_x, delta_y)| {
-        pixels[(y as i32 + *delta_y) as usize][(x as i32 + *delta_x) as usize] as f32
-    }).sum();
-
-    sum / 9.0
-}
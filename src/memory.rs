@@ -0,0 +1,337 @@
+//! A [SignatureStore] in memory, keeping a payload with each signature, for applications whose
+//! collections fit in RAM.
+
+use std::collections::HashMap;
+
+#[cfg(feature = "img")]
+use image::GenericImageView;
+
+use crate::index::{emptied, Index, LinearIndex};
+use crate::{Signature, SignatureError, SignatureParams, SignatureStore};
+
+/// Signatures by id in memory, each with a metadata payload `M`, such as a path or a database
+/// key, searched through an [Index] `I`.
+///
+/// [MemorySignatureStore::new] compares queries against every signature, which is exact and the
+/// quickest for up to a few thousand signatures; [MemorySignatureStore::with_index] searches
+/// through another [Index], e.g. an [HnswIndex](crate::index::HnswIndex) for millions. Signatures
+/// computed with different [SignatureParams] than a query are never returned as similar to it,
+/// and all signatures must be of the same length.
+///
+/// ```
+/// use image_match::{MemorySignatureStore, SignatureConfig};
+///
+/// # let rgba: Vec<u8> = (0..4 * 120 * 80).map(|i| ((i % 480) * (i / 480) % 251) as u8).collect();
+/// let config = SignatureConfig::default();
+/// let mut store = MemorySignatureStore::new();
+/// store.insert("cat.png", &config.compute_signature(&rgba, 120), "/photos/cat.png");
+///
+/// let query = config.compute_signature(&rgba, 120);
+/// assert_eq!(store.query(&query, 0.6), [("cat.png", 1.0, &"/photos/cat.png")]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MemorySignatureStore<M = (), I = LinearIndex> {
+    index: I,
+    /// The entry of each key of the index, `None` once it's removed or replaced.
+    entries: Vec<Option<Entry<M>>>,
+    keys: HashMap<String, usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Entry<M> {
+    id: String,
+    params: SignatureParams,
+    metadata: M,
+}
+
+impl<M> MemorySignatureStore<M> {
+    /// Creates an empty store, comparing queries against every signature.
+    pub fn new() -> Self {
+        Self::with_index(LinearIndex::new())
+    }
+}
+
+impl<M> Default for MemorySignatureStore<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M, I: Index> MemorySignatureStore<M, I> {
+    /// Creates an empty store searching through `index`, dropping any signatures already in it.
+    pub fn with_index(index: I) -> Self {
+        MemorySignatureStore { index: emptied(index), entries: Vec::new(), keys: HashMap::new() }
+    }
+
+    /// The number of signatures in the store.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the store holds no signatures.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Stores a signature and its metadata under `id`, returning the metadata it replaces, if
+    /// any. Panics if the signature is not of the same length as those already stored, see
+    /// [MemorySignatureStore::try_insert] for a non-panicking version.
+    pub fn insert<S>(&mut self, id: S, signature: &Signature, metadata: M) -> Option<M>
+    where
+        S: Into<String>,
+    {
+        self.try_insert(id, signature, metadata).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [MemorySignatureStore::insert] which returns an error rather
+    /// than crashing when the signature is of a different length than those already stored.
+    pub fn try_insert<S: Into<String>>(
+        &mut self,
+        id: S,
+        signature: &Signature,
+        metadata: M,
+    ) -> Result<Option<M>, SignatureError> {
+        let id = id.into();
+        let key = self.index.try_insert(signature)?;
+        self.entries.resize_with(key + 1, || None);
+        let params = *signature.params();
+        let previous = self.keys.insert(id.clone(), key).and_then(|previous| {
+            self.index.remove(previous);
+            self.entries[previous].take()
+        });
+        self.entries[key] = Some(Entry { id, params, metadata });
+        self.compact_if_sparse();
+        Ok(previous.map(|entry| entry.metadata))
+    }
+
+    /// Removes the signature stored under `id`, returning it and its metadata, if any.
+    pub fn remove(&mut self, id: &str) -> Option<(Signature, M)> {
+        let key = self.keys.remove(id)?;
+        let entry = self.entries[key].take()?;
+        let signature = self.signature(key, &entry);
+        self.index.remove(key);
+        self.compact_if_sparse();
+        Some((signature, entry.metadata))
+    }
+
+    /// Frees the memory of the signatures removed or replaced since the index was last
+    /// [compacted](Index::compact). Done automatically once they outnumber the stored signatures,
+    /// so that a long-running store can take and drop signatures indefinitely.
+    pub fn compact(&mut self) {
+        let keys = self.index.compact();
+        let mut key = 0;
+        self.entries.retain(|_| {
+            key += 1;
+            keys[key - 1].is_some()
+        });
+        for key in self.keys.values_mut() {
+            *key = keys[*key].expect("stored signatures aren't removed from the index");
+        }
+    }
+
+    fn compact_if_sparse(&mut self) {
+        if self.index.removed() > self.index.len() {
+            self.compact();
+        }
+    }
+
+    /// The signature stored under `id` and its metadata, if any.
+    pub fn get(&self, id: &str) -> Option<(Signature, &M)> {
+        let key = *self.keys.get(id)?;
+        let entry = self.entries[key].as_ref()?;
+        Some((self.signature(key, entry), &entry.metadata))
+    }
+
+    /// Finds the stored signatures with a cosine similarity of at least `threshold` to `query`, as
+    /// their ids, similarities, and metadata, most similar first. Ties are broken in favor of the
+    /// smaller id. Panics if the query is not of the same length as the stored signatures, see
+    /// [MemorySignatureStore::try_query] for a non-panicking version.
+    pub fn query(&self, query: &Signature, threshold: f64) -> Vec<(&str, f64, &M)> {
+        self.try_query(query, threshold).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [MemorySignatureStore::query] which returns an error rather
+    /// than crashing when the query is of a different length than the stored signatures.
+    pub fn try_query(
+        &self,
+        query: &Signature,
+        threshold: f64,
+    ) -> Result<Vec<(&str, f64, &M)>, SignatureError> {
+        let matches = self.index.try_find_similar(query, threshold)?;
+        Ok(self.resolve(query, matches))
+    }
+
+    /// The parameters of the stored signatures, those of the one stored first if they differ, or
+    /// `None` if the store is empty.
+    pub fn params(&self) -> Option<SignatureParams> {
+        self.entries.iter().flatten().next().map(|entry| entry.params)
+    }
+
+    /// Computes the signature of an RGBA buffer with the [parameters](MemorySignatureStore::params)
+    /// of the stored signatures, and [queries](MemorySignatureStore::query) the store with it, so
+    /// that it's never computed with other parameters by mistake.
+    ///
+    /// Panics if the signature can't be computed, see [MemorySignatureStore::try_query_buffer]
+    /// for a non-panicking version.
+    pub fn query_buffer(&self, rgba: &[u8], width: usize, threshold: f64) -> Vec<(&str, f64, &M)> {
+        self.try_query_buffer(rgba, width, threshold).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [MemorySignatureStore::query_buffer] which returns an error
+    /// rather than crashing when the signature can't be computed, see
+    /// [SignatureConfig::try_compute](crate::SignatureConfig::try_compute).
+    pub fn try_query_buffer(
+        &self,
+        rgba: &[u8],
+        width: usize,
+        threshold: f64,
+    ) -> Result<Vec<(&str, f64, &M)>, SignatureError> {
+        let Some(params) = self.params() else {
+            return Ok(Vec::new());
+        };
+        let query = params.config().try_compute_signature(rgba, width)?;
+        self.try_query(&query, threshold)
+    }
+
+    /// Computes the signature of an image with the [parameters](MemorySignatureStore::params) of
+    /// the stored signatures, and [queries](MemorySignatureStore::query) the store with it.
+    ///
+    /// Panics if the signature can't be computed, see [MemorySignatureStore::try_query_image] for
+    /// a non-panicking version.
+    #[cfg(feature = "img")]
+    pub fn query_image<G>(&self, img: G, threshold: f64) -> Vec<(&str, f64, &M)>
+    where
+        G: GenericImageView,
+    {
+        self.try_query_image(img, threshold).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [MemorySignatureStore::query_image] which returns an error
+    /// rather than crashing when the image is too small or too featureless to place the signature
+    /// grid on.
+    #[cfg(feature = "img")]
+    pub fn try_query_image<G: GenericImageView>(
+        &self,
+        img: G,
+        threshold: f64,
+    ) -> Result<Vec<(&str, f64, &M)>, SignatureError> {
+        let Some(params) = self.params() else {
+            return Ok(Vec::new());
+        };
+        let query = Signature::new(params, params.config().try_compute_image(img)?);
+        self.try_query(&query, threshold)
+    }
+
+    /// Streams the stored signatures with a cosine similarity of at least `min_similarity` to
+    /// `query`, as their ids, similarities, and metadata, in no particular order, without
+    /// collecting them first, e.g. for the thousands of copies of a common logo or meme. Panics if
+    /// the query is not of the same length as the stored signatures, see
+    /// [MemorySignatureStore::try_find_all_within] for a non-panicking version.
+    pub fn find_all_within(
+        &self,
+        query: &Signature,
+        min_similarity: f64,
+    ) -> impl Iterator<Item = (&str, f64, &M)> {
+        self.try_find_all_within(query, min_similarity).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [MemorySignatureStore::find_all_within] which returns an error
+    /// rather than crashing when the query is of a different length than the stored signatures.
+    pub fn try_find_all_within(
+        &self,
+        query: &Signature,
+        min_similarity: f64,
+    ) -> Result<impl Iterator<Item = (&str, f64, &M)>, SignatureError> {
+        let params = *query.params();
+        let matches = self.index.try_find_all_within(query, min_similarity)?;
+        Ok(matches.filter_map(move |(key, similarity)| {
+            self.resolve_one(&params, key, similarity)
+        }))
+    }
+
+    /// Finds the `k` stored signatures most similar to `query`, most similar first. Ties are
+    /// broken in favor of the smaller id. Panics if the query is not of the same length as the
+    /// stored signatures, see [MemorySignatureStore::try_knn] for a non-panicking version.
+    pub fn knn(&self, query: &Signature, k: usize) -> Vec<(&str, f64, &M)> {
+        self.try_knn(query, k).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [MemorySignatureStore::knn] which returns an error rather than
+    /// crashing when the query is of a different length than the stored signatures.
+    pub fn try_knn(
+        &self,
+        query: &Signature,
+        k: usize,
+    ) -> Result<Vec<(&str, f64, &M)>, SignatureError> {
+        // Signatures computed with other parameters take places in the index's results, so ask
+        // for more until there are enough of the rest, or no more to ask for.
+        let mut wanted = k;
+        loop {
+            let matches = self.index.try_nearest(query, wanted)?;
+            let exhausted = matches.len() < wanted;
+            let mut resolved = self.resolve(query, matches);
+            if resolved.len() >= k || exhausted {
+                resolved.truncate(k);
+                return Ok(resolved);
+            }
+            wanted = wanted.saturating_mul(2);
+        }
+    }
+
+    /// Turns the index's matches into those of the stored signatures compatible with `query`.
+    fn resolve(&self, query: &Signature, matches: Vec<(usize, f64)>) -> Vec<(&str, f64, &M)> {
+        let mut resolved: Vec<(&str, f64, &M)> = matches.into_iter()
+            .filter_map(|(key, similarity)| self.resolve_one(query.params(), key, similarity))
+            .collect();
+        resolved.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        resolved
+    }
+
+    /// The match of the signature with `key`, unless it's been removed or was computed with other
+    /// parameters than `params`.
+    fn resolve_one(
+        &self,
+        params: &SignatureParams,
+        key: usize,
+        similarity: f64,
+    ) -> Option<(&str, f64, &M)> {
+        let entry = self.entries.get(key)?.as_ref()?;
+        entry.params.check_compatible(params).ok()?;
+        Some((entry.id.as_str(), similarity, &entry.metadata))
+    }
+
+    fn signature(&self, key: usize, entry: &Entry<M>) -> Signature {
+        let elements = self.index.get(key).expect("stored signatures are in the index");
+        Signature::new(entry.params, elements.to_vec())
+    }
+}
+
+impl<M: Default, I: Index> SignatureStore for MemorySignatureStore<M, I> {
+    type Error = SignatureError;
+
+    /// Stores a signature under `id` with default metadata.
+    fn insert(&mut self, id: &str, signature: &Signature) -> Result<(), Self::Error> {
+        self.try_insert(id, signature, M::default()).map(|_| ())
+    }
+
+    fn get(&mut self, id: &str) -> Result<Option<Signature>, Self::Error> {
+        Ok(MemorySignatureStore::get(self, id).map(|(signature, _)| signature))
+    }
+
+    fn delete(&mut self, id: &str) -> Result<bool, Self::Error> {
+        Ok(self.remove(id).is_some())
+    }
+
+    fn search_similar(
+        &mut self,
+        query: &Signature,
+        threshold: f64,
+        limit: usize,
+    ) -> Result<Vec<(String, f64)>, Self::Error> {
+        let matches = self.try_query(query, threshold)?;
+        Ok(matches.into_iter()
+            .take(limit)
+            .map(|(id, similarity, _)| (id.to_string(), similarity))
+            .collect())
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{cosine, manhattan_distance, normalized, squared_distance, SignatureError};
+
+/// A way of measuring how far apart two signatures are, so that index structures and matching code
+/// can be written once and used with any metric. Smaller distances mean more similar images, and
+/// identical signatures are always at distance 0.
+pub trait DistanceMetric {
+    /// The distance between `a` and `b`, which must be of equal length. Implementations panic if
+    /// they aren't, see [DistanceMetric::try_distance] for a non-panicking version.
+    fn distance(&self, a: &[i8], b: &[i8]) -> f64;
+
+    /// A non-panicking version of [DistanceMetric::distance] which returns an error rather than
+    /// crashing when the compared vectors are of different lengths.
+    fn try_distance(&self, a: &[i8], b: &[i8]) -> Result<f64, SignatureError> {
+        if a.len() != b.len() {
+            return Err(SignatureError::MismatchedLengths { left: a.len(), right: b.len() });
+        }
+
+        Ok(self.distance(a, b))
+    }
+}
+
+/// One minus [cosine_similarity(a, b)], in [0, 2].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Cosine;
+
+/// The straight-line distance between two signatures, `‖a - b‖`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Euclidean;
+
+/// The sum of the absolute differences between two signatures.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Manhattan;
+
+/// The source paper's [normalized_distance(a, b)], in [0, 1].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NormalizedDistance;
+
+impl DistanceMetric for Cosine {
+    fn distance(&self, a: &[i8], b: &[i8]) -> f64 {
+        assert_eq!(a.len(), b.len(), "Compared vectors must be of equal length");
+        (1.0 - cosine(a, b)).max(0.0)
+    }
+}
+
+impl DistanceMetric for Euclidean {
+    fn distance(&self, a: &[i8], b: &[i8]) -> f64 {
+        assert_eq!(a.len(), b.len(), "Compared vectors must be of equal length");
+        (squared_distance(a, b) as f64).sqrt()
+    }
+}
+
+impl DistanceMetric for Manhattan {
+    fn distance(&self, a: &[i8], b: &[i8]) -> f64 {
+        assert_eq!(a.len(), b.len(), "Compared vectors must be of equal length");
+        manhattan_distance(a, b) as f64
+    }
+}
+
+impl DistanceMetric for NormalizedDistance {
+    fn distance(&self, a: &[i8], b: &[i8]) -> f64 {
+        assert_eq!(a.len(), b.len(), "Compared vectors must be of equal length");
+        normalized(a, b)
+    }
+}
+
+impl<M: DistanceMetric + ?Sized> DistanceMetric for &M {
+    fn distance(&self, a: &[i8], b: &[i8]) -> f64 {
+        (**self).distance(a, b)
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::{
+    compute_from_gray, gray_buffer_rows, grayscale_buffer, PixelFormat, SignatureConfig,
+    SignatureError,
+};
+
+/// A raw dump of pixels in a file, memory-mapped rather than read, so that signing it keeps its
+/// pixels in the page cache instead of anonymous memory. Only the gray image, a quarter the size
+/// of RGBA pixels, is ever allocated, which allows signing decoded frames larger than RAM.
+#[derive(Debug)]
+pub struct MappedImage {
+    map: Mmap,
+    width: usize,
+    /// The layout of color pixels, or `None` for one byte of gray per pixel.
+    format: Option<PixelFormat>,
+}
+
+impl MappedImage {
+    /// Maps a file of color pixels in `format`, with the provided width.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it's mapped, by this process or any other.
+    /// Doing so is undefined behavior, see [memmap2::Mmap::map].
+    pub unsafe fn open<P: AsRef<Path>>(
+        path: P,
+        width: usize,
+        format: PixelFormat,
+    ) -> io::Result<Self> {
+        Self::map(path.as_ref(), width, Some(format))
+    }
+
+    /// Maps a file of 8-bit gray levels, one byte per pixel, with the provided width.
+    ///
+    /// # Safety
+    ///
+    /// The same as for [MappedImage::open].
+    pub unsafe fn open_gray<P: AsRef<Path>>(path: P, width: usize) -> io::Result<Self> {
+        Self::map(path.as_ref(), width, None)
+    }
+
+    unsafe fn map(path: &Path, width: usize, format: Option<PixelFormat>) -> io::Result<Self> {
+        let map = Mmap::map(&File::open(path)?)?;
+        // Pixels are read once, front to back. The advice is only a hint, so failing to give it
+        // changes nothing.
+        #[cfg(unix)]
+        let _ = map.advise(memmap2::Advice::Sequential);
+        Ok(MappedImage { map, width, format })
+    }
+
+    /// The mapped bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// The width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+}
+
+impl SignatureConfig {
+    /// Produces a signature for a memory-mapped image. The image's own pixel format is used in
+    /// place of the configured one.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_mapped] for
+    /// details.
+    pub fn compute_mapped(&self, image: &MappedImage) -> Vec<i8> {
+        self.try_compute_mapped(image).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_mapped]. Returns an error if the file
+    /// doesn't hold whole rows of pixels, or if the image is too small or too featureless to place
+    /// the signature grid on.
+    pub fn try_compute_mapped(&self, image: &MappedImage) -> Result<Vec<i8>, SignatureError> {
+        let gray = match image.format {
+            Some(format) => grayscale_buffer(image.bytes(), image.width, format, self)?,
+            None => gray_buffer_rows(image.bytes(), image.width)?,
+        };
+        compute_from_gray(&gray, self)
+    }
+}
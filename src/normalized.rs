@@ -0,0 +1,78 @@
+use std::ops::Deref;
+
+use crate::{cosine_with_squares, sum_of_squares, SignatureError};
+
+/// A signature stored along with its precomputed vector length, so comparing it against many
+/// others doesn't repeat that work for both operands on every call. Useful for signatures kept
+/// around in an index and compared over and over. Similarities are identical to those of
+/// [cosine-similarity(a, b)].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NormalizedSignature {
+    elements: Vec<i8>,
+    squares: i64,
+}
+
+impl NormalizedSignature {
+    /// Wraps a signature, computing its vector length once.
+    pub fn new(elements: Vec<i8>) -> Self {
+        let squares = sum_of_squares(&elements);
+        NormalizedSignature { elements, squares }
+    }
+
+    /// The signature's vector length, the square root of the sum of its squared elements.
+    pub fn norm(&self) -> f64 {
+        (self.squares as f64).sqrt()
+    }
+
+    /// Computes the cosine similarity of this signature and `other`.
+    ///
+    /// Panics if the signatures are of different lengths, see
+    /// [NormalizedSignature::try_similarity] for a non-panicking version.
+    pub fn similarity(&self, other: &NormalizedSignature) -> f64 {
+        self.try_similarity(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [NormalizedSignature::similarity] which returns an error rather
+    /// than crashing when the signatures are of different lengths.
+    pub fn try_similarity(&self, other: &NormalizedSignature) -> Result<f64, SignatureError> {
+        if self.len() != other.len() {
+            return Err(SignatureError::MismatchedLengths {
+                left: self.len(),
+                right: other.len(),
+            });
+        }
+
+        Ok(cosine_with_squares(&self.elements, &other.elements, self.squares, other.squares))
+    }
+
+    /// Unwraps the signature's elements.
+    pub fn into_inner(self) -> Vec<i8> {
+        self.elements
+    }
+}
+
+impl Deref for NormalizedSignature {
+    type Target = [i8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.elements
+    }
+}
+
+impl AsRef<[i8]> for NormalizedSignature {
+    fn as_ref(&self) -> &[i8] {
+        &self.elements
+    }
+}
+
+impl From<Vec<i8>> for NormalizedSignature {
+    fn from(elements: Vec<i8>) -> Self {
+        NormalizedSignature::new(elements)
+    }
+}
+
+impl From<&[i8]> for NormalizedSignature {
+    fn from(elements: &[i8]) -> Self {
+        NormalizedSignature::new(elements.to_vec())
+    }
+}
@@ -0,0 +1,116 @@
+//! A [SignatureStore] in PostgreSQL, for services sharing one collection of signatures.
+
+use postgres::Client;
+
+use crate::store::{quote_identifier, rank, store_words};
+use crate::{Signature, SignatureStore, StoreError};
+
+/// The table [PgSignatureStore::new] keeps signatures in.
+pub const DEFAULT_PG_TABLE: &str = "image_signatures";
+
+/// A [SignatureStore] in PostgreSQL tables, so that several processes can share and search one
+/// collection of signatures.
+///
+/// Signatures are kept in a table of ids and their [Signature::encode] containers, and their
+/// words in a second table, named after the first with a `_words` suffix, with a row per word and
+/// an index on the words. [SignatureStore::search_similar] looks up the signatures sharing a word
+/// with the query in the database, then rescores them with cosine similarity here.
+pub struct PgSignatureStore {
+    client: Client,
+    table: String,
+    words: String,
+}
+
+impl PgSignatureStore {
+    /// Stores signatures in the [DEFAULT_PG_TABLE] of the database `client` is connected to,
+    /// creating the tables if they don't exist.
+    pub fn new(client: Client) -> Result<Self, postgres::Error> {
+        Self::with_table(client, DEFAULT_PG_TABLE)
+    }
+
+    /// Stores signatures in `table` and `{table}_words`, creating them if they don't exist, e.g.
+    /// to keep signatures computed with different parameters apart.
+    pub fn with_table(mut client: Client, table: &str) -> Result<Self, postgres::Error> {
+        let (table, words, index) = (
+            quote_identifier(table),
+            quote_identifier(&format!("{}_words", table)),
+            quote_identifier(&format!("{}_words_lookup", table)),
+        );
+        client.batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                id TEXT PRIMARY KEY,
+                signature BYTEA NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS {words} (
+                id TEXT NOT NULL REFERENCES {table} (id) ON DELETE CASCADE,
+                word_index INTEGER NOT NULL,
+                word BIGINT NOT NULL,
+                PRIMARY KEY (id, word_index)
+            );
+            CREATE INDEX IF NOT EXISTS {index} ON {words} (word_index, word);"
+        ))?;
+        Ok(PgSignatureStore { client, table, words })
+    }
+
+    /// Releases the connection.
+    pub fn into_client(self) -> Client {
+        self.client
+    }
+}
+
+impl SignatureStore for PgSignatureStore {
+    type Error = StoreError<postgres::Error>;
+
+    fn insert(&mut self, id: &str, signature: &Signature) -> Result<(), Self::Error> {
+        let (indices, words): (Vec<i32>, Vec<i64>) = store_words(signature).into_iter().unzip();
+        let mut transaction = self.client.transaction().map_err(StoreError::Backend)?;
+        let insert = format!(
+            "INSERT INTO {} (id, signature) VALUES ($1, $2)
+            ON CONFLICT (id) DO UPDATE SET signature = EXCLUDED.signature",
+            self.table
+        );
+        transaction.execute(&insert, &[&id, &signature.encode()]).map_err(StoreError::Backend)?;
+        let delete = format!("DELETE FROM {} WHERE id = $1", self.words);
+        transaction.execute(&delete, &[&id]).map_err(StoreError::Backend)?;
+        let insert_words = format!(
+            "INSERT INTO {} (id, word_index, word)
+            SELECT $1, * FROM UNNEST($2::INTEGER[], $3::BIGINT[])",
+            self.words
+        );
+        transaction.execute(&insert_words, &[&id, &indices, &words]).map_err(StoreError::Backend)?;
+        transaction.commit().map_err(StoreError::Backend)
+    }
+
+    fn get(&mut self, id: &str) -> Result<Option<Signature>, Self::Error> {
+        let select = format!("SELECT signature FROM {} WHERE id = $1", self.table);
+        let row = self.client.query_opt(&select, &[&id]).map_err(StoreError::Backend)?;
+        Ok(row.map(|row| Signature::decode(row.get(0))).transpose()?)
+    }
+
+    fn delete(&mut self, id: &str) -> Result<bool, Self::Error> {
+        let delete = format!("DELETE FROM {} WHERE id = $1", self.table);
+        Ok(self.client.execute(&delete, &[&id]).map_err(StoreError::Backend)? > 0)
+    }
+
+    fn search_similar(
+        &mut self,
+        query: &Signature,
+        threshold: f64,
+        limit: usize,
+    ) -> Result<Vec<(String, f64)>, Self::Error> {
+        let (indices, words): (Vec<i32>, Vec<i64>) = store_words(query).into_iter().unzip();
+        let select = format!(
+            "SELECT id, signature FROM {} WHERE id IN (
+                SELECT w.id FROM {} w
+                JOIN UNNEST($1::INTEGER[], $2::BIGINT[]) AS q (word_index, word)
+                ON w.word_index = q.word_index AND w.word = q.word
+            )",
+            self.table, self.words
+        );
+        let rows = self.client.query(&select, &[&indices, &words]).map_err(StoreError::Backend)?;
+        let candidates = rows.iter()
+            .map(|row| Ok((row.get(0), Signature::decode(row.get(1))?)))
+            .collect::<Result<Vec<_>, Self::Error>>()?;
+        Ok(rank(query, candidates, threshold, limit))
+    }
+}
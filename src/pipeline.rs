@@ -0,0 +1,119 @@
+//! The individual steps of signature computation, for inspecting how an image is cropped and
+//! measured while tuning parameters. Running the steps in order reproduces
+//! [SignatureConfig::compute]:
+//!
+//! ```
+//! use image_match::{GridPlacement, NeighborPadding, SignatureConfig};
+//! use image_match::pipeline::*;
+//!
+//! # let rgba: Vec<u8> = (0..4 * 120 * 80).map(|i| ((i % 480) * (i / 480) % 251) as u8).collect();
+//! let config = SignatureConfig::default();
+//! let gray = grayscale(&rgba, 120, &config).unwrap();
+//! let bounds = crop_boundaries(&gray, 0.05);
+//! let points = grid_points(&bounds, 10, GridPlacement::ImageOrigin);
+//! let averages = grid_averages(&gray, &points, square_edge(&bounds, &config));
+//! let signature = signature_from_averages(&averages, 10, 2, NeighborPadding::Omit);
+//!
+//! assert_eq!(signature, config.compute(&rgba, 120));
+//! ```
+//!
+//! Only available with the `internals` feature. These functions mirror the crate's internals and
+//! may change between releases without notice.
+
+use crate::{
+    Arithmetic, Bounds, GridPlacement, NeighborPadding, Scratch, SignatureConfig, SignatureError,
+};
+
+pub use crate::gray::GrayImage;
+
+/// The columns and rows delimiting the region of the image left after cropping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CropBounds {
+    pub lower_x: usize,
+    pub upper_x: usize,
+    pub lower_y: usize,
+    pub upper_y: usize,
+}
+
+/// Step 1: converts a buffer laid out per the config's [crate::PixelFormat] into a gray image.
+pub fn grayscale(
+    buffer: &[u8],
+    width: usize,
+    config: &SignatureConfig,
+) -> Result<GrayImage, SignatureError> {
+    crate::grayscale_buffer(buffer, width, config.pixel_format, config)
+}
+
+/// Step 2, part 1: finds the columns and rows such that `crop` of the total sum of differences
+/// between adjacent pixels lies on either side.
+pub fn crop_boundaries(gray: &GrayImage, crop: f32) -> CropBounds {
+    let bounds = crate::crop_boundaries(gray, crop, Arithmetic::Float, &mut Scratch::default());
+    CropBounds {
+        lower_x: bounds.lower_x,
+        upper_x: bounds.upper_x,
+        lower_y: bounds.lower_y,
+        upper_y: bounds.upper_y,
+    }
+}
+
+/// Step 2, part 2: places `(grid_size - 1)^2` points on the cropped image. Points are returned as
+/// pixel coordinates, ordered left-to-right, top-to-bottom.
+pub fn grid_points(
+    bounds: &CropBounds,
+    grid_size: usize,
+    placement: GridPlacement,
+) -> Vec<(usize, usize)> {
+    let mut points = Vec::new();
+    crate::grid_points(&to_bounds(bounds), grid_size, placement, &mut points);
+    points
+}
+
+/// The half-width of the square averaged around each grid point, as chosen by the config's
+/// average square width function for the cropped image.
+pub fn square_edge(bounds: &CropBounds, config: &SignatureConfig) -> usize {
+    (config.average_square_width_fn)(
+        bounds.upper_x - bounds.lower_x,
+        bounds.upper_y - bounds.lower_y,
+    )
+}
+
+/// Step 3: the average gray level of the soft-edged square around each point, in the same order as
+/// `points`. Squares reaching past the border sample the nearest pixel on it instead.
+pub fn grid_averages(gray: &GrayImage, points: &[(usize, usize)], square_edge: usize) -> Vec<u8> {
+    let mut averages = Vec::new();
+    crate::grid_averages(gray, points, square_edge, &mut averages);
+    averages
+}
+
+/// Steps 4 and 5: compares each grid point's average with its neighbors and concatenates the
+/// quantized differences. `averages` must hold `(grid_size - 1)^2` values ordered left-to-right,
+/// top-to-bottom.
+pub fn signature_from_averages(
+    averages: &[u8],
+    grid_size: usize,
+    identical_tolerance: u8,
+    padding: NeighborPadding,
+) -> Vec<i8> {
+    let (mut raw_diffs, mut sorted_diffs) = (Vec::new(), Vec::new());
+    let config = SignatureConfig::new().grid_size(grid_size).neighbor_padding(padding);
+    let mut signature = vec![0; config.signature_length()];
+    crate::compute_signature(
+        averages,
+        grid_size,
+        identical_tolerance,
+        padding,
+        &mut raw_diffs,
+        &mut sorted_diffs,
+        &mut signature,
+    );
+    signature
+}
+
+fn to_bounds(bounds: &CropBounds) -> Bounds {
+    Bounds {
+        lower_x: bounds.lower_x,
+        upper_x: bounds.upper_x,
+        lower_y: bounds.lower_y,
+        upper_y: bounds.upper_y,
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{cosine_similarity, SignatureError};
+
+/// The similarity above which two images are considered likely matches, per the source paper and
+/// [this research](https://github.com/alt-text-org/image-algo-testing).
+pub const DEFAULT_MATCH_THRESHOLD: f64 = 0.6;
+
+/// The similarity above which two images are considered near duplicates, e.g. the same photo
+/// recompressed, resized, or with minor edits.
+pub const NEAR_DUPLICATE_THRESHOLD: f64 = 0.9;
+
+/// How closely two signatures resemble each other, from most to least similar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatchClass {
+    /// The signatures are exactly equal.
+    Identical,
+    /// The similarity is at least [NEAR_DUPLICATE_THRESHOLD].
+    NearDuplicate,
+    /// The similarity is at least the policy's threshold.
+    Similar,
+    /// The similarity is below the policy's threshold, or the signatures weren't computed with the
+    /// same parameters.
+    Different,
+}
+
+/// Decides whether two signatures should be considered a match. The default threshold of
+/// [DEFAULT_MATCH_THRESHOLD] suits signatures computed with default settings; if
+/// [crate::SignatureConfig] is used for tuning, additional research will likely be needed to
+/// assess a new cutoff.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchPolicy {
+    /// The cosine similarity at or above which two signatures match.
+    pub threshold: f64,
+    /// Signatures computed with different parameters can't be meaningfully compared. When set,
+    /// such pairs are [MatchClass::Different]; otherwise comparing them panics like
+    /// [cosine_similarity(a, b)].
+    pub require_same_params: bool,
+}
+
+impl MatchPolicy {
+    /// A policy matching at the provided threshold.
+    pub fn new(threshold: f64) -> Self {
+        MatchPolicy { threshold, ..MatchPolicy::default() }
+    }
+
+    /// Whether `a` and `b` are at least [MatchClass::Similar] under this policy.
+    pub fn is_match<A: AsRef<[i8]>, B: AsRef<[i8]>>(&self, a: A, b: B) -> bool {
+        self.classify(a, b) != MatchClass::Different
+    }
+
+    /// Classifies how closely `a` and `b` resemble each other under this policy. A threshold above
+    /// [NEAR_DUPLICATE_THRESHOLD] only narrows [MatchClass::Similar], so near duplicates below the
+    /// threshold are still classified as different.
+    pub fn classify<A: AsRef<[i8]>, B: AsRef<[i8]>>(&self, a: A, b: B) -> MatchClass {
+        let (a, b) = (a.as_ref(), b.as_ref());
+        if a.len() != b.len() && self.require_same_params {
+            return MatchClass::Different;
+        }
+        if a == b {
+            return MatchClass::Identical;
+        }
+
+        let similarity = cosine_similarity(a, b);
+        if similarity < self.threshold {
+            MatchClass::Different
+        } else if similarity >= NEAR_DUPLICATE_THRESHOLD {
+            MatchClass::NearDuplicate
+        } else {
+            MatchClass::Similar
+        }
+    }
+
+    /// A non-panicking version of [MatchPolicy::classify] which returns an error rather than
+    /// crashing when signatures of different lengths are compared and `require_same_params` isn't
+    /// set.
+    pub fn try_classify<A: AsRef<[i8]>, B: AsRef<[i8]>>(
+        &self,
+        a: A,
+        b: B,
+    ) -> Result<MatchClass, SignatureError> {
+        let (a, b) = (a.as_ref(), b.as_ref());
+        if a.len() != b.len() && !self.require_same_params {
+            return Err(SignatureError::MismatchedLengths { left: a.len(), right: b.len() });
+        }
+
+        Ok(self.classify(a, b))
+    }
+}
+
+impl Default for MatchPolicy {
+    fn default() -> Self {
+        MatchPolicy { threshold: DEFAULT_MATCH_THRESHOLD, require_same_params: true }
+    }
+}
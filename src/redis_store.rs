@@ -0,0 +1,156 @@
+//! A [SignatureStore] in Redis, for matcher instances sharing low-latency candidate lookups.
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use redis::{cmd, pipe, Connection, Pipeline};
+
+use crate::store::{rank, store_words};
+use crate::{Signature, SignatureStore, StoreError};
+
+/// The prefix of the keys [RedisSignatureStore::new] stores signatures under.
+pub const DEFAULT_REDIS_PREFIX: &str = "image_signatures";
+
+/// A [SignatureStore] in Redis, so that several matcher instances can share candidate lookups
+/// with low latency, optionally expiring signatures after a while.
+///
+/// A signature is stored as its [Signature::encode] container under `{prefix}:sig:{id}`, and each
+/// of its words as a set of ids under `{prefix}:word:{position}:{word}`. Storing and removing a
+/// signature updates its key and word sets in one transaction. Ids left in word sets by expired
+/// signatures are removed from the sets searches find them in.
+pub struct RedisSignatureStore {
+    connection: Connection,
+    prefix: String,
+    ttl: Option<Duration>,
+}
+
+impl RedisSignatureStore {
+    /// Stores signatures under keys starting with [DEFAULT_REDIS_PREFIX].
+    pub fn new(connection: Connection) -> Self {
+        Self::with_prefix(connection, DEFAULT_REDIS_PREFIX)
+    }
+
+    /// Stores signatures under keys starting with `prefix`, e.g. to keep signatures computed with
+    /// different parameters apart.
+    pub fn with_prefix(connection: Connection, prefix: &str) -> Self {
+        RedisSignatureStore { connection, prefix: prefix.to_string(), ttl: None }
+    }
+
+    /// Expires signatures `ttl` after they're inserted, rounded up to whole milliseconds. Word
+    /// sets expire `ttl` after the last signature was added to them. Signatures don't expire by
+    /// default.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Releases the connection.
+    pub fn into_connection(self) -> Connection {
+        self.connection
+    }
+
+    fn signature_key(&self, id: &str) -> String {
+        format!("{}:sig:{}", self.prefix, id)
+    }
+
+    fn word_key(&self, index: i32, word: i64) -> String {
+        format!("{}:word:{}:{}", self.prefix, index, word)
+    }
+
+    /// Adds the commands removing `id` from the word sets of the signature stored under it, if
+    /// any, to `pipeline`. Returns whether there was a signature.
+    fn remove_words(
+        &mut self,
+        pipeline: &mut Pipeline,
+        id: &str,
+    ) -> Result<bool, StoreError<redis::RedisError>> {
+        let Some(previous) = self.get(id)? else {
+            return Ok(false);
+        };
+        for (index, word) in store_words(&previous) {
+            pipeline.cmd("SREM").arg(self.word_key(index, word)).arg(id).ignore();
+        }
+        Ok(true)
+    }
+}
+
+impl SignatureStore for RedisSignatureStore {
+    type Error = StoreError<redis::RedisError>;
+
+    fn insert(&mut self, id: &str, signature: &Signature) -> Result<(), Self::Error> {
+        let mut pipeline = pipe();
+        pipeline.atomic();
+        self.remove_words(&mut pipeline, id)?;
+
+        let ttl = self.ttl.map(|ttl| ttl.as_nanos().div_ceil(1_000_000).max(1) as u64);
+        let set = pipeline.cmd("SET").arg(self.signature_key(id)).arg(signature.encode());
+        if let Some(ttl) = ttl {
+            set.arg("PX").arg(ttl);
+        }
+        set.ignore();
+        for (index, word) in store_words(signature) {
+            let key = self.word_key(index, word);
+            pipeline.cmd("SADD").arg(&key).arg(id).ignore();
+            if let Some(ttl) = ttl {
+                pipeline.cmd("PEXPIRE").arg(&key).arg(ttl).ignore();
+            }
+        }
+        pipeline.exec(&mut self.connection).map_err(StoreError::Backend)
+    }
+
+    fn get(&mut self, id: &str) -> Result<Option<Signature>, Self::Error> {
+        let container: Option<Vec<u8>> = cmd("GET").arg(self.signature_key(id))
+            .query(&mut self.connection)
+            .map_err(StoreError::Backend)?;
+        Ok(container.map(|container| Signature::decode(&container)).transpose()?)
+    }
+
+    fn delete(&mut self, id: &str) -> Result<bool, Self::Error> {
+        let mut pipeline = pipe();
+        pipeline.atomic();
+        if !self.remove_words(&mut pipeline, id)? {
+            return Ok(false);
+        }
+        pipeline.cmd("DEL").arg(self.signature_key(id));
+        let (deleted,): (u64,) = pipeline.query(&mut self.connection).map_err(StoreError::Backend)?;
+        Ok(deleted > 0)
+    }
+
+    fn search_similar(
+        &mut self,
+        query: &Signature,
+        threshold: f64,
+        limit: usize,
+    ) -> Result<Vec<(String, f64)>, Self::Error> {
+        let words: Vec<String> = store_words(query).into_iter()
+            .map(|(index, word)| self.word_key(index, word))
+            .collect();
+        let ids: BTreeSet<String> = cmd("SUNION").arg(&words)
+            .query(&mut self.connection)
+            .map_err(StoreError::Backend)?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let keys: Vec<String> = ids.iter().map(|id| self.signature_key(id)).collect();
+        let containers: Vec<Option<Vec<u8>>> = cmd("MGET").arg(&keys)
+            .query(&mut self.connection)
+            .map_err(StoreError::Backend)?;
+        let mut candidates = Vec::with_capacity(ids.len());
+        let mut expired = pipe();
+        for (id, container) in ids.into_iter().zip(containers) {
+            match container {
+                Some(container) => candidates.push((id, Signature::decode(&container)?)),
+                None => {
+                    for word in &words {
+                        expired.cmd("SREM").arg(word).arg(&id).ignore();
+                    }
+                }
+            }
+        }
+        if !expired.is_empty() {
+            expired.exec(&mut self.connection).map_err(StoreError::Backend)?;
+        }
+        Ok(rank(query, candidates, threshold, limit))
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{SignatureConfig, SignatureError};
+
+/// A rectangle of an image to sign on its own, such as a face or product found by a detector, so
+/// that it's matched independently of the rest of the frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Region {
+    /// A rectangle of whole pixels, from its top left corner.
+    Pixels { x: usize, y: usize, width: usize, height: usize },
+    /// A rectangle in fractions of the image's width and height, from 0.0 to 1.0, the way most
+    /// detectors report their boxes. It's rounded to the nearest pixels of the image it's applied
+    /// to.
+    Relative { x: f32, y: f32, width: f32, height: f32 },
+}
+
+impl Region {
+    /// A rectangle of `width` by `height` pixels with its top left corner at `x`, `y`.
+    pub fn pixels(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Region::Pixels { x, y, width, height }
+    }
+
+    /// A rectangle in fractions of the image's size, see [Region::Relative].
+    pub fn relative(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Region::Relative { x, y, width, height }
+    }
+
+    /// The left edge, top edge, width, and height in pixels that the region covers in an image of
+    /// the given size. Returns [SignatureError::RegionOutOfBounds] if the region is empty or
+    /// extends past the edges of the image.
+    pub fn bounds(
+        self,
+        image_width: usize,
+        image_height: usize,
+    ) -> Result<(usize, usize, usize, usize), SignatureError> {
+        let out_of_bounds = || SignatureError::RegionOutOfBounds {
+            region: format!("{:?}", self),
+            width: image_width,
+            height: image_height,
+        };
+        let (x, y, width, height) = match self {
+            Region::Pixels { x, y, width, height } => (x, y, width, height),
+            Region::Relative { x, y, width, height } => {
+                let (left, right) =
+                    relative_span(x, width, image_width).ok_or_else(out_of_bounds)?;
+                let (top, bottom) =
+                    relative_span(y, height, image_height).ok_or_else(out_of_bounds)?;
+                (left, top, right - left, bottom - top)
+            }
+        };
+        let fits = |start: usize, len: usize, size: usize| {
+            len > 0 && start.checked_add(len).is_some_and(|end| end <= size)
+        };
+        if fits(x, width, image_width) && fits(y, height, image_height) {
+            Ok((x, y, width, height))
+        } else {
+            Err(out_of_bounds())
+        }
+    }
+}
+
+/// The first and one past the last pixel of a relative span of a side of `size` pixels.
+fn relative_span(start: f32, len: f32, size: usize) -> Option<(usize, usize)> {
+    let end = start + len;
+    if !(start >= 0.0 && len > 0.0 && end <= 1.0) {
+        return None;
+    }
+    let pixel = |fraction: f32| (fraction as f64 * size as f64).round() as usize;
+    Some((pixel(start), pixel(end)))
+}
+
+/// The number of rows of `width` pixels of `channels` each that `buffer` holds, or
+/// [SignatureError::BadBufferLength] if it doesn't hold a whole number of them.
+pub(crate) fn buffer_height<T>(
+    buffer: &[T],
+    width: usize,
+    channels: usize,
+) -> Result<usize, SignatureError> {
+    let row_len = width.checked_mul(channels).filter(|&len| len > 0);
+    let whole_rows = |len: &usize| !buffer.is_empty() && buffer.len().is_multiple_of(*len);
+    match row_len.filter(whole_rows) {
+        Some(row_len) => Ok(buffer.len() / row_len),
+        None => Err(SignatureError::BadBufferLength { len: buffer.len(), width }),
+    }
+}
+
+impl SignatureConfig {
+    /// Produces a signature for a region of a provided image that's encoded as an array of
+    /// conceptually grouped bytes in the configured [PixelFormat](crate::PixelFormat) with the
+    /// provided width, as if the image had been cropped to the region first. The region is read in
+    /// place rather than copied out.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_region] for
+    /// details.
+    pub fn compute_region(&self, rgba_buffer: &[u8], width: usize, region: Region) -> Vec<i8> {
+        self.try_compute_region(rgba_buffer, width, region).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_region]. Returns an error if the
+    /// buffer doesn't hold whole rows of pixels, if the region doesn't fit in the image, or if the
+    /// region is too small or too featureless to place the signature grid on.
+    pub fn try_compute_region(
+        &self,
+        rgba_buffer: &[u8],
+        width: usize,
+        region: Region,
+    ) -> Result<Vec<i8>, SignatureError> {
+        let channels = self.pixel_format.channels();
+        let height = buffer_height(rgba_buffer, width, channels)?;
+        let row_len = width * channels;
+        let (x, y, region_width, region_height) = region.bounds(width, height)?;
+        let start = y * row_len + x * channels;
+        self.try_compute_strided(&rgba_buffer[start..], region_width, region_height, row_len)
+    }
+}
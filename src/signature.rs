@@ -0,0 +1,638 @@
+use std::ops::Deref;
+
+use crate::{
+    cosine, encoding, sum_of_squares, AlphaMode, Arithmetic, Compat, GrayscaleMode, GridPlacement,
+    NeighborPadding, SignatureConfig, SignatureError,
+};
+
+/// Identifies [Signature::encode] containers.
+const CONTAINER_MAGIC: &[u8; 4] = b"IMSG";
+
+/// The version of the container layout written by [Signature::encode], incremented whenever the
+/// layout or the meaning of its fields changes.
+const CONTAINER_VERSION: u8 = 2;
+
+/// The container flag marking that the sum of squared elements follows the elements.
+const CONTAINER_HAS_NORM: u8 = 1;
+
+/// The tuning parameters a [Signature] was computed with, which every signature it's compared to
+/// must share. Options that only change how pixels are read, like the pixel format, aren't
+/// included, since signatures of the same image in different formats are meant to match, but the
+/// grayscale, alpha, and arithmetic modes are, since they change the gray levels themselves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub struct SignatureParams {
+    crop: f32,
+    grid_size: usize,
+    identical_tolerance: u8,
+    grid_placement: GridPlacement,
+    neighbor_padding: NeighborPadding,
+    compat: Compat,
+    grayscale_mode: GrayscaleMode,
+    alpha_mode: AlphaMode,
+    arithmetic: Arithmetic,
+}
+
+impl SignatureParams {
+    /// The config's crop, see [SignatureConfig::crop].
+    pub fn crop(&self) -> f32 {
+        self.crop
+    }
+
+    /// The config's grid size, see [SignatureConfig::grid_size].
+    pub fn grid_size(&self) -> usize {
+        self.grid_size
+    }
+
+    /// The config's identical tolerance, see [SignatureConfig::identical_tolerance].
+    pub fn identical_tolerance(&self) -> u8 {
+        self.identical_tolerance
+    }
+
+    /// The config's grid placement, see [SignatureConfig::grid_placement].
+    pub fn grid_placement(&self) -> GridPlacement {
+        self.grid_placement
+    }
+
+    /// The config's neighbor padding, see [SignatureConfig::neighbor_padding].
+    pub fn neighbor_padding(&self) -> NeighborPadding {
+        self.neighbor_padding
+    }
+
+    /// The config's compatibility mode, see [SignatureConfig::compat].
+    pub fn compat(&self) -> Compat {
+        self.compat
+    }
+
+    /// The config's grayscale mode, see [SignatureConfig::grayscale_mode].
+    pub fn grayscale_mode(&self) -> GrayscaleMode {
+        self.grayscale_mode
+    }
+
+    /// The config's alpha mode, see [SignatureConfig::alpha_mode].
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// The config's arithmetic, see [SignatureConfig::arithmetic].
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    /// The length of signatures computed with these parameters.
+    pub fn signature_length(&self) -> usize {
+        SignatureConfig::new()
+            .grid_size(self.grid_size)
+            .neighbor_padding(self.neighbor_padding)
+            .compat(self.compat)
+            .signature_length()
+    }
+
+    /// A config with these parameters, and the defaults of the options that only change how
+    /// pixels are read, to compute signatures comparable to those computed with these
+    /// parameters, e.g. queries against a collection.
+    pub fn config(&self) -> SignatureConfig {
+        SignatureConfig::new()
+            .crop(self.crop)
+            .grid_size(self.grid_size)
+            .identical_tolerance(self.identical_tolerance)
+            .grid_placement(self.grid_placement)
+            .neighbor_padding(self.neighbor_padding)
+            .compat(self.compat)
+            .grayscale_mode(self.grayscale_mode)
+            .alpha_mode(self.alpha_mode)
+            .arithmetic(self.arithmetic)
+    }
+
+    /// Returns an error naming the first parameter that differs from `other`'s.
+    pub fn check_compatible(&self, other: &SignatureParams) -> Result<(), SignatureError> {
+        let mismatch = |name, left: &dyn ToString, right: &dyn ToString| {
+            Err(SignatureError::IncompatibleSignatures {
+                name,
+                left: left.to_string(),
+                right: right.to_string(),
+            })
+        };
+        let debug = |value: &dyn std::fmt::Debug| format!("{:?}", value);
+
+        if self.crop.to_bits() != other.crop.to_bits() {
+            mismatch("crop", &self.crop, &other.crop)
+        } else if self.grid_size != other.grid_size {
+            mismatch("grid_size", &self.grid_size, &other.grid_size)
+        } else if self.identical_tolerance != other.identical_tolerance {
+            mismatch("identical_tolerance", &self.identical_tolerance, &other.identical_tolerance)
+        } else if self.grid_placement != other.grid_placement {
+            mismatch("grid_placement", &debug(&self.grid_placement), &debug(&other.grid_placement))
+        } else if self.neighbor_padding != other.neighbor_padding {
+            let (left, right) = (debug(&self.neighbor_padding), debug(&other.neighbor_padding));
+            mismatch("neighbor_padding", &left, &right)
+        } else if self.compat != other.compat {
+            mismatch("compat", &debug(&self.compat), &debug(&other.compat))
+        } else if self.grayscale_mode != other.grayscale_mode {
+            mismatch("grayscale_mode", &debug(&self.grayscale_mode), &debug(&other.grayscale_mode))
+        } else if self.alpha_mode != other.alpha_mode {
+            mismatch("alpha_mode", &debug(&self.alpha_mode), &debug(&other.alpha_mode))
+        } else if self.arithmetic != other.arithmetic {
+            mismatch("arithmetic", &debug(&self.arithmetic), &debug(&other.arithmetic))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl From<&SignatureConfig> for SignatureParams {
+    fn from(config: &SignatureConfig) -> Self {
+        SignatureParams {
+            crop: config.crop,
+            grid_size: config.grid_size,
+            identical_tolerance: config.identical_tolerance,
+            grid_placement: config.grid_placement,
+            neighbor_padding: config.neighbor_padding,
+            compat: config.compat,
+            grayscale_mode: config.grayscale_mode,
+            alpha_mode: config.alpha_mode,
+            arithmetic: config.arithmetic,
+        }
+    }
+}
+
+/// A signature stored along with the [SignatureParams] it was computed with, so that comparing it
+/// to a signature computed with a different config is reported as an error rather than producing
+/// a meaningless similarity or a panic on mismatched lengths.
+///
+/// With the `serde` feature, signatures serialize as their params and their elements packed three
+/// to a byte, as bytes in binary formats and as in [Signature::to_base64] in human-readable ones
+/// like JSON.
+///
+/// With the `rkyv` feature, signatures can be archived with rkyv and read in place as
+/// [ArchivedSignature]s, e.g. from a memory-mapped file, without deserializing each one first.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "serialized::Signature", try_from = "serialized::Signature")
+)]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize))]
+pub struct Signature {
+    params: SignatureParams,
+    elements: Vec<i8>,
+}
+
+impl Signature {
+    /// Pairs elements with the parameters they were computed with, e.g. when loading signatures
+    /// from storage.
+    ///
+    /// Panics if the elements don't fit the parameters, see [Signature::try_new] for a
+    /// non-panicking version.
+    pub fn new(params: SignatureParams, elements: Vec<i8>) -> Self {
+        Self::try_new(params, elements).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [Signature::new] which returns an error rather than crashing
+    /// when the parameters are outside the ranges signatures can be computed with, see
+    /// [SignatureConfig::validate], the number of elements doesn't match
+    /// [SignatureParams::signature_length], or an element is outside of -2 to 2.
+    pub fn try_new(params: SignatureParams, elements: Vec<i8>) -> Result<Self, SignatureError> {
+        params.config().validate()?;
+        let length = params.signature_length();
+        if elements.len() != length {
+            return Err(SignatureError::MismatchedLengths { left: elements.len(), right: length });
+        }
+        if let Some(index) = elements.iter().position(|element| !(-2..=2).contains(element)) {
+            return Err(SignatureError::ElementOutOfRange { index, value: elements[index] });
+        }
+
+        Ok(Signature { params, elements })
+    }
+
+    /// The parameters the signature was computed with.
+    pub fn params(&self) -> &SignatureParams {
+        &self.params
+    }
+
+    /// Computes the cosine similarity of this signature and `other`.
+    ///
+    /// Panics if the signatures were computed with different parameters, see
+    /// [Signature::try_similarity] for a non-panicking version.
+    pub fn similarity(&self, other: &Signature) -> f64 {
+        self.try_similarity(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [Signature::similarity] which returns
+    /// [SignatureError::IncompatibleSignatures] rather than crashing when the signatures were
+    /// computed with different parameters.
+    pub fn try_similarity(&self, other: &Signature) -> Result<f64, SignatureError> {
+        self.params.check_compatible(&other.params)?;
+        Ok(cosine(&self.elements, &other.elements))
+    }
+
+    /// Unwraps the signature's elements.
+    pub fn into_inner(self) -> Vec<i8> {
+        self.elements
+    }
+
+    /// Encodes the signature's elements as URL-safe base64 without padding, which can be stored
+    /// in text columns and passed in query strings as is. Elements are packed three to a byte,
+    /// so a signature of the default length takes 243 characters. The params aren't included and
+    /// have to be stored alongside, or be implied by the column's contents.
+    pub fn to_base64(&self) -> String {
+        encoding::to_base64(&encoding::pack(&self.elements))
+    }
+
+    /// Decodes a signature encoded by [Signature::to_base64] that was computed with `params`.
+    /// Returns [SignatureError::InvalidEncoding] if `encoded` isn't the canonical encoding of some
+    /// signature, and [SignatureError::MismatchedLengths] if it encodes a signature of a
+    /// different length than `params` produce.
+    pub fn from_base64(params: SignatureParams, encoded: &str) -> Result<Self, SignatureError> {
+        let packed = encoding::from_base64(encoded)
+            .ok_or(SignatureError::InvalidEncoding { format: "base64" })?;
+        Self::from_packed(params, &packed)
+    }
+
+    /// Encodes the signature's elements as hex, two to a byte, the convention of tools exchanging
+    /// perceptual hashes. Each element is one digit of 4-bit two's complement, so "same" reads as
+    /// `0`, lighter as `1` and `2`, and darker as `f` and `e`. Signatures always have an even
+    /// number of elements, so every byte is full. The params aren't included.
+    pub fn to_hex(&self) -> String {
+        encoding::to_hex(&self.elements)
+    }
+
+    /// Decodes a signature encoded by [Signature::to_hex] that was computed with `params`, in
+    /// either case. Returns [SignatureError::InvalidEncoding] if `encoded` isn't whole bytes of
+    /// hex, [SignatureError::MismatchedLengths] if it encodes a signature of a different length
+    /// than `params` produce, and [SignatureError::ElementOutOfRange] for digits outside of -2
+    /// to 2.
+    pub fn from_hex(params: SignatureParams, encoded: &str) -> Result<Self, SignatureError> {
+        let elements = encoding::from_hex(encoded)
+            .ok_or(SignatureError::InvalidEncoding { format: "hex" })?;
+        Self::try_new(params, elements)
+    }
+
+    /// Encodes the signature and its params in a self-describing binary container, for databases
+    /// that need to tell signatures from different pipelines apart. The container is the magic
+    /// bytes `IMSG`, a format version, a flags byte, the params, the element count, and the
+    /// elements packed three to a byte, with numbers in little endian. The params take the same
+    /// space whatever their values, with the weights of a custom grayscale mode and the color of
+    /// an alpha background zeroed when unused. A signature of the default length takes 222 bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_container(false)
+    }
+
+    /// Like [Signature::encode], but also stores the signature's sum of squared elements, the
+    /// square of its norm, so readers can check the elements or skip recomputing it.
+    pub fn encode_with_norm(&self) -> Vec<u8> {
+        self.encode_container(true)
+    }
+
+    /// Decodes a container produced by [Signature::encode] or [Signature::encode_with_norm].
+    /// Returns [SignatureError::UnsupportedVersion] for containers written by another version of
+    /// the format, including version 1, which didn't record the grayscale, alpha, and arithmetic
+    /// modes, and [SignatureError::InvalidEncoding] for anything that isn't a well-formed
+    /// container, including one whose stored norm doesn't match its elements.
+    pub fn decode(bytes: &[u8]) -> Result<Self, SignatureError> {
+        let invalid = SignatureError::InvalidEncoding { format: "signature container" };
+        let mut rest = bytes;
+        let mut take = |len: usize| -> Result<&[u8], SignatureError> {
+            let (taken, remaining) = rest.split_at_checked(len).ok_or(invalid.clone())?;
+            rest = remaining;
+            Ok(taken)
+        };
+
+        if take(CONTAINER_MAGIC.len())? != CONTAINER_MAGIC {
+            return Err(invalid);
+        }
+        let version = take(1)?[0];
+        if version != CONTAINER_VERSION {
+            return Err(SignatureError::UnsupportedVersion { version });
+        }
+        let flags = take(1)?[0];
+        if flags & !CONTAINER_HAS_NORM != 0 {
+            return Err(invalid);
+        }
+
+        let grid_size = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        let crop = f32::from_le_bytes(take(4)?.try_into().unwrap());
+        let [identical_tolerance, placement, padding, compat] = take(4)?.try_into().unwrap();
+        let [grayscale, alpha, arithmetic] = take(3)?.try_into().unwrap();
+        let background: [u8; 3] = take(3)?.try_into().unwrap();
+        let weights = take(12)?;
+        let weights: [f32; 3] =
+            std::array::from_fn(|i| f32::from_le_bytes(weights[i * 4..][..4].try_into().unwrap()));
+        let params = SignatureParams {
+            crop,
+            grid_size: grid_size as usize,
+            identical_tolerance,
+            grid_placement: match placement {
+                0 => GridPlacement::ImageOrigin,
+                1 => GridPlacement::CropOrigin,
+                _ => return Err(invalid),
+            },
+            neighbor_padding: match padding {
+                0 => NeighborPadding::Omit,
+                1 => NeighborPadding::Zeros,
+                _ => return Err(invalid),
+            },
+            compat: match compat {
+                0 => Compat::Native,
+                1 => Compat::Goldberg,
+                _ => return Err(invalid),
+            },
+            grayscale_mode: match grayscale {
+                0 => GrayscaleMode::Average,
+                1 => GrayscaleMode::Rec601,
+                2 => GrayscaleMode::Rec709,
+                3 => GrayscaleMode::Custom(weights),
+                _ => return Err(invalid),
+            },
+            alpha_mode: match alpha {
+                0 => AlphaMode::Ignore,
+                1 => AlphaMode::Multiply,
+                2 => AlphaMode::Background(background),
+                3 => AlphaMode::Checkerboard,
+                _ => return Err(invalid),
+            },
+            arithmetic: match arithmetic {
+                0 => Arithmetic::Float,
+                1 => Arithmetic::Integer,
+                _ => return Err(invalid),
+            },
+        };
+        // Unused weights and backgrounds are zeroed, so each signature has a single encoding.
+        let (used_weights, used_background) = params_extras(&params);
+        if used_weights.map(f32::to_bits) != weights.map(f32::to_bits)
+            || used_background != background
+        {
+            return Err(invalid);
+        }
+
+        // Checked before the length is, which only representable grids have.
+        params.config().validate().map_err(|_| invalid.clone())?;
+        let count = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        if count != params.signature_length() {
+            return Err(invalid);
+        }
+        let signature = Self::from_packed(params, take(encoding::packed_len(count))?)?;
+        if flags & CONTAINER_HAS_NORM != 0 {
+            let squares = u64::from_le_bytes(take(8)?.try_into().unwrap());
+            if squares != sum_of_squares(&signature.elements) as u64 {
+                return Err(invalid);
+            }
+        }
+        if !rest.is_empty() {
+            return Err(invalid);
+        }
+        Ok(signature)
+    }
+
+    fn encode_container(&self, include_norm: bool) -> Vec<u8> {
+        let params = &self.params;
+        let mut bytes = Vec::with_capacity(32 + encoding::packed_len(self.len()));
+        bytes.extend_from_slice(CONTAINER_MAGIC);
+        bytes.push(CONTAINER_VERSION);
+        bytes.push(if include_norm { CONTAINER_HAS_NORM } else { 0 });
+        bytes.extend((params.grid_size as u32).to_le_bytes());
+        bytes.extend(params.crop.to_le_bytes());
+        bytes.push(params.identical_tolerance);
+        bytes.push(match params.grid_placement {
+            GridPlacement::ImageOrigin => 0,
+            GridPlacement::CropOrigin => 1,
+        });
+        bytes.push(match params.neighbor_padding {
+            NeighborPadding::Omit => 0,
+            NeighborPadding::Zeros => 1,
+        });
+        bytes.push(match params.compat {
+            Compat::Native => 0,
+            Compat::Goldberg => 1,
+        });
+        bytes.push(match params.grayscale_mode {
+            GrayscaleMode::Average => 0,
+            GrayscaleMode::Rec601 => 1,
+            GrayscaleMode::Rec709 => 2,
+            GrayscaleMode::Custom(_) => 3,
+        });
+        bytes.push(match params.alpha_mode {
+            AlphaMode::Ignore => 0,
+            AlphaMode::Multiply => 1,
+            AlphaMode::Background(_) => 2,
+            AlphaMode::Checkerboard => 3,
+        });
+        bytes.push(match params.arithmetic {
+            Arithmetic::Float => 0,
+            Arithmetic::Integer => 1,
+        });
+        let (weights, background) = params_extras(params);
+        bytes.extend(background);
+        bytes.extend(weights.iter().flat_map(|weight| weight.to_le_bytes()));
+        bytes.extend((self.len() as u32).to_le_bytes());
+        bytes.extend(encoding::pack(&self.elements));
+        if include_norm {
+            bytes.extend((sum_of_squares(&self.elements) as u64).to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Unpacks elements packed by [encoding::pack].
+    fn from_packed(params: SignatureParams, packed: &[u8]) -> Result<Self, SignatureError> {
+        let length = params.signature_length();
+        let mut elements = encoding::unpack(packed);
+        if packed.len() != encoding::packed_len(length) {
+            return Err(SignatureError::MismatchedLengths { left: elements.len(), right: length });
+        }
+        elements.truncate(length);
+        Self::try_new(params, elements)
+    }
+}
+
+/// The custom grayscale weights and alpha background color stored in containers, or zeros where
+/// the params don't use them.
+fn params_extras(params: &SignatureParams) -> ([f32; 3], [u8; 3]) {
+    let weights = match params.grayscale_mode {
+        GrayscaleMode::Custom(weights) => weights,
+        _ => [0.0; 3],
+    };
+    let background = match params.alpha_mode {
+        AlphaMode::Background(color) => color,
+        _ => [0; 3],
+    };
+    (weights, background)
+}
+
+impl Deref for Signature {
+    type Target = [i8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.elements
+    }
+}
+
+impl AsRef<[i8]> for Signature {
+    fn as_ref(&self) -> &[i8] {
+        &self.elements
+    }
+}
+
+/// A [Signature] read in place from an rkyv archive. It's compared to signatures without copying
+/// its elements; [ArchivedSignature::to_signature] copies it out when an owned one is needed.
+#[cfg(feature = "rkyv")]
+impl ArchivedSignature {
+    /// The parameters the signature was computed with.
+    pub fn params(&self) -> SignatureParams {
+        rkyv::deserialize::<_, rkyv::rancor::Infallible>(&self.params)
+            .unwrap_or_else(|e| match e {})
+    }
+
+    /// Computes the cosine similarity of this signature and `other`.
+    ///
+    /// Panics if the signatures were computed with different parameters, see
+    /// [ArchivedSignature::try_similarity] for a non-panicking version.
+    pub fn similarity(&self, other: &Signature) -> f64 {
+        self.try_similarity(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [ArchivedSignature::similarity] which returns
+    /// [SignatureError::IncompatibleSignatures] when the signatures were computed with different
+    /// parameters, and [SignatureError::MismatchedLengths] when the archive holds a signature of
+    /// the wrong length for its parameters.
+    pub fn try_similarity(&self, other: &Signature) -> Result<f64, SignatureError> {
+        self.params().check_compatible(&other.params)?;
+        crate::try_cosine_similarity(self, other)
+    }
+
+    /// Copies the signature out of the archive. Returns the errors of [Signature::try_new], since
+    /// archives aren't checked for elements that don't fit their parameters.
+    pub fn to_signature(&self) -> Result<Signature, SignatureError> {
+        Signature::try_new(self.params(), self.elements.to_vec())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl Deref for ArchivedSignature {
+    type Target = [i8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.elements
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl AsRef<[i8]> for ArchivedSignature {
+    fn as_ref(&self) -> &[i8] {
+        &self.elements
+    }
+}
+
+impl SignatureConfig {
+    /// The parameters signatures computed with this config are tagged with.
+    pub fn params(&self) -> SignatureParams {
+        SignatureParams::from(self)
+    }
+
+    /// Like [SignatureConfig::compute], but tags the result with the config's parameters so that
+    /// comparisons can check them.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute] for details.
+    pub fn compute_signature(&self, buffer: &[u8], width: usize) -> Signature {
+        self.try_compute_signature(buffer, width).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_signature], with the errors of
+    /// [SignatureConfig::try_compute].
+    pub fn try_compute_signature(
+        &self,
+        buffer: &[u8],
+        width: usize,
+    ) -> Result<Signature, SignatureError> {
+        let elements = self.try_compute(buffer, width)?;
+        Ok(Signature { params: self.params(), elements })
+    }
+}
+
+/// The serialized forms of signatures, which pack their elements.
+#[cfg(feature = "serde")]
+mod serialized {
+    use std::fmt::Formatter;
+
+    use serde::de::{Error, SeqAccess, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::SignatureParams;
+    use crate::{encoding, SignatureError};
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Signature {
+        params: SignatureParams,
+        elements: PackedElements,
+    }
+
+    impl From<super::Signature> for Signature {
+        fn from(signature: super::Signature) -> Self {
+            let elements = PackedElements(encoding::pack(&signature.elements));
+            Signature { params: signature.params, elements }
+        }
+    }
+
+    impl TryFrom<Signature> for super::Signature {
+        type Error = SignatureError;
+
+        fn try_from(Signature { params, elements }: Signature) -> Result<Self, Self::Error> {
+            super::Signature::from_packed(params, &elements.0)
+        }
+    }
+
+    /// Elements packed by [encoding::pack].
+    struct PackedElements(Vec<u8>);
+
+    impl Serialize for PackedElements {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                serializer.serialize_str(&encoding::to_base64(&self.0))
+            } else {
+                serializer.serialize_bytes(&self.0)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for PackedElements {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_str(PackedElementsVisitor)
+            } else {
+                deserializer.deserialize_bytes(PackedElementsVisitor)
+            }
+        }
+    }
+
+    struct PackedElementsVisitor;
+
+    impl<'de> Visitor<'de> for PackedElementsVisitor {
+        type Value = PackedElements;
+
+        fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+            write!(f, "packed signature elements as bytes or base64")
+        }
+
+        fn visit_str<E: Error>(self, encoded: &str) -> Result<Self::Value, E> {
+            encoding::from_base64(encoded)
+                .map(PackedElements)
+                .ok_or_else(|| E::custom(SignatureError::InvalidEncoding { format: "base64" }))
+        }
+
+        fn visit_bytes<E: Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+            Ok(PackedElements(bytes.to_vec()))
+        }
+
+        fn visit_byte_buf<E: Error>(self, bytes: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(PackedElements(bytes))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(PackedElements(bytes))
+        }
+    }
+}
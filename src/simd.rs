@@ -0,0 +1,189 @@
+//! Vectorized comparison and grayscale kernels. The widest implementation the CPU supports is
+//! picked at runtime, falling back to plain loops the compiler is left to vectorize. Every
+//! implementation returns exactly the same results.
+
+use crate::{PixelFormat, SignatureConfig};
+
+pub(crate) fn dot_product(a: &[i8], b: &[i8]) -> i64 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: AVX2 support was just checked.
+        return unsafe { avx2::dot_product(a, b) };
+    }
+
+    scalar_dot_product(a, b)
+}
+
+pub(crate) fn sum_of_squares(v: &[i8]) -> i64 {
+    dot_product(v, v)
+}
+
+/// Converts as many leading pixels of a row of 8-bit pixels to gray as a vectorized kernel can
+/// handle, writing them to the start of `gray`, and returns how many were converted. The remaining
+/// pixels are left for the scalar conversion. Results are identical to converting each pixel with
+/// `pixel_gray`.
+pub(crate) fn gray_pixels(
+    row: &[u8],
+    format: PixelFormat,
+    config: &SignatureConfig,
+    gray: &mut [u8],
+) -> usize {
+    #[cfg(target_arch = "x86_64")]
+    if avx2::supports_gray(format, config) && is_x86_feature_detected!("avx2") {
+        // Safety: AVX2 support was just checked.
+        return unsafe { avx2::gray_pixels(row, format, config, gray) };
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = (row, format, config, gray);
+
+    0
+}
+
+fn scalar_dot_product(a: &[i8], b: &[i8]) -> i64 {
+    a.iter().zip(b.iter()).map(|(av, bv)| *av as i64 * *bv as i64).sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use crate::{AlphaMode, Arithmetic, PixelFormat, SignatureConfig};
+
+    /// Elements consumed per iteration.
+    const LANES: usize = 32;
+
+    /// Iterations between flushes of the i32 accumulators into an i64. Each iteration adds at most
+    /// 4 * 128^2 = 2^16 to a lane, so 2^14 iterations stay well clear of `i32::MAX`.
+    const FLUSH_EVERY: usize = 1 << 14;
+
+    /// Safety: the CPU must support AVX2.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn dot_product(a: &[i8], b: &[i8]) -> i64 {
+        let len = a.len().min(b.len());
+        let chunks = len / LANES;
+        let mut total = 0;
+        let mut sums = _mm256_setzero_si256();
+        for i in 0..chunks {
+            let av = _mm256_loadu_si256(a.as_ptr().add(i * LANES) as *const __m256i);
+            let bv = _mm256_loadu_si256(b.as_ptr().add(i * LANES) as *const __m256i);
+
+            // Widen each half to i16, then multiply and add adjacent pairs into i32 lanes.
+            let a_low = _mm256_cvtepi8_epi16(_mm256_castsi256_si128(av));
+            let a_high = _mm256_cvtepi8_epi16(_mm256_extracti128_si256::<1>(av));
+            let b_low = _mm256_cvtepi8_epi16(_mm256_castsi256_si128(bv));
+            let b_high = _mm256_cvtepi8_epi16(_mm256_extracti128_si256::<1>(bv));
+            sums = _mm256_add_epi32(sums, _mm256_madd_epi16(a_low, b_low));
+            sums = _mm256_add_epi32(sums, _mm256_madd_epi16(a_high, b_high));
+
+            if (i + 1).is_multiple_of(FLUSH_EVERY) {
+                total += horizontal_sum(sums);
+                sums = _mm256_setzero_si256();
+            }
+        }
+        total += horizontal_sum(sums);
+
+        let rest = chunks * LANES;
+        total + super::scalar_dot_product(&a[rest..len], &b[rest..len])
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn horizontal_sum(sums: __m256i) -> i64 {
+        let mut lanes = [0i32; 8];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sums);
+        lanes.iter().map(|lane| *lane as i64).sum()
+    }
+
+    /// Pixels per iteration of the grayscale kernel.
+    const GRAY_LANES: usize = 8;
+
+    /// The grayscale kernel handles four channel formats, and any alpha mode that doesn't depend on
+    /// the pixel's position, in floating point arithmetic.
+    pub(super) fn supports_gray(format: PixelFormat, config: &SignatureConfig) -> bool {
+        format.channels() == 4
+            && config.alpha_mode != AlphaMode::Checkerboard
+            && config.arithmetic == Arithmetic::Float
+    }
+
+    /// Mirrors `pixel_gray` operation for operation in f32 lanes, so that rounding matches exactly.
+    ///
+    /// Safety: the CPU must support AVX2, and `supports_gray` must hold.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn gray_pixels(
+        row: &[u8],
+        format: PixelFormat,
+        config: &SignatureConfig,
+        gray: &mut [u8],
+    ) -> usize {
+        let shift = |offset: usize| _mm_cvtsi32_si128(8 * offset as i32);
+        let [r_shift, g_shift, b_shift] = format.color_offsets().map(shift);
+        let alpha_shift = format.alpha_offset().map(shift);
+
+        let low_byte = _mm256_set1_epi32(0xFF);
+        let (zero, half, one) = (_mm256_setzero_ps(), _mm256_set1_ps(0.5), _mm256_set1_ps(1.0));
+        let (three, max) = (_mm256_set1_ps(3.0), _mm256_set1_ps(u8::MAX as f32));
+        let weights = config.grayscale_mode.weights().map(|w| w.map(|w| _mm256_set1_ps(w)));
+        let background = match config.alpha_mode {
+            AlphaMode::Background([r, g, b]) => {
+                let mode = config.grayscale_mode;
+                let luma = crate::pixel_luma::<u8>(r as f32, g as f32, b as f32, mode);
+                _mm256_set1_ps(luma / 255.0 * u8::MAX as f32)
+            }
+            _ => zero,
+        };
+
+        let mut levels = [0i32; GRAY_LANES];
+        let blocks = row.chunks_exact(4 * GRAY_LANES).zip(gray.chunks_exact_mut(GRAY_LANES));
+        let mut converted = 0;
+        for (pixels, gray) in blocks {
+            let pixels = _mm256_loadu_si256(pixels.as_ptr() as *const __m256i);
+            let channel = |shift| {
+                _mm256_cvtepi32_ps(_mm256_and_si256(_mm256_srl_epi32(pixels, shift), low_byte))
+            };
+            let (r, g, b) = (channel(r_shift), channel(g_shift), channel(b_shift));
+
+            let (luma, nan) = match weights {
+                None => {
+                    let sum = _mm256_add_ps(_mm256_add_ps(r, g), b);
+                    (_mm256_floor_ps(_mm256_div_ps(sum, three)), zero)
+                }
+                Some([r_weight, g_weight, b_weight]) => {
+                    let sum = _mm256_add_ps(
+                        _mm256_add_ps(_mm256_mul_ps(r, r_weight), _mm256_mul_ps(g, g_weight)),
+                        _mm256_mul_ps(b, b_weight),
+                    );
+                    // Rounds half away from zero like f32::round for the non-negative values that
+                    // survive clamping.
+                    let floor = _mm256_floor_ps(sum);
+                    let round_up = _mm256_cmp_ps::<_CMP_GE_OQ>(_mm256_sub_ps(sum, floor), half);
+                    let rounded = _mm256_add_ps(floor, _mm256_and_ps(round_up, one));
+                    let clamped = _mm256_min_ps(_mm256_max_ps(rounded, zero), max);
+                    (clamped, _mm256_cmp_ps::<_CMP_UNORD_Q>(sum, sum))
+                }
+            };
+
+            let alpha = match alpha_shift {
+                Some(shift) => _mm256_div_ps(channel(shift), max),
+                None => one,
+            };
+            let value = match config.alpha_mode {
+                AlphaMode::Ignore => luma,
+                AlphaMode::Multiply => _mm256_mul_ps(luma, alpha),
+                _ => _mm256_add_ps(
+                    _mm256_mul_ps(luma, alpha),
+                    _mm256_mul_ps(background, _mm256_sub_ps(one, alpha)),
+                ),
+            };
+
+            // A NaN luma, from degenerate custom weights, stays NaN through compositing and casts
+            // to 0.
+            let value = _mm256_andnot_ps(nan, value);
+            _mm256_storeu_si256(levels.as_mut_ptr() as *mut __m256i, _mm256_cvttps_epi32(value));
+            for (gray, level) in gray.iter_mut().zip(levels) {
+                *gray = level as u8;
+            }
+            converted += GRAY_LANES;
+        }
+
+        converted
+    }
+}
@@ -0,0 +1,214 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{cosine_with_squares, dot_product, sum_of_squares, SignatureError};
+
+/// Computes the cosine similarity of `query` with each of `candidates`, in order. Equivalent to
+/// calling [cosine_similarity(a, b)] for each candidate, but the query's vector length is only
+/// computed once, which matters when scanning a large collection for duplicates.
+///
+/// Panics if any candidate's length differs from the query's, see [try_similarities] for a
+/// non-panicking version.
+pub fn similarities<Q: AsRef<[i8]>, C: AsRef<[i8]>>(query: Q, candidates: &[C]) -> Vec<f64> {
+    try_similarities(query, candidates).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// A non-panicking version of [similarities(query, candidates)] which returns an error rather than
+/// crashing when a candidate's length differs from the query's.
+pub fn try_similarities<Q: AsRef<[i8]>, C: AsRef<[i8]>>(
+    query: Q,
+    candidates: &[C],
+) -> Result<Vec<f64>, SignatureError> {
+    let query = query.as_ref();
+    let query_squares = sum_of_squares(query);
+
+    candidates.iter().map(|candidate| {
+        let candidate = candidate.as_ref();
+        if candidate.len() != query.len() {
+            return Err(SignatureError::MismatchedLengths {
+                left: query.len(),
+                right: candidate.len(),
+            });
+        }
+
+        Ok(cosine_with_squares(query, candidate, query_squares, sum_of_squares(candidate)))
+    }).collect()
+}
+
+/// Computes the cosine similarity of `a` and `b` if it's at least `threshold`, and returns `None`
+/// otherwise. The dot product is accumulated in blocks, and the comparison stops as soon as the
+/// remaining elements can no longer lift the similarity to the threshold, so scanning many
+/// candidates against a cutoff skips most of the work for clear mismatches. Returned similarities
+/// are identical to those of [cosine_similarity(a, b)].
+///
+/// Panics if the compared vectors are of different lengths, see [try_similarity_at_least] for a
+/// non-panicking version.
+pub fn similarity_at_least<A: AsRef<[i8]>, B: AsRef<[i8]>>(
+    a: A,
+    b: B,
+    threshold: f64,
+) -> Option<f64> {
+    try_similarity_at_least(a, b, threshold).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// A non-panicking version of [similarity_at_least(a, b, threshold)] which returns an error rather
+/// than crashing when the compared vectors are of different lengths.
+pub fn try_similarity_at_least<A: AsRef<[i8]>, B: AsRef<[i8]>>(
+    a: A,
+    b: B,
+    threshold: f64,
+) -> Result<Option<f64>, SignatureError> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    if a.len() != b.len() {
+        return Err(SignatureError::MismatchedLengths { left: a.len(), right: b.len() });
+    }
+
+    let (a_squares, b_squares) = (sum_of_squares(a), sum_of_squares(b));
+    if a_squares == 0 || b_squares == 0 {
+        // Two zero vectors are identical, a zero vector is unrelated to anything else.
+        let similarity = if a_squares == b_squares { 1.0 } else { 0.0 };
+        return Ok(Some(similarity).filter(|s| *s >= threshold));
+    }
+
+    let norms = (a_squares as f64 * b_squares as f64).sqrt();
+    let (mut dot, mut a_seen, mut b_seen) = (0i64, 0i64, 0i64);
+    for (a_block, b_block) in a.chunks(EARLY_EXIT_BLOCK).zip(b.chunks(EARLY_EXIT_BLOCK)) {
+        dot += dot_product(a_block, b_block);
+        a_seen += sum_of_squares(a_block);
+        b_seen += sum_of_squares(b_block);
+
+        // By Cauchy-Schwarz the rest of the dot product is at most the product of the lengths of
+        // the rest of each vector.
+        let remaining = ((a_squares - a_seen) as f64 * (b_squares - b_seen) as f64).sqrt();
+        if (dot as f64 + remaining) / norms < threshold {
+            return Ok(None);
+        }
+    }
+
+    let similarity = cosine_with_squares(a, b, a_squares, b_squares);
+    Ok(Some(similarity).filter(|s| *s >= threshold))
+}
+
+/// Finds the `k` candidates most similar to `query`, returned as pairs of candidate index and
+/// cosine similarity, most similar first. Ties are broken in favor of the earlier candidate. Only
+/// the best `k` matches found so far are kept while scanning, so this is cheaper than scoring and
+/// sorting every candidate when `k` is small.
+///
+/// Panics if any candidate's length differs from the query's, see [try_top_k] for a non-panicking
+/// version.
+pub fn top_k<Q: AsRef<[i8]>, C: AsRef<[i8]>>(
+    query: Q,
+    candidates: &[C],
+    k: usize,
+) -> Vec<(usize, f64)> {
+    try_top_k(query, candidates, k).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// A non-panicking version of [top_k(query, candidates, k)] which returns an error rather than
+/// crashing when a candidate's length differs from the query's.
+pub fn try_top_k<Q: AsRef<[i8]>, C: AsRef<[i8]>>(
+    query: Q,
+    candidates: &[C],
+    k: usize,
+) -> Result<Vec<(usize, f64)>, SignatureError> {
+    let query = query.as_ref();
+    let query_squares = sum_of_squares(query);
+    let mut best: BinaryHeap<Reverse<Scored>> = BinaryHeap::with_capacity(k + 1);
+
+    for (index, candidate) in candidates.iter().enumerate() {
+        let candidate = candidate.as_ref();
+        if candidate.len() != query.len() {
+            return Err(SignatureError::MismatchedLengths {
+                left: query.len(),
+                right: candidate.len(),
+            });
+        }
+        if k == 0 {
+            continue;
+        }
+
+        let score = cosine_with_squares(query, candidate, query_squares, sum_of_squares(candidate));
+        let scored = Scored { score, index };
+        if best.len() < k {
+            best.push(Reverse(scored));
+        } else if best.peek().is_some_and(|Reverse(worst)| scored > *worst) {
+            best.pop();
+            best.push(Reverse(scored));
+        }
+    }
+
+    Ok(best.into_sorted_vec().into_iter().map(|Reverse(s)| (s.index, s.score)).collect())
+}
+
+/// Computes the cosine similarity of every pair of `signatures` as a condensed upper-triangular
+/// matrix: the similarities of the first signature with each later one, followed by those of the
+/// second with each later one, and so on. The result holds `n * (n - 1) / 2` values for `n`
+/// signatures, and the similarity of `i` and `j`, where `i < j`, is found at index
+/// `n * i - i * (i + 1) / 2 + (j - i - 1)`. Each signature's vector length is only computed once.
+/// With the `rayon` feature, rows of the matrix are computed in parallel.
+///
+/// Panics if the signatures aren't all the same length, see [try_similarity_matrix] for a
+/// non-panicking version.
+pub fn similarity_matrix<S: AsRef<[i8]> + Sync>(signatures: &[S]) -> Vec<f64> {
+    try_similarity_matrix(signatures).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// A non-panicking version of [similarity_matrix(signatures)] which returns an error rather than
+/// crashing when the signatures aren't all the same length.
+pub fn try_similarity_matrix<S: AsRef<[i8]> + Sync>(
+    signatures: &[S],
+) -> Result<Vec<f64>, SignatureError> {
+    let signatures: Vec<&[i8]> = signatures.iter().map(|s| s.as_ref()).collect();
+    if let Some(first) = signatures.first() {
+        if let Some(other) = signatures.iter().find(|s| s.len() != first.len()) {
+            return Err(SignatureError::MismatchedLengths { left: first.len(), right: other.len() });
+        }
+    }
+
+    let squares: Vec<i64> = signatures.iter().map(|s| sum_of_squares(s)).collect();
+    let row = |i: usize| -> Vec<f64> {
+        (i + 1..signatures.len())
+            .map(|j| cosine_with_squares(signatures[i], signatures[j], squares[i], squares[j]))
+            .collect()
+    };
+
+    #[cfg(feature = "rayon")]
+    let rows: Vec<Vec<f64>> = (0..signatures.len()).into_par_iter().map(row).collect();
+    #[cfg(not(feature = "rayon"))]
+    let rows: Vec<Vec<f64>> = (0..signatures.len()).map(row).collect();
+
+    Ok(rows.concat())
+}
+
+/// The number of elements compared between checks of whether [similarity_at_least] can exit early.
+const EARLY_EXIT_BLOCK: usize = 64;
+
+/// A candidate's score, ordered so that higher scores and then earlier indices rank greater.
+#[derive(Clone, Copy, Debug)]
+struct Scored {
+    score: f64,
+    index: usize,
+}
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score.total_cmp(&other.score).then_with(|| other.index.cmp(&self.index))
+    }
+}
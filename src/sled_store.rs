@@ -0,0 +1,155 @@
+//! A [SignatureStore] in a sled database, for pure Rust applications that want embedded storage.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use sled::transaction::{ConflictableTransactionError, TransactionError, TransactionalTree};
+use sled::{Db, Transactional, Tree};
+
+use crate::store::{rank, store_words};
+use crate::{Signature, SignatureError, SignatureStore, StoreError};
+
+/// The tree [SledSignatureStore::new] keeps signatures in.
+pub const DEFAULT_SLED_TREE: &str = "image_signatures";
+
+/// A [SignatureStore] in sled trees, embedded in the application with no C dependencies. Writes
+/// are transactional and survive crashes once flushed, see [SledSignatureStore::flush].
+///
+/// Signatures are kept by id as their [Signature::encode] containers in one tree, and their words
+/// in a second, named after the first with a `_words` suffix, as keys of the word's position and
+/// value followed by the id, so that the ids sharing a word are found with a prefix scan.
+pub struct SledSignatureStore {
+    signatures: Tree,
+    words: Tree,
+}
+
+impl SledSignatureStore {
+    /// Opens or creates the database at `path`, storing signatures in the [DEFAULT_SLED_TREE].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, sled::Error> {
+        Self::new(&sled::open(path)?)
+    }
+
+    /// Stores signatures in the [DEFAULT_SLED_TREE] of `db`.
+    pub fn new(db: &Db) -> Result<Self, sled::Error> {
+        Self::with_tree(db, DEFAULT_SLED_TREE)
+    }
+
+    /// Stores signatures in the tree `name` and `{name}_words` of `db`, e.g. to keep signatures
+    /// computed with different parameters apart.
+    pub fn with_tree(db: &Db, name: &str) -> Result<Self, sled::Error> {
+        Ok(SledSignatureStore {
+            signatures: db.open_tree(name)?,
+            words: db.open_tree(format!("{}_words", name))?,
+        })
+    }
+
+    /// Writes everything stored so far to disk, returning once it's durable. sled also flushes
+    /// in the background every half a second by default.
+    pub fn flush(&self) -> Result<(), sled::Error> {
+        self.signatures.flush()?;
+        self.words.flush()?;
+        Ok(())
+    }
+
+    /// Replaces the signature stored under `id` with `signature`, or removes it if `None`, along
+    /// with its words, in one transaction. Returns whether a signature was stored before.
+    fn replace(
+        &self,
+        id: &str,
+        signature: Option<&Signature>,
+    ) -> Result<bool, StoreError<sled::Error>> {
+        let container = signature.map(Signature::encode);
+        let result = (&self.signatures, &self.words).transaction(|(signatures, words)| {
+            let previous = match &container {
+                Some(container) => signatures.insert(id.as_bytes(), container.as_slice())?,
+                None => signatures.remove(id.as_bytes())?,
+            };
+            if let Some(previous) = &previous {
+                let previous =
+                    Signature::decode(previous).map_err(ConflictableTransactionError::Abort)?;
+                update_words(words, id, &previous, false)?;
+            }
+            if let Some(signature) = signature {
+                update_words(words, id, signature, true)?;
+            }
+            Ok(previous.is_some())
+        });
+        result.map_err(|e| match e {
+            TransactionError::Abort(e) => StoreError::Signature(e),
+            TransactionError::Storage(e) => StoreError::Backend(e),
+        })
+    }
+}
+
+impl SignatureStore for SledSignatureStore {
+    type Error = StoreError<sled::Error>;
+
+    fn insert(&mut self, id: &str, signature: &Signature) -> Result<(), Self::Error> {
+        self.replace(id, Some(signature))?;
+        Ok(())
+    }
+
+    fn get(&mut self, id: &str) -> Result<Option<Signature>, Self::Error> {
+        let container = self.signatures.get(id).map_err(StoreError::Backend)?;
+        Ok(container.map(|container| Signature::decode(&container)).transpose()?)
+    }
+
+    fn delete(&mut self, id: &str) -> Result<bool, Self::Error> {
+        self.replace(id, None)
+    }
+
+    fn search_similar(
+        &mut self,
+        query: &Signature,
+        threshold: f64,
+        limit: usize,
+    ) -> Result<Vec<(String, f64)>, Self::Error> {
+        let mut ids = BTreeSet::new();
+        for (index, word) in store_words(query) {
+            let prefix = word_key(index, word, "");
+            for key in self.words.scan_prefix(&prefix).keys() {
+                let key = key.map_err(StoreError::Backend)?;
+                ids.insert(key[prefix.len()..].to_vec());
+            }
+        }
+
+        let mut candidates = Vec::with_capacity(ids.len());
+        for id in ids {
+            let Some(container) = self.signatures.get(&id).map_err(StoreError::Backend)? else {
+                continue;
+            };
+            let id = String::from_utf8(id)
+                .map_err(|_| SignatureError::InvalidEncoding { format: "sled key" })?;
+            candidates.push((id, Signature::decode(&container)?));
+        }
+        Ok(rank(query, candidates, threshold, limit))
+    }
+}
+
+/// Adds or removes the keys of a signature's words.
+fn update_words(
+    words: &TransactionalTree,
+    id: &str,
+    signature: &Signature,
+    add: bool,
+) -> Result<(), ConflictableTransactionError<SignatureError>> {
+    for (index, word) in store_words(signature) {
+        let key = word_key(index, word, id);
+        if add {
+            words.insert(key, &[])?;
+        } else {
+            words.remove(key)?;
+        }
+    }
+    Ok(())
+}
+
+/// The key of a word at `index` of the signature stored under `id`, big endian so that keys of
+/// the same word are adjacent.
+fn word_key(index: i32, word: i64, id: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(12 + id.len());
+    key.extend(index.to_be_bytes());
+    key.extend(word.to_be_bytes());
+    key.extend_from_slice(id.as_bytes());
+    key
+}
@@ -0,0 +1,150 @@
+use crate::{
+    check_buffer_length, compute_from_gray, pixel_gray, AlphaMode, GrayImage, GrayscaleMode,
+    SignatureConfig, SignatureError,
+};
+
+/// Anything that can provide the 8-bit gray levels of an image, pixel by pixel or row by row. This
+/// allows feeding the signature pipeline from tiled readers, GPU textures, or virtual images
+/// without first materializing an RGBA buffer. Pure white is represented by 255 and pure black
+/// by 0.
+pub trait PixelSource {
+    fn width(&self) -> usize;
+
+    fn height(&self) -> usize;
+
+    /// The gray level of the pixel at column `x` and row `y`. Both are always in bounds.
+    fn gray_at(&self, x: usize, y: usize) -> u8;
+
+    /// Fills `row`, which is exactly `width()` long, with the gray levels of row `y`. Override this
+    /// when whole rows can be produced faster than pixel by pixel.
+    fn gray_row(&self, y: usize, row: &mut [u8]) {
+        for (x, gray) in row.iter_mut().enumerate() {
+            *gray = self.gray_at(x, y);
+        }
+    }
+}
+
+/// A [PixelSource] over a buffer of 8-bit gray levels, one byte per pixel.
+#[derive(Clone, Copy, Debug)]
+pub struct GrayBuffer<'a> {
+    data: &'a [u8],
+    width: usize,
+}
+
+impl<'a> GrayBuffer<'a> {
+    /// Wraps a buffer of gray levels with the provided width. Returns an error if the buffer
+    /// doesn't hold a whole number of rows.
+    pub fn new(data: &'a [u8], width: usize) -> Result<Self, SignatureError> {
+        check_buffer_length(data.len(), width, 1)?;
+        Ok(GrayBuffer { data, width })
+    }
+}
+
+impl PixelSource for GrayBuffer<'_> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.data.len() / self.width
+    }
+
+    fn gray_at(&self, x: usize, y: usize) -> u8 {
+        self.data[y * self.width + x]
+    }
+
+    fn gray_row(&self, y: usize, row: &mut [u8]) {
+        row.copy_from_slice(&self.data[y * self.width..(y + 1) * self.width]);
+    }
+}
+
+/// A [PixelSource] over a buffer of conceptually grouped RGBA bytes. Pixels are converted to gray
+/// the same way as [SignatureConfig::compute] with default settings, unless overridden with
+/// [RgbaBuffer::grayscale_mode] or [RgbaBuffer::alpha_mode].
+#[derive(Clone, Copy, Debug)]
+pub struct RgbaBuffer<'a> {
+    data: &'a [u8],
+    width: usize,
+    conversion: SignatureConfig,
+}
+
+impl<'a> RgbaBuffer<'a> {
+    /// Wraps a buffer of RGBA pixels with the provided width. Returns an error if the buffer
+    /// doesn't hold a whole number of rows.
+    pub fn new(data: &'a [u8], width: usize) -> Result<Self, SignatureError> {
+        check_buffer_length(data.len(), width, 4)?;
+        Ok(RgbaBuffer { data, width, conversion: SignatureConfig::default() })
+    }
+
+    /// See [SignatureConfig::grayscale_mode].
+    pub fn grayscale_mode(mut self, grayscale_mode: GrayscaleMode) -> Self {
+        self.conversion = self.conversion.grayscale_mode(grayscale_mode);
+        self
+    }
+
+    /// See [SignatureConfig::alpha_mode].
+    pub fn alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.conversion = self.conversion.alpha_mode(alpha_mode);
+        self
+    }
+}
+
+impl PixelSource for RgbaBuffer<'_> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.data.len() / (4 * self.width)
+    }
+
+    fn gray_at(&self, x: usize, y: usize) -> u8 {
+        let idx = 4 * (y * self.width + x);
+        let p = &self.data[idx..idx + 4];
+        pixel_gray(p[0], p[1], p[2], p[3], (x, y), &self.conversion)
+    }
+}
+
+impl<S: PixelSource + ?Sized> PixelSource for &S {
+    fn width(&self) -> usize {
+        (**self).width()
+    }
+
+    fn height(&self) -> usize {
+        (**self).height()
+    }
+
+    fn gray_at(&self, x: usize, y: usize) -> u8 {
+        (**self).gray_at(x, y)
+    }
+
+    fn gray_row(&self, y: usize, row: &mut [u8]) {
+        (**self).gray_row(y, row)
+    }
+}
+
+impl SignatureConfig {
+    /// Produces a signature for an image read from a [PixelSource]. Sources do their own gray
+    /// conversion, so the pixel format, grayscale, and alpha options of this config don't apply.
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_source] for
+    /// details.
+    pub fn compute_source<S: PixelSource>(&self, source: S) -> Vec<i8> {
+        self.try_compute_source(source).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_source]. Returns an error if the image
+    /// is too small or too featureless to place the signature grid on.
+    pub fn try_compute_source<S: PixelSource>(&self, source: S) -> Result<Vec<i8>, SignatureError> {
+        let (width, height) = (source.width(), source.height());
+        let mut data = vec![0; width * height];
+        if width > 0 {
+            for (y, row) in data.chunks_exact_mut(width).enumerate() {
+                source.gray_row(y, row);
+            }
+        }
+        let gray = GrayImage::from_raw(data, width, height);
+
+        compute_from_gray(&gray, self)
+    }
+}
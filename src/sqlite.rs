@@ -0,0 +1,141 @@
+//! A [SignatureStore] in an SQLite database, for applications that want a single-file store.
+
+use std::path::Path;
+
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+
+use crate::store::{quote_identifier, rank, store_words};
+use crate::{Signature, SignatureStore, StoreError};
+
+/// The table [SqliteSignatureStore::new] keeps signatures in.
+pub const DEFAULT_SQLITE_TABLE: &str = "image_signatures";
+
+/// A [SignatureStore] in SQLite tables, embedded in the application rather than on a server.
+///
+/// The tables are laid out like [PgSignatureStore](crate::PgSignatureStore)'s: ids and their
+/// [Signature::encode] containers in one table, and their words in a second, named after the
+/// first with a `_words` suffix, with an index on the words. The database is switched to
+/// write-ahead logging, so searches can run in other connections while signatures are inserted.
+pub struct SqliteSignatureStore {
+    connection: Connection,
+    table: String,
+    words: String,
+}
+
+impl SqliteSignatureStore {
+    /// Opens or creates the database file at `path`, storing signatures in the
+    /// [DEFAULT_SQLITE_TABLE].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, rusqlite::Error> {
+        Self::new(Connection::open(path)?)
+    }
+
+    /// Stores signatures in the [DEFAULT_SQLITE_TABLE] of the database `connection` is open on,
+    /// creating the tables if they don't exist.
+    pub fn new(connection: Connection) -> Result<Self, rusqlite::Error> {
+        Self::with_table(connection, DEFAULT_SQLITE_TABLE)
+    }
+
+    /// Stores signatures in `table` and `{table}_words`, creating them if they don't exist, e.g.
+    /// to keep signatures computed with different parameters apart.
+    pub fn with_table(connection: Connection, table: &str) -> Result<Self, rusqlite::Error> {
+        let (table, words, index) = (
+            quote_identifier(table),
+            quote_identifier(&format!("{}_words", table)),
+            quote_identifier(&format!("{}_words_lookup", table)),
+        );
+        // Returns the new mode as a row, so it can't go through `execute_batch`.
+        connection.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        connection.execute_batch(&format!(
+            "PRAGMA foreign_keys = ON;
+            CREATE TABLE IF NOT EXISTS {table} (
+                id TEXT PRIMARY KEY,
+                signature BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS {words} (
+                id TEXT NOT NULL REFERENCES {table} (id) ON DELETE CASCADE,
+                word_index INTEGER NOT NULL,
+                word INTEGER NOT NULL,
+                PRIMARY KEY (id, word_index)
+            ) WITHOUT ROWID;
+            CREATE INDEX IF NOT EXISTS {index} ON {words} (word_index, word);"
+        ))?;
+        Ok(SqliteSignatureStore { connection, table, words })
+    }
+
+    /// Closes the store, returning its connection.
+    pub fn into_connection(self) -> Connection {
+        self.connection
+    }
+}
+
+impl SignatureStore for SqliteSignatureStore {
+    type Error = StoreError<rusqlite::Error>;
+
+    fn insert(&mut self, id: &str, signature: &Signature) -> Result<(), Self::Error> {
+        let transaction = self.connection.transaction().map_err(StoreError::Backend)?;
+        let insert = format!(
+            "INSERT INTO {} (id, signature) VALUES (?1, ?2)
+            ON CONFLICT (id) DO UPDATE SET signature = excluded.signature",
+            self.table
+        );
+        transaction.execute(&insert, params![id, signature.encode()])
+            .map_err(StoreError::Backend)?;
+        let delete = format!("DELETE FROM {} WHERE id = ?1", self.words);
+        transaction.execute(&delete, [id]).map_err(StoreError::Backend)?;
+        let insert_word = format!(
+            "INSERT INTO {} (id, word_index, word) VALUES (?1, ?2, ?3)",
+            self.words
+        );
+        {
+            let mut statement = transaction.prepare(&insert_word).map_err(StoreError::Backend)?;
+            for (index, word) in store_words(signature) {
+                statement.execute(params![id, index, word]).map_err(StoreError::Backend)?;
+            }
+        }
+        transaction.commit().map_err(StoreError::Backend)
+    }
+
+    fn get(&mut self, id: &str) -> Result<Option<Signature>, Self::Error> {
+        let select = format!("SELECT signature FROM {} WHERE id = ?1", self.table);
+        let container: Option<Vec<u8>> = self.connection
+            .query_row(&select, [id], |row| row.get(0))
+            .optional()
+            .map_err(StoreError::Backend)?;
+        Ok(container.map(|container| Signature::decode(&container)).transpose()?)
+    }
+
+    fn delete(&mut self, id: &str) -> Result<bool, Self::Error> {
+        let delete = format!("DELETE FROM {} WHERE id = ?1", self.table);
+        Ok(self.connection.execute(&delete, [id]).map_err(StoreError::Backend)? > 0)
+    }
+
+    fn search_similar(
+        &mut self,
+        query: &Signature,
+        threshold: f64,
+        limit: usize,
+    ) -> Result<Vec<(String, f64)>, Self::Error> {
+        let words = store_words(query);
+        let pairs = vec!["(?, ?)"; words.len()].join(", ");
+        let select = format!(
+            "SELECT id, signature FROM {} WHERE id IN (
+                SELECT id FROM {} WHERE (word_index, word) IN (VALUES {})
+            )",
+            self.table, self.words, pairs
+        );
+        let values = words.iter().flat_map(|(index, word)| [*index as i64, *word]);
+
+        let mut statement = self.connection.prepare(&select).map_err(StoreError::Backend)?;
+        let rows = statement
+            .query_map(params_from_iter(values), |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .map_err(StoreError::Backend)?;
+        let mut candidates = Vec::new();
+        for row in rows {
+            let (id, container) = row.map_err(StoreError::Backend)?;
+            candidates.push((id, Signature::decode(&container)?));
+        }
+        Ok(rank(query, candidates, threshold, limit))
+    }
+}
@@ -0,0 +1,132 @@
+//! The interface shared by the places signatures are kept and searched, and the word-based search
+//! they have in common.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[cfg(any(
+    feature = "postgres",
+    feature = "redis",
+    feature = "sled",
+    feature = "sqlite"
+))]
+use crate::{try_signature_words, DEFAULT_WORD_COUNT, DEFAULT_WORD_LENGTH};
+use crate::{Signature, SignatureError};
+
+/// A collection of signatures by id that finds those similar to a query, whether it's kept in
+/// memory, in an embedded database, or on a server.
+///
+/// Database backends index each signature by its [words](crate::signature_words), gather the
+/// signatures sharing at least one word with a query, and rescore them with cosine similarity,
+/// the scheme of the Python `image_match` library. Signatures computed with different
+/// [SignatureParams](crate::SignatureParams) than a query are never returned as similar to it.
+pub trait SignatureStore {
+    /// The error returned when the backing storage fails.
+    type Error;
+
+    /// Stores a signature under `id`, replacing any signature already stored under it.
+    fn insert(&mut self, id: &str, signature: &Signature) -> Result<(), Self::Error>;
+
+    /// The signature stored under `id`, if any.
+    fn get(&mut self, id: &str) -> Result<Option<Signature>, Self::Error>;
+
+    /// Removes the signature stored under `id`, returning whether there was one.
+    fn delete(&mut self, id: &str) -> Result<bool, Self::Error>;
+
+    /// Finds up to `limit` stored signatures with a cosine similarity of at least `threshold` to
+    /// `query`, returned as pairs of id and similarity, most similar first. Ties are broken in
+    /// favor of the smaller id.
+    fn search_similar(
+        &mut self,
+        query: &Signature,
+        threshold: f64,
+        limit: usize,
+    ) -> Result<Vec<(String, f64)>, Self::Error>;
+}
+
+/// An error from a [SignatureStore] backed by a database.
+#[derive(Debug)]
+pub enum StoreError<E> {
+    /// The database failed, with its error.
+    Backend(E),
+    /// A stored signature couldn't be decoded, e.g. because it was written by a newer version of
+    /// this crate.
+    Signature(SignatureError),
+}
+
+impl<E: Display> Display for StoreError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::Backend(e) => write!(f, "Signature store failed: {}", e),
+            StoreError::Signature(e) => write!(f, "Stored signature is invalid: {}", e),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for StoreError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StoreError::Backend(e) => Some(e),
+            StoreError::Signature(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<SignatureError> for StoreError<E> {
+    fn from(e: SignatureError) -> Self {
+        StoreError::Signature(e)
+    }
+}
+
+/// The words stores index a signature by, as integers paired with their position. Signatures
+/// shorter than the default word length or count, from the smallest grids, use shorter or fewer
+/// words.
+#[cfg(any(
+    feature = "postgres",
+    feature = "redis",
+    feature = "sled",
+    feature = "sqlite"
+))]
+pub(crate) fn store_words(signature: &[i8]) -> Vec<(i32, i64)> {
+    let word_length = DEFAULT_WORD_LENGTH.min(signature.len());
+    let word_count = DEFAULT_WORD_COUNT.min(signature.len());
+    try_signature_words(signature, word_length, word_count)
+        .map(|words| words.iter().enumerate().map(|(i, w)| (i as i32, w.to_int())).collect())
+        .unwrap_or_default()
+}
+
+/// Scores `candidates` against `query`, keeping the `limit` most similar of those at least
+/// `threshold` similar, as [SignatureStore::search_similar] returns them.
+#[cfg(any(
+    feature = "elasticsearch",
+    feature = "postgres",
+    feature = "redis",
+    feature = "sled",
+    feature = "sqlite"
+))]
+pub(crate) fn rank<I>(
+    query: &Signature,
+    candidates: I,
+    threshold: f64,
+    limit: usize,
+) -> Vec<(String, f64)>
+where
+    I: IntoIterator<Item = (String, Signature)>,
+{
+    let mut matches: Vec<(String, f64)> = candidates.into_iter()
+        .filter_map(|(id, candidate)| {
+            let similarity = query.try_similarity(&candidate).ok()?;
+            (similarity >= threshold).then_some((id, similarity))
+        })
+        .collect();
+    matches.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    matches.truncate(limit);
+    matches
+}
+
+/// Quotes a table or index name for SQL, so that any name can be used without opening the door to
+/// SQL injection.
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
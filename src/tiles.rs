@@ -0,0 +1,159 @@
+use crate::region::buffer_height;
+use crate::{Region, Signature, SignatureConfig, SignatureError};
+
+/// The signatures of the tiles of an image split into an even grid of columns and rows, to find
+/// images embedded in others, like a known photo within a collage or a screenshot. See
+/// [TiledSignature::matches].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TiledSignature {
+    columns: usize,
+    rows: usize,
+    tiles: Vec<Signature>,
+}
+
+/// A tile of one [TiledSignature] that matches a tile of another, each given as its column and
+/// row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileMatch {
+    pub left: (usize, usize),
+    pub right: (usize, usize),
+    pub similarity: f64,
+}
+
+impl TiledSignature {
+    /// Signs each of the tiles of an image of `width` by `height` pixels with `sign`, which is
+    /// given the tile's region of the image.
+    pub(crate) fn try_from_tiles<F>(
+        config: &SignatureConfig,
+        columns: usize,
+        rows: usize,
+        width: usize,
+        height: usize,
+        mut sign: F,
+    ) -> Result<Self, SignatureError>
+    where
+        F: FnMut(Region) -> Result<Vec<i8>, SignatureError>,
+    {
+        for (name, count, side) in [("columns", columns, width), ("rows", rows, height)] {
+            if count == 0 || count > side {
+                return Err(SignatureError::InvalidParameter {
+                    name,
+                    value: count.to_string(),
+                    supported: "1 to the image's size in pixels",
+                });
+            }
+        }
+
+        // Tiles start at `floor(i * side / count)`, so they differ in size by at most a pixel.
+        let edge = |i: usize, count: usize, side: usize| i * side / count;
+        let params = config.params();
+        let mut tiles = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            let (top, bottom) = (edge(row, rows, height), edge(row + 1, rows, height));
+            for column in 0..columns {
+                let left = edge(column, columns, width);
+                let right = edge(column + 1, columns, width);
+                let elements = sign(Region::pixels(left, top, right - left, bottom - top))?;
+                tiles.push(Signature::new(params, elements));
+            }
+        }
+        Ok(TiledSignature { columns, rows, tiles })
+    }
+
+    /// The number of columns the image was split into.
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// The number of rows the image was split into.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The signature of the tile at `column` and `row`, or `None` if it's outside the grid.
+    pub fn tile(&self, column: usize, row: usize) -> Option<&Signature> {
+        if column < self.columns && row < self.rows {
+            self.tiles.get(row * self.columns + column)
+        } else {
+            None
+        }
+    }
+
+    /// The signatures of every tile, row by row.
+    pub fn tiles(&self) -> &[Signature] {
+        &self.tiles
+    }
+
+    /// Compares every tile of this image to every tile of `other`, returning the pairs whose
+    /// similarity is at least `threshold`, such as [DEFAULT_MATCH_THRESHOLD], most similar first.
+    /// To find a known image within a collage, tile the collage in a grid fine enough for the image
+    /// to fill about one tile, and compare it against the image tiled 1 by 1.
+    ///
+    /// Panics if the signatures were computed with different parameters, see
+    /// [TiledSignature::try_matches] for a non-panicking version.
+    ///
+    /// [DEFAULT_MATCH_THRESHOLD]: crate::DEFAULT_MATCH_THRESHOLD
+    pub fn matches(&self, other: &TiledSignature, threshold: f64) -> Vec<TileMatch> {
+        self.try_matches(other, threshold).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [TiledSignature::matches] which returns
+    /// [SignatureError::IncompatibleSignatures] rather than crashing when the signatures were
+    /// computed with different parameters.
+    pub fn try_matches(
+        &self,
+        other: &TiledSignature,
+        threshold: f64,
+    ) -> Result<Vec<TileMatch>, SignatureError> {
+        let mut matches = Vec::new();
+        for (i, left) in self.tiles.iter().enumerate() {
+            for (j, right) in other.tiles.iter().enumerate() {
+                let similarity = left.try_similarity(right)?;
+                if similarity >= threshold {
+                    matches.push(TileMatch {
+                        left: (i % self.columns, i / self.columns),
+                        right: (j % other.columns, j / other.columns),
+                        similarity,
+                    });
+                }
+            }
+        }
+        matches.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        Ok(matches)
+    }
+}
+
+impl SignatureConfig {
+    /// Splits a provided image that's encoded as an array of conceptually grouped bytes in the
+    /// configured [PixelFormat](crate::PixelFormat) with the provided width into `columns` by
+    /// `rows` even tiles and signs each of them, as if it were cropped out with
+    /// [SignatureConfig::compute_region].
+    ///
+    /// Panics if a tile can't be signed, see [SignatureConfig::try_compute_tiles] for details.
+    pub fn compute_tiles(
+        &self,
+        rgba_buffer: &[u8],
+        width: usize,
+        columns: usize,
+        rows: usize,
+    ) -> TiledSignature {
+        self.try_compute_tiles(rgba_buffer, width, columns, rows)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_tiles]. Returns an error if the buffer
+    /// doesn't hold whole rows of pixels, if there are no columns or rows or more of them than
+    /// pixels, or if a tile is too small or too featureless to place the signature grid on.
+    pub fn try_compute_tiles(
+        &self,
+        rgba_buffer: &[u8],
+        width: usize,
+        columns: usize,
+        rows: usize,
+    ) -> Result<TiledSignature, SignatureError> {
+        let height = buffer_height(rgba_buffer, width, self.pixel_format.channels())?;
+        TiledSignature::try_from_tiles(self, columns, rows, width, height, |region| {
+            self.try_compute_region(rgba_buffer, width, region)
+        })
+    }
+}
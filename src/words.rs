@@ -0,0 +1,98 @@
+//! Words, short runs of a signature's elements reduced to their signs, which let near duplicates
+//! be found without comparing against every stored signature. Similar images share many words,
+//! so indexing signatures by their words and only comparing those sharing at least one with a
+//! query narrows a search to a handful of candidates. This is the scheme of the Python
+//! `image_match` library, whose Elasticsearch backend stores each word in its own field.
+
+use std::fmt::{Display, Formatter};
+
+use crate::SignatureError;
+
+/// The number of elements in each word, the `k` of the Python library's `make_record`.
+pub const DEFAULT_WORD_LENGTH: usize = 16;
+
+/// The number of words taken from each signature, the `N` of the Python library's `make_record`.
+pub const DEFAULT_WORD_COUNT: usize = 63;
+
+/// A word of a signature, each element reduced to -1, 0, or 1.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Word {
+    trits: Vec<i8>,
+}
+
+impl Word {
+    /// The word's elements, each -1, 0, or 1.
+    pub fn trits(&self) -> &[i8] {
+        &self.trits
+    }
+
+    /// The word read as a base 3 number with digits of one more than each element, first element
+    /// least significant, like the Python library's `words_to_int`. Distinct words of up to 39
+    /// elements have distinct integers; longer ones wrap around like NumPy's 64-bit integers.
+    pub fn to_int(&self) -> i64 {
+        self.trits.iter().rev().fold(0i64, |word, trit| {
+            word.wrapping_mul(3).wrapping_add((trit + 1) as i64)
+        })
+    }
+}
+
+/// Formats the word as its base 3 digits, one per element in order, e.g. `"2101"` for the
+/// elements 1, 0, -1, 0.
+impl Display for Word {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for trit in &self.trits {
+            write!(f, "{}", trit + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a signature into [DEFAULT_WORD_COUNT] overlapping words of [DEFAULT_WORD_LENGTH]
+/// elements, the Python library's defaults.
+///
+/// Panics if the signature is shorter than a word, see [try_signature_words] for a non-panicking
+/// version.
+pub fn signature_words<S: AsRef<[i8]>>(signature: S) -> Vec<Word> {
+    try_signature_words(signature, DEFAULT_WORD_LENGTH, DEFAULT_WORD_COUNT)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Splits a signature into `word_count` words of `word_length` elements, reduced to their signs.
+/// Words start at evenly spaced positions, `floor(i * len / word_count)` like the Python library's
+/// `get_words`, so they overlap whenever `word_count * word_length` exceeds the signature's
+/// length, and the last ones are filled in with zeros where they run past its end. Returns
+/// [SignatureError::InvalidParameter] if either is zero or longer than the signature.
+pub fn try_signature_words<S: AsRef<[i8]>>(
+    signature: S,
+    word_length: usize,
+    word_count: usize,
+) -> Result<Vec<Word>, SignatureError> {
+    let signature = signature.as_ref();
+    let supported = "1 to the signature length";
+    if word_length == 0 || word_length > signature.len() {
+        return Err(SignatureError::InvalidParameter {
+            name: "word_length",
+            value: word_length.to_string(),
+            supported,
+        });
+    }
+    if word_count == 0 || word_count > signature.len() {
+        return Err(SignatureError::InvalidParameter {
+            name: "word_count",
+            value: word_count.to_string(),
+            supported,
+        });
+    }
+
+    // `np.linspace(0, len, N, endpoint=False).astype(int)`.
+    let step = signature.len() as f64 / word_count as f64;
+    Ok((0..word_count)
+        .map(|i| {
+            let start = (i as f64 * step) as usize;
+            let trits = (start..start + word_length)
+                .map(|j| signature.get(j).map_or(0, |element| element.signum()))
+                .collect();
+            Word { trits }
+        })
+        .collect())
+}
@@ -0,0 +1,73 @@
+use crate::{compute_from_gray, gray_strided_rows, SignatureConfig, SignatureError};
+
+/// A frame of YUV video, e.g. as handed back by a hardware decoder. The luma (Y) plane already is
+/// the grayscale image the paper asks for, so only it is read. Chroma planes are accepted so frames
+/// can be passed along as they are, but are never touched.
+#[derive(Clone, Copy, Debug)]
+pub struct YuvFrame<'a> {
+    pub width: usize,
+    pub height: usize,
+    /// Distance in bytes between the starts of consecutive rows of the Y plane. Equal to `width`
+    /// for tightly packed planes.
+    pub y_stride: usize,
+    pub range: YuvRange,
+    pub planes: YuvPlanes<'a>,
+}
+
+/// The plane layout of a [YuvFrame].
+#[derive(Clone, Copy, Debug)]
+pub enum YuvPlanes<'a> {
+    /// Separate Y, U, and V planes, e.g. I420 or YV12.
+    Planar { y: &'a [u8], u: &'a [u8], v: &'a [u8] },
+    /// A Y plane followed by a single plane of interleaved U and V samples.
+    Nv12 { y: &'a [u8], uv: &'a [u8] },
+}
+
+/// The range of luma values used by a [YuvFrame].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum YuvRange {
+    /// Luma spans 16 to 235, as is standard for broadcast and most decoded video.
+    #[default]
+    Limited,
+    /// Luma spans the full 0 to 255, as in JPEG.
+    Full,
+}
+
+impl<'a> YuvFrame<'a> {
+    /// The frame's luma plane.
+    pub fn luma(&self) -> &'a [u8] {
+        match self.planes {
+            YuvPlanes::Planar { y, .. } => y,
+            YuvPlanes::Nv12 { y, .. } => y,
+        }
+    }
+}
+
+impl SignatureConfig {
+    /// Produces a signature for a YUV frame from its luma plane. Limited range luma is expanded to
+    /// the full range first, so the result is designed to be compared to other signatures computed
+    /// with an identical config from any input using [cosine-similarity(a, b)].
+    ///
+    /// Panics if the signature can't be computed, see [SignatureConfig::try_compute_yuv] for
+    /// details.
+    pub fn compute_yuv(&self, frame: &YuvFrame) -> Vec<i8> {
+        self.try_compute_yuv(frame).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A non-panicking version of [SignatureConfig::compute_yuv]. Returns an error if the luma
+    /// plane is too short for the frame's dimensions and stride, or if the image is too small or
+    /// too featureless to place the signature grid on.
+    pub fn try_compute_yuv(&self, frame: &YuvFrame) -> Result<Vec<i8>, SignatureError> {
+        let mut gray = gray_strided_rows(frame.luma(), frame.width, frame.height, frame.y_stride)?;
+        if frame.range == YuvRange::Limited {
+            gray.data_mut().iter_mut().for_each(|y| *y = expand_limited_luma(*y));
+        }
+
+        compute_from_gray(&gray, self)
+    }
+}
+
+fn expand_limited_luma(y: u8) -> u8 {
+    let expanded = ((y.clamp(16, 235) as u16 - 16) * 255 + 109) / 219;
+    expanded as u8
+}
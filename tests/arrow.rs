@@ -0,0 +1,42 @@
+#![cfg(feature = "arrow")]
+
+use arrow_array::{Array, FixedSizeBinaryArray};
+use image_match::{signature_record_batch, write_parquet, SignatureConfig, SIGNATURE_COLUMN};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+mod common;
+
+#[test]
+fn signatures_export_as_fixed_size_binary() {
+    let config = SignatureConfig::new();
+    let params = config.params();
+    let signatures: Vec<_> = [(120, 80), (64, 64), (90, 140)].iter()
+        .map(|(width, height)| {
+            config.compute_signature(&common::pattern_rgba(*width, *height), *width)
+        })
+        .collect();
+    let ids = ["a.png", "b.png", "c.png"];
+    let records = || ids.iter().copied().zip(&signatures);
+
+    let batch = signature_record_batch(&params, records()).unwrap();
+    assert_eq!(batch.num_rows(), 3);
+    assert_eq!(batch.schema().metadata()["image_match.grid_size"], "10");
+    let column = batch.column_by_name(SIGNATURE_COLUMN).unwrap();
+    let column = column.as_any().downcast_ref::<FixedSizeBinaryArray>().unwrap();
+    let elements: Vec<i8> = column.value(1).iter().map(|byte| *byte as i8).collect();
+    assert_eq!(elements, *signatures[1]);
+
+    let path = std::env::temp_dir().join("image-match-signatures.parquet");
+    write_parquet(std::fs::File::create(&path).unwrap(), &params, records()).unwrap();
+    let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap());
+    let reader = reader.unwrap();
+    assert_eq!(reader.schema().metadata(), batch.schema().metadata());
+    let batches: Vec<_> = reader.build().unwrap().map(Result::unwrap).collect();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].columns(), batch.columns());
+    std::fs::remove_file(path).unwrap();
+
+    let other = SignatureConfig::new().grid_size(7).params();
+    assert!(signature_record_batch(&other, records()).is_err());
+    assert!(write_parquet(Vec::new(), &other, records()).is_err());
+}
@@ -0,0 +1,277 @@
+use image_match::{
+    compute_batch, cosine_similarity, get_buffer_signature, get_buffer_signature_f32,
+    get_buffer_signature_u16, get_formatted_buffer_signature, get_gray_buffer_signature,
+    get_gray_buffer_signature_u16, get_region_signature, get_rgb_buffer_signature,
+    get_source_signature, get_strided_buffer_signature, get_yuv_signature,
+    try_get_buffer_signature, try_get_region_signature, AlphaMode, Arithmetic, GrayscaleMode,
+    PixelFormat, PixelSource, Region, RgbaBuffer, SignatureBuilder, SignatureConfig,
+    SignatureError, Tonemap, YuvFrame, YuvPlanes, YuvRange,
+};
+
+mod common;
+
+#[test]
+fn gray_buffer_matches_equivalent_rgba() {
+    let rgba = common::pattern_rgba(120, 80);
+    let gray: Vec<u8> = rgba.chunks_exact(4)
+        .map(|p| ((p[0] as u16 + p[1] as u16 + p[2] as u16) / 3) as u8)
+        .collect();
+
+    assert_eq!(get_gray_buffer_signature(&gray, 120), get_buffer_signature(&rgba, 120));
+}
+
+#[test]
+fn rgb_buffer_matches_opaque_rgba() {
+    let rgba = common::pattern_rgba(120, 80);
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+
+    assert_eq!(get_rgb_buffer_signature(&rgb, 120), get_buffer_signature(&rgba, 120));
+}
+
+#[test]
+fn luma_weights_only_matter_for_color() {
+    let rgba = common::pattern_rgba(120, 80);
+    let neutral: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[0], p[0], 255]).collect();
+    let rec601 = SignatureConfig::new().grayscale_mode(GrayscaleMode::Rec601);
+
+    assert_eq!(rec601.compute(&neutral, 120), get_buffer_signature(&neutral, 120));
+    assert_ne!(rec601.compute(&rgba, 120), get_buffer_signature(&rgba, 120));
+}
+
+#[test]
+fn strided_buffer_matches_packed() {
+    let rgba = common::pattern_rgba(120, 80);
+    let stride = 120 * 4 + 24;
+    let mut padded = vec![0xAB_u8; stride * 80];
+    for (y, row) in rgba.chunks_exact(120 * 4).enumerate() {
+        padded[y * stride..y * stride + row.len()].copy_from_slice(row);
+    }
+
+    assert_eq!(
+        get_strided_buffer_signature(&padded, 120, 80, stride),
+        get_buffer_signature(&rgba, 120)
+    );
+    assert_eq!(
+        get_strided_buffer_signature(&padded[..stride * 79 + 120 * 4], 120, 80, stride),
+        get_buffer_signature(&rgba, 120)
+    );
+}
+
+#[test]
+fn regions_sign_like_cropped_buffers() {
+    let rgba = common::pattern_rgba(200, 100);
+    let cropped: Vec<u8> = rgba.chunks_exact(200 * 4)
+        .skip(20)
+        .take(60)
+        .flat_map(|row| row[50 * 4..150 * 4].to_vec())
+        .collect();
+    let expected = get_buffer_signature(&cropped, 100);
+
+    assert_eq!(get_region_signature(&rgba, 200, Region::pixels(50, 20, 100, 60)), expected);
+    assert_eq!(get_region_signature(&rgba, 200, Region::relative(0.25, 0.2, 0.5, 0.6)), expected);
+    for region in [Region::pixels(150, 20, 51, 60), Region::relative(0.5, 0.5, 0.5, 0.6)] {
+        assert!(matches!(
+            try_get_region_signature(&rgba, 200, region),
+            Err(SignatureError::RegionOutOfBounds { width: 200, height: 100, .. })
+        ));
+    }
+    assert!(matches!(
+        try_get_region_signature(&rgba, 200, Region::pixels(0, 0, 0, 60)),
+        Err(SignatureError::RegionOutOfBounds { .. })
+    ));
+}
+
+#[test]
+fn sixteen_bit_buffers_match_eight_bit() {
+    let rgba = common::pattern_rgba(120, 80);
+    let rgba16: Vec<u16> = rgba.iter().map(|c| *c as u16 * 257).collect();
+    let gray16: Vec<u16> = rgba.chunks_exact(4)
+        .map(|p| (p[0] as u16 + p[1] as u16 + p[2] as u16) / 3 * 257)
+        .collect();
+
+    let expected = get_buffer_signature(&rgba, 120);
+    assert!(cosine_similarity(get_buffer_signature_u16(&rgba16, 120), &expected) > 0.95);
+    assert!(cosine_similarity(get_gray_buffer_signature_u16(&gray16, 120), &expected) > 0.95);
+}
+
+#[test]
+fn float_buffers_are_tonemapped() {
+    let rgba = common::pattern_rgba(120, 80);
+    let rgba32: Vec<f32> = rgba.iter().map(|c| *c as f32 / 255.0).collect();
+    let overexposed: Vec<f32> = rgba32.iter().map(|c| c * 4.0).collect();
+
+    let expected = get_buffer_signature(&rgba, 120);
+    assert!(cosine_similarity(get_buffer_signature_f32(&rgba32, 120), &expected) > 0.95);
+
+    let corrected = SignatureConfig::new().tonemap(Tonemap::Exposure(0.25));
+    assert!(cosine_similarity(corrected.compute_f32(&overexposed, 120), &expected) > 0.95);
+}
+
+#[test]
+fn full_range_luma_matches_gray_buffer() {
+    let rgba = common::pattern_rgba(120, 80);
+    let gray: Vec<u8> = rgba.chunks_exact(4)
+        .map(|p| ((p[0] as u16 + p[1] as u16 + p[2] as u16) / 3) as u8)
+        .collect();
+    let chroma = vec![128_u8; 60 * 40 * 2];
+    let frame = YuvFrame {
+        width: 120,
+        height: 80,
+        y_stride: 120,
+        range: YuvRange::Full,
+        planes: YuvPlanes::Nv12 { y: &gray, uv: &chroma },
+    };
+
+    assert_eq!(get_yuv_signature(&frame), get_gray_buffer_signature(&gray, 120));
+}
+
+#[test]
+fn channel_orders_match_rgba() {
+    let rgba = common::pattern_rgba(120, 80);
+    let expected = get_buffer_signature(&rgba, 120);
+    let reorder = |order: [usize; 4]| -> Vec<u8> {
+        rgba.chunks_exact(4).flat_map(|p| order.map(|c| p[c])).collect()
+    };
+
+    let bgra = reorder([2, 1, 0, 3]);
+    assert_eq!(get_formatted_buffer_signature(&bgra, 120, PixelFormat::Bgra), expected);
+    let argb = reorder([3, 0, 1, 2]);
+    assert_eq!(get_formatted_buffer_signature(&argb, 120, PixelFormat::Argb), expected);
+    let mut xrgb = reorder([3, 0, 1, 2]);
+    xrgb.chunks_exact_mut(4).for_each(|p| p[0] = 0);
+    assert_eq!(get_formatted_buffer_signature(&xrgb, 120, PixelFormat::Xrgb), expected);
+}
+
+#[test]
+fn transparency_composites_over_background() {
+    let mut logo = common::pattern_rgba(120, 80);
+    let mut flattened = logo.clone();
+    for (idx, pixel) in logo.chunks_exact_mut(4).enumerate() {
+        if (idx % 120) < 30 || (idx / 120) > 60 {
+            pixel[3] = 0;
+            flattened[idx * 4..idx * 4 + 4].copy_from_slice(&[255, 255, 255, 255]);
+        }
+    }
+
+    let over_white = SignatureConfig::new().alpha_mode(AlphaMode::Background([255, 255, 255]));
+    assert_eq!(over_white.compute(&logo, 120), get_buffer_signature(&flattened, 120));
+    assert_ne!(get_buffer_signature(&logo, 120), get_buffer_signature(&flattened, 120));
+}
+
+struct Gradient;
+
+impl PixelSource for Gradient {
+    fn width(&self) -> usize {
+        120
+    }
+
+    fn height(&self) -> usize {
+        80
+    }
+
+    fn gray_at(&self, x: usize, y: usize) -> u8 {
+        ((x * 7 + y * 13 + (x * y) / 5) % 256) as u8
+    }
+}
+
+#[test]
+fn pixel_sources_match_buffers() {
+    let gray: Vec<u8> = (0..80)
+        .flat_map(|y| (0..120).map(move |x| Gradient.gray_at(x, y)))
+        .collect();
+    assert_eq!(get_source_signature(Gradient), get_gray_buffer_signature(&gray, 120));
+
+    let rgba = common::pattern_rgba(120, 80);
+    let source = RgbaBuffer::new(&rgba, 120).unwrap();
+    assert_eq!(get_source_signature(source), get_buffer_signature(&rgba, 120));
+}
+
+#[test]
+fn vectorized_grayscale_matches_per_pixel() {
+    // Noise with varying alpha, so every channel combination the kernels see differs.
+    let (width, height) = (123, 81);
+    let rgba: Vec<u8> = (0..4 * width * height as u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+        .collect();
+    let width = width as usize;
+
+    let modes = [
+        GrayscaleMode::Average,
+        GrayscaleMode::Rec601,
+        GrayscaleMode::Rec709,
+        GrayscaleMode::Custom([0.5, 0.5, 0.5]),
+    ];
+    let alphas = [AlphaMode::Ignore, AlphaMode::Multiply, AlphaMode::Background([20, 200, 90])];
+    for mode in modes {
+        for alpha in alphas {
+            let config = SignatureConfig::new().grayscale_mode(mode).alpha_mode(alpha);
+            let source = RgbaBuffer::new(&rgba, width).unwrap()
+                .grayscale_mode(mode)
+                .alpha_mode(alpha);
+            assert_eq!(
+                config.compute(&rgba, width),
+                config.compute_source(source),
+                "{:?} {:?}",
+                mode,
+                alpha
+            );
+        }
+    }
+}
+
+#[test]
+fn streamed_rows_match_whole_buffer() {
+    let rgba = common::pattern_rgba(120, 80);
+    let config = SignatureConfig::new().alpha_mode(AlphaMode::Checkerboard);
+
+    let mut builder = config.builder(120);
+    for rows in rgba.chunks(4 * 120 * 7) {
+        builder.push_rows(rows).unwrap();
+    }
+    assert_eq!(builder.rows(), 80);
+    assert_eq!(builder.finish().unwrap(), config.compute(&rgba, 120));
+
+    let mut builder = SignatureBuilder::new(120);
+    assert!(builder.push_rows(&rgba[..4 * 119]).is_err());
+    assert!(builder.finish().is_err());
+}
+
+#[test]
+fn reused_computer_matches_fresh_config() {
+    let config = SignatureConfig::new().grid_size(7);
+    let mut computer = config.computer();
+    for (width, height) in [(120, 80), (64, 300), (120, 80)] {
+        let rgba = common::pattern_rgba(width, height);
+        assert_eq!(computer.compute(&rgba, width), config.compute(&rgba, width));
+    }
+    assert!(computer.try_compute(&[0; 4 * 10], 3).is_err());
+}
+
+#[test]
+fn batch_matches_one_at_a_time() {
+    let mut images: Vec<(Vec<u8>, usize)> = (0..12)
+        .map(|i| (common::pattern_rgba(100 + i * 7, 80), 100 + i * 7))
+        .collect();
+    images[5] = (vec![0; 4 * 10], 3);
+
+    let signatures = compute_batch(&images);
+    assert_eq!(signatures.len(), images.len());
+    for ((buffer, width), signature) in images.iter().zip(signatures) {
+        assert_eq!(signature, try_get_buffer_signature(buffer, *width));
+    }
+}
+
+#[test]
+fn integer_arithmetic_stays_close_to_float() {
+    let rgba = common::pattern_rgba(160, 120);
+    let modes = [GrayscaleMode::Average, GrayscaleMode::Rec601, GrayscaleMode::Custom([0.2; 3])];
+    let alphas = [AlphaMode::Multiply, AlphaMode::Background([255; 3]), AlphaMode::Checkerboard];
+    for mode in modes {
+        for alpha in alphas {
+            let config = SignatureConfig::new().grayscale_mode(mode).alpha_mode(alpha);
+            let float = config.compute(&rgba, 160);
+            let integer = config.arithmetic(Arithmetic::Integer).compute(&rgba, 160);
+            assert!(cosine_similarity(&float, &integer) > 0.95, "{:?} {:?}", mode, alpha);
+        }
+    }
+}
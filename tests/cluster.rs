@@ -0,0 +1,117 @@
+use image_match::index::{LinearIndex, LshIndex};
+use image_match::{
+    centroid, cluster_duplicates, cluster_duplicates_with, cosine_similarity, similarity_matrix,
+    try_centroid, SignatureError,
+};
+
+/// Unrelated signature-like vectors, then a chain of three edits of each of the first few, each
+/// edit changing every fourth element of the one before it from a different offset.
+fn signatures() -> Vec<Vec<i8>> {
+    let mut state = 0x2545_f491_u32;
+    let mut signatures: Vec<Vec<i8>> = (0..60)
+        .map(|_| {
+            (0..544)
+                .map(|_| {
+                    state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                    ((state >> 24) % 5) as i8 - 2
+                })
+                .collect()
+        })
+        .collect();
+    for original in 0..6 {
+        let mut edited = signatures[original].clone();
+        for offset in 0..3 {
+            for element in edited.iter_mut().skip(offset).step_by(4) {
+                *element = -*element;
+            }
+            signatures.push(edited.clone());
+        }
+    }
+    signatures
+}
+
+/// The connected components of the graph linking signatures at least `threshold` similar,
+/// found by comparing every pair.
+fn components(signatures: &[Vec<i8>], threshold: f64) -> Vec<Vec<usize>> {
+    let n = signatures.len();
+    let matrix = similarity_matrix(signatures);
+    let similar = |i: usize, j: usize| {
+        let (i, j) = (i.min(j), i.max(j));
+        i != j && matrix[n * i - i * (i + 1) / 2 + (j - i - 1)] >= threshold
+    };
+    let mut component = vec![None; n];
+    let mut components = Vec::new();
+    for start in 0..n {
+        if component[start].is_some() {
+            continue;
+        }
+        component[start] = Some(components.len());
+        let mut members = vec![start];
+        let mut next = 0;
+        while next < members.len() {
+            let member = members[next];
+            next += 1;
+            for (other, other_component) in component.iter_mut().enumerate() {
+                if other_component.is_none() && similar(member, other) {
+                    *other_component = Some(components.len());
+                    members.push(other);
+                }
+            }
+        }
+        members.sort_unstable();
+        components.push(members);
+    }
+    components.retain(|members| members.len() > 1);
+    components
+}
+
+#[test]
+fn duplicates_cluster_into_the_components_of_their_similarity_graph() {
+    let signatures = signatures();
+    let expected = components(&signatures, 0.4);
+    assert_eq!(expected.len(), 6);
+    assert_eq!(expected[0], [0, 60, 61, 62]);
+    // The last edit of each chain is only similar enough to the one before it.
+    let matrix = similarity_matrix(&signatures);
+    assert!(matrix[62 - 1] < 0.4);
+
+    assert_eq!(cluster_duplicates_with(LinearIndex::new(), &signatures, 0.4), expected);
+    assert_eq!(cluster_duplicates(&signatures, 0.4), expected);
+    // Edits all over a signature change most of its words, so few duplicates share one.
+    let lsh = cluster_duplicates_with(LshIndex::new(), &signatures, 0.4);
+    assert!(lsh.len() < 6);
+    for cluster in lsh {
+        assert!(expected.iter().any(|e| cluster.iter().all(|key| e.contains(key))));
+    }
+    assert!(cluster_duplicates(&signatures, 1.1).is_empty());
+}
+
+#[test]
+fn centroids_are_element_wise_medians_that_represent_their_clusters() {
+    let odd: [&[i8]; 3] = [&[-2, 0, 2, 1], &[-1, 0, 2, -2], &[2, 1, -1, 1]];
+    assert_eq!(centroid(&odd), [-1, 0, 2, 1]);
+    let even: [&[i8]; 4] = [&[-2, 2, 1, 0], &[-1, 2, 2, 0], &[1, -2, 2, 1], &[2, -2, 2, 2]];
+    assert_eq!(centroid(&even), [0, 0, 2, 0]);
+    let negated: Vec<Vec<i8>> = even.iter().map(|s| s.iter().map(|e| -e).collect()).collect();
+    assert_eq!(centroid(&negated), [0, 0, -2, 0]);
+    assert!(centroid::<Vec<i8>>(&[]).is_empty());
+    assert!(try_centroid(&[&[0i8, 1][..], &[0]]).is_err());
+    assert_eq!(
+        try_centroid(&[[0i8, 3]]),
+        Err(SignatureError::ElementOutOfRange { index: 1, value: 3 })
+    );
+
+    // Light edits of one original, each changing different elements, are centered on it.
+    let original = &signatures()[0];
+    let mut cluster = vec![original.clone()];
+    for offset in 0..3 {
+        let mut edited = original.clone();
+        for element in edited.iter_mut().skip(offset).step_by(8) {
+            *element = -*element;
+        }
+        cluster.push(edited);
+    }
+    assert_eq!(&centroid(&cluster), original);
+    assert_eq!(&centroid(&cluster[1..]), original);
+    assert!(cosine_similarity(&cluster[1], &cluster[2]) < 0.9);
+}
@@ -0,0 +1,37 @@
+#![allow(dead_code)]
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// A deterministic RGBA image with enough structure for every grid point to differ from its
+/// neighbors.
+pub fn pattern_rgba(width: usize, height: usize) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let v = ((x * 7 + y * 13 + (x * y) / 5) % 256) as u8;
+            buffer.extend_from_slice(&[v, v.wrapping_mul(3), 255 - v, 255]);
+        }
+    }
+    buffer
+}
+
+/// Serves one response with `status` and `body` on a local port and returns its URL.
+pub fn serve_once(status: &'static str, body: Vec<u8>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/image", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request);
+        let head = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            body.len()
+        );
+        let _ = stream.write_all(head.as_bytes());
+        let _ = stream.write_all(&body);
+    });
+    url
+}
@@ -0,0 +1,258 @@
+use image_match::{
+    checked_cosine_similarity, cosine_similarity, get_buffer_signature, is_flat_signature,
+    normalized_distance, similarities, similarity_at_least, similarity_matrix, top_k,
+    try_similarities, weighted_similarity, AlphaMode, Arithmetic, Cosine, DistanceMetric,
+    Euclidean, GrayscaleMode, Manhattan, MatchClass, MatchPolicy, NormalizedDistance,
+    PackedSignature, Signature, SignatureConfig, SignatureError,
+};
+
+mod common;
+
+#[test]
+fn normalized_distance_bounds() {
+    let a = get_buffer_signature(&common::pattern_rgba(120, 80), 120);
+    let negated: Vec<i8> = a.iter().map(|v| -v).collect();
+
+    assert_eq!(normalized_distance(&a, &a), 0.0);
+    assert!((normalized_distance(&a, &negated) - 1.0).abs() < 1e-12);
+    assert_eq!(normalized_distance([0, 0], [0, 0]), 0.0);
+    assert!((cosine_similarity(&a, &negated) + 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn similarities_match_pairwise() {
+    let query = get_buffer_signature(&common::pattern_rgba(120, 80), 120);
+    let candidates = vec![
+        query.clone(),
+        get_buffer_signature(&common::pattern_rgba(100, 90), 100),
+        vec![0; query.len()],
+    ];
+
+    let expected: Vec<f64> = candidates.iter().map(|c| cosine_similarity(&query, c)).collect();
+    assert_eq!(similarities(&query, &candidates), expected);
+    assert!(try_similarities(&query, &[vec![0i8; 3]]).is_err());
+}
+
+#[test]
+fn similarity_matrix_is_condensed() {
+    let signatures: Vec<Vec<i8>> = [(120, 80), (100, 90), (90, 100), (80, 120)].iter()
+        .map(|(width, height)| get_buffer_signature(&common::pattern_rgba(*width, *height), *width))
+        .collect();
+
+    let matrix = similarity_matrix(&signatures);
+    assert_eq!(matrix.len(), 6);
+
+    let n = signatures.len();
+    for i in 0..n {
+        for j in i + 1..n {
+            let index = n * i - i * (i + 1) / 2 + (j - i - 1);
+            assert_eq!(matrix[index], cosine_similarity(&signatures[i], &signatures[j]));
+        }
+    }
+    assert!(similarity_matrix::<Vec<i8>>(&[]).is_empty());
+}
+
+#[test]
+fn top_k_orders_best_first() {
+    let query = get_buffer_signature(&common::pattern_rgba(120, 80), 120);
+    let mut perturbed = query.clone();
+    perturbed[..50].iter_mut().for_each(|v| *v = 0);
+    let candidates = vec![
+        vec![0; query.len()],
+        perturbed,
+        query.clone(),
+        query.clone(),
+    ];
+
+    let best = top_k(&query, &candidates, 3);
+    assert_eq!(best.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![2, 3, 1]);
+    assert_eq!(best[2].1, cosine_similarity(&query, &candidates[1]));
+    assert_eq!(top_k(&query, &candidates, 10).len(), 4);
+    assert!(top_k(&query, &candidates, 0).is_empty());
+}
+
+#[test]
+fn match_policy_classifies() {
+    let a = get_buffer_signature(&common::pattern_rgba(120, 80), 120);
+    let mut near = a.clone();
+    near[..20].iter_mut().for_each(|v| *v = 0);
+    let negated: Vec<i8> = a.iter().map(|v| -v).collect();
+
+    let policy = MatchPolicy::default();
+    assert_eq!(policy.classify(&a, &a), MatchClass::Identical);
+    assert_eq!(policy.classify(&a, &near), MatchClass::NearDuplicate);
+    assert_eq!(policy.classify(&a, &negated), MatchClass::Different);
+    assert_eq!(policy.classify(&a, [0i8; 3]), MatchClass::Different);
+    assert!(policy.is_match(&a, &near));
+    assert!(!MatchPolicy::new(1.0).is_match(&a, &near));
+
+    let lenient = MatchPolicy { require_same_params: false, ..policy };
+    assert!(lenient.try_classify(&a, [0i8; 3]).is_err());
+}
+
+#[test]
+fn weighted_similarity_discounts_shared_zeros() {
+    let a = [0, 0, 0, 0, 0, 0, 2, -1];
+    let b = [0, 0, 0, 0, 0, 0, -2, 1];
+
+    assert_eq!(weighted_similarity(a, b, 1.0), 0.75);
+    assert_eq!(weighted_similarity(a, b, 0.0), 0.0);
+    assert_eq!(weighted_similarity(a, a, 0.0), 1.0);
+    assert_eq!(weighted_similarity([0, 0], [0, 0], 0.0), 1.0);
+    assert_eq!(weighted_similarity([2, 0], [1, 0], 0.0), 1.0 - 1.0 / 3.0);
+}
+
+#[test]
+fn similarity_at_least_agrees_with_cosine() {
+    let a = get_buffer_signature(&common::pattern_rgba(120, 80), 120);
+    let b = get_buffer_signature(&common::pattern_rgba(100, 90), 100);
+    let negated: Vec<i8> = a.iter().map(|v| -v).collect();
+    let similarity = cosine_similarity(&a, &b);
+
+    assert_eq!(similarity_at_least(&a, &b, similarity), Some(similarity));
+    assert_eq!(similarity_at_least(&a, &b, similarity + 1e-9), None);
+    assert_eq!(similarity_at_least(&a, &a, 0.6), Some(cosine_similarity(&a, &a)));
+    assert_eq!(similarity_at_least(&a, &negated, 0.6), None);
+    assert_eq!(similarity_at_least([0, 0], [0, 0], 1.0), Some(1.0));
+    assert_eq!(similarity_at_least([0, 0], [1, 0], 0.0), Some(0.0));
+}
+
+#[test]
+fn distance_metrics() {
+    let (a, b) = ([2i8, -1, 0, 1], [0i8, 1, 0, -1]);
+    let metrics: [&dyn DistanceMetric; 4] = [&Cosine, &Euclidean, &Manhattan, &NormalizedDistance];
+    for metric in metrics {
+        assert_eq!(metric.distance(&a, &a), 0.0);
+        assert!(metric.try_distance(&a, &[0]).is_err());
+    }
+
+    assert_eq!(Cosine.distance(&a, &b), 1.0 - cosine_similarity(a, b));
+    assert_eq!(Euclidean.distance(&a, &b), 12f64.sqrt());
+    assert_eq!(Manhattan.distance(&a, &b), 6.0);
+    assert_eq!(NormalizedDistance.distance(&a, &b), normalized_distance(a, b));
+}
+
+#[test]
+fn kernels_are_exact_over_the_i8_range() {
+    const LEN: usize = 64;
+    for x in i8::MIN..=i8::MAX {
+        for y in i8::MIN..=i8::MAX {
+            let (a, b) = ([x; LEN], [y; LEN]);
+            let (xf, yf) = (x as f64, y as f64);
+            let diff = (xf - yf).abs();
+
+            let cosine = match (x, y) {
+                (0, 0) => 1.0,
+                (0, _) | (_, 0) => 0.0,
+                _ => (xf * yf).signum(),
+            };
+            assert_eq!(cosine_similarity(a, b), cosine, "{} {}", x, y);
+            assert_eq!(Manhattan.distance(&a, &b), LEN as f64 * diff, "{} {}", x, y);
+
+            let euclidean = diff * (LEN as f64).sqrt();
+            assert!((Euclidean.distance(&a, &b) - euclidean).abs() < 1e-9, "{} {}", x, y);
+
+            let normalized = if x == 0 && y == 0 { 0.0 } else { diff / (xf.abs() + yf.abs()) };
+            assert!((normalized_distance(a, b) - normalized).abs() < 1e-12, "{} {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn kernels_handle_every_length() {
+    let values: Vec<i8> = (0..1000u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as i8).collect();
+    for len in [0, 1, 15, 31, 32, 33, 64, 100, 544, 1000] {
+        let (a, b) = (&values[..len], &values[1000 - len..]);
+        let dot: i64 = a.iter().zip(b).map(|(x, y)| *x as i64 * *y as i64).sum();
+        let squares = |v: &[i8]| v.iter().map(|x| *x as i64 * *x as i64).sum::<i64>() as f64;
+
+        let expected = match (squares(a), squares(b)) {
+            (0.0, 0.0) => 1.0,
+            (sa, sb) if sa == 0.0 || sb == 0.0 => 0.0,
+            (sa, sb) => dot as f64 / (sa * sb).sqrt(),
+        };
+        assert_eq!(cosine_similarity(a, b), expected, "{}", len);
+    }
+}
+
+#[test]
+fn packed_signatures_match_unpacked() {
+    let values: Vec<i8> = (0..700u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 24) as i8 % 3)
+        .collect();
+    for len in [0, 1, 63, 64, 65, 544, 700] {
+        let (a, b) = (&values[..len], &values[700 - len..]);
+        let (packed_a, packed_b) = (PackedSignature::new(a), PackedSignature::new(b));
+        assert_eq!(packed_a.unpack(), a);
+        assert_eq!(packed_a.similarity(&packed_b), cosine_similarity(a, b), "{}", len);
+    }
+
+    let mismatched = PackedSignature::new(&values[..10]).try_similarity(&PackedSignature::new(&[]));
+    assert_eq!(mismatched, Err(SignatureError::MismatchedLengths { left: 10, right: 0 }));
+    assert_eq!(
+        PackedSignature::try_new(&[0, 2, -3]),
+        Err(SignatureError::ElementOutOfRange { index: 2, value: -3 })
+    );
+}
+
+#[test]
+fn flat_signatures_have_no_similarity() {
+    let flat = get_buffer_signature(&vec![128; 4 * 120 * 80], 120);
+    let pattern = get_buffer_signature(&common::pattern_rgba(120, 80), 120);
+    assert!(is_flat_signature(&flat));
+    assert_eq!(checked_cosine_similarity(&flat, &pattern), None);
+    assert_eq!(checked_cosine_similarity(&pattern, &pattern), Some(1.0));
+
+    let rejecting = SignatureConfig::new().reject_flat_images(true);
+    assert_eq!(
+        rejecting.try_compute(&vec![128; 4 * 120 * 80], 120),
+        Err(SignatureError::FlatImage)
+    );
+    assert_eq!(rejecting.try_compute(&common::pattern_rgba(120, 80), 120), Ok(pattern));
+}
+
+#[test]
+fn signatures_check_their_params() {
+    let rgba = common::pattern_rgba(120, 80);
+    let config = SignatureConfig::new();
+    let a = config.compute_signature(&rgba, 120);
+    assert_eq!(a.params(), &config.params());
+    assert_eq!(a.similarity(&a), cosine_similarity(&a, config.compute(&rgba, 120)));
+
+    let cropped = config.crop(0.1).compute_signature(&rgba, 120);
+    assert_eq!(
+        a.try_similarity(&cropped),
+        Err(SignatureError::IncompatibleSignatures {
+            name: "crop",
+            left: "0.05".to_string(),
+            right: "0.1".to_string(),
+        })
+    );
+    let finer = config.grid_size(12).compute_signature(&rgba, 120);
+    assert!(matches!(
+        a.try_similarity(&finer),
+        Err(SignatureError::IncompatibleSignatures { name: "grid_size", .. })
+    ));
+    let luma = config.grayscale_mode(GrayscaleMode::Rec709).compute_signature(&rgba, 120);
+    assert!(matches!(
+        a.try_similarity(&luma),
+        Err(SignatureError::IncompatibleSignatures { name: "grayscale_mode", .. })
+    ));
+    let composited = config.alpha_mode(AlphaMode::Checkerboard).compute_signature(&rgba, 120);
+    assert!(matches!(
+        a.try_similarity(&composited),
+        Err(SignatureError::IncompatibleSignatures { name: "alpha_mode", .. })
+    ));
+    let integer = config.arithmetic(Arithmetic::Integer).compute_signature(&rgba, 120);
+    assert!(matches!(
+        a.try_similarity(&integer),
+        Err(SignatureError::IncompatibleSignatures { name: "arithmetic", .. })
+    ));
+
+    let reloaded = Signature::new(*a.params(), a.to_vec());
+    assert_eq!(reloaded, a);
+    assert_eq!(
+        Signature::try_new(*a.params(), vec![0; 10]),
+        Err(SignatureError::MismatchedLengths { left: 10, right: a.len() })
+    );
+}
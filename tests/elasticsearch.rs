@@ -0,0 +1,43 @@
+#![cfg(feature = "elasticsearch")]
+
+use image_match::{EsSignatureStore, SignatureConfig, SignatureStore};
+use reqwest::blocking::Client;
+
+mod common;
+
+#[test]
+fn elasticsearch_stores_find_similar_signatures() {
+    let Ok(url) = std::env::var("IMAGE_MATCH_ELASTICSEARCH_URL") else {
+        eprintln!("IMAGE_MATCH_ELASTICSEARCH_URL isn't set, skipping");
+        return;
+    };
+    let config = SignatureConfig::new();
+    let url = url.parse().unwrap();
+    let index = "image-match-test";
+    let mut store = EsSignatureStore::with_index(Client::new(), url, index, config.params());
+
+    let original = common::pattern_rgba(120, 80);
+    let mut edited = original.clone();
+    edited[..4 * 120 * 8].fill(0);
+    let signature = config.compute_signature(&original, 120);
+    let similar = config.compute_signature(&edited, 120);
+    let other = config.compute_signature(&common::pattern_rgba(333, 517), 333);
+
+    store.insert("original.png", &signature).unwrap();
+    store.insert("photos/edited.png", &other).unwrap();
+    store.insert("photos/edited.png", &similar).unwrap();
+    store.insert("other.png", &other).unwrap();
+    store.refresh().unwrap();
+    assert_eq!(store.get("photos/edited.png").unwrap(), Some(similar.clone()));
+    assert_eq!(store.get("missing.png").unwrap(), None);
+
+    let matches = store.search_similar(&signature, 0.5, 10).unwrap();
+    let ids: Vec<_> = matches.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, ["original.png", "photos/edited.png"]);
+    assert_eq!(matches[1].1, signature.similarity(&similar));
+
+    for id in ["original.png", "photos/edited.png", "other.png"] {
+        assert!(store.delete(id).unwrap());
+    }
+    assert!(!store.delete("original.png").unwrap());
+}
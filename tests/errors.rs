@@ -0,0 +1,176 @@
+use image_match::{
+    get_buffer_signature, try_cosine_similarity, try_get_buffer_signature, GrayBuffer,
+    SignatureConfig, SignatureError, SmallImagePolicy, YuvFrame, YuvPlanes, YuvRange,
+};
+
+mod common;
+
+#[test]
+fn try_signature_matches_panicking_version() {
+    let rgba = common::pattern_rgba(120, 80);
+    assert_eq!(try_get_buffer_signature(&rgba, 120).unwrap(), get_buffer_signature(&rgba, 120));
+}
+
+#[test]
+fn bad_buffer_lengths_are_errors() {
+    let rgba = common::pattern_rgba(120, 80);
+    assert_eq!(
+        try_get_buffer_signature(&rgba[1..], 120),
+        Err(SignatureError::BadBufferLength { len: rgba.len() - 1, width: 120 })
+    );
+    assert_eq!(
+        try_get_buffer_signature(&rgba, 0),
+        Err(SignatureError::BadBufferLength { len: rgba.len(), width: 0 })
+    );
+    assert_eq!(
+        try_get_buffer_signature(&[], 10),
+        Err(SignatureError::BadBufferLength { len: 0, width: 10 })
+    );
+}
+
+#[test]
+fn empty_and_zero_width_inputs_are_errors() {
+    let config = SignatureConfig::default();
+    let bad_length = |len, width| Err(SignatureError::BadBufferLength { len, width });
+    assert_eq!(config.try_compute_gray(&[], 0), bad_length(0, 0));
+    assert_eq!(config.try_compute_u16(&[7; 16], 0), bad_length(16, 0));
+    assert_eq!(config.try_compute_f32(&[], 3), bad_length(0, 3));
+    assert_eq!(config.try_compute_strided(&[], 0, 0, 0), bad_length(0, 0));
+    assert_eq!(config.try_compute(&[7; 64], usize::MAX), bad_length(64, usize::MAX));
+    assert_eq!(config.try_compute_strided(&[7; 64], 2, 3, usize::MAX), bad_length(64, 2));
+    assert!(GrayBuffer::new(&[], 4).is_err());
+
+    let frame = YuvFrame {
+        width: 0,
+        height: 0,
+        y_stride: 0,
+        range: YuvRange::Full,
+        planes: YuvPlanes::Nv12 { y: &[], uv: &[] },
+    };
+    assert_eq!(config.try_compute_yuv(&frame), bad_length(0, 0));
+
+    let mut builder = config.builder(0);
+    assert!(builder.push_rows(&[]).is_err());
+    assert_eq!(builder.finish(), Err(SignatureError::ImageTooSmall { width: 0, height: 0 }));
+}
+
+#[test]
+fn small_images_are_errors_or_upscaled() {
+    let icon = common::pattern_rgba(10, 8);
+    assert_eq!(
+        try_get_buffer_signature(&icon, 10),
+        Err(SignatureError::ImageTooSmall { width: 10, height: 8 })
+    );
+
+    let upscale = SignatureConfig::new().small_image_policy(SmallImagePolicy::Upscale);
+    // The shorter side of 8 pixels is repeated 5 times to reach 4 pixels per grid cell.
+    let enlarged: Vec<u8> = icon.chunks(4 * 10)
+        .flat_map(|row| {
+            let row: Vec<u8> = row.chunks(4).flat_map(|pixel| pixel.repeat(5)).collect();
+            row.repeat(5)
+        })
+        .collect();
+    let signature = upscale.try_compute(&icon, 10).unwrap();
+    assert_eq!(signature, get_buffer_signature(&enlarged, 50));
+
+    // Just larger than the grid, so the squares around the outer grid points reach past the
+    // border.
+    assert!(try_get_buffer_signature(&common::pattern_rgba(16, 12), 16).is_ok());
+}
+
+#[test]
+fn large_images_are_downscaled_to_their_megapixel_limit() {
+    let rgba = common::pattern_rgba(120, 80);
+    // Every pixel repeated 3 times along both sides, which a factor of 3 averages back out.
+    let enlarged: Vec<u8> = rgba.chunks(4 * 120)
+        .flat_map(|row| {
+            let row: Vec<u8> = row.chunks(4).flat_map(|pixel| pixel.repeat(3)).collect();
+            row.repeat(3)
+        })
+        .collect();
+    let limited = SignatureConfig::new().max_megapixels(Some(0.01));
+    assert_eq!(limited.compute(&enlarged, 360), get_buffer_signature(&rgba, 120));
+    assert_eq!(limited.compute(&rgba, 120), get_buffer_signature(&rgba, 120));
+
+    let invalid = SignatureConfig::new().max_megapixels(Some(0.0));
+    assert!(matches!(
+        invalid.try_compute(&rgba, 120),
+        Err(SignatureError::InvalidParameter { name: "max_megapixels", .. })
+    ));
+}
+
+#[test]
+fn truncated_buffers_are_errors() {
+    let rgba = common::pattern_rgba(120, 80);
+    let config = SignatureConfig::default();
+    assert_eq!(config.try_compute_sized(&rgba, 120, 80), config.try_compute(&rgba, 120));
+    assert_eq!(
+        config.try_compute_sized(&rgba[..4 * 120 * 79], 120, 80),
+        Err(SignatureError::BufferLengthMismatch { expected: rgba.len(), actual: 4 * 120 * 79 })
+    );
+    assert!(config.try_compute_sized(&rgba, 120, usize::MAX).is_err());
+}
+
+#[test]
+fn tiny_and_over_cropped_images_are_errors() {
+    let rgba = common::pattern_rgba(8, 8);
+    assert_eq!(
+        try_get_buffer_signature(&rgba, 8),
+        Err(SignatureError::ImageTooSmall { width: 8, height: 8 })
+    );
+
+    let rgba = common::pattern_rgba(120, 80);
+    assert_eq!(
+        SignatureConfig::new().crop(0.49).try_compute(&rgba, 120),
+        Err(SignatureError::DegenerateCrop)
+    );
+}
+
+#[test]
+fn crops_taking_in_all_detail_are_errors() {
+    // A dot on a blank canvas puts all of the detail in two rows and columns, which even the
+    // default crop takes in from both sides.
+    let mut rgba = vec![255; 4 * 120 * 80];
+    for (x, y) in [(60, 40), (61, 40), (60, 41), (61, 41)] {
+        rgba[4 * (y * 120 + x)..][..3].fill(0);
+    }
+    assert_eq!(try_get_buffer_signature(&rgba, 120), Err(SignatureError::DegenerateCrop));
+
+    #[cfg(feature = "internals")]
+    {
+        use image_match::pipeline::{crop_boundaries, grayscale, square_edge};
+
+        let config = SignatureConfig::new();
+        let bounds = crop_boundaries(&grayscale(&rgba, 120, &config).unwrap(), 0.05);
+        assert!(bounds.lower_x <= bounds.upper_x && bounds.lower_y <= bounds.upper_y);
+        square_edge(&bounds, &config);
+    }
+}
+
+#[test]
+fn invalid_parameters_are_errors() {
+    let rgba = common::pattern_rgba(120, 80);
+    assert_eq!(
+        SignatureConfig::new().crop(0.6).try_compute(&rgba, 120),
+        Err(SignatureError::InvalidParameter {
+            name: "crop",
+            value: "0.6".to_string(),
+            supported: "[0, 0.5)",
+        })
+    );
+    assert!(SignatureConfig::new().crop(f32::NAN).validate().is_err());
+    for grid_size in [0, 1, 2, usize::MAX] {
+        let config = SignatureConfig::new().grid_size(grid_size);
+        assert!(config.validate().is_err());
+        assert_eq!(config.computer().try_compute(&rgba, 120), Err(config.validate().unwrap_err()));
+    }
+}
+
+#[test]
+fn mismatched_lengths_are_errors() {
+    assert_eq!(
+        try_cosine_similarity([1, 2], [1, 2, 0]),
+        Err(SignatureError::MismatchedLengths { left: 2, right: 3 })
+    );
+    assert_eq!(try_cosine_similarity([1, 0], [1, 0]), Ok(1.0));
+}
@@ -0,0 +1,52 @@
+use std::fs::OpenOptions;
+
+use image_match::{SignatureConfig, SignatureFile};
+
+mod common;
+
+#[test]
+fn signature_files_round_trip_and_recover() {
+    let path = std::env::temp_dir().join("image-match-signatures.sig");
+    let _ = std::fs::remove_file(&path);
+    let config = SignatureConfig::new();
+    let signatures: Vec<_> = [(120, 80), (64, 64), (90, 140)].iter()
+        .map(|(width, height)| {
+            config.compute_signature(&common::pattern_rgba(*width, *height), *width)
+        })
+        .collect();
+
+    let mut file = SignatureFile::open(&path).unwrap();
+    assert!(file.is_empty());
+    file.append("first", &signatures[0]).unwrap();
+    file.append("second", &signatures[1]).unwrap();
+    drop(file);
+
+    let mut file = SignatureFile::open(&path).unwrap();
+    assert_eq!(file.len(), 2);
+    assert_eq!(file.get(1).unwrap(), Some(("second".to_string(), signatures[1].clone())));
+    assert_eq!(file.get(2).unwrap(), None);
+    file.append("third", &signatures[2]).unwrap();
+    file.flush().unwrap();
+    let records: Vec<_> = file.iter().unwrap().map(Result::unwrap).collect();
+    let ids: Vec<_> = records.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, ["first", "second", "third"]);
+    assert!(records.iter().zip(&signatures).all(|((_, read), written)| read == written));
+    drop(file);
+
+    // Cuts off the footer and half of the last record, as a crash mid-append would.
+    let len = std::fs::metadata(&path).unwrap().len();
+    let footer_len = 8 * 3 + 12;
+    let file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(len - footer_len - 50).unwrap();
+    drop(file);
+    let mut file = SignatureFile::open(&path).unwrap();
+    assert_eq!(file.len(), 2);
+    assert_eq!(file.get(0).unwrap().unwrap().0, "first");
+    file.append("third", &signatures[2]).unwrap();
+    drop(file);
+    assert_eq!(SignatureFile::open(&path).unwrap().len(), 3);
+
+    std::fs::write(&path, b"not signatures").unwrap();
+    assert!(SignatureFile::open(&path).is_err());
+    std::fs::remove_file(path).unwrap();
+}
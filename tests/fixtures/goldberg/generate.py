@@ -0,0 +1,19 @@
+"""Writes signatures.txt: the signature the Python image_match library computes for each image
+here, one line per image, the file name followed by its signature's elements. reference.py
+writes the same file where the library can't be installed.
+
+    pip install image_match
+    python tests/fixtures/goldberg/generate.py
+"""
+
+from pathlib import Path
+
+from image_match.goldberg import ImageSignature
+
+here = Path(__file__).parent
+signer = ImageSignature()
+with open(here / "signatures.txt", "w") as out:
+    out.write("# Written by generate.py with image_match\n")
+    for path in sorted(here.glob("*.png")):
+        signature = signer.generate_signature(str(path))
+        out.write(" ".join([path.name] + [str(int(element)) for element in signature]) + "\n")
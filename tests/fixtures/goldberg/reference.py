@@ -0,0 +1,244 @@
+"""Writes signatures.txt without NumPy: a transliteration of image_match.goldberg.ImageSignature
+with its defaults, and of the NumPy and scikit-image routines it calls, for machines where the
+library can't be installed. generate.py writes the same file with the library itself.
+
+    python tests/fixtures/goldberg/reference.py
+"""
+
+import struct
+import zlib
+from pathlib import Path
+
+
+def read_png(path):
+    """skimage.io.imread for 8-bit, non-interlaced PNGs: rows of levels for gray images, rows of
+    channel lists for color ones."""
+    data = Path(path).read_bytes()
+    assert data[:8] == b"\x89PNG\r\n\x1a\n"
+    offset, idat = 8, b""
+    while offset < len(data):
+        (length,), kind = struct.unpack(">I", data[offset:offset + 4]), data[offset + 4:offset + 8]
+        body = data[offset + 8:offset + 8 + length]
+        if kind == b"IHDR":
+            width, height, depth, color, _, _, interlace = struct.unpack(">IIBBBBB", body)
+            assert depth == 8 and interlace == 0
+        elif kind == b"IDAT":
+            idat += body
+        offset += 12 + length
+    channels = {0: 1, 2: 3, 4: 2, 6: 4}[color]
+    raw, stride = zlib.decompress(idat), width * channels
+    rows, previous = [], bytes(stride)
+    for y in range(height):
+        start = y * (stride + 1)
+        kind, line = raw[start], bytearray(raw[start + 1:start + 1 + stride])
+        for i in range(stride):
+            left = line[i - channels] if i >= channels else 0
+            up, corner = previous[i], previous[i - channels] if i >= channels else 0
+            if kind == 1:
+                line[i] = (line[i] + left) % 256
+            elif kind == 2:
+                line[i] = (line[i] + up) % 256
+            elif kind == 3:
+                line[i] = (line[i] + (left + up) // 2) % 256
+            elif kind == 4:
+                p = left + up - corner
+                pa, pb, pc = abs(p - left), abs(p - up), abs(p - corner)
+                predictor = left if pa <= pb and pa <= pc else up if pb <= pc else corner
+                line[i] = (line[i] + predictor) % 256
+        previous = bytes(line)
+        if channels == 1:
+            rows.append(list(line))
+        else:
+            rows.append([list(line[x:x + channels]) for x in range(0, stride, channels)])
+    return rows, channels
+
+
+def rgb2gray(image, channels):
+    """scikit-image's rgb2gray, which returns 2-D images as they are, integers included, and
+    weights the first three channels of others after img_as_float multiplies them by 1/255."""
+    if channels == 1:
+        return image
+    scale = 1 / 255
+    return [[0.2125 * (p[0] * scale) + 0.7154 * (p[1] * scale) + 0.0721 * (p[2] * scale)
+             for p in row] for row in image]
+
+
+def pairwise_sum(values):
+    """NumPy's pairwise summation of contiguous float64 values."""
+    n = len(values)
+    if n < 8:
+        total = 0.0
+        for value in values:
+            total += value
+        return total
+    if n <= 128:
+        r = list(values[:8])
+        blocks = n - n % 8
+        for i in range(8, blocks, 8):
+            for j in range(8):
+                r[j] += values[i + j]
+        total = ((r[0] + r[1]) + (r[2] + r[3])) + ((r[4] + r[5]) + (r[6] + r[7]))
+        for value in values[blocks:]:
+            total += value
+        return total
+    half = n // 2
+    half -= half % 8
+    return pairwise_sum(values[:half]) + pairwise_sum(values[half:])
+
+
+def diff(a, b, integer):
+    """One element of np.diff, which wraps around for unsigned integers like uint8 levels."""
+    return (b - a) % 256 if integer else b - a
+
+
+def percentile(values, q):
+    """np.percentile with linear interpolation, as NumPy 1.22 and later compute it."""
+    values = sorted(values)
+    last = len(values) - 1
+    index = last * (q / 100)
+    if index >= last:
+        return values[last]
+    below = int(index)
+    a, b, t = values[below], values[below + 1], index - below
+    return b - (b - a) * (1 - t) if t >= 0.5 else a + (b - a) * t
+
+
+def searchsorted(values, value, side):
+    for i, v in enumerate(values):
+        if (v >= value) if side == "left" else (v > value):
+            return i
+    return len(values)
+
+
+def cumsum(values):
+    out, total = [], 0
+    for value in values:
+        total += value
+        out.append(total)
+    return out
+
+
+def crop_image(image, integer, lower_percentile=5, upper_percentile=95):
+    height, width = len(image), len(image[0])
+    # np.sum(np.abs(np.diff(image, axis=1)), axis=1): each row, contiguous, summed pairwise.
+    row_sums = []
+    for row in image:
+        diffs = [abs(diff(row[x], row[x + 1], integer)) for x in range(width - 1)]
+        row_sums.append(sum(diffs) if integer else pairwise_sum([float(d) for d in diffs]))
+    # np.sum(np.abs(np.diff(image, axis=0)), axis=0): each column accumulated row after row.
+    column_sums = [0] * width if integer else [0.0] * width
+    for y in range(height - 1):
+        for x in range(width):
+            column_sums[x] += abs(diff(image[y][x], image[y + 1][x], integer))
+    rw, cw = cumsum(row_sums), cumsum(column_sums)
+
+    upper_column_limit = searchsorted(cw, percentile(cw, upper_percentile), "left")
+    lower_column_limit = searchsorted(cw, percentile(cw, lower_percentile), "right")
+    upper_row_limit = searchsorted(rw, percentile(rw, upper_percentile), "left")
+    lower_row_limit = searchsorted(rw, percentile(rw, lower_percentile), "right")
+    if lower_row_limit > upper_row_limit:
+        lower_row_limit = int(lower_percentile / 100. * height)
+        upper_row_limit = int(upper_percentile / 100. * height)
+    if lower_column_limit > upper_column_limit:
+        lower_column_limit = int(lower_percentile / 100. * width)
+        upper_column_limit = int(upper_percentile / 100. * width)
+    return [(lower_row_limit, upper_row_limit), (lower_column_limit, upper_column_limit)]
+
+
+def linspace_int(start, stop, num):
+    """np.linspace(start, stop, num, dtype=int)."""
+    step = (stop - start) / (num - 1)
+    values = [start if step == 0 else i * step + start for i in range(num)]
+    values[-1] = stop
+    return [int(value) for value in values]
+
+
+def compute_grid_points(window, n=9):
+    x_coords = linspace_int(window[0][0], window[0][1], n + 2)[1:-1]
+    y_coords = linspace_int(window[1][0], window[1][1], n + 2)[1:-1]
+    return x_coords, y_coords
+
+
+def mean(image, top, bottom, left, right):
+    """np.mean of image[top:bottom, left:right] in float64. NumPy's iterator coalesces slices of
+    whole rows into one contiguous run, takes a one column slice as a run down the column, and
+    otherwise adds each row's pairwise sum in turn."""
+    width = len(image[0])
+    if right - left == width:
+        total = pairwise_sum([float(v) for row in image[top:bottom] for v in row])
+    elif right - left == 1:
+        total = pairwise_sum([float(image[y][left]) for y in range(top, bottom)])
+    else:
+        total = 0.0
+        for y in range(top, bottom):
+            total += pairwise_sum([float(v) for v in image[y][left:right]])
+    return total / ((bottom - top) * (right - left))
+
+
+def compute_mean_level(image, x_coords, y_coords):
+    height, width = len(image), len(image[0])
+    P = max([2.0, int(0.5 + min(height, width) / 20.)])
+    avg_grey = [[0.0] * len(y_coords) for _ in x_coords]
+    for i, x in enumerate(x_coords):
+        lower_x_lim = int(max([x - P / 2, 0]))
+        upper_x_lim = int(min([lower_x_lim + P, height]))
+        for j, y in enumerate(y_coords):
+            lower_y_lim = int(max([y - P / 2, 0]))
+            upper_y_lim = int(min([lower_y_lim + P, width]))
+            avg_grey[i][j] = mean(image, lower_x_lim, upper_x_lim, lower_y_lim, upper_y_lim)
+    return avg_grey
+
+
+def compute_differentials(g):
+    """The eight neighbor matrices the library builds from np.diff, np.diagflat and np.fliplr,
+    stacked along a third axis: each element is the point's level minus its neighbor's, or 0 at
+    the border."""
+    n = len(g)
+
+    def at(i, j):
+        return g[i][j] if 0 <= i < n and 0 <= j < n else None
+
+    def neighbor(i, j, di, dj):
+        other = at(i + di, j + dj)
+        return 0.0 if other is None else g[i][j] - other
+
+    # upper_left, up, fliplr(upper_right), left, right, fliplr(lower_left), down, lower_right
+    offsets = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)]
+    return [[[neighbor(i, j, di, dj) for di, dj in offsets] for j in range(n)] for i in range(n)]
+
+
+def normalize_and_threshold(values, identical_tolerance=2 / 255., n_levels=2):
+    values = [0. if abs(v) < identical_tolerance else v for v in values]
+    if all(v == 0. for v in values):
+        return values
+    positive = [v for v in values if v > 0.]
+    negative = [v for v in values if v < 0.]
+    positive_cutoffs = [percentile(positive, q) for q in (0., 50., 100.)]
+    negative_cutoffs = [percentile(negative, q) for q in (100., 50., 0.)]
+    for level in range(n_levels):
+        low, high = positive_cutoffs[level:level + 2]
+        values = [level + 1 if low <= v <= high else v for v in values]
+    for level in range(n_levels):
+        high, low = negative_cutoffs[level:level + 2]
+        values = [-(level + 1) if high >= v >= low else v for v in values]
+    return values
+
+
+def generate_signature(path):
+    image, channels = read_png(path)
+    im_array = rgb2gray(image, channels)
+    image_limits = crop_image(im_array, integer=channels == 1)
+    x_coords, y_coords = compute_grid_points(image_limits)
+    avg_grey = compute_mean_level(im_array, x_coords, y_coords)
+    diff_mat = compute_differentials(avg_grey)
+    flat = [v for row in diff_mat for point in row for v in point]
+    return [int(v) for v in normalize_and_threshold(flat)]
+
+
+if __name__ == "__main__":
+    here = Path(__file__).parent
+    with open(here / "signatures.txt", "w") as out:
+        out.write("# Written by reference.py\n")
+        for path in sorted(here.glob("*.png")):
+            signature = generate_signature(path)
+            out.write(" ".join([path.name] + [str(element) for element in signature]) + "\n")
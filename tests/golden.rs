@@ -0,0 +1,85 @@
+//! Signatures of fixed inputs, pinned so that any change in results, whether from a change to the
+//! pipeline or from running on a different platform, fails here. The inputs and hashes only use
+//! integer arithmetic, so the expected values are the same on every target.
+
+use image_match::{
+    AlphaMode, Arithmetic, Compat, GrayscaleMode, GridPlacement, NeighborPadding, PixelFormat,
+    SignatureConfig, Tonemap,
+};
+
+const WIDTH: usize = 157;
+const HEIGHT: usize = 103;
+
+/// A noisy image with smooth gradients underneath and partially transparent regions.
+fn golden_rgba() -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(WIDTH * HEIGHT * 4);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let noise = ((x * 7919 + y * 104729) ^ (x * y * 31)) % 61;
+            let r = (x * 255 / WIDTH + noise) % 256;
+            let g = (y * 255 / HEIGHT + 2 * noise) % 256;
+            let b = ((x + y) * 3 + noise) % 256;
+            let a = if (x / 20 + y / 15) % 3 == 0 { 96 + noise } else { 255 };
+            buffer.extend([r, g, b, a].map(|channel| channel as u8));
+        }
+    }
+    buffer
+}
+
+/// FNV-1a, which unlike the standard library's hashers is fixed across platforms and versions.
+fn fnv(signature: &[i8]) -> u64 {
+    signature.iter().fold(0xcbf29ce484222325, |hash, element| {
+        (hash ^ *element as u8 as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[test]
+fn eight_bit_signatures_are_pinned() {
+    let rgba = golden_rgba();
+    let configs = [
+        SignatureConfig::new(),
+        SignatureConfig::new().grayscale_mode(GrayscaleMode::Rec601),
+        SignatureConfig::new().grayscale_mode(GrayscaleMode::Rec709).alpha_mode(AlphaMode::Ignore),
+        SignatureConfig::new().alpha_mode(AlphaMode::Background([250, 240, 10])),
+        SignatureConfig::new().alpha_mode(AlphaMode::Checkerboard).crop(0.1),
+        SignatureConfig::new()
+            .arithmetic(Arithmetic::Integer)
+            .grayscale_mode(GrayscaleMode::Rec709)
+            .alpha_mode(AlphaMode::Background([250, 240, 10])),
+        SignatureConfig::new().pixel_format(PixelFormat::Bgra).identical_tolerance(5),
+        SignatureConfig::new()
+            .grid_size(13)
+            .grid_placement(GridPlacement::CropOrigin)
+            .neighbor_padding(NeighborPadding::Zeros),
+        SignatureConfig::new().compat(Compat::Goldberg),
+    ];
+    let expected: [u64; 9] = [
+        16467426101256054049,
+        12483679572120236067,
+        1618561188339326457,
+        9235268734842787103,
+        15825373472120316575,
+        1050293309634582369,
+        5113398702447316061,
+        17948672577458878681,
+        10639937115552635529,
+    ];
+
+    let actual = configs.map(|config| fnv(&config.compute(&rgba, WIDTH)));
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn deep_signatures_are_pinned() {
+    let rgba = golden_rgba();
+    let deep: Vec<u16> = rgba.iter().map(|channel| (*channel as u16 * 257) ^ 0x55).collect();
+    let linear: Vec<f32> = rgba.iter().map(|channel| *channel as f32 / 200.0).collect();
+
+    let expected: [u64; 3] = [15903341887823704683, 433461533704420133, 4751344643350447667];
+    let actual = [
+        fnv(&SignatureConfig::new().compute_u16(&deep, WIDTH)),
+        fnv(&SignatureConfig::new().tonemap(Tonemap::Reinhard).compute_f32(&linear, WIDTH)),
+        fnv(&SignatureConfig::new().tonemap(Tonemap::Exposure(0.8)).compute_f32(&linear, WIDTH)),
+    ];
+    assert_eq!(actual, expected);
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "gpu")]
+
+use image_match::{
+    AlphaMode, Arithmetic, GrayscaleMode, GridPlacement, PixelFormat, SignatureConfig,
+};
+
+mod common;
+
+#[test]
+fn gpu_signatures_match_integer_arithmetic() {
+    let configs = [
+        (SignatureConfig::new(), 4),
+        (SignatureConfig::new().grayscale_mode(GrayscaleMode::Rec709).grid_size(7), 4),
+        (SignatureConfig::new().alpha_mode(AlphaMode::Checkerboard), 4),
+        (SignatureConfig::new().grid_placement(GridPlacement::CropOrigin), 4),
+        (SignatureConfig::new().alpha_mode(AlphaMode::Background([250, 20, 90])), 4),
+        (SignatureConfig::new().pixel_format(PixelFormat::Bgr).crop(0.1), 3),
+    ];
+    for (config, channels) in configs {
+        let Ok(signer) = config.gpu_signer() else {
+            eprintln!("No GPU available, skipping");
+            return;
+        };
+
+        // Varying alpha, so that every alpha mode has something to composite.
+        let sizes = [(120, 80), (333, 517), (2, 2), (97, 61), (16, 12)];
+        let images: Vec<(Vec<u8>, usize)> = sizes.iter()
+            .map(|(width, height)| {
+                let buffer = common::pattern_rgba(*width, *height).chunks(4)
+                    .flat_map(|p| [p[0], p[1], p[2], p[0] ^ p[1]].into_iter().take(channels))
+                    .collect();
+                (buffer, *width)
+            })
+            .collect();
+
+        let cpu = config.arithmetic(Arithmetic::Integer);
+        for ((buffer, width), signature) in images.iter().zip(signer.compute_batch(&images)) {
+            assert_eq!(signature, cpu.try_compute(buffer, *width), "{:?}", config);
+        }
+    }
+}
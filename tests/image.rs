@@ -0,0 +1,427 @@
+#![cfg(feature = "img")]
+
+use std::error::Error;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::time::Duration;
+
+use image::imageops::FilterType;
+use image::{
+    DynamicImage, GenericImage, GenericImageView, ImageOutputFormat, Rgba, RgbaImage,
+};
+use image_match::image::{
+    animation_signature, get_bytes_signature, get_dynamic_image_signature,
+    get_image_region_signature, get_image_signature, get_reader_signature, get_view_signature,
+    thumbnail_candidates, ImageReadError, DEFAULT_THUMBNAIL_THRESHOLD,
+};
+use image_match::{
+    cosine_similarity, get_buffer_signature, Compat, Region, Signature, SignatureConfig,
+};
+
+mod common;
+
+#[test]
+fn dynamic_images_of_every_color_type_sign_like_rgba() {
+    let rgba = common::pattern_rgba(120, 80);
+    let expected = SignatureConfig::new().compute(&rgba, 120);
+    let img = DynamicImage::ImageRgba8(RgbaImage::from_raw(120, 80, rgba).unwrap());
+
+    let color_types = [
+        img.clone(),
+        DynamicImage::ImageRgb8(img.to_rgb8()),
+        DynamicImage::ImageRgba16(img.to_rgba16()),
+        DynamicImage::ImageRgb16(img.to_rgb16()),
+        DynamicImage::ImageRgba32F(img.to_rgba32f()),
+        DynamicImage::ImageRgb32F(img.to_rgb32f()),
+    ];
+    assert_eq!(get_dynamic_image_signature(&color_types[0]), expected);
+    assert_eq!(get_dynamic_image_signature(&color_types[1]), expected);
+    // Deeper channels keep gray levels the 8-bit ones round away.
+    for img in &color_types[2..] {
+        let signature = get_dynamic_image_signature(img);
+        assert!(cosine_similarity(&signature, &expected) > 0.9, "{:?}", img.color());
+    }
+
+    // Gray images sign alike whatever their depth, or whether they're read pixel by pixel.
+    let gray = get_dynamic_image_signature(&DynamicImage::ImageLuma8(img.to_luma8()));
+    for img in [
+        DynamicImage::ImageLuma16(img.to_luma16()),
+        DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        DynamicImage::ImageLumaA16(img.to_luma_alpha16()),
+    ] {
+        let signature = SignatureConfig::new().compute_dynamic_image(&img);
+        assert!(cosine_similarity(&signature, &gray) > 0.9, "{:?}", img.color());
+    }
+    let tiny = DynamicImage::new_rgb16(2, 2);
+    assert!(SignatureConfig::new().try_compute_dynamic_image(&tiny).is_err());
+}
+
+#[test]
+fn goldberg_signatures_read_gray_files_as_gray_levels() {
+    let config = SignatureConfig::new().compat(Compat::Goldberg);
+    let rgba = common::pattern_rgba(120, 80);
+    let img = DynamicImage::ImageRgba8(RgbaImage::from_raw(120, 80, rgba).unwrap());
+    let gray = img.to_luma8();
+    let mut png = Vec::new();
+    gray.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png).unwrap();
+    assert_eq!(config.compute_bytes(&png).unwrap(), config.compute_gray(gray.as_raw(), 120));
+
+    // Gray images with alpha are signed as the RGBA images Python's imaging library makes of them.
+    let gray_alpha = DynamicImage::ImageLumaA8(img.to_luma_alpha8());
+    let converted = gray_alpha.to_rgba8();
+    assert_eq!(
+        config.compute_dynamic_image(&gray_alpha),
+        config.compute(converted.as_raw(), 120)
+    );
+}
+
+#[test]
+fn goldberg_signatures_match_the_python_library() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/goldberg");
+    let expected = fs::read_to_string(dir.join("signatures.txt")).unwrap();
+    let config = SignatureConfig::new().compat(Compat::Goldberg);
+    let mut checked = 0;
+    for line in expected.lines().filter(|line| !line.starts_with('#')) {
+        let mut fields = line.split_whitespace();
+        let name = fields.next().unwrap();
+        let signature: Vec<i8> = fields.map(|element| element.parse().unwrap()).collect();
+        assert_eq!(config.compute_file(dir.join(name)).unwrap(), signature, "{}", name);
+        checked += 1;
+    }
+    // The color, gray and transparent fixtures each take a different path through the pipeline.
+    assert_eq!(checked, 3);
+}
+
+#[test]
+fn image_files_are_opened_decoded_and_signed_in_one_call() {
+    let dir = std::env::temp_dir();
+    let path = dir.join("image-match-file-signature.png");
+    let rgba = common::pattern_rgba(120, 80);
+    RgbaImage::from_raw(120, 80, rgba.clone()).unwrap().save(&path).unwrap();
+
+    let config = SignatureConfig::new().grid_size(8);
+    let signature = config.compute_file_signature(&path).unwrap();
+    assert_eq!(signature.params(), &config.params());
+    assert_eq!(*signature, config.compute(&rgba, 120));
+
+    let missing = config.compute_file_signature(dir.join("image-match-missing.png"));
+    assert!(matches!(missing, Err(ImageReadError::IoError(_))));
+    let garbage = dir.join("image-match-garbage.png");
+    std::fs::write(&garbage, b"not an image").unwrap();
+    let error = config.compute_file_signature(&garbage).unwrap_err();
+    assert!(matches!(error, ImageReadError::DecodeError(_)));
+    assert!(error.source().is_some());
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&garbage);
+}
+
+#[test]
+fn encoded_images_are_signed_from_memory() {
+    let rgba = common::pattern_rgba(120, 80);
+    let img = RgbaImage::from_raw(120, 80, rgba.clone()).unwrap();
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png).unwrap();
+
+    assert_eq!(get_bytes_signature(&png).unwrap(), get_buffer_signature(&rgba, 120));
+    let config = SignatureConfig::new().crop(0.1);
+    let signature = config.compute_bytes_signature(&png).unwrap();
+    assert_eq!(signature, config.compute_signature(&rgba, 120));
+    assert!(matches!(get_bytes_signature(b"not an image"), Err(ImageReadError::DecodeError(_))));
+}
+
+#[test]
+fn streamed_images_are_signed_as_they_are_read() {
+    let rgba = common::pattern_rgba(120, 80);
+    let mut png = Vec::new();
+    let img = RgbaImage::from_raw(120, 80, rgba.clone()).unwrap();
+    img.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png).unwrap();
+
+    // A stream handing out a few bytes at a time, like a network body.
+    let chunked = png.chunks(100).map(Cursor::new).fold(
+        Box::new(std::io::empty()) as Box<dyn Read>,
+        |stream, chunk| Box::new(stream.chain(chunk)),
+    );
+    assert_eq!(get_reader_signature(chunked).unwrap(), get_buffer_signature(&rgba, 120));
+    let config = SignatureConfig::new();
+    let signature = config.compute_reader_signature(png.as_slice()).unwrap();
+    assert_eq!(signature, config.compute_signature(&rgba, 120));
+
+    let truncated = config.compute_reader(&png[..png.len() / 2]);
+    assert!(matches!(truncated, Err(ImageReadError::DecodeError(_))));
+}
+
+#[test]
+fn photos_rotated_by_exif_orientation_sign_like_rotated_pixels() {
+    let img = DynamicImage::ImageRgba8(
+        RgbaImage::from_raw(120, 80, common::pattern_rgba(120, 80)).unwrap(),
+    );
+    let jpeg = |img: &DynamicImage| {
+        let mut jpeg = Vec::new();
+        img.to_rgb8().write_to(&mut Cursor::new(&mut jpeg), ImageOutputFormat::Jpeg(95)).unwrap();
+        jpeg
+    };
+    let upright = get_bytes_signature(&jpeg(&img.rotate90())).unwrap();
+    // Orientation 6: the camera was turned a quarter clockwise of the pixels it stored.
+    let tagged = with_exif_orientation(&jpeg(&img), 6);
+
+    assert!(cosine_similarity(get_bytes_signature(&tagged).unwrap(), &upright) > 0.95);
+    let unrotated = SignatureConfig::new().exif_orientation(false).compute_bytes(&tagged);
+    assert_eq!(unrotated.unwrap(), get_bytes_signature(&jpeg(&img)).unwrap());
+    // Python signs the stored pixels.
+    let goldberg = SignatureConfig::new().compat(Compat::Goldberg);
+    let stored = goldberg.compute_bytes(&jpeg(&img)).unwrap();
+    assert_eq!(goldberg.compute_bytes(&tagged).unwrap(), stored);
+}
+
+/// `jpeg` with an APP1 segment holding nothing but an EXIF Orientation tag.
+fn with_exif_orientation(jpeg: &[u8], orientation: u8) -> Vec<u8> {
+    let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01".to_vec();
+    exif.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, orientation, 0, 0, 0, 0, 0, 0]);
+    let mut tagged = jpeg[..2].to_vec();
+    tagged.extend_from_slice(&[0xff, 0xe1, 0, exif.len() as u8 + 2]);
+    tagged.extend_from_slice(&exif);
+    tagged.extend_from_slice(&jpeg[2..]);
+    tagged
+}
+
+#[test]
+fn views_sign_like_the_pixels_they_show() {
+    let rgba = common::pattern_rgba(120, 80);
+    let pattern = RgbaImage::from_raw(120, 80, rgba.clone()).unwrap();
+    let mut canvas = RgbaImage::from_pixel(200, 160, Rgba([255, 0, 0, 255]));
+    canvas.copy_from(&pattern, 40, 30).unwrap();
+
+    let view = canvas.view(40, 30, 120, 80);
+    assert_eq!(get_view_signature(&*view), get_buffer_signature(&rgba, 120));
+    assert_eq!(SignatureConfig::new().compute_view(&pattern), get_image_signature(pattern));
+
+    let region = get_image_region_signature(&canvas, Region::pixels(40, 30, 120, 80));
+    assert_eq!(region, get_buffer_signature(&rgba, 120));
+    let relative = Region::relative(0.2, 0.1875, 0.6, 0.5);
+    assert_eq!(get_image_region_signature(&canvas, relative), region);
+}
+
+#[test]
+fn gif_frames_are_composited_and_signed_in_order() {
+    let rgba = common::pattern_rgba(120, 80);
+    let still = RgbaImage::from_raw(120, 80, rgba.clone()).unwrap();
+    let turned = image::imageops::rotate180(&still);
+    let frame = |image: &RgbaImage, delay| {
+        let mut pixels = image.as_raw().clone();
+        let (width, height) = (image.width() as u16, image.height() as u16);
+        let mut frame = gif::Frame::from_rgba(width, height, &mut pixels);
+        frame.dispose = gif::DisposalMethod::Keep;
+        frame.delay = delay;
+        frame
+    };
+
+    let mut gif = Vec::new();
+    let mut encoder = gif::Encoder::new(&mut gif, 120, 80, &[]).unwrap();
+    encoder.write_frame(&frame(&still, 20)).unwrap();
+    // Only redraws the bottom right corner, leaving the rest of the first frame on the canvas.
+    let mut corner = frame(&turned.view(80, 40, 40, 40).to_image(), 20);
+    (corner.left, corner.top) = (80, 40);
+    encoder.write_frame(&corner).unwrap();
+    encoder.write_frame(&frame(&turned, 10)).unwrap();
+    drop(encoder);
+
+    let frames = SignatureConfig::new().compute_gif(gif.as_slice()).unwrap();
+    let indices: Vec<usize> = frames.iter().map(|frame| frame.index).collect();
+    assert_eq!(indices, [0, 1, 2]);
+    assert_eq!(frames[2].delay, Duration::from_millis(100));
+
+    // The palette the encoder quantizes to shifts a few gray levels.
+    let expected = get_buffer_signature(&rgba, 120);
+    assert!(cosine_similarity(&frames[0].signature, &expected) > 0.9);
+    assert!(cosine_similarity(&frames[1].signature, &expected) > 0.9);
+    let turned = get_buffer_signature(turned.as_raw(), 120);
+    assert!(cosine_similarity(&frames[2].signature, &turned) > 0.9);
+
+    // The still is what's on screen most of the time.
+    let animation = animation_signature(&frames).unwrap();
+    assert!(cosine_similarity(&animation, &expected) > 0.9);
+    assert!(cosine_similarity(&animation, &turned) < 0.5);
+    assert_eq!(animation_signature(&[]), None);
+}
+
+#[test]
+fn apng_frames_and_stills_go_through_one_call() {
+    let rgba = common::pattern_rgba(120, 80);
+    let still = RgbaImage::from_raw(120, 80, rgba.clone()).unwrap();
+    let turned = image::imageops::rotate180(&still);
+
+    let mut apng = Vec::new();
+    let mut encoder = png::Encoder::new(&mut apng, 120, 80);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_animated(2, 0).unwrap();
+    let mut writer = encoder.write_header().unwrap();
+    writer.set_frame_delay(1, 10).unwrap();
+    writer.write_image_data(still.as_raw()).unwrap();
+    writer.set_frame_delay(1, 5).unwrap();
+    writer.write_image_data(turned.as_raw()).unwrap();
+    writer.finish().unwrap();
+
+    let config = SignatureConfig::new();
+    let frames = config.compute_animation(apng.as_slice()).unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].signature, config.compute_signature(&rgba, 120));
+    assert_eq!(frames[1].signature, config.compute_signature(turned.as_raw(), 120));
+    assert_eq!(frames[1].delay, Duration::from_millis(200));
+
+    let mut png = Vec::new();
+    still.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png).unwrap();
+    let frames = config.compute_animation(png.as_slice()).unwrap();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].delay, Duration::ZERO);
+    assert_eq!(frames[0].signature, config.compute_signature(&rgba, 120));
+}
+
+#[test]
+fn tiff_pages_are_signed_one_by_one() {
+    let rgba = common::pattern_rgba(120, 80);
+    let still = RgbaImage::from_raw(120, 80, rgba.clone()).unwrap();
+    let gray = DynamicImage::ImageRgba8(still.clone()).to_luma16();
+
+    let mut tiff = Cursor::new(Vec::new());
+    let mut encoder = tiff::encoder::TiffEncoder::new(&mut tiff).unwrap();
+    encoder.write_image::<tiff::encoder::colortype::RGBA8>(120, 80, &rgba).unwrap();
+    encoder.write_image::<tiff::encoder::colortype::Gray16>(120, 80, gray.as_raw()).unwrap();
+    tiff.set_position(0);
+
+    let config = SignatureConfig::new();
+    let pages = config.compute_tiff_pages(&mut tiff).unwrap();
+    assert_eq!(pages.len(), 2);
+    assert_eq!(pages[0], config.compute_signature(&rgba, 120));
+    assert_eq!(*pages[1], config.compute_gray_u16(gray.as_raw(), 120));
+
+    let truncated = config.compute_tiff_pages(Cursor::new(&tiff.get_ref()[..100]));
+    assert!(matches!(truncated, Err(ImageReadError::IoError(_) | ImageReadError::DecodeError(_))));
+}
+
+#[test]
+fn linear_light_images_sign_like_their_srgb_exports() {
+    let rgba = common::pattern_rgba(120, 80);
+    let linear: Vec<u8> = rgba.chunks(4)
+        .flat_map(|pixel| {
+            let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+        })
+        .collect();
+
+    let mut info = png::Info::with_size(120, 80);
+    info.color_type = png::ColorType::Rgba;
+    info.icc_profile = Some(linear_icc_profile().into());
+    let mut tagged = Vec::new();
+    let mut writer = png::Encoder::with_info(&mut tagged, info).unwrap().write_header().unwrap();
+    writer.write_image_data(&linear).unwrap();
+    writer.finish().unwrap();
+
+    let expected = get_buffer_signature(&rgba, 120);
+    let converted = get_bytes_signature(&tagged).unwrap();
+    let unconverted = SignatureConfig::new().icc_profiles(false).compute_bytes(&tagged).unwrap();
+    // Linear levels crush the shadows, which is most of what the grid sees, until converted back.
+    assert!(cosine_similarity(&converted, &expected) > 0.9);
+    assert!(cosine_similarity(&unconverted, &expected) < 0.5);
+    assert_eq!(unconverted, get_buffer_signature(&linear, 120));
+    // Python ignores profiles.
+    let goldberg = SignatureConfig::new().compat(Compat::Goldberg);
+    assert_eq!(goldberg.compute_bytes(&tagged).unwrap(), goldberg.compute(&linear, 120));
+}
+
+fn srgb_to_linear(level: u8) -> u8 {
+    let level = level as f64 / 255.0;
+    let linear = if level <= 0.04045 { level / 12.92 } else { ((level + 0.055) / 1.055).powf(2.4) };
+    (linear * 255.0).round() as u8
+}
+
+/// An ICC profile of sRGB's primaries with linear channels, as raw photo developers export.
+fn linear_icc_profile() -> Vec<u8> {
+    let fixed = |value: f64| ((value * 65536.0).round() as i32).to_be_bytes();
+    let xyz = |values: [f64; 3]| {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        tag.extend(values.into_iter().flat_map(fixed));
+        tag
+    };
+    // A gamma of 1, padded to a multiple of 4 bytes.
+    let curve = b"curv\0\0\0\0\0\0\0\x01\x01\0\0\0".to_vec();
+    let tags = [
+        (b"wtpt", xyz([0.9642, 1.0, 0.8249])),
+        (b"rXYZ", xyz([0.4361, 0.2225, 0.0139])),
+        (b"gXYZ", xyz([0.3851, 0.7169, 0.0971])),
+        (b"bXYZ", xyz([0.1431, 0.0606, 0.7141])),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let start = 128 + table.len() + 12 * tags.len();
+    for (signature, tag) in &tags {
+        table.extend_from_slice(*signature);
+        table.extend(((start + data.len()) as u32).to_be_bytes());
+        table.extend((tag.len() as u32).to_be_bytes());
+        data.extend_from_slice(tag);
+    }
+    let mut profile = vec![0; 128];
+    profile[0..4].copy_from_slice(&((start + data.len()) as u32).to_be_bytes());
+    profile[8..12].copy_from_slice(&[2, 0x10, 0, 0]);
+    profile[12..24].copy_from_slice(b"mntrRGB XYZ ");
+    profile[36..40].copy_from_slice(b"acsp");
+    profile[68..80].copy_from_slice(&xyz([0.9642, 1.0, 0.8249])[8..]);
+    profile.extend(table);
+    profile.extend(data);
+    profile
+}
+
+#[test]
+fn exif_thumbnails_prefilter_pairs_to_compare_in_full() {
+    let still = DynamicImage::ImageRgba8(
+        RgbaImage::from_raw(480, 320, common::pattern_rgba(480, 320)).unwrap(),
+    );
+    let other = still.rotate90().resize_exact(480, 320, FilterType::Triangle);
+    let jpeg = |img: &DynamicImage, quality| {
+        let mut jpeg = Vec::new();
+        let format = ImageOutputFormat::Jpeg(quality);
+        img.to_rgb8().write_to(&mut Cursor::new(&mut jpeg), format).unwrap();
+        jpeg
+    };
+    let with_thumbnail = |img: &DynamicImage, quality| {
+        let thumbnail = jpeg(&img.resize(160, 160, FilterType::Triangle), quality);
+        let orientation = exif::Field {
+            tag: exif::Tag::Orientation,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Short(vec![1]),
+        };
+        let mut writer = exif::experimental::Writer::new();
+        writer.push_field(&orientation);
+        writer.set_jpeg(&thumbnail, exif::In::THUMBNAIL);
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        with_app1(&jpeg(img, quality), &[b"Exif\0\0".as_slice(), tiff.get_ref()].concat())
+    };
+
+    let config = SignatureConfig::new();
+    let thumbnails: Vec<Option<Signature>> = [
+        with_thumbnail(&still, 90),
+        with_thumbnail(&still, 60),
+        with_thumbnail(&other, 90),
+        jpeg(&still, 90),
+    ]
+    .iter()
+    .map(|file| config.compute_thumbnail(Cursor::new(file)).unwrap())
+    .collect();
+    assert!(thumbnails[..3].iter().all(Option::is_some));
+    assert_eq!(thumbnails[3], None);
+
+    // The unrelated image is ruled out, and the one without a thumbnail is left to compare.
+    let candidates = thumbnail_candidates(&thumbnails, DEFAULT_THUMBNAIL_THRESHOLD);
+    assert_eq!(candidates, [(0, 1), (0, 3), (1, 3), (2, 3)]);
+}
+
+/// `jpeg` with an APP1 segment holding `payload`.
+fn with_app1(jpeg: &[u8], payload: &[u8]) -> Vec<u8> {
+    let length = (payload.len() as u16 + 2).to_be_bytes();
+    [&jpeg[..2], &[0xff, 0xe1, length[0], length[1]], payload, &jpeg[2..]].concat()
+}
@@ -0,0 +1,432 @@
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use image_match::index::{
+    BkTree, BoundedMatches, HnswIndex, Index, LinearIndex, LshIndex, RankedIndex, SearchBudget,
+    ShardedIndex, SharedIndex,
+};
+use image_match::{
+    cosine_similarity, Cosine, DistanceMetric, Manhattan, NormalizedDistance, SignatureConfig,
+    SignatureError,
+};
+
+mod common;
+
+/// Deterministic signature-like vectors, with elements in [-2, 2].
+fn vectors(count: usize, length: usize) -> Vec<Vec<i8>> {
+    let mut state = 0x2545_f491_u32;
+    (0..count)
+        .map(|_| {
+            (0..length)
+                .map(|_| {
+                    state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                    ((state >> 24) % 5) as i8 - 2
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn bk_tree_finds_the_same_signatures_as_a_linear_scan() {
+    let signatures = vectors(500, 64);
+    let mut tree = BkTree::new();
+    for signature in &signatures {
+        tree.insert(signature);
+    }
+    assert_eq!(tree.len(), 500);
+    assert_eq!(tree.get(42), Some(signatures[42].as_slice()));
+
+    for query in vectors(20, 64).iter().chain(&signatures[..5]) {
+        for max_distance in [0, 40, 70, 90] {
+            let mut expected: Vec<(usize, u64)> = signatures.iter()
+                .map(|s| Manhattan.distance(s, query) as u64)
+                .enumerate()
+                .filter(|&(_, distance)| distance <= max_distance)
+                .collect();
+            expected.sort_by_key(|&(key, distance)| (distance, key));
+            assert_eq!(tree.find_within(query, max_distance), expected);
+        }
+    }
+}
+
+#[test]
+fn bk_tree_finds_near_duplicate_images() {
+    let config = SignatureConfig::new();
+    let original = common::pattern_rgba(120, 80);
+    let mut edited = original.clone();
+    edited[..4 * 120 * 8].fill(0);
+
+    let mut tree = BkTree::new();
+    let key = tree.insert(config.compute(&original, 120));
+    tree.insert(config.compute(&common::pattern_rgba(333, 517), 333));
+
+    let edited = config.compute(&edited, 120);
+    let matches = tree.find_within(&edited, 300);
+    assert_eq!(matches.iter().map(|(key, _)| *key).collect::<Vec<_>>(), [key]);
+    assert!(matches!(
+        tree.try_insert([0i8; 3]),
+        Err(SignatureError::MismatchedLengths { left: 544, right: 3 })
+    ));
+}
+
+#[test]
+fn hnsw_index_finds_most_of_the_nearest_signatures() {
+    let signatures = vectors(1000, 64);
+    let mut index = HnswIndex::new().ef_construction(64);
+    for signature in &signatures {
+        index.insert(signature);
+    }
+    assert_eq!(index.len(), 1000);
+
+    let found = nearest_found(&index, &signatures);
+    assert!(found >= 180, "found {} of the 200 nearest signatures", found);
+}
+
+/// How many of the 10 nearest signatures of every 50th of `signatures` `index` finds.
+fn nearest_found<I: Index>(index: &I, signatures: &[Vec<i8>]) -> usize {
+    let mut found = 0;
+    for (i, query) in signatures.iter().enumerate().step_by(50) {
+        let mut expected: Vec<(usize, f64)> = signatures.iter()
+            .map(|s| cosine_similarity(s, query))
+            .enumerate()
+            .collect();
+        expected.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        expected.truncate(10);
+
+        let nearest = index.nearest(query, 10);
+        assert_eq!(nearest[0], (i, 1.0));
+        for (key, similarity) in &nearest {
+            assert_eq!(*similarity, cosine_similarity(&signatures[*key], query));
+        }
+        found += nearest.iter().filter(|n| expected.contains(n)).count();
+    }
+    found
+}
+
+#[test]
+fn lsh_index_finds_signatures_sharing_words_with_the_query() {
+    let config = SignatureConfig::new();
+    let original = common::pattern_rgba(120, 80);
+    let mut edited = original.clone();
+    edited[..4 * 120 * 8].fill(0);
+    let signature = config.compute(&original, 120);
+    let similar = config.compute(&edited, 120);
+
+    let mut index = LshIndex::new();
+    for noise in vectors(200, 544) {
+        index.insert(noise);
+    }
+    let original_key = index.insert(&signature);
+    let edited_key = index.insert(&similar);
+    index.insert(config.compute(&common::pattern_rgba(333, 517), 333));
+
+    let matches = index.find_similar(&signature, 0.5);
+    assert_eq!(
+        matches,
+        [(original_key, 1.0), (edited_key, cosine_similarity(&signature, &similar))]
+    );
+    assert!(index.find_similar(&signature, 1.1).is_empty());
+    assert!(index.try_find_similar([0i8; 3], 0.5).is_err());
+}
+
+#[test]
+fn sharded_indexes_merge_the_matches_of_their_shards() {
+    let signatures = vectors(500, 64);
+    let mut linear = LinearIndex::new();
+    let mut sharded = ShardedIndex::with_shards(4, LinearIndex::new);
+    for signature in &signatures {
+        assert_eq!(sharded.insert(signature), linear.insert(signature));
+    }
+    assert!(sharded.shards().iter().all(|shard| shard.len() == 125));
+    assert_eq!(sharded.get(321), Some(&*signatures[321]));
+    for query in signatures.iter().step_by(50) {
+        assert_eq!(sharded.nearest(query, 10), linear.nearest(query, 10));
+        assert_eq!(sharded.find_similar(query, 0.2), linear.find_similar(query, 0.2));
+    }
+    assert!(sharded.try_nearest(&[0; 3], 1).is_err());
+}
+
+#[test]
+fn bulk_built_indexes_find_what_inserted_ones_do() {
+    let signatures = vectors(1000, 64);
+
+    let mut inserted = LshIndex::new();
+    for signature in &signatures {
+        inserted.insert(signature);
+    }
+    let built = LshIndex::new().build_from(&signatures);
+    let (built_stats, inserted_stats) = (built.stats(), inserted.stats());
+    assert_eq!(built_stats.postings, inserted_stats.postings);
+    assert_eq!(built_stats.posting_lists, inserted_stats.posting_lists);
+    assert!(built_stats.memory_bytes() <= inserted_stats.memory_bytes());
+    for query in signatures.iter().step_by(100) {
+        assert_eq!(built.find_similar(query, 0.3), inserted.find_similar(query, 0.3));
+    }
+
+    let built = HnswIndex::new().ef_construction(64).build_from(&signatures);
+    assert_eq!(built.len(), 1000);
+    let found = nearest_found(&built, &signatures);
+    assert!(found >= 180, "found {} of the 200 nearest signatures", found);
+
+    let linear = LinearIndex::new().build_from(&signatures);
+    let sharded = ShardedIndex::with_shards(3, LinearIndex::new).build_from(&signatures);
+    assert_eq!(sharded.get(500), Some(&*signatures[500]));
+    for query in signatures.iter().step_by(100) {
+        assert_eq!(sharded.nearest(query, 10), linear.nearest(query, 10));
+    }
+
+    let mixed = [vec![0i8; 64], vec![0i8; 3]];
+    assert!(matches!(
+        HnswIndex::new().try_build_from(&mixed),
+        Err(SignatureError::MismatchedLengths { left: 64, right: 3 })
+    ));
+    assert!(linear.try_build_from([[0i8; 3]]).is_err());
+}
+
+#[test]
+fn shared_indexes_are_searched_while_inserts_are_batched() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedIndex<HnswIndex>>();
+    assert_send_sync::<ShardedIndex<LshIndex>>();
+
+    let signatures = vectors(400, 64);
+    let index = Arc::new(SharedIndex::new(LinearIndex::new()).batch_size(16));
+    let writers: Vec<_> = (0..4)
+        .map(|writer| {
+            let (index, signatures) = (Arc::clone(&index), signatures.clone());
+            thread::spawn(move || {
+                let mut keys = Vec::new();
+                for signature in signatures.iter().skip(writer).step_by(4) {
+                    keys.push((index.insert(signature), signature.clone()));
+                    // Searches run between the batches of every writer's inserts.
+                    let nearest = index.read().nearest(signature, 1);
+                    assert!(nearest.iter().all(|&(key, _)| key < 400));
+                }
+                keys
+            })
+        })
+        .collect();
+    let keys: Vec<_> = writers.into_iter().flat_map(|writer| writer.join().unwrap()).collect();
+
+    assert!(index.queued() < 16);
+    index.flush();
+    assert_eq!(index.queued(), 0);
+    let read = index.read();
+    assert_eq!(read.len(), 400);
+    for (key, signature) in &keys {
+        assert_eq!(read.get(*key), Some(signature.as_slice()));
+    }
+    drop(read);
+    assert!(matches!(
+        index.try_insert([0i8; 3]),
+        Err(SignatureError::MismatchedLengths { left: 64, right: 3 })
+    ));
+
+    assert!(index.remove(keys[0].0));
+    index.write().compact();
+    assert_eq!(index.insert(&signatures[0]), 399);
+    assert_eq!(Arc::try_unwrap(index).unwrap().into_inner().len(), 400);
+}
+
+#[test]
+fn ranked_indexes_order_matches_by_their_metric() {
+    let signatures = vectors(300, 64);
+    let index = LinearIndex::new().build_from(&signatures);
+
+    let by_manhattan = RankedIndex::new(index.clone(), Manhattan);
+    let by_cosine = RankedIndex::new(index, Cosine);
+    for query in signatures.iter().step_by(30) {
+        let mut expected: Vec<(usize, f64)> = signatures.iter()
+            .map(|s| Manhattan.distance(s, query))
+            .enumerate()
+            .filter(|&(_, distance)| distance <= 100.0)
+            .collect();
+        expected.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        assert_eq!(by_manhattan.find_within(query, 100.0), expected);
+
+        let nearest: Vec<usize> = by_cosine.nearest(query, 5).iter().map(|m| m.0).collect();
+        let linear: Vec<usize> = by_cosine.index().nearest(query, 5).iter().map(|m| m.0).collect();
+        assert_eq!(nearest, linear);
+    }
+
+    let mut by_normalized = RankedIndex::new(HnswIndex::new(), NormalizedDistance).candidates(8);
+    let key = by_normalized.index_mut().insert(&signatures[7]);
+    assert_eq!(by_normalized.nearest(&signatures[7], 3), [(key, 0.0)]);
+    assert!(by_normalized.try_nearest([0i8; 3], 1).is_err());
+}
+
+#[test]
+fn bounded_searches_stop_when_their_budget_runs_out() {
+    let signatures = vectors(500, 64);
+    let query = &signatures[250];
+    let linear = LinearIndex::new().build_from(&signatures);
+
+    let found = linear.find_similar_bounded(query, 0.1, &SearchBudget::new().candidates(100));
+    assert!(found.truncated);
+    let first = LinearIndex::new().build_from(&signatures[..100]);
+    assert_eq!(found.matches, first.find_similar(query, 0.1));
+    assert_eq!(linear.stats().comparisons, 100);
+
+    let unlimited = linear.nearest_bounded(query, 10, &SearchBudget::new().candidates(500));
+    assert!(!unlimited.truncated);
+    assert_eq!(unlimited.matches, linear.nearest(query, 10));
+    let expired = SearchBudget::new().deadline(Instant::now());
+    let late = linear.find_similar_bounded(query, 0.1, &expired);
+    assert!(late.truncated && late.matches.is_empty());
+
+    let hnsw = HnswIndex::new().build_from(&signatures);
+    let found = hnsw.nearest_bounded(query, 10, &SearchBudget::new().candidates(5));
+    assert!(found.truncated && found.matches.len() <= 6);
+    let unlimited = hnsw.find_similar_bounded(query, 0.3, &SearchBudget::new());
+    let matches = hnsw.find_similar(query, 0.3);
+    assert_eq!(unlimited, BoundedMatches { matches, truncated: false });
+
+    let lsh = LshIndex::new().build_from(&signatures);
+    let found = lsh.find_similar_bounded(query, -1.0, &SearchBudget::new().candidates(3));
+    assert!(found.truncated && found.matches.len() == 3);
+
+    let sharded = ShardedIndex::with_shards(4, LinearIndex::new).build_from(&signatures);
+    let found = sharded.nearest_bounded(query, 500, &SearchBudget::new().candidates(100));
+    assert!(found.truncated);
+    assert_eq!(found.matches.len(), 100);
+    assert!(sharded.try_nearest_bounded(&[0; 3], 1, &SearchBudget::new()).is_err());
+}
+
+#[test]
+fn index_stats_account_for_memory_and_searches() {
+    let signatures = vectors(200, 64);
+    let indexes: [Box<dyn Index>; 4] = [
+        Box::new(LinearIndex::new()),
+        Box::new(LshIndex::new().words(4, 32)),
+        Box::new(HnswIndex::new()),
+        Box::new(ShardedIndex::with_shards(4, LinearIndex::new)),
+    ];
+    let mut stats = Vec::new();
+    for mut index in indexes {
+        for signature in &signatures {
+            index.insert(signature);
+        }
+        index.remove(0);
+        index.find_similar(&signatures[1], 0.5);
+        index.nearest(&signatures[2], 3);
+        index.find_all_within(&signatures[3], 0.5).count();
+        stats.push(index.stats());
+    }
+
+    for stats in &stats {
+        assert_eq!((stats.len, stats.removed, stats.queries), (199, 1, 3));
+        assert!(stats.signature_bytes >= 200 * 64 && stats.bookkeeping_bytes >= 200);
+        assert!(stats.comparisons > 0);
+    }
+    let [linear, lsh, hnsw, sharded] = &stats[..] else { unreachable!() };
+    assert_eq!(linear.comparisons, 3 * 199);
+    assert_eq!((linear.structure_bytes, linear.average_posting_length()), (0, None));
+    assert_eq!(lsh.postings, 200 * 32);
+    assert!(lsh.posting_lists > 32 && lsh.structure_bytes > lsh.postings * 8);
+    assert!(lsh.average_posting_length().unwrap() > 1.0);
+    assert!(hnsw.layer_nodes >= 200 && hnsw.average_degree().unwrap() > 1.0);
+    assert!(hnsw.structure_bytes > hnsw.links * 8 && hnsw.average_posting_length().is_none());
+    assert_eq!(sharded.comparisons, linear.comparisons);
+    assert!(sharded.memory_bytes() > linear.memory_bytes());
+}
+
+#[test]
+fn range_queries_stream_the_same_matches_as_threshold_queries() {
+    let signatures = vectors(400, 64);
+    let indexes: [Box<dyn Index>; 4] = [
+        Box::new(LinearIndex::new()),
+        Box::new(LshIndex::new().words(4, 32)),
+        Box::new(HnswIndex::new()),
+        Box::new(ShardedIndex::with_shards(3, LinearIndex::new)),
+    ];
+    for mut index in indexes {
+        for signature in &signatures {
+            index.insert(signature);
+        }
+        for query in &signatures[..10] {
+            let mut streamed: Vec<_> = index.find_all_within(query, 0.2).collect();
+            streamed.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+            assert!(streamed.len() > 1);
+            assert_eq!(streamed, index.find_similar(query, 0.2));
+        }
+    }
+}
+
+#[test]
+fn removed_signatures_are_skipped_until_compacted_away() {
+    let signatures = vectors(300, 64);
+    let indexes: [Box<dyn Index>; 4] = [
+        Box::new(LinearIndex::new()),
+        Box::new(LshIndex::new().words(4, 32)),
+        Box::new(HnswIndex::new()),
+        Box::new(ShardedIndex::with_shards(3, LinearIndex::new)),
+    ];
+    for mut index in indexes {
+        for signature in &signatures {
+            index.insert(signature);
+        }
+        for key in (0..300).step_by(3) {
+            assert!(index.remove(key));
+        }
+        assert!(!index.remove(0));
+        assert_eq!((index.len(), index.removed()), (200, 100));
+        assert_eq!(index.get(3), None);
+        assert!(index.find_similar(&signatures[3], 0.2).iter().all(|(key, _)| key % 3 != 0));
+        assert_eq!(index.nearest(&signatures[4], 1), [(4, 1.0)]);
+
+        let keys = index.compact();
+        assert_eq!(keys.len(), 300);
+        assert_eq!((keys[3], keys[4], keys[5]), (None, Some(2), Some(3)));
+        assert_eq!((index.len(), index.removed()), (200, 0));
+        assert_eq!(index.get(2), Some(&*signatures[4]));
+        assert_eq!(index.nearest(&signatures[4], 1), [(2, 1.0)]);
+        assert_eq!(index.insert(&signatures[3]), 200);
+    }
+}
+
+#[test]
+fn saved_indexes_load_with_the_same_structure() {
+    let signatures = vectors(300, 64);
+    let dir = std::env::temp_dir();
+    let linear = dir.join("image-match-index.linear");
+    let hnsw = dir.join("image-match-index.hnsw");
+    round_trip(LinearIndex::new(), &signatures, &linear);
+    round_trip(LshIndex::new().words(4, 32), &signatures, &dir.join("image-match-index.lsh"));
+    round_trip(HnswIndex::new().connections(8), &signatures, &hnsw);
+    // Sharded indexes save their shards into the one snapshot, so it's replaced all at once.
+    let sharded = dir.join("image-match-index.sharded");
+    let shard = dir.join("image-match-index.sharded.0");
+    let _ = std::fs::remove_file(&shard);
+    let index = ShardedIndex::with_shards(3, || HnswIndex::new().connections(8));
+    round_trip(index, &signatures, &sharded);
+    assert!(!shard.exists());
+
+    assert_eq!(LshIndex::load(&linear).unwrap_err().kind(), ErrorKind::InvalidData);
+    let mut bytes = std::fs::read(&hnsw).unwrap();
+    bytes[1000] ^= 1;
+    std::fs::write(&hnsw, &bytes).unwrap();
+    assert_eq!(HnswIndex::load(&hnsw).unwrap_err().kind(), ErrorKind::InvalidData);
+}
+
+/// Fills `index`, removes a signature, and checks that the index saved at `path` loads to search
+/// and grow like the original.
+fn round_trip<I: Index>(mut index: I, signatures: &[Vec<i8>], path: &Path) {
+    for signature in signatures {
+        index.insert(signature);
+    }
+    index.remove(7);
+    index.save(path).unwrap();
+    let mut loaded = I::load(path).unwrap();
+
+    assert_eq!((loaded.len(), loaded.removed()), (299, 1));
+    for query in &signatures[..10] {
+        assert_eq!(loaded.find_similar(query, 0.2), index.find_similar(query, 0.2));
+        assert_eq!(loaded.nearest(query, 5), index.nearest(query, 5));
+    }
+    assert_eq!(loaded.insert(&signatures[7]), index.insert(&signatures[7]));
+    assert_eq!(loaded.nearest(&signatures[7], 5), index.nearest(&signatures[7], 5));
+}
@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+
+use image_match::index::HnswIndex;
+use image_match::{
+    AlphaMode, Arithmetic, GrayscaleMode, MemorySignatureStore, SignatureConfig, SignatureStore,
+};
+
+mod common;
+
+#[test]
+fn memory_stores_find_similar_signatures_with_their_metadata() {
+    let config = SignatureConfig::new();
+    let original = common::pattern_rgba(120, 80);
+    let mut edited = original.clone();
+    edited[..4 * 120 * 8].fill(0);
+    let signature = config.compute_signature(&original, 120);
+    let similar = config.compute_signature(&edited, 120);
+    let other = config.compute_signature(&common::pattern_rgba(333, 517), 333);
+    let cropped = config.crop(0.1).compute_signature(&original, 120);
+
+    let mut store = MemorySignatureStore::new();
+    assert_eq!(store.insert("original", &signature, PathBuf::from("a.png")), None);
+    store.insert("edited", &signature, PathBuf::from("b.png"));
+    assert_eq!(store.insert("edited", &similar, PathBuf::from("c.png")), Some("b.png".into()));
+    store.insert("other", &other, PathBuf::from("d.png"));
+    store.insert("cropped", &cropped, PathBuf::from("e.png"));
+    assert_eq!(store.len(), 4);
+    assert_eq!(store.get("edited"), Some((similar.clone(), &PathBuf::from("c.png"))));
+
+    let similarity = signature.similarity(&similar);
+    let matches = store.query(&signature, 0.5);
+    assert_eq!(
+        matches,
+        [("original", 1.0, &PathBuf::from("a.png")), ("edited", similarity, &"c.png".into())]
+    );
+    let nearest = store.knn(&signature, 3);
+    assert_eq!(nearest.iter().map(|m| m.0).collect::<Vec<_>>(), ["original", "edited", "other"]);
+    let mut streamed: Vec<_> = store.find_all_within(&signature, 0.5).collect();
+    streamed.sort_by_key(|m| m.0);
+    assert_eq!(streamed, [matches[1], matches[0]]);
+
+    assert_eq!(store.remove("original"), Some((signature.clone(), PathBuf::from("a.png"))));
+    assert_eq!(store.remove("original"), None);
+    let nearest = store.knn(&signature, 3);
+    assert_eq!(nearest.iter().map(|m| m.0).collect::<Vec<_>>(), ["edited", "other"]);
+    let smaller = config.grid_size(4).compute_signature(&original, 120);
+    assert!(store.try_knn(&smaller, 1).is_err());
+}
+
+#[test]
+fn memory_stores_search_through_other_indexes() {
+    let config = SignatureConfig::new();
+    let original = common::pattern_rgba(120, 80);
+    let mut edited = original.clone();
+    edited[..4 * 120 * 8].fill(0);
+    let signature = config.compute_signature(&original, 120);
+    let similar = config.compute_signature(&edited, 120);
+
+    let mut store = MemorySignatureStore::<(), _>::with_index(HnswIndex::new());
+    for (i, width) in (100..140).step_by(4).enumerate() {
+        let noise = config.compute_signature(&common::pattern_rgba(width, 517), width);
+        SignatureStore::insert(&mut store, &format!("noise {}", i), &noise).unwrap();
+    }
+    SignatureStore::insert(&mut store, "original", &signature).unwrap();
+    SignatureStore::insert(&mut store, "edited", &similar).unwrap();
+
+    let matches = store.search_similar(&signature, 0.5, 10).unwrap();
+    let ids: Vec<_> = matches.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, ["original", "edited"]);
+    assert!(store.delete("original").unwrap());
+    assert_eq!(store.knn(&signature, 1)[0].0, "edited");
+
+    // Removing most signatures compacts the index under the remaining ones.
+    for i in 0..8 {
+        assert!(store.delete(&format!("noise {}", i)).unwrap());
+    }
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.get("edited"), Some((similar.clone(), &())));
+    assert_eq!(store.knn(&signature, 1)[0].0, "edited");
+    store.insert("original", &signature, ());
+    assert_eq!(store.query(&signature, 0.99), [("original", 1.0, &())]);
+}
+
+#[test]
+fn memory_stores_compute_queries_with_their_own_parameters() {
+    // Every option that changes signatures is carried over to the query, not just the grid's.
+    let config = SignatureConfig::new()
+        .crop(0.1)
+        .identical_tolerance(5)
+        .grayscale_mode(GrayscaleMode::Rec709)
+        .alpha_mode(AlphaMode::Background([250, 240, 10]))
+        .arithmetic(Arithmetic::Integer);
+    let original = common::pattern_rgba(120, 80);
+    let mut store = MemorySignatureStore::new();
+    assert!(store.query_buffer(&original, 120, 0.5).is_empty());
+    store.insert("original", &config.compute_signature(&original, 120), ());
+    assert_eq!(store.params(), Some(config.params()));
+    assert_eq!(store.params().unwrap().config().params(), config.params());
+
+    assert_eq!(store.query_buffer(&original, 120, 0.9), [("original", 1.0, &())]);
+    assert!(store.try_query_buffer(&original[..7], 120, 0.9).is_err());
+
+    #[cfg(feature = "img")]
+    {
+        let image = image::RgbaImage::from_raw(120, 80, original).unwrap();
+        assert_eq!(store.query_image(image, 0.9), [("original", 1.0, &())]);
+    }
+}
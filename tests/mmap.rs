@@ -0,0 +1,69 @@
+#![cfg(feature = "mmap")]
+
+use image_match::index::{LshIndex, MappedLshIndex};
+use image_match::{get_buffer_signature, MappedImage, PixelFormat, SignatureConfig};
+
+mod common;
+
+#[test]
+fn mapped_files_match_buffers() {
+    let rgba = common::pattern_rgba(120, 80);
+    let gray: Vec<u8> = rgba.chunks(4).map(|p| p[0]).collect();
+    let dir = std::env::temp_dir();
+    let rgba_path = dir.join("image-match-mapped.rgba");
+    let gray_path = dir.join("image-match-mapped.gray");
+    std::fs::write(&rgba_path, &rgba).unwrap();
+    std::fs::write(&gray_path, &gray).unwrap();
+
+    let config = SignatureConfig::default();
+    // Safety: the files are written above and not touched again until they're removed.
+    let mapped = unsafe { MappedImage::open(&rgba_path, 120, PixelFormat::Rgba) }.unwrap();
+    assert_eq!(config.compute_mapped(&mapped), get_buffer_signature(&rgba, 120));
+    let mapped = unsafe { MappedImage::open_gray(&gray_path, 120) }.unwrap();
+    assert_eq!(config.compute_mapped(&mapped), config.compute_gray(&gray, 120));
+    let mapped = unsafe { MappedImage::open_gray(&gray_path, 7) }.unwrap();
+    assert!(config.try_compute_mapped(&mapped).is_err());
+
+    std::fs::remove_file(rgba_path).unwrap();
+    std::fs::remove_file(gray_path).unwrap();
+}
+
+#[test]
+fn mapped_indexes_match_the_indexes_they_were_written_from() {
+    let config = SignatureConfig::default();
+    let mut index = LshIndex::new();
+    for width in (100..300).step_by(5) {
+        index.insert(config.compute(&common::pattern_rgba(width, 151), width));
+    }
+    let path = std::env::temp_dir().join("image-match-mapped.lsh");
+    index.write_mapped(std::fs::File::create(&path).unwrap()).unwrap();
+
+    // Safety: the file is written above and not touched again until it's removed.
+    let mapped = unsafe { MappedLshIndex::open(&path) }.unwrap();
+    assert_eq!(mapped.len(), index.len());
+    assert_eq!(mapped.get(7), index.get(7));
+    assert_eq!(mapped.get(index.len()), None);
+    for width in (100..300).step_by(15) {
+        let query = config.compute(&common::pattern_rgba(width + 2, 151), width + 2);
+        let matches = index.find_similar(&query, 0.3);
+        assert!(!matches.is_empty());
+        assert_eq!(mapped.find_similar(&query, 0.3), matches);
+        assert_eq!(mapped.nearest(&query, 3), index.nearest(&query, 3));
+    }
+    assert!(mapped.try_nearest([0i8; 5], 3).is_err());
+    drop(mapped);
+
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+    assert!(unsafe { MappedLshIndex::open(&path) }.is_err());
+
+    // A posting list starting near the end of the address space, in the entry before the one
+    // ending the last list, which sits just before the postings.
+    let posting_total = u64::from_le_bytes(bytes[40..48].try_into().unwrap()) as usize;
+    let postings = bytes.len() - posting_total * 4;
+    let mut corrupted = bytes.clone();
+    corrupted[postings - 24..postings - 16].copy_from_slice(&(u64::MAX - 1).to_le_bytes());
+    std::fs::write(&path, &corrupted).unwrap();
+    assert!(unsafe { MappedLshIndex::open(&path) }.is_err());
+    std::fs::remove_file(path).unwrap();
+}
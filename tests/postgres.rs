@@ -0,0 +1,48 @@
+#![cfg(feature = "postgres")]
+
+use image_match::{PgSignatureStore, SignatureConfig, SignatureStore};
+use postgres::{Client, NoTls};
+
+mod common;
+
+#[test]
+fn postgres_stores_find_similar_signatures() {
+    let Ok(url) = std::env::var("IMAGE_MATCH_POSTGRES_URL") else {
+        eprintln!("IMAGE_MATCH_POSTGRES_URL isn't set, skipping");
+        return;
+    };
+    let client = Client::connect(&url, NoTls).unwrap();
+    let mut store = PgSignatureStore::with_table(client, "image_match_test").unwrap();
+
+    let config = SignatureConfig::new();
+    let original = common::pattern_rgba(120, 80);
+    let mut edited = original.clone();
+    edited[..4 * 120 * 8].fill(0);
+    let signature = config.compute_signature(&original, 120);
+    let similar = config.compute_signature(&edited, 120);
+    let other = config.compute_signature(&common::pattern_rgba(333, 517), 333);
+    let shorter = SignatureConfig::new().grid_size(7).compute_signature(&original, 120);
+
+    store.insert("original", &signature).unwrap();
+    store.insert("edited", &other).unwrap();
+    store.insert("edited", &similar).unwrap();
+    store.insert("other", &other).unwrap();
+    store.insert("shorter", &shorter).unwrap();
+    assert_eq!(store.get("edited").unwrap(), Some(similar.clone()));
+    assert_eq!(store.get("missing").unwrap(), None);
+
+    let matches = store.search_similar(&signature, 0.5, 10).unwrap();
+    let ids: Vec<_> = matches.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, ["original", "edited"]);
+    assert_eq!(matches[0].1, 1.0);
+    assert_eq!(matches[1].1, signature.similarity(&similar));
+    assert_eq!(store.search_similar(&signature, 0.5, 1).unwrap().len(), 1);
+
+    assert!(store.delete("original").unwrap());
+    assert!(!store.delete("original").unwrap());
+    let matches = store.search_similar(&signature, 0.5, 10).unwrap();
+    assert_eq!(matches.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(), ["edited"]);
+
+    let mut client = store.into_client();
+    client.batch_execute("DROP TABLE image_match_test_words, image_match_test").unwrap();
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "redis")]
+
+use std::time::Duration;
+
+use image_match::{RedisSignatureStore, SignatureConfig, SignatureStore};
+
+mod common;
+
+#[test]
+fn redis_stores_find_similar_signatures() {
+    let Ok(url) = std::env::var("IMAGE_MATCH_REDIS_URL") else {
+        eprintln!("IMAGE_MATCH_REDIS_URL isn't set, skipping");
+        return;
+    };
+    let client = redis::Client::open(url).unwrap();
+    let mut store = RedisSignatureStore::with_prefix(client.get_connection().unwrap(), "test");
+
+    let config = SignatureConfig::new();
+    let original = common::pattern_rgba(120, 80);
+    let mut edited = original.clone();
+    edited[..4 * 120 * 8].fill(0);
+    let signature = config.compute_signature(&original, 120);
+    let similar = config.compute_signature(&edited, 120);
+    let other = config.compute_signature(&common::pattern_rgba(333, 517), 333);
+
+    store.insert("original", &signature).unwrap();
+    store.insert("edited", &signature).unwrap();
+    store.insert("edited", &similar).unwrap();
+    store.insert("other", &other).unwrap();
+    assert_eq!(store.get("edited").unwrap(), Some(similar.clone()));
+    assert_eq!(store.get("missing").unwrap(), None);
+
+    let matches = store.search_similar(&signature, 0.5, 10).unwrap();
+    let ids: Vec<_> = matches.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, ["original", "edited"]);
+    assert_eq!(matches[1].1, signature.similarity(&similar));
+
+    for id in ["original", "edited", "other"] {
+        assert!(store.delete(id).unwrap());
+    }
+    assert!(!store.delete("original").unwrap());
+    assert_eq!(store.search_similar(&signature, -1.0, 10).unwrap(), []);
+
+    let mut store = RedisSignatureStore::with_prefix(store.into_connection(), "test")
+        .ttl(Duration::from_millis(50));
+    store.insert("expiring", &signature).unwrap();
+    assert_eq!(store.search_similar(&signature, 0.5, 10).unwrap().len(), 1);
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(store.get("expiring").unwrap(), None);
+    assert_eq!(store.search_similar(&signature, 0.5, 10).unwrap(), []);
+}
@@ -0,0 +1,32 @@
+#![cfg(feature = "rkyv")]
+
+use image_match::{ArchivedSignature, SignatureConfig};
+use rkyv::rancor::Error;
+use rkyv::vec::ArchivedVec;
+
+mod common;
+
+#[test]
+fn archived_signatures_compare_in_place() {
+    let config = SignatureConfig::new();
+    let signatures: Vec<_> = [(120, 80), (64, 64), (90, 140)].iter()
+        .map(|(width, height)| {
+            config.compute_signature(&common::pattern_rgba(*width, *height), *width)
+        })
+        .collect();
+
+    let bytes = rkyv::to_bytes::<Error>(&signatures).unwrap();
+    let archived = rkyv::access::<ArchivedVec<ArchivedSignature>, Error>(&bytes).unwrap();
+    assert_eq!(archived.len(), 3);
+    for (archived, signature) in archived.iter().zip(&signatures) {
+        assert_eq!(archived.params(), *signature.params());
+        assert_eq!(archived.similarity(&signatures[0]), signature.similarity(&signatures[0]));
+        assert_eq!(&archived.to_signature().unwrap(), signature);
+    }
+
+    let other = SignatureConfig::new().grid_size(7);
+    let other = other.compute_signature(&common::pattern_rgba(64, 64), 64);
+    assert!(archived[0].try_similarity(&other).is_err());
+    let truncated = &bytes[..bytes.len() - 4];
+    assert!(rkyv::access::<ArchivedVec<ArchivedSignature>, Error>(truncated).is_err());
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "serde")]
+
+use image_match::{
+    AlphaMode, GrayscaleMode, MatchClass, MatchPolicy, NeighborPadding, Signature, SignatureConfig,
+};
+
+mod common;
+
+#[test]
+fn signatures_round_trip_compactly() {
+    let rgba = common::pattern_rgba(120, 80);
+    for config in [SignatureConfig::new(), SignatureConfig::new().grid_size(7).crop(0.1)] {
+        let signature = config.compute_signature(&rgba, 120);
+
+        let json = serde_json::to_string(&signature).unwrap();
+        assert!(json.len() < 2 * signature.len(), "{}", json);
+        assert_eq!(serde_json::from_str::<Signature>(&json).unwrap(), signature);
+
+        let bytes = bincode::serialize(&signature).unwrap();
+        // The elements packed three to a byte, and the params.
+        assert!(bytes.len() < signature.len() / 3 + 64, "{}", bytes.len());
+        assert_eq!(bincode::deserialize::<Signature>(&bytes).unwrap(), signature);
+    }
+
+    let signature = SignatureConfig::new().compute_signature(&rgba, 120);
+    let mut json: serde_json::Value = serde_json::to_value(&signature).unwrap();
+    json["params"]["grid_size"] = 9.into();
+    assert!(serde_json::from_value::<Signature>(json).is_err());
+}
+
+#[test]
+fn configs_and_policies_round_trip() {
+    let config = SignatureConfig::new()
+        .grid_size(12)
+        .grayscale_mode(GrayscaleMode::Custom([0.5, 0.25, 0.25]))
+        .alpha_mode(AlphaMode::Background([1, 2, 3]))
+        .neighbor_padding(NeighborPadding::Zeros);
+    let json = serde_json::to_string(&config).unwrap();
+    let restored: SignatureConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.params(), config.params());
+    assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+
+    // Fields left out take their defaults.
+    let partial: SignatureConfig = serde_json::from_str(r#"{"grid_size": 8}"#).unwrap();
+    assert_eq!(partial.params(), SignatureConfig::new().grid_size(8).params());
+
+    let policy = MatchPolicy::new(0.7);
+    let report = (policy, MatchClass::NearDuplicate);
+    let json = serde_json::to_string(&report).unwrap();
+    assert_eq!(serde_json::from_str::<(MatchPolicy, MatchClass)>(&json).unwrap(), report);
+}
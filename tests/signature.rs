@@ -0,0 +1,353 @@
+use image_match::{
+    compute_fixed_signature, compute_signature_into, cosine_similarity, get_buffer_signature,
+    elasticsearch_document, export_csv, import_csv, signature_length, signature_words,
+    try_compute_signature_into, try_elasticsearch_document, try_signature_words, AlphaMode,
+    Arithmetic, Compat, FixedSignature, GrayscaleMode, GridPlacement, NeighborPadding,
+    NormalizedSignature, Signature, SignatureConfig, SignatureError, CLEAN_RENDER_TOLERANCE,
+    DEFAULT_IDENTICAL_TOLERANCE, DEFAULT_SIGNATURE_LENGTH, NOISY_SCAN_TOLERANCE,
+};
+
+mod common;
+
+#[test]
+fn signature_lengths_follow_grid_size() {
+    let rgba = common::pattern_rgba(240, 160);
+    for grid_size in [3, 5, 10, 13] {
+        let signature = SignatureConfig::new().grid_size(grid_size).compute(&rgba, 240);
+        assert_eq!(signature.len(), signature_length(grid_size));
+    }
+    // Releases up to 0.2.3 also compared each grid point with itself, for 625 elements.
+    assert_eq!(DEFAULT_SIGNATURE_LENGTH, 544);
+}
+
+#[test]
+fn fixed_signature_matches_vec() {
+    let rgba = common::pattern_rgba(120, 80);
+    let fixed = compute_fixed_signature(&rgba, 120);
+    assert_eq!(fixed.as_ref(), get_buffer_signature(&rgba, 120).as_slice());
+
+    let mismatched: Result<FixedSignature<100>, _> =
+        SignatureConfig::default().try_compute_fixed(&rgba, 120);
+    assert_eq!(mismatched, Err(SignatureError::MismatchedLengths { left: 100, right: 544 }));
+}
+
+#[test]
+fn signature_written_into_slot_matches_vec() {
+    let rgba = common::pattern_rgba(120, 80);
+    let mut table = vec![0; 3 * DEFAULT_SIGNATURE_LENGTH];
+    let slot = DEFAULT_SIGNATURE_LENGTH..2 * DEFAULT_SIGNATURE_LENGTH;
+    compute_signature_into(&rgba, 120, &mut table[slot.clone()]);
+    assert_eq!(table[slot], get_buffer_signature(&rgba, 120));
+    assert!(table[..DEFAULT_SIGNATURE_LENGTH].iter().all(|v| *v == 0));
+
+    let config = SignatureConfig::new().grid_size(5);
+    let mut computer = config.computer();
+    let mut out = [0; signature_length(5)];
+    computer.compute_into(&rgba, 120, &mut out);
+    assert_eq!(out.as_slice(), config.compute(&rgba, 120));
+
+    assert_eq!(
+        try_compute_signature_into(&rgba, 120, &mut [0; 100]),
+        Err(SignatureError::MismatchedLengths { left: 100, right: 544 })
+    );
+}
+
+#[test]
+fn normalized_signature_matches_cosine() {
+    let a = get_buffer_signature(&common::pattern_rgba(120, 80), 120);
+    let b = get_buffer_signature(&common::pattern_rgba(100, 90), 100);
+    let (na, nb) = (NormalizedSignature::from(a.clone()), NormalizedSignature::from(b.as_slice()));
+
+    let squares: i32 = a.iter().map(|v| *v as i32 * *v as i32).sum();
+    assert_eq!(na.norm(), (squares as f64).sqrt());
+    assert_eq!(na.similarity(&nb), cosine_similarity(&a, &b));
+    assert_eq!(na.similarity(&na), 1.0);
+    assert!(na.try_similarity(&NormalizedSignature::new(vec![1])).is_err());
+    assert_eq!(na.into_inner(), a);
+}
+
+#[test]
+fn crop_origin_grid_ignores_padding() {
+    // The same image with a flat border added on the top and left.
+    let (width, height, border) = (120, 80, 30);
+    let rgba = common::pattern_rgba(width, height);
+    let padded: Vec<u8> = (0..height + border)
+        .flat_map(|y| {
+            let row = y.checked_sub(border).map(|y| &rgba[4 * width * y..4 * width * (y + 1)]);
+            let fill_width = if row.is_some() { border } else { border + width };
+            let fill = [128, 128, 128, 255].repeat(fill_width);
+            fill.into_iter().chain(row.into_iter().flatten().copied())
+        })
+        .collect();
+
+    let similarity = |config: SignatureConfig| {
+        cosine_similarity(config.compute(&rgba, width), config.compute(&padded, width + border))
+    };
+    let uncorrected = similarity(SignatureConfig::new());
+    let corrected = similarity(SignatureConfig::new().grid_placement(GridPlacement::CropOrigin));
+    assert!(uncorrected < 0.6, "{}", uncorrected);
+    assert!(corrected > 0.99, "{}", corrected);
+}
+
+#[test]
+fn zero_padding_fills_in_missing_neighbors() {
+    let rgba = common::pattern_rgba(120, 80);
+    let padded = SignatureConfig::new().neighbor_padding(NeighborPadding::Zeros);
+    let signature = padded.compute(&rgba, 120);
+    assert_eq!(signature.len(), 8 * 9 * 9);
+    assert_eq!(signature.len(), padded.signature_length());
+
+    // Each grid point's neighbors, in signature order, with those outside the grid removed.
+    let deltas = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+    let in_grid = (0..9 * 9).flat_map(|point| {
+        deltas.iter().map(move |(dx, dy)| {
+            (0..9).contains(&(point % 9 + dx)) && (0..9).contains(&(point / 9 + dy))
+        })
+    });
+    let omitted: Vec<i8> = signature.iter()
+        .zip(in_grid)
+        .filter_map(|(element, in_grid)| in_grid.then_some(*element))
+        .collect();
+    assert_eq!(omitted, get_buffer_signature(&rgba, 120));
+}
+
+#[test]
+fn goldberg_signatures_use_the_padded_layout() {
+    let rgba = common::pattern_rgba(300, 200);
+    let config = SignatureConfig::new().compat(Compat::Goldberg);
+    let signature = config.compute(&rgba, 300);
+    assert_eq!(signature.len(), 8 * 9 * 9);
+    assert!(signature.iter().all(|element| (-2..=2).contains(element)));
+    // The top left grid point has no neighbors above or to its left.
+    assert!([0, 1, 2, 3, 5].iter().all(|i| signature[*i] == 0));
+
+    let mut out = vec![0; config.signature_length()];
+    config.computer().compute_into(&rgba, 300, &mut out);
+    assert_eq!(out, signature);
+
+    // Gray levels keep their scale of 0 to 255, so the tolerance of 2/255 zeroes no differences
+    // and only the padding is left zero, where it zeroes many of the same image's color levels.
+    let gray: Vec<u8> = rgba.chunks_exact(4).map(|pixel| pixel[0]).collect();
+    let opaque_gray: Vec<u8> = gray.iter().flat_map(|&level| [level, level, level, 255]).collect();
+    let gray_signature = config.try_compute_gray(&gray, 300).unwrap();
+    let zeros = |signature: &[i8]| signature.iter().filter(|element| **element == 0).count();
+    let padding = signature.len() - signature_length(10);
+    assert_eq!(zeros(&gray_signature), padding);
+    assert!(zeros(&config.compute(&opaque_gray, 300)) > padding);
+    assert_eq!(
+        config.try_compute_f32(&vec![0.0; 300 * 200 * 4], 300),
+        Err(SignatureError::CompatUnsupported)
+    );
+}
+
+#[test]
+fn tolerance_presets_trade_detail_for_noise() {
+    let rgba = common::pattern_rgba(240, 160);
+    let zeros = |tolerance| {
+        let config = SignatureConfig::new().identical_tolerance(tolerance);
+        config.compute(&rgba, 240).iter().filter(|v| **v == 0).count()
+    };
+
+    assert_eq!(
+        SignatureConfig::new().compute(&rgba, 240),
+        SignatureConfig::new().identical_tolerance(DEFAULT_IDENTICAL_TOLERANCE).compute(&rgba, 240)
+    );
+    assert!(zeros(CLEAN_RENDER_TOLERANCE) <= zeros(DEFAULT_IDENTICAL_TOLERANCE));
+    assert!(zeros(DEFAULT_IDENTICAL_TOLERANCE) <= zeros(NOISY_SCAN_TOLERANCE));
+}
+
+#[test]
+fn signatures_round_trip_through_base64() {
+    let rgba = common::pattern_rgba(120, 80);
+    for grid_size in [10, 7, 5] {
+        let config = SignatureConfig::new().grid_size(grid_size);
+        let signature = config.compute_signature(&rgba, 120);
+        let encoded = signature.to_base64();
+        assert!(encoded.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_'));
+        assert_eq!(Signature::from_base64(config.params(), &encoded), Ok(signature));
+    }
+
+    let params = SignatureConfig::new().params();
+    let encoded = SignatureConfig::new().compute_signature(&rgba, 120).to_base64();
+    assert_eq!(encoded.len(), 243);
+    let invalid = SignatureError::InvalidEncoding { format: "base64" };
+    assert_eq!(Signature::from_base64(params, &encoded.replace('A', "+")), Err(invalid.clone()));
+    assert_eq!(Signature::from_base64(params, &encoded[..241]), Err(invalid.clone()));
+    // The last character only carries four bits, so an odd one leaves stray bits.
+    let stray = format!("{}B", &encoded[..242]);
+    assert_eq!(Signature::from_base64(params, &stray), Err(invalid));
+    assert!(matches!(
+        Signature::from_base64(params, &encoded[..240]),
+        Err(SignatureError::MismatchedLengths { .. })
+    ));
+    assert_eq!(
+        Signature::from_base64(params, &format!("{}{}", "_".repeat(240), &encoded[240..])),
+        Err(SignatureError::ElementOutOfRange { index: 0, value: 8 })
+    );
+}
+
+#[test]
+fn signatures_round_trip_through_containers() {
+    let rgba = common::pattern_rgba(120, 80);
+    let configs = [
+        SignatureConfig::new(),
+        SignatureConfig::new()
+            .grid_size(7)
+            .crop(0.1)
+            .grid_placement(GridPlacement::CropOrigin)
+            .neighbor_padding(NeighborPadding::Zeros),
+        SignatureConfig::new().compat(Compat::Goldberg),
+        SignatureConfig::new()
+            .grayscale_mode(GrayscaleMode::Custom([0.3, 0.6, 0.1]))
+            .alpha_mode(AlphaMode::Background([250, 240, 10]))
+            .arithmetic(Arithmetic::Integer),
+    ];
+    for config in configs {
+        let signature = config.compute_signature(&rgba, 120);
+        assert_eq!(Signature::decode(&signature.encode()), Ok(signature.clone()));
+        assert_eq!(Signature::decode(&signature.encode_with_norm()), Ok(signature));
+    }
+
+    let encoded = SignatureConfig::new().compute_signature(&rgba, 120).encode_with_norm();
+    assert_eq!(encoded.len(), 230);
+    let invalid = Err(SignatureError::InvalidEncoding { format: "signature container" });
+    assert_eq!(Signature::decode(&encoded[..229]), invalid);
+    assert_eq!(Signature::decode(&[&encoded[..], &[0]].concat()), invalid);
+
+    // Version 1 containers didn't record the grayscale, alpha, and arithmetic modes.
+    for version in [1, 3] {
+        let mut other = encoded.clone();
+        other[4] = version;
+        assert_eq!(Signature::decode(&other), Err(SignatureError::UnsupportedVersion { version }));
+    }
+
+    let mut corrupted = encoded.clone();
+    corrupted[48] = if corrupted[48] == 0 { 124 } else { 0 };
+    assert_eq!(Signature::decode(&corrupted), invalid);
+    // A background color stored for a mode without one.
+    let mut corrupted = encoded.clone();
+    corrupted[21] = 1;
+    assert_eq!(Signature::decode(&corrupted), invalid);
+
+    // Grid sizes whose signature length overflows, or that no signature can be computed with.
+    for grid_size in [u32::MAX, 1 << 31, 1] {
+        let mut corrupted = encoded.clone();
+        corrupted[6..10].copy_from_slice(&grid_size.to_le_bytes());
+        assert_eq!(Signature::decode(&corrupted), invalid);
+    }
+    let params = SignatureConfig::new().grid_size(usize::MAX).params();
+    assert!(matches!(
+        Signature::try_new(params, vec![0; 544]),
+        Err(SignatureError::InvalidParameter { name: "grid_size", .. })
+    ));
+}
+
+#[test]
+fn signatures_round_trip_through_hex() {
+    let rgba = common::pattern_rgba(120, 80);
+    let signature = SignatureConfig::new().compute_signature(&rgba, 120);
+    let encoded = signature.to_hex();
+    assert_eq!(encoded.len(), signature.len());
+    for (digit, element) in encoded.chars().zip(signature.iter()) {
+        let expected = match element { -2 => 'e', -1 => 'f', 0 => '0', 1 => '1', _ => '2' };
+        assert_eq!(digit, expected);
+    }
+    let params = *signature.params();
+    assert_eq!(Signature::from_hex(params, &encoded.to_uppercase()), Ok(signature));
+
+    let invalid = Err(SignatureError::InvalidEncoding { format: "hex" });
+    assert_eq!(Signature::from_hex(params, &encoded[1..]), invalid);
+    assert_eq!(Signature::from_hex(params, &encoded.replace('0', "g")), invalid);
+    assert!(matches!(
+        Signature::from_hex(params, &encoded[2..]),
+        Err(SignatureError::MismatchedLengths { .. })
+    ));
+    assert_eq!(
+        Signature::from_hex(params, &format!("7{}", &encoded[1..])),
+        Err(SignatureError::ElementOutOfRange { index: 0, value: 7 })
+    );
+}
+
+#[test]
+fn signatures_round_trip_through_csv() {
+    let first = SignatureConfig::new().compute_signature(&common::pattern_rgba(120, 80), 120);
+    let second = SignatureConfig::new()
+        .grid_size(7)
+        .crop(0.1)
+        .neighbor_padding(NeighborPadding::Zeros)
+        .grayscale_mode(GrayscaleMode::Custom([0.3, 0.6, 0.1]))
+        .alpha_mode(AlphaMode::Background([250, 240, 10]))
+        .arithmetic(Arithmetic::Integer)
+        .compute_signature(&common::pattern_rgba(64, 64), 64);
+    let records = [("plain.png", &first), ("a, \"quoted\"\nname.png", &second)];
+
+    let expected: Vec<_> =
+        records.iter().map(|(id, sig)| (id.to_string(), (*sig).clone())).collect();
+    for delimiter in [b',', b'\t'] {
+        let mut text = Vec::new();
+        export_csv(&mut text, delimiter, records).unwrap();
+        assert_eq!(import_csv(&text[..], delimiter).unwrap(), expected);
+    }
+
+    // As saved by a spreadsheet application.
+    let mut text = Vec::new();
+    export_csv(&mut text, b',', records).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    let saved = text.replace('\n', "\r\n").replacen("\r\nname", "\nname", 1);
+    let saved = format!("\u{feff}{}\r\n", saved);
+    assert_eq!(import_csv(saved.as_bytes(), b',').unwrap(), expected);
+
+    let error = import_csv(text.replacen("Omit", "Nope", 1).as_bytes(), b',').unwrap_err();
+    assert!(error.to_string().starts_with("line 2: "), "{}", error);
+    let error = import_csv(format!("{}x,y\n", text).as_bytes(), b',').unwrap_err();
+    assert!(error.to_string().starts_with("line 5: "), "{}", error);
+    assert!(import_csv(&b"id,signature\n"[..], b',').is_err());
+}
+
+#[test]
+fn elasticsearch_documents_match_the_python_layout() {
+    let signature = [1, -2, 0, 2, -1, 0, 0, 0, 1, 1, -1, 2];
+    let document = try_elasticsearch_document("a \"b\"\n.jpg", signature, 4, 5).unwrap();
+    let document: serde_json::Value = serde_json::from_str(&document).unwrap();
+    assert_eq!(
+        document,
+        serde_json::json!({
+            "path": "a \"b\"\n.jpg",
+            "signature": signature,
+            // Words start at floor(i * 12 / 5), and the last runs past the end.
+            "simple_word_0": 2 + 9 + 2 * 27,
+            "simple_word_1": 1 + 2 * 3 + 27,
+            "simple_word_2": 3 + 9 + 27,
+            "simple_word_3": 1 + 2 * 3 + 2 * 9,
+            "simple_word_4": 2 + 2 * 9 + 27,
+        })
+    );
+
+    let rgba = common::pattern_rgba(300, 200);
+    let goldberg = SignatureConfig::new().compat(Compat::Goldberg).compute(&rgba, 300);
+    let document: serde_json::Value =
+        serde_json::from_str(&elasticsearch_document("x.png", &goldberg)).unwrap();
+    assert_eq!(document.as_object().unwrap().len(), 2 + 63);
+    assert!(matches!(
+        try_elasticsearch_document("x.png", signature, 13, 5),
+        Err(SignatureError::InvalidParameter { name: "word_length", .. })
+    ));
+}
+
+#[test]
+fn words_reduce_runs_of_elements_to_signs() {
+    let signature = [1, -2, 0, 2, -1, 0, 0, 0, 1, 1, -1, 2];
+    let words = try_signature_words(signature, 4, 5).unwrap();
+    let strings: Vec<String> = words.iter().map(|word| word.to_string()).collect();
+    assert_eq!(strings, ["2012", "1201", "0111", "1220", "2021"]);
+    assert_eq!(words[0].trits(), [1, -1, 0, 1]);
+    assert_eq!(words[0].to_int(), 2 + 9 + 2 * 27);
+
+    let rgba = common::pattern_rgba(300, 200);
+    let mut brightened = rgba.clone();
+    brightened.iter_mut().for_each(|channel| *channel = channel.saturating_add(3));
+    let a = signature_words(get_buffer_signature(&rgba, 300));
+    let b = signature_words(get_buffer_signature(&brightened, 300));
+    assert_eq!(a.len(), 63);
+    assert!(a.iter().zip(&b).filter(|(a, b)| a == b).count() > 10);
+}
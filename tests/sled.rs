@@ -0,0 +1,48 @@
+#![cfg(feature = "sled")]
+
+use image_match::{SignatureConfig, SignatureStore, SledSignatureStore};
+
+mod common;
+
+#[test]
+fn sled_stores_find_similar_signatures() {
+    let path = std::env::temp_dir().join("image-match-signatures.sled");
+    let _ = std::fs::remove_dir_all(&path);
+    let db = sled::open(&path).unwrap();
+    let mut store = SledSignatureStore::new(&db).unwrap();
+
+    let config = SignatureConfig::new();
+    let original = common::pattern_rgba(120, 80);
+    let mut edited = original.clone();
+    edited[..4 * 120 * 8].fill(0);
+    let signature = config.compute_signature(&original, 120);
+    let similar = config.compute_signature(&edited, 120);
+    let other = config.compute_signature(&common::pattern_rgba(333, 517), 333);
+
+    store.insert("original", &signature).unwrap();
+    store.insert("edited", &signature).unwrap();
+    store.insert("edited", &similar).unwrap();
+    store.insert("other", &other).unwrap();
+    assert_eq!(store.get("edited").unwrap(), Some(similar.clone()));
+    assert_eq!(store.get("missing").unwrap(), None);
+
+    let matches = store.search_similar(&signature, 0.5, 10).unwrap();
+    let ids: Vec<_> = matches.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, ["original", "edited"]);
+    assert_eq!(matches[1].1, signature.similarity(&similar));
+
+    assert!(store.delete("original").unwrap());
+    assert!(!store.delete("original").unwrap());
+    store.flush().unwrap();
+    drop(store);
+
+    let mut store = SledSignatureStore::new(&db).unwrap();
+    let matches = store.search_similar(&signature, 0.5, 10).unwrap();
+    assert_eq!(matches.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(), ["edited"]);
+    // Replacing a signature removes the words of the one it replaces, so it's no longer a
+    // candidate at any threshold.
+    store.insert("edited", &other).unwrap();
+    assert_eq!(store.search_similar(&signature, -1.0, 10).unwrap(), []);
+    drop((store, db));
+    std::fs::remove_dir_all(path).unwrap();
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "sqlite")]
+
+use image_match::{SignatureConfig, SignatureStore, SqliteSignatureStore};
+
+mod common;
+
+#[test]
+fn sqlite_stores_find_similar_signatures() {
+    let path = std::env::temp_dir().join("image-match-signatures.sqlite");
+    let _ = std::fs::remove_file(&path);
+    let mut store = SqliteSignatureStore::open(&path).unwrap();
+
+    let config = SignatureConfig::new();
+    let original = common::pattern_rgba(120, 80);
+    let mut edited = original.clone();
+    edited[..4 * 120 * 8].fill(0);
+    let signature = config.compute_signature(&original, 120);
+    let similar = config.compute_signature(&edited, 120);
+    let other = config.compute_signature(&common::pattern_rgba(333, 517), 333);
+    let smallest = SignatureConfig::new().grid_size(3).compute_signature(&original, 120);
+
+    store.insert("original", &signature).unwrap();
+    store.insert("edited", &other).unwrap();
+    store.insert("edited", &similar).unwrap();
+    store.insert("other", &other).unwrap();
+    store.insert("smallest", &smallest).unwrap();
+    assert_eq!(store.get("edited").unwrap(), Some(similar.clone()));
+    assert_eq!(store.get("missing").unwrap(), None);
+
+    let matches = store.search_similar(&signature, 0.5, 10).unwrap();
+    let ids: Vec<_> = matches.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, ["original", "edited"]);
+    assert_eq!(matches[1].1, signature.similarity(&similar));
+    assert_eq!(store.search_similar(&smallest, 0.5, 10).unwrap(), [("smallest".into(), 1.0)]);
+
+    assert!(store.delete("original").unwrap());
+    assert!(!store.delete("original").unwrap());
+    drop(store);
+
+    let mut store = SqliteSignatureStore::open(&path).unwrap();
+    let matches = store.search_similar(&signature, 0.5, 10).unwrap();
+    assert_eq!(matches.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(), ["edited"]);
+    drop(store);
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
@@ -0,0 +1,44 @@
+use image_match::{
+    get_tiled_signature, try_get_tiled_signature, SignatureError, TileMatch,
+    DEFAULT_MATCH_THRESHOLD,
+};
+
+mod common;
+
+/// Lays out four images of `width` by `height` pixels in a 2 by 2 collage, row by row.
+fn collage(quadrants: [&[u8]; 4], width: usize, height: usize) -> Vec<u8> {
+    let mut collage = Vec::with_capacity(quadrants.len() * width * height * 4);
+    for pair in quadrants.chunks(2) {
+        for y in 0..height {
+            for quadrant in pair {
+                collage.extend_from_slice(&quadrant[y * width * 4..(y + 1) * width * 4]);
+            }
+        }
+    }
+    collage
+}
+
+#[test]
+fn tiles_find_an_image_embedded_in_a_collage() {
+    let (width, height) = (120, 80);
+    let photo = common::pattern_rgba(width, height);
+    let inverted: Vec<u8> = photo.iter().map(|&v| 255 - v).collect();
+    let mirrored: Vec<u8> = photo.chunks_exact(width * 4)
+        .flat_map(|row| row.chunks_exact(4).rev().flatten().copied().collect::<Vec<_>>())
+        .collect();
+    let flipped: Vec<u8> = photo.chunks_exact(width * 4).rev().flatten().copied().collect();
+    let collage = collage([&inverted, &photo, &mirrored, &flipped], width, height);
+
+    let tiles = get_tiled_signature(&collage, width * 2, 2, 2);
+    assert_eq!((tiles.columns(), tiles.rows(), tiles.tiles().len()), (2, 2, 4));
+    let known = get_tiled_signature(&photo, width, 1, 1);
+    let matches = tiles.matches(&known, DEFAULT_MATCH_THRESHOLD);
+    assert_eq!(matches, vec![TileMatch { left: (1, 0), right: (0, 0), similarity: 1.0 }]);
+    assert_eq!(tiles.tile(1, 0), known.tile(0, 0));
+    assert_eq!(tiles.tile(2, 0), None);
+
+    assert!(matches!(
+        try_get_tiled_signature(&photo, width, 0, 1),
+        Err(SignatureError::InvalidParameter { name: "columns", .. })
+    ));
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "tokio")]
+
+use std::io::Cursor;
+
+use image::{ImageOutputFormat, RgbaImage};
+use image_match::image::{get_file_signature, get_file_signature_async};
+use image_match::SignatureConfig;
+
+mod common;
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let pixels = common::pattern_rgba(width as usize, height as usize);
+    let image = RgbaImage::from_raw(width, height, pixels).unwrap();
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png).unwrap();
+    png
+}
+
+#[tokio::test]
+async fn async_signatures_match_blocking_ones() {
+    let bytes = png(120, 80);
+    let path = std::env::temp_dir().join("image-match-async.png");
+    std::fs::write(&path, &bytes).unwrap();
+    let expected = get_file_signature(&path).unwrap();
+    assert_eq!(get_file_signature_async(&path).await.unwrap(), expected);
+    let config = SignatureConfig::new();
+    assert_eq!(config.compute_bytes_async(bytes.clone()).await.unwrap(), expected);
+    assert!(config.compute_bytes_async(bytes[..100].to_vec()).await.is_err());
+
+    #[cfg(feature = "http")]
+    {
+        use image_match::image::{get_url_signature_async, UrlLimits, UrlReadError};
+
+        let url = common::serve_once("200 OK", bytes.clone());
+        let signature = get_url_signature_async(url).await.unwrap();
+        assert_eq!(signature.into_inner(), expected);
+
+        let small = UrlLimits::new().max_bytes(bytes.len() as u64 - 1);
+        let url = common::serve_once("200 OK", bytes);
+        let result = config.compute_url_async(&reqwest::Client::new(), url, small).await;
+        assert!(matches!(result, Err(UrlReadError::TooLarge { .. })));
+    }
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "http")]
+
+use std::io::Cursor;
+
+use image::{ImageOutputFormat, RgbaImage};
+use image_match::image::{get_bytes_signature, get_url_signature, UrlLimits, UrlReadError};
+use image_match::SignatureConfig;
+use reqwest::blocking::Client;
+
+mod common;
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let pixels = common::pattern_rgba(width as usize, height as usize);
+    let image = RgbaImage::from_raw(width, height, pixels).unwrap();
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png).unwrap();
+    png
+}
+
+#[test]
+fn remote_images_sign_like_their_bytes_within_limits() {
+    let bytes = png(120, 80);
+    let signature = get_url_signature(common::serve_once("200 OK", bytes.clone())).unwrap();
+    assert_eq!(signature.into_inner(), get_bytes_signature(&bytes).unwrap());
+
+    let config = SignatureConfig::new();
+    let client = Client::new();
+    let small = UrlLimits::new().max_bytes(bytes.len() as u64 - 1);
+    let url = common::serve_once("200 OK", bytes.clone());
+    let result = config.compute_url(&client, url, small);
+    assert!(matches!(result, Err(UrlReadError::TooLarge { .. })));
+
+    let few_pixels = UrlLimits::new().max_pixels(120 * 80 - 1);
+    let url = common::serve_once("200 OK", bytes);
+    let result = config.compute_url(&client, url, few_pixels);
+    assert!(matches!(result, Err(UrlReadError::TooManyPixels { width: 120, height: 80 })));
+
+    let result = get_url_signature(common::serve_once("404 Not Found", Vec::new()));
+    assert!(matches!(result, Err(UrlReadError::Http(_))));
+}